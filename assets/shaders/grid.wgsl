@@ -8,6 +8,9 @@
 @group(2) @binding(5) var<uniform> scale_factor: f32;
 @group(2) @binding(6) var<uniform> inside_grid_color: vec3<f32>;
 @group(2) @binding(7) var<uniform> outside_grid_color: vec3<f32>;
+@group(2) @binding(8) var<uniform> overmap_guide_spacing: i32;
+@group(2) @binding(9) var<uniform> min_zoom: f32;
+@group(2) @binding(10) var<uniform> grid_fade_start: f32;
 
 fn to_linear(nonlinear_color: vec4<f32>) -> vec4<f32> {
     let cutoff = step(nonlinear_color, vec4<f32>(0.04045));
@@ -25,6 +28,12 @@ fn fragment(output: VertexOutput) -> @location(0) vec4<f32> {
     let tile_size = tile_size * scale_factor;
     let mouse_pos = vec2<f32>(mouse_pos.x * scale_factor, mouse_pos.y * scale_factor);
 
+    // The grid lines fade out while zooming out from the fade start to the min zoom, so they do not cover dense maps
+    var grid_line_fade = 1.;
+    if (grid_fade_start > min_zoom) {
+        grid_line_fade = clamp((tile_size / scale_factor - min_zoom) / (grid_fade_start - min_zoom), 0., 1.);
+    }
+
     // EXAMPLE -> 32. or 64. ...
     var tile_start_x = abs(i32(mouse_pos.x + offset.x)) - abs(i32(mouse_pos.x + offset.x)) % i32(tile_size);
     var tile_start_y = abs(i32(mouse_pos.y + offset.y)) - abs(i32(mouse_pos.y + offset.y)) % i32(tile_size);
@@ -57,17 +66,35 @@ fn fragment(output: VertexOutput) -> @location(0) vec4<f32> {
     // Make Lines at 0, 0 more noticable
     if (abs(i32(output.position.x + offset.x)) % i32(tile_size) == i32(0) ||
     abs(i32(output.position.y + offset.y)) % i32(tile_size) == i32(0)) {
-         color.x = 1.0;
-         color.y = 1.0;
-         color.z = 1.0;
-         alpha = 0.1;
+         color = mix(color, vec3<f32>(1.0, 1.0, 1.0), grid_line_fade);
+         alpha = mix(alpha, 0.1, grid_line_fade);
+    }
+
+    // Draw thicker lines at the boundaries of the overmap tiles
+    if (overmap_guide_spacing > 0) {
+        let guide_size = i32(tile_size) * overmap_guide_spacing;
+        let guide_width = max(i32(2. * scale_factor), 1);
+        let guide_x = abs(i32(output.position.x + offset.x)) % guide_size;
+        let guide_y = abs(i32(output.position.y + offset.y)) % guide_size;
+
+        if (
+            guide_x < guide_width || guide_x > guide_size - guide_width ||
+            guide_y < guide_width || guide_y > guide_size - guide_width
+        ) {
+             color.x = 1.0;
+             color.y = 1.0;
+             color.z = 1.0;
+             alpha = 0.4;
+        }
     }
 
     let tile_x = i32(output.position.x + tile_size + offset.x) / i32(tile_size);
     let tile_y = i32(output.position.y + tile_size + offset.y) / i32(tile_size);
 
-    // Make any tiles on the ouside of the map darker
+    // Make any tiles on the ouside of the map darker, a map without a size has no inside
     if (
+        map_size.x < 1. ||
+        map_size.y < 1. ||
         i32(abs(tile_x)) > i32(map_size.x) ||
         i32(tile_x) <= 0 ||
         i32(abs(tile_y)) > i32(map_size.y) ||
@@ -0,0 +1,181 @@
+//! Benchmarks of the hot paths of the editor, loading the sprites of a tileset, resolving the sprites of a map
+//! and placing, updating and culling its tiles.
+//! They use the synthetic tileset in testing_data/bench_tileset, so no CDDA install is required
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use bevy::ecs::system::SystemState;
+use bevy::math::Vec2;
+use bevy::prelude::{Assets, Image, ResMut, World};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use cdda_map_editor::common::Coordinates;
+use cdda_map_editor::common::io::Load;
+use cdda_map_editor::graphics::{GetTexture, LegacyTextures};
+use cdda_map_editor::graphics::tileset::TilesetLoader;
+use cdda_map_editor::graphics::tileset::legacy::LegacyTilesetLoader;
+use cdda_map_editor::map::data::{MapEntity, Single};
+use cdda_map_editor::map::io::MapEntityLoader;
+use cdda_map_editor::program::data::CDDAData;
+use cdda_map_editor::project::data::Project;
+use cdda_map_editor::region_settings::io::RegionSettingsLoader;
+use cdda_map_editor::settings::data::RowSizeMismatch;
+use cdda_map_editor::ui::grid::resources::Grid;
+
+const TILESET_PATH: &str = "./testing_data/bench_tileset";
+
+fn load_project(cdda_data: &CDDAData) -> Project {
+    let loader = MapEntityLoader {
+        path: PathBuf::from("./testing_data/bench_mapgen.json"),
+        id: "bench_house".into(),
+        cdda_data,
+        row_size_mismatch: RowSizeMismatch::default(),
+        variant: None,
+        warnings: RefCell::default(),
+    };
+
+    let single: Single = loader.load().unwrap();
+
+    return Project {
+        name: "Bench".into(),
+        map_entity: MapEntity::Single(single),
+        ..Default::default()
+    };
+}
+
+fn load_textures(images: &mut ResMut<Assets<Image>>) -> LegacyTextures {
+    let loader = LegacyTilesetLoader::new(PathBuf::from(TILESET_PATH));
+    let region_settings_loader = RegionSettingsLoader::new(
+        PathBuf::from(TILESET_PATH).join("regional_map_settings.json"),
+        "default".into(),
+    );
+
+    return LegacyTextures::new(&loader, region_settings_loader, images);
+}
+
+fn tileset_loading(c: &mut Criterion) {
+    let mut world = World::new();
+    world.init_resource::<Assets<Image>>();
+
+    let mut state: SystemState<ResMut<Assets<Image>>> = SystemState::new(&mut world);
+    let mut images = state.get_mut(&mut world);
+
+    c.bench_function("load_sprite_handles", |b| b.iter(|| {
+        let loader = LegacyTilesetLoader::new(PathBuf::from(TILESET_PATH));
+        black_box(loader.load_sprite_handles(&mut images).unwrap())
+    }));
+
+    c.bench_function("load_legacy_textures", |b| b.iter(|| {
+        black_box(load_textures(&mut images))
+    }));
+}
+
+fn sprite_resolution(c: &mut Criterion) {
+    let mut world = World::new();
+    world.init_resource::<Assets<Image>>();
+
+    let mut state: SystemState<ResMut<Assets<Image>>> = SystemState::new(&mut world);
+    let mut images = state.get_mut(&mut world);
+
+    let cdda_data = CDDAData { palettes: HashMap::new() };
+    let project = load_project(&cdda_data);
+    let textures = load_textures(&mut images);
+
+    c.bench_function("get_ids_24x24", |b| b.iter(|| {
+        for tile in project.map_entity.tiles().values() {
+            black_box(project.map_entity.get_ids(&cdda_data, &tile.character));
+        }
+    }));
+
+    c.bench_function("get_textures_24x24", |b| b.iter(|| {
+        for (coordinates, tile) in project.map_entity.tiles().iter() {
+            black_box(textures.get_textures(&project, &cdda_data, &tile.character, coordinates, None, false));
+        }
+    }));
+}
+
+fn tile_placement(c: &mut Criterion) {
+    let mut world = World::new();
+    world.init_resource::<Assets<Image>>();
+
+    let mut state: SystemState<ResMut<Assets<Image>>> = SystemState::new(&mut world);
+    let mut images = state.get_mut(&mut world);
+
+    let cdda_data = CDDAData { palettes: HashMap::new() };
+    let project = load_project(&cdda_data);
+    let textures = load_textures(&mut images);
+
+    // Spawning a map used to place every tile on its own and update its neighbors afterwards
+    c.bench_function("place_tiles_one_by_one_24x24", |b| b.iter(|| {
+        for (coordinates, tile) in project.map_entity.tiles().iter() {
+            black_box(textures.get_textures(&project, &cdda_data, &tile.character, coordinates, None, false));
+
+            for (neighbor, coordinates) in project.map_entity.get_neighbors_to_update(&vec![coordinates.clone()], |_| true) {
+                black_box(textures.get_textures(&project, &cdda_data, &neighbor.character, &coordinates, None, false));
+            }
+        }
+    }));
+
+    c.bench_function("place_tiles_in_bulk_24x24", |b| b.iter(|| {
+        for (coordinates, tile) in project.map_entity.tiles().iter() {
+            black_box(textures.get_textures(&project, &cdda_data, &tile.character, coordinates, None, false));
+        }
+    }));
+
+    // A drag over four rows of the map
+    let dragged: Vec<Coordinates> = (10..14)
+        .flat_map(|y| (0..24).map(move |x| Coordinates::new(x, y)))
+        .collect();
+
+    c.bench_function("update_all_neighbors_of_drag", |b| b.iter(|| {
+        for (neighbor, coordinates) in project.map_entity.get_neighbors_to_update(&dragged, |_| true) {
+            black_box(textures.get_textures(&project, &cdda_data, &neighbor.character, &coordinates, None, false));
+        }
+    }));
+
+    c.bench_function("update_autotiled_neighbors_of_drag", |b| b.iter(|| {
+        let neighbors = project.map_entity.get_neighbors_to_update(
+            &dragged,
+            |tile| textures.is_autotiled(&project, &cdda_data, &tile.character),
+        );
+
+        for (neighbor, coordinates) in neighbors {
+            black_box(textures.get_textures(&project, &cdda_data, &neighbor.character, &coordinates, None, false));
+        }
+    }));
+}
+
+fn tile_culling(c: &mut Criterion) {
+    let grid = Grid {
+        tile_size: 32.,
+        default_tile_size: 32.,
+        offset: Vec2::ZERO,
+        min_zoom: 6.,
+        max_zoom: 128.,
+        instantiated_grid: None,
+    };
+
+    let window_size = Vec2::new(1280., 720.);
+
+    // Only the sprites of the tiles which are on screen are drawn, see the update system
+    c.bench_function("cull_tiles_240x240", |b| b.iter(|| {
+        let mut on_screen = 0;
+
+        for y in 0..240 {
+            for x in 0..240 {
+                let translation = grid.get_tile_translation(window_size, &Coordinates::new(x, y));
+
+                if grid.is_on_screen(translation, window_size) {
+                    on_screen += 1;
+                }
+            }
+        }
+
+        black_box(on_screen)
+    }));
+}
+
+criterion_group!(benches, tileset_loading, sprite_resolution, tile_placement, tile_culling);
+criterion_main!(benches);
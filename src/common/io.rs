@@ -1,21 +1,81 @@
+use std::fmt::{Display, Formatter};
 use std::fs;
+use std::io::ErrorKind;
+use std::process::Command;
 use std::path::{Path, PathBuf};
 
+use serde::Serialize;
+
 #[derive(Debug)]
 pub enum LoadError {
     NoAutoSave,
     DirectoryNotFound,
     ParseError,
+    /// The file at the path could not be read
+    Io(PathBuf, std::io::Error),
     Other(anyhow::Error),
 }
 
+impl LoadError {
+    /// Returns the file which could not be loaded, if it is known
+    pub fn path(&self) -> Option<&Path> {
+        return match self {
+            LoadError::Io(path, _) => Some(path),
+            _ => None
+        };
+    }
+
+    /// Returns true if the file does not exist, which is expected on the first run
+    pub fn is_not_found(&self) -> bool {
+        return match self {
+            LoadError::Io(_, e) => e.kind() == ErrorKind::NotFound,
+            _ => false
+        };
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            LoadError::NoAutoSave => write!(f, "No auto save exists"),
+            LoadError::DirectoryNotFound => write!(f, "The data directory could not be found"),
+            LoadError::ParseError => write!(f, "The file could not be parsed"),
+            LoadError::Io(path, e) => write!(f, "Could not read {:?}: {}", path, e),
+            LoadError::Other(e) => write!(f, "{:#}", e)
+        };
+    }
+}
+
 #[derive(Debug)]
 pub enum SaveError {
     DirectoryNotFound(String),
     InvalidPath(anyhow::Error),
+    /// The file at the path could not be written
+    Io(PathBuf, std::io::Error),
     Other(anyhow::Error),
 }
 
+impl SaveError {
+    /// Returns the file which could not be saved, if it is known
+    pub fn path(&self) -> Option<&Path> {
+        return match self {
+            SaveError::Io(path, _) => Some(path),
+            _ => None
+        };
+    }
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            SaveError::DirectoryNotFound(dir) => write!(f, "The directory {} could not be found", dir),
+            SaveError::InvalidPath(e) => write!(f, "Invalid path: {:#}", e),
+            SaveError::Io(path, e) => write!(f, "Could not write {:?}: {}", path, e),
+            SaveError::Other(e) => write!(f, "{:#}", e)
+        };
+    }
+}
+
 pub trait Load<T> {
     fn load(&self) -> Result<T, LoadError>;
 }
@@ -24,6 +84,33 @@ pub trait Save<T> {
     fn save(&self, value: &T) -> Result<(), SaveError>;
 }
 
+/// Serializes the value and writes it to the file at the path, creating the parent directories if needed
+pub fn write_json_file<T: Serialize>(path: &Path, value: &T) -> Result<(), SaveError> {
+    let json = match serde_json::to_string(value) {
+        Ok(j) => j,
+        Err(e) => return Err(SaveError::Other(e.into()))
+    };
+
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return Err(SaveError::Io(parent.to_path_buf(), e));
+        }
+    }
+
+    return match fs::write(path, json) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(SaveError::Io(path.to_path_buf(), e))
+    };
+}
+
+/// Reads the file at the path to a string
+pub fn read_file(path: &Path) -> Result<String, LoadError> {
+    return match fs::read_to_string(path) {
+        Ok(s) => Ok(s),
+        Err(e) => Err(LoadError::Io(path.to_path_buf(), e))
+    };
+}
+
 pub fn recurse_files(path: impl AsRef<Path>) -> std::io::Result<Vec<PathBuf>> {
     let mut buf = vec![];
     let entries = fs::read_dir(path)?;
@@ -43,4 +130,50 @@ pub fn recurse_files(path: impl AsRef<Path>) -> std::io::Result<Vec<PathBuf>> {
     }
 
     Ok(buf)
-}
\ No newline at end of file
+}
+
+/// Opens the passed directory in the file explorer of the operating system
+pub fn open_in_file_explorer(path: impl AsRef<Path>) -> std::io::Result<()> {
+    let program = match std::env::consts::OS {
+        "windows" => "explorer",
+        "macos" => "open",
+        _ => "xdg-open"
+    };
+
+    Command::new(program).arg(path.as_ref()).spawn()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+
+    use crate::common::io::{read_file, SaveError, write_json_file};
+
+    #[test]
+    pub fn test_read_only_json_file_is_a_save_error() {
+        let directory = std::env::temp_dir().join(format!("cdda_map_editor_test_read_only_json_file_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("data.json");
+
+        fs::write(&path, "[]").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        let result = write_json_file(&path, &vec![1, 2, 3]);
+
+        match result {
+            Err(SaveError::Io(ref error_path, _)) => assert_eq!(error_path, &path),
+            _ => panic!("Expected an Io SaveError but got {:?}", result)
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+
+        let error = read_file(&directory.join("missing.json")).unwrap_err();
+        assert!(error.is_not_found());
+        assert_eq!(error.path(), Some(directory.join("missing.json").as_path()));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
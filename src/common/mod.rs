@@ -3,6 +3,7 @@ use std::fmt::{Debug, Formatter};
 use std::ops::{Add, DerefMut};
 use std::sync::{Arc, RwLock};
 
+use bevy::math::IVec2;
 use bevy::prelude::Component;
 use bevy::prelude::Event;
 use color_print::cformat;
@@ -17,7 +18,7 @@ use rand::rngs::StdRng;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde::de::Visitor;
 
-pub(crate) mod io;
+pub mod io;
 
 pub type TileId = String;
 
@@ -132,20 +133,30 @@ pub trait GetRandom<T> {
     fn get_random_weighted(&self) -> Option<&T>;
 }
 
+/// Picks a random index where the chance of each index is its weight divided by the sum of all weights.
+/// The weights are integers so that big weights, like the ones used in region settings, do not lose precision
+/// Returns None if there are no weights or all of them are 0
+fn get_random_weighted_index<R: Rng>(weights: Vec<u64>, rng: &mut R) -> Option<usize> {
+    return match WeightedIndex::new(weights) {
+        Ok(dist) => Some(dist.sample(rng)),
+        Err(_) => None
+    };
+}
+
 impl<T: Debug> GetRandom<T> for Vec<Weighted<T>> {
     fn get_random_weighted(&self) -> Option<&T> {
-        if self.len() == 0 { return None; }
-
-        let dist = WeightedIndex::new(self.iter().map(|w| {
+        let weights = self.iter().map(|w| {
             match w.weight {
                 // TODO: Figure out what to do when all weights are 0
-                0 => 1.,
-                _ => w.weight as f32
+                0 => 1,
+                _ => w.weight as u64
             }
-        }).collect::<Vec<f32>>().as_slice()).unwrap();
+        }).collect::<Vec<u64>>();
         let mut lock = RANDOM.write().unwrap();
 
-        return match self.get(dist.sample(lock.deref_mut())) {
+        let index = get_random_weighted_index(weights, lock.deref_mut())?;
+
+        return match self.get(index) {
             None => None,
             Some(v) => Some(&v.value)
         };
@@ -154,15 +165,15 @@ impl<T: Debug> GetRandom<T> for Vec<Weighted<T>> {
 
 impl<T> GetRandom<T> for Vec<MeabyWeighted<T>> {
     fn get_random_weighted(&self) -> Option<&T> {
-        if self.len() == 0 { return None; }
-
-        let dist = WeightedIndex::new(self.iter().map(|mw| match mw {
-            MeabyWeighted::NotWeighted(_) => 1.,
-            MeabyWeighted::Weighted(w) => w.weight as f32
-        }).collect::<Vec<f32>>().as_slice()).unwrap();
+        let weights = self.iter().map(|mw| match mw {
+            MeabyWeighted::NotWeighted(_) => 1,
+            MeabyWeighted::Weighted(w) => w.weight as u64
+        }).collect::<Vec<u64>>();
         let mut lock = RANDOM.write().unwrap();
 
-        return match self.get(dist.sample(lock.deref_mut())) {
+        let index = get_random_weighted_index(weights, lock.deref_mut())?;
+
+        return match self.get(index) {
             None => None,
             Some(v) => match v {
                 MeabyWeighted::NotWeighted(nw) => Some(nw),
@@ -174,14 +185,13 @@ impl<T> GetRandom<T> for Vec<MeabyWeighted<T>> {
 
 impl<K> GetRandom<K> for HashMap<K, u32> {
     fn get_random_weighted(&self) -> Option<&K> {
-        if self.is_empty() { return None; }
-
-        let dist = WeightedIndex::new(self.values().map(|v| *v as f32).collect::<Vec<f32>>()).unwrap();
-
         let items = self.keys().collect::<Vec<&K>>();
+        let weights = self.values().map(|v| *v as u64).collect::<Vec<u64>>();
         let mut lock = RANDOM.write().unwrap();
 
-        return Some(items[dist.sample(lock.deref_mut())]);
+        let index = get_random_weighted_index(weights, lock.deref_mut())?;
+
+        return Some(items[index]);
     }
 }
 
@@ -269,10 +279,18 @@ impl<'de> Visitor<'de> for CoordinatesVisitor {
     {
         let split: Vec<&str> = v.split(";").collect::<Vec<&str>>();
 
-        return Ok(Coordinates {
-            x: split.get(0).expect("Value before ';'").parse().expect("Valid i32"),
-            y: split.get(1).expect("Value after ';'").parse().expect("Valid i32"),
-        });
+        // Return an error instead of panicking so corrupted tile maps can be reported
+        let x = match split.get(0).map(|x| x.parse::<i32>()) {
+            Some(Ok(x)) => x,
+            _ => return Err(E::custom(format!("Invalid x value in coordinates {}", v)))
+        };
+
+        let y = match split.get(1).map(|y| y.parse::<i32>()) {
+            Some(Ok(y)) => y,
+            _ => return Err(E::custom(format!("Invalid y value in coordinates {}", v)))
+        };
+
+        return Ok(Coordinates { x, y });
     }
 }
 
@@ -289,6 +307,46 @@ impl Coordinates {
             y,
         };
     }
+
+    /// Returns all coordinates on the line from self to the end, both included (Bresenham)
+    pub fn line_to(&self, end: &Coordinates) -> Vec<Coordinates> {
+        let dx = (end.x - self.x).abs();
+        let dy = -(end.y - self.y).abs();
+        let step_x = if self.x < end.x { 1 } else { -1 };
+        let step_y = if self.y < end.y { 1 } else { -1 };
+
+        let mut error = dx + dy;
+        let mut current = self.clone();
+        let mut coordinates = vec![current.clone()];
+
+        while current != *end {
+            let doubled_error = 2 * error;
+
+            if doubled_error >= dy {
+                error += dy;
+                current.x += step_x;
+            }
+
+            if doubled_error <= dx {
+                error += dx;
+                current.y += step_y;
+            }
+
+            coordinates.push(current.clone());
+        }
+
+        return coordinates;
+    }
+
+    /// Returns the coordinates after turning a map of the size clockwise by the amount of quarters
+    pub fn rotated(&self, size: IVec2, rotation: u8) -> Coordinates {
+        return match rotation % 4 {
+            1 => Coordinates::new(size.y - 1 - self.y, self.x),
+            2 => Coordinates::new(size.x - 1 - self.x, size.y - 1 - self.y),
+            3 => Coordinates::new(self.y, size.x - 1 - self.x),
+            _ => self.clone()
+        };
+    }
 }
 
 impl Add for Coordinates {
@@ -317,3 +375,60 @@ impl<'de> Deserialize<'de> for Coordinates {
     }
 }
 
+#[cfg(test)]
+pub mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::common::{Coordinates, get_random_weighted_index};
+
+    #[test]
+    pub fn test_rare_weight_can_be_selected() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut rare_count = 0;
+
+        // The sum of the weights is larger than what a f32 can represent exactly
+        for _ in 0..5_000 {
+            if get_random_weighted_index(vec![99_000_000, 1_000_000], &mut rng) == Some(1) {
+                rare_count += 1;
+            }
+        }
+
+        // Around 50 selections are expected
+        assert!((25..=75).contains(&rare_count), "The rare weight was selected {} times", rare_count);
+    }
+
+    #[test]
+    pub fn test_all_weights_zero() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        assert_eq!(get_random_weighted_index(vec![0, 0], &mut rng), None);
+        assert_eq!(get_random_weighted_index(vec![], &mut rng), None);
+    }
+
+    #[test]
+    pub fn test_line_from_0_0_to_5_0_has_six_tiles() {
+        let line = Coordinates::new(0, 0).line_to(&Coordinates::new(5, 0));
+
+        assert_eq!(line, (0..=5).map(|x| Coordinates::new(x, 0)).collect::<Vec<Coordinates>>());
+    }
+
+    #[test]
+    pub fn test_line_to_the_same_coordinates_has_one_tile() {
+        let line = Coordinates::new(2, -3).line_to(&Coordinates::new(2, -3));
+
+        assert_eq!(line, vec![Coordinates::new(2, -3)]);
+    }
+
+    #[test]
+    pub fn test_diagonal_line_has_no_gaps() {
+        let line = Coordinates::new(3, 4).line_to(&Coordinates::new(0, 0));
+
+        assert_eq!(line.first(), Some(&Coordinates::new(3, 4)));
+        assert_eq!(line.last(), Some(&Coordinates::new(0, 0)));
+
+        for pair in line.windows(2) {
+            assert!((pair[0].x - pair[1].x).abs() <= 1 && (pair[0].y - pair[1].y).abs() <= 1);
+        }
+    }
+}
@@ -1,26 +1,74 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use bevy::prelude::{Assets, Image, ResMut, Resource};
+use bevy::prelude::{Assets, Color, Image, ResMut, Resource};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use serde::Serialize;
+use serde_json::{Map, Value};
 
 use crate::common::{Coordinates, TileId};
 use crate::common::io::Load;
 use crate::program::data::CDDAData;
 use crate::graphics::tileset::{GetBackground, GetForeground, TilesetLoader};
-use crate::graphics::tileset::legacy::{LegacyTileset, SingleForeground};
+use crate::graphics::tileset::legacy::{LegacyTileset, SingleForeground, TilesetInfo};
 use crate::project::data::Project;
 use crate::region_settings::data::RegionSettings;
+use crate::settings::data::{FallbackColor, MapEdge};
 
-pub(crate) mod tileset;
+pub mod tileset;
 
 // Not sure if this is the best way to do this
 #[derive(Clone)]
 pub struct Sprite {
     pub fg: Option<Arc<dyn GetForeground>>,
+    /// Additional foreground sprites which are drawn on top of fg in order
+    pub fg_layers: Vec<Arc<dyn GetForeground>>,
     pub bg: Option<Arc<dyn GetBackground>>,
     pub offset_x: i32,
     pub offset_y: i32,
     pub is_animated: bool,
+    /// Milliseconds between two frames of an animated sprite
+    pub animation_interval: u64,
+}
+
+/// Used when the tileset does not specify how fast an animated tile cycles
+pub const DEFAULT_ANIMATION_INTERVAL: u64 = 1000;
+
+/// Returns a color for tiles which have no background sprite.
+/// The hue is derived from the id, so the same id always results in the same color
+pub fn get_fill_color(id: &TileId) -> Color {
+    // FNV-1a, the std hasher is not guaranteed to be stable between Rust versions
+    let hash = id.bytes().fold(0x811c9dc5_u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+
+    return Color::hsl((hash % 360) as f32, 0.35, 0.35);
+}
+
+/// Returns the magenta and black checkerboard which marks sprites that are missing in the tileset
+pub fn get_missing_texture_image(width: u32, height: u32) -> Image {
+    let cell_size = (width / 4).max(1);
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            match (x / cell_size + y / cell_size) % 2 == 0 {
+                true => data.extend_from_slice(&[255, 0, 255, 255]),
+                false => data.extend_from_slice(&[0, 0, 0, 255])
+            }
+        }
+    }
+
+    return Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::all(),
+    );
 }
 
 pub struct FullCardinal {
@@ -30,37 +78,45 @@ pub struct FullCardinal {
     pub west: Sprite,
 }
 
-impl From<(Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool)> for FullCardinal {
-    fn from(value: (Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool)) -> Self {
+impl From<(Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool, Vec<Arc<dyn GetForeground>>, u64)> for FullCardinal {
+    fn from(value: (Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool, Vec<Arc<dyn GetForeground>>, u64)) -> Self {
         return FullCardinal {
             north: Sprite {
                 fg: value.0.get(0).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
             west: Sprite {
                 fg: value.0.get(1).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
             south: Sprite {
                 fg: value.0.get(2).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
 
             },
             east: Sprite {
                 fg: value.0.get(3).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1,
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
         };
     }
@@ -74,36 +130,44 @@ pub struct Corner {
     pub north_east: Sprite,
 }
 
-impl From<(Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool)> for Corner {
-    fn from(value: (Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool)) -> Self {
+impl From<(Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool, Vec<Arc<dyn GetForeground>>, u64)> for Corner {
+    fn from(value: (Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool, Vec<Arc<dyn GetForeground>>, u64)) -> Self {
         return Corner {
             north_west: Sprite {
                 fg: value.0.get(0).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
             south_west: Sprite {
                 fg: value.0.get(1).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
             south_east: Sprite {
                 fg: value.0.get(2).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
             north_east: Sprite {
                 fg: value.0.get(3).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
         };
     }
@@ -114,22 +178,26 @@ pub struct Edge {
     pub east_west: Sprite,
 }
 
-impl From<(Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool)> for Edge {
-    fn from(value: (Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool)) -> Self {
+impl From<(Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool, Vec<Arc<dyn GetForeground>>, u64)> for Edge {
+    fn from(value: (Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool, Vec<Arc<dyn GetForeground>>, u64)) -> Self {
         return Self {
             north_south: Sprite {
                 fg: value.0.get(0).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
             east_west: Sprite {
                 fg: value.0.get(1).cloned(),
+                fg_layers: value.5.clone(),
                 bg: value.1.clone(),
                 offset_x: value.2,
                 offset_y: value.3,
                 is_animated: value.4,
+                animation_interval: value.6,
             },
         };
     }
@@ -144,9 +212,110 @@ pub enum SpriteType {
         edge: Edge,
         end_piece: FullCardinal,
         unconnected: Sprite,
+        /// If the whole set is turned with the rotation of the tile, like doors which can face any direction
+        is_rotating: bool,
     },
 }
 
+impl SpriteType {
+    /// Returns the sprite which is drawn for the subtile, single sprites are used for every subtile
+    pub fn get_subtile_sprite(&self, subtile: &MultitileSubtile) -> &Sprite {
+        let (center, corner, t_connection, edge, end_piece, unconnected) = match self {
+            SpriteType::Single(s) => return s,
+            SpriteType::Multitile { center, corner, t_connection, edge, end_piece, unconnected, .. } => {
+                (center, corner, t_connection, edge, end_piece, unconnected)
+            }
+        };
+
+        // The fields are named after the order of the sprites in the tileset, see the table of MultitileSubtile
+        return match subtile {
+            MultitileSubtile::Center => center,
+            MultitileSubtile::Unconnected => unconnected,
+            MultitileSubtile::Edge(0) => &edge.north_south,
+            MultitileSubtile::Edge(_) => &edge.east_west,
+            MultitileSubtile::Corner(0) => &corner.north_west,
+            MultitileSubtile::Corner(1) => &corner.south_west,
+            MultitileSubtile::Corner(2) => &corner.south_east,
+            MultitileSubtile::Corner(_) => &corner.north_east,
+            MultitileSubtile::TConnection(0) => &t_connection.north,
+            MultitileSubtile::TConnection(1) => &t_connection.west,
+            MultitileSubtile::TConnection(2) => &t_connection.south,
+            MultitileSubtile::TConnection(_) => &t_connection.east,
+            MultitileSubtile::EndPiece(0) => &end_piece.north,
+            MultitileSubtile::EndPiece(1) => &end_piece.west,
+            MultitileSubtile::EndPiece(2) => &end_piece.south,
+            MultitileSubtile::EndPiece(_) => &end_piece.east,
+        };
+    }
+}
+
+/// The part of a multitile which is drawn for the connections of a tile.
+/// The index is the position of the sprite in the fg list of the tileset. A single sprite is
+/// rotated counterclockwise by 90 degrees for every step instead, like in CDDA.
+///
+/// | top | right | below | left | subtile          |
+/// |-----|-------|-------|------|------------------|
+/// |  x  |   x   |   x   |  x   | center           |
+/// |     |       |       |      | unconnected      |
+/// |  x  |       |   x   |      | edge 0           |
+/// |     |   x   |       |  x   | edge 1           |
+/// |     |   x   |   x   |      | corner 0 ┌       |
+/// |  x  |   x   |       |      | corner 1 └       |
+/// |  x  |       |       |  x   | corner 2 ┘       |
+/// |     |       |   x   |  x   | corner 3 ┐       |
+/// |     |   x   |   x   |  x   | t_connection 0 ┬ |
+/// |  x  |   x   |   x   |      | t_connection 1 ├ |
+/// |  x  |   x   |       |  x   | t_connection 2 ┴ |
+/// |  x  |       |   x   |  x   | t_connection 3 ┤ |
+/// |     |       |   x   |      | end_piece 0      |
+/// |     |   x   |       |      | end_piece 1      |
+/// |  x  |       |       |      | end_piece 2      |
+/// |     |       |       |  x   | end_piece 3      |
+#[derive(Debug, PartialEq)]
+pub enum MultitileSubtile {
+    Center,
+    Unconnected,
+    Edge(usize),
+    Corner(usize),
+    TConnection(usize),
+    EndPiece(usize),
+}
+
+impl MultitileSubtile {
+    pub fn from_connections(top: bool, right: bool, below: bool, left: bool) -> Self {
+        return match (top, right, below, left) {
+            (true, true, true, true) => MultitileSubtile::Center,
+            (false, false, false, false) => MultitileSubtile::Unconnected,
+            (true, false, true, false) => MultitileSubtile::Edge(0),
+            (false, true, false, true) => MultitileSubtile::Edge(1),
+            (false, true, true, false) => MultitileSubtile::Corner(0),
+            (true, true, false, false) => MultitileSubtile::Corner(1),
+            (true, false, false, true) => MultitileSubtile::Corner(2),
+            (false, false, true, true) => MultitileSubtile::Corner(3),
+            (false, true, true, true) => MultitileSubtile::TConnection(0),
+            (true, true, true, false) => MultitileSubtile::TConnection(1),
+            (true, true, false, true) => MultitileSubtile::TConnection(2),
+            (true, false, true, true) => MultitileSubtile::TConnection(3),
+            (false, false, true, false) => MultitileSubtile::EndPiece(0),
+            (false, true, false, false) => MultitileSubtile::EndPiece(1),
+            (true, false, false, false) => MultitileSubtile::EndPiece(2),
+            (false, false, false, true) => MultitileSubtile::EndPiece(3),
+        };
+    }
+
+    /// Returns the subtile of a multitile which is drawn turned clockwise by the rotation.
+    /// The connections are in the order top, right, below, left and are turned into the frame of the turned sprite
+    pub fn from_rotated_connections(connections: [bool; 4], rotation: u8) -> Self {
+        let rotation = rotation as usize % 4;
+
+        return Self::from_connections(
+            connections[rotation],
+            connections[(rotation + 1) % 4],
+            connections[(rotation + 2) % 4],
+            connections[(rotation + 3) % 4],
+        );
+    }
+}
 
 pub enum TileSprite<'a> {
     Exists {
@@ -169,39 +338,105 @@ pub enum SpriteState<'a> {
     NotMapped,
 }
 
+impl SpriteState<'_> {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            SpriteState::Defined(_) => "Defined",
+            SpriteState::TextureNotFound => "TextureNotFound",
+            SpriteState::NotMapped => "NotMapped"
+        };
+    }
+}
+
+/// How the sprites of a single tile were resolved
+#[derive(Debug, Serialize)]
+pub struct SpriteMappingEntry {
+    pub character: char,
+    pub terrain: Option<TileId>,
+    pub state: &'static str,
+    pub furniture: Option<TileId>,
+    pub furniture_state: &'static str,
+}
+
+/// Resolves the terrain and furniture of every tile in the project.
+/// The keys are the coordinates of the tiles in the same "x;y" format which is used to save them
+pub fn get_sprite_mapping_report(textures: &dyn GetTexture, project: &Project, cdda_data: &CDDAData) -> Map<String, Value> {
+    let mut coordinates: Vec<&Coordinates> = project.map_entity.tiles().keys().collect();
+    coordinates.sort_by_key(|c| (c.y, c.x));
+
+    let mut report = Map::new();
+
+    for coordinates in coordinates {
+        let tile = project.map_entity.tiles().get(coordinates).unwrap();
+        let ids = project.map_entity.get_ids(cdda_data, &tile.character);
+
+        let entry = SpriteMappingEntry {
+            character: tile.character,
+            terrain: ids.terrain,
+            state: textures.get_terrain(project, cdda_data, &tile.character, coordinates).name(),
+            furniture: ids.furniture,
+            furniture_state: textures.get_furniture(project, cdda_data, &tile.character, coordinates).name(),
+        };
+
+        report.insert(
+            format!("{};{}", coordinates.x, coordinates.y),
+            serde_json::to_value(entry).unwrap(),
+        );
+    }
+
+    return report;
+}
+
 pub trait GetTexture: Send + Sync {
-    fn get_textures(&self, project: &Project, cdda_data: &CDDAData, character: &char, coordinates: &Coordinates) -> TileSprite {
+    /// Resolves every sprite of the tile. If floor_under_furniture is passed,
+    /// it is used as the terrain of tiles which only have furniture.
+    /// Ids which are missing in the tileset are drawn with the missing texture if show_missing_textures is true,
+    /// otherwise with the fallback of the character
+    fn get_textures(&self, project: &Project, cdda_data: &CDDAData, character: &char, coordinates: &Coordinates, floor_under_furniture: Option<&TileId>, show_missing_textures: bool) -> TileSprite {
         let terrain = self.get_terrain(project, cdda_data, character, coordinates);
         let furniture = self.get_furniture(project, cdda_data, character, coordinates);
         let items = self.get_item(project, cdda_data, character, coordinates);
         let toilets = self.get_toilets(project, cdda_data, character, coordinates);
 
+        let is_empty_space = *character == ' ' && project.treat_space_as_empty;
+
+        let get_texture_not_found = || match show_missing_textures {
+            true => self.get_missing_texture(),
+            false => self.get_fallback_texture(character)
+        };
+
         let terrain_sprite: Option<&Sprite> = match terrain {
             SpriteState::Defined(s) => Some(s),
-            SpriteState::TextureNotFound => Some(self.get_fallback_texture(character)),
+            SpriteState::TextureNotFound => Some(get_texture_not_found()),
             SpriteState::NotMapped => {
-                match &project.map_entity.object().fill_ter {
+                match project.map_entity.object().get_fill_ter() {
                     None => None,
-                    Some(fill) => self.get_terrain_texture_from_tile_id(project, cdda_data, coordinates, fill)
+                    Some(_) if is_empty_space => None,
+                    Some(fill) => self.get_terrain_texture_from_tile_id(project, cdda_data, coordinates, &fill)
                 }
             }
         };
 
         let furniture_sprite: Option<&Sprite> = match furniture {
             SpriteState::Defined(s) => Some(s),
-            SpriteState::TextureNotFound => Some(self.get_fallback_texture(character)),
+            SpriteState::TextureNotFound => Some(get_texture_not_found()),
             SpriteState::NotMapped => None
         };
 
+        let terrain_sprite = match (terrain_sprite, furniture_sprite, floor_under_furniture) {
+            (None, Some(_), Some(floor)) => self.get_terrain_texture_from_tile_id(project, cdda_data, coordinates, floor),
+            (terrain_sprite, _, _) => terrain_sprite
+        };
+
         let items_sprite: Option<&Sprite> = match items {
             SpriteState::Defined(s) => Some(s),
-            SpriteState::TextureNotFound => Some(self.get_fallback_texture(character)),
+            SpriteState::TextureNotFound => Some(get_texture_not_found()),
             SpriteState::NotMapped => None
         };
 
         let toilets_sprite: Option<&Sprite> = match toilets {
             SpriteState::Defined(s) => Some(s),
-            SpriteState::TextureNotFound => Some(self.get_fallback_texture(character)),
+            SpriteState::TextureNotFound => Some(get_texture_not_found()),
             SpriteState::NotMapped => None
         };
 
@@ -220,24 +455,55 @@ pub trait GetTexture: Send + Sync {
     fn get_terrain_representation(&self, tile_id: &TileId) -> &Sprite;
 
     fn get_terrain_texture_from_tile_id(&self, project: &Project, cdda_data: &CDDAData, coordinates: &Coordinates, id: &TileId) -> Option<&Sprite>;
+
     fn get_terrain(&self, project: &Project, cdda_data: &CDDAData, character: &char, coordinates: &Coordinates) -> SpriteState;
     fn get_furniture(&self, project: &Project, cdda_data: &CDDAData, character: &char, coordinates: &Coordinates) -> SpriteState;
     fn get_item(&self, project: &Project, cdda_data: &CDDAData, character: &char, coordinates: &Coordinates) -> SpriteState;
     fn get_toilets(&self, project: &Project, cdda_data: &CDDAData, character: &char, coordinates: &Coordinates) -> SpriteState;
     fn get_fallback_texture(&self, character: &char) -> &Sprite;
+
+    /// Sets which of the colored fallback glyphs get_fallback_texture returns
+    fn set_fallback_color(&mut self, fallback_color: FallbackColor);
+
+    /// Sets if autotiled tiles connect to the edge of the map
+    fn set_map_edge(&mut self, map_edge: MapEdge);
+
+    /// Returns the sprite which marks ids that are defined but missing in the tileset
+    fn get_missing_texture(&self) -> &Sprite;
+
+    /// Returns the ids of every tile which has a sprite in the tileset
+    fn get_tile_ids(&self) -> Vec<TileId>;
+
+    /// Returns true if the sprite of the character depends on the tiles around it
+    fn is_autotiled(&self, project: &Project, cdda_data: &CDDAData, character: &char) -> bool;
 }
 
 pub struct LegacyTextures {
     textures: HashMap<TileId, SpriteType>,
     fallback_textures: HashMap<String, Sprite>,
+    missing_texture: Sprite,
     region_settings: RegionSettings,
+    fallback_color: FallbackColor,
+    map_edge: MapEdge,
 }
 
 impl LegacyTextures {
-    pub fn new(loader: impl TilesetLoader<LegacyTileset, i32>, region_settings: impl Load<RegionSettings>, image_resource: &mut ResMut<Assets<Image>>) -> Self {
+    pub fn new(loader: &impl TilesetLoader<LegacyTileset, i32>, region_settings: impl Load<RegionSettings>, image_resource: &mut ResMut<Assets<Image>>) -> Self {
         let textures = loader.load_sprite_handles(image_resource).unwrap();
         let fallback_textures = loader.load_fallback_textures().unwrap();
 
+        // The missing texture has the same size as the fallback glyphs, which are as big as a tile
+        let missing_texture_size = fallback_textures.get("?_WHITE").map_or((32, 32), |i| (i.width(), i.height()));
+        let missing_texture = Sprite {
+            fg: Some(Arc::new(SingleForeground::new(image_resource.add(get_missing_texture_image(missing_texture_size.0, missing_texture_size.1))))),
+            fg_layers: vec![],
+            bg: None,
+            offset_x: 0,
+            offset_y: 0,
+            is_animated: false,
+            animation_interval: DEFAULT_ANIMATION_INTERVAL,
+        };
+
         let mut fallback_sprites: HashMap<String, Sprite> = HashMap::new();
 
         for (key, image) in fallback_textures {
@@ -245,10 +511,12 @@ impl LegacyTextures {
                 key,
                 Sprite {
                     fg: Some(Arc::new(SingleForeground::new(image_resource.add(image)))),
+                    fg_layers: vec![],
                     bg: None,
                     offset_x: 0,
                     offset_y: 0,
                     is_animated: false,
+                    animation_interval: DEFAULT_ANIMATION_INTERVAL,
                 },
             );
         };
@@ -256,7 +524,10 @@ impl LegacyTextures {
         return Self {
             textures,
             fallback_textures: fallback_sprites,
+            missing_texture,
             region_settings: region_settings.load().unwrap(),
+            fallback_color: FallbackColor::default(),
+            map_edge: MapEdge::default(),
         };
     }
 }
@@ -270,11 +541,13 @@ macro_rules! define_get_sprite_from_sprite_type {
             coordinates: &Coordinates,
             character: &char,
             sprite_type: &'a SpriteType,
+            map_edge: MapEdge,
         ) -> &'a Sprite {
             return match sprite_type {
                 SpriteType::Single(s) => s,
-                SpriteType::Multitile { center, corner, t_connection, edge, end_piece, unconnected } => {
-                    let tiles_around = project.map_entity.get_tiles_around(coordinates);
+                SpriteType::Multitile { .. } => {
+                    let tiles_around = project.map_entity.get_tiles_around(coordinates, map_edge);
+                    let fill_ter = project.map_entity.object().get_fill_ter();
                     let field_this = project.map_entity.get_ids(cdda_data, character).$field;
 
                     macro_rules! match_tiles_around {
@@ -285,20 +558,20 @@ macro_rules! define_get_sprite_from_sprite_type {
                                     let field_around = project.map_entity.get_ids(cdda_data, &t.character).$field;
                                     let is_same_character = t.character == *character;
 
-                                    let is_this_filled = match (&field_this, &project.map_entity.object().fill_ter) {
+                                    let is_this_filled = match (&field_this, &fill_ter) {
                                         (None, Some(_)) => $is_terrain,
                                         (_, _) => false
                                     };
 
-                                    let is_around_filled = match(&field_around, &project.map_entity.object().fill_ter) {
+                                    let is_around_filled = match(&field_around, &fill_ter) {
                                         (None, Some(_)) => $is_terrain,
                                         (_, _) => false
                                     };
 
                                     let is_same_id = match (&field_around, &field_this) {
                                         (Some(around), Some(this)) => *around == *this,
-                                        (None, Some(this)) => is_around_filled && this == project.map_entity.object().fill_ter.as_ref().unwrap(),
-                                        (Some(around), None) => is_this_filled && around == project.map_entity.object().fill_ter.as_ref().unwrap(),
+                                        (None, Some(this)) => is_around_filled && this == fill_ter.as_ref().unwrap(),
+                                        (Some(around), None) => is_this_filled && around == fill_ter.as_ref().unwrap(),
                                         (None, None) => is_this_filled && is_around_filled
                                     };
 
@@ -314,25 +587,18 @@ macro_rules! define_get_sprite_from_sprite_type {
                     match_tiles_around!(is_tile_left_same_type, 3);
 
 
-                    return match (is_tile_ontop_same_type, is_tile_right_same_type, is_tile_below_same_type, is_tile_left_same_type) {
-                        // Some of the worst code i've ever written lol
-                        (true, true, true, true) => &center,
-                        (true, true, true, false) => &t_connection.west,
-                        (true, true, false, true) => &t_connection.south,
-                        (true, false, true, true) => &t_connection.east,
-                        (false, true, true, true) => &t_connection.north,
-                        (true, true, false, false) => &corner.south_west,
-                        (true, false, false, true) => &corner.south_east,
-                        (false, true, true, false) => &corner.north_west,
-                        (false, false, true, true) => &corner.north_east,
-                        (true, false, false, false) => &end_piece.south,
-                        (false, true, false, false) => &end_piece.west,
-                        (false, false, true, false) => &end_piece.north,
-                        (false, false, false, true) => &end_piece.east,
-                        (false, true, false, true) => &edge.east_west,
-                        (true, false, true, false) => &edge.north_south,
-                        (false, false, false, false) => &unconnected
+                    // Rotating multitiles turn their whole set with the tile, so the connections are looked up in the frame of the tile
+                    let rotation = match sprite_type {
+                        SpriteType::Multitile { is_rotating: true, .. } => project.map_entity.tiles().get(coordinates).map_or(0, |t| t.rotation),
+                        _ => 0
                     };
+
+                    let subtile = MultitileSubtile::from_rotated_connections(
+                        [is_tile_ontop_same_type, is_tile_right_same_type, is_tile_below_same_type, is_tile_left_same_type],
+                        rotation,
+                    );
+
+                    return sprite_type.get_subtile_sprite(&subtile);
                 }
             };
         } 
@@ -353,11 +619,13 @@ macro_rules! define_get_sprite_from_sprite_type_and_tile_id {
             coordinates: &Coordinates,
             tile_id: Option<&TileId>,
             sprite_type: &'a SpriteType,
+            map_edge: MapEdge,
         ) -> &'a Sprite {
             return match sprite_type {
                 SpriteType::Single(s) => s,
-                SpriteType::Multitile { center, corner, t_connection, edge, end_piece, unconnected } => {
-                    let tiles_around = project.map_entity.get_tiles_around(coordinates);
+                SpriteType::Multitile { .. } => {
+                    let tiles_around = project.map_entity.get_tiles_around(coordinates, map_edge);
+                    let fill_ter = project.map_entity.object().get_fill_ter();
 
                     macro_rules! match_tiles_around {
                         ($name: ident, $num: expr) => {
@@ -366,20 +634,20 @@ macro_rules! define_get_sprite_from_sprite_type_and_tile_id {
                                Some(t)  => {
                                     let field_around = project.map_entity.get_ids(cdda_data, &t.character).$field;
 
-                                    let is_this_filled = match (&tile_id, &project.map_entity.object().fill_ter) {
+                                    let is_this_filled = match (&tile_id, &fill_ter) {
                                         (None, Some(_)) => $is_terrain,
                                         (_, _) => false
                                     };
 
-                                    let is_around_filled = match(&field_around, &project.map_entity.object().fill_ter) {
+                                    let is_around_filled = match(&field_around, &fill_ter) {
                                         (None, Some(_)) => $is_terrain,
                                         (_, _) => false
                                     };
 
                                     let is_same_id = match (&field_around, tile_id) {
                                         (Some(around), Some(this)) => *around == *this,
-                                        (None, Some(this)) => is_around_filled && this == project.map_entity.object().fill_ter.as_ref().unwrap(),
-                                        (Some(around), None) => is_this_filled && around == project.map_entity.object().fill_ter.as_ref().unwrap(),
+                                        (None, Some(this)) => is_around_filled && this == fill_ter.as_ref().unwrap(),
+                                        (Some(around), None) => is_this_filled && around == fill_ter.as_ref().unwrap(),
                                         (None, None) => is_this_filled && is_around_filled
                                     };
 
@@ -395,25 +663,18 @@ macro_rules! define_get_sprite_from_sprite_type_and_tile_id {
                     match_tiles_around!(is_tile_left_same_type, 3);
 
 
-                    return match (is_tile_ontop_same_type, is_tile_right_same_type, is_tile_below_same_type, is_tile_left_same_type) {
-                        // Some of the worst code i've ever written lol
-                        (true, true, true, true) => &center,
-                        (true, true, true, false) => &t_connection.west,
-                        (true, true, false, true) => &t_connection.south,
-                        (true, false, true, true) => &t_connection.east,
-                        (false, true, true, true) => &t_connection.north,
-                        (true, true, false, false) => &corner.south_west,
-                        (true, false, false, true) => &corner.south_east,
-                        (false, true, true, false) => &corner.north_west,
-                        (false, false, true, true) => &corner.north_east,
-                        (true, false, false, false) => &end_piece.south,
-                        (false, true, false, false) => &end_piece.west,
-                        (false, false, true, false) => &end_piece.north,
-                        (false, false, false, true) => &end_piece.east,
-                        (false, true, false, true) => &edge.east_west,
-                        (true, false, true, false) => &edge.north_south,
-                        (false, false, false, false) => &unconnected
+                    // Rotating multitiles turn their whole set with the tile, so the connections are looked up in the frame of the tile
+                    let rotation = match sprite_type {
+                        SpriteType::Multitile { is_rotating: true, .. } => project.map_entity.tiles().get(coordinates).map_or(0, |t| t.rotation),
+                        _ => 0
                     };
+
+                    let subtile = MultitileSubtile::from_rotated_connections(
+                        [is_tile_ontop_same_type, is_tile_right_same_type, is_tile_below_same_type, is_tile_left_same_type],
+                        rotation,
+                    );
+
+                    return sprite_type.get_subtile_sprite(&subtile);
                 }
             };
         } 
@@ -424,6 +685,10 @@ define_get_sprite_from_sprite_type_and_tile_id!(terrain, get_terrain_sprite_from
 
 impl GetTexture for LegacyTextures {
     fn get_terrain_texture_from_tile_id(&self, project: &Project, cdda_data: &CDDAData, coordinates: &Coordinates, id: &TileId) -> Option<&Sprite> {
+        // The fill terrain can be a region, which picks a different terrain for every tile.
+        // Like in the game, the region groundcover falls back to the default groundcover
+        let id = self.region_settings.get_random_terrain_from_fill(id).unwrap_or(id);
+
         let sprite_type = match self.textures.get(id) {
             None => return None,
             Some(s) => s
@@ -436,6 +701,7 @@ impl GetTexture for LegacyTextures {
             coordinates,
             Some(id),
             sprite_type,
+            self.map_edge,
         ));
     }
 
@@ -478,6 +744,7 @@ impl GetTexture for LegacyTextures {
                         coordinates,
                         character,
                         sprite_type,
+                        self.map_edge,
                     ));
                 }
 
@@ -492,6 +759,7 @@ impl GetTexture for LegacyTextures {
                     coordinates,
                     character,
                     sprite_type,
+                    self.map_edge,
                 ))
             }
         };
@@ -513,6 +781,7 @@ impl GetTexture for LegacyTextures {
                         coordinates,
                         character,
                         sprite_type,
+                        self.map_edge,
                     ));
                 }
 
@@ -527,6 +796,7 @@ impl GetTexture for LegacyTextures {
                     coordinates,
                     character,
                     sprite_type,
+                    self.map_edge,
                 ))
             }
         };
@@ -547,6 +817,7 @@ impl GetTexture for LegacyTextures {
                     coordinates,
                     character,
                     sprite_type,
+                    self.map_edge,
                 ))
             }
         };
@@ -567,27 +838,285 @@ impl GetTexture for LegacyTextures {
                     coordinates,
                     character,
                     sprite_type,
+                    self.map_edge,
                 ))
             }
         };
     }
 
+    fn get_tile_ids(&self) -> Vec<TileId> {
+        return self.textures.keys().cloned().collect();
+    }
+
+    fn is_autotiled(&self, project: &Project, cdda_data: &CDDAData, character: &char) -> bool {
+        let ids = project.map_entity.get_ids(cdda_data, character);
+
+        let terrain = match ids.terrain {
+            None => project.map_entity.object().get_fill_ter(),
+            Some(t) => Some(t)
+        };
+
+        let is_multitile = |id: &TileId| matches!(self.textures.get(id), Some(SpriteType::Multitile { .. }));
+
+        if let Some(terrain) = terrain {
+            // Regions pick a random terrain, so any of them could be a multitile
+            if self.region_settings.get_random_terrain_from_region(&terrain).is_some() || is_multitile(&terrain) {
+                return true;
+            }
+        }
+
+        if let Some(furniture) = ids.furniture {
+            if self.region_settings.get_random_furniture_from_region(&furniture).is_some() || is_multitile(&furniture) {
+                return true;
+            }
+        }
+
+        return false;
+    }
+
     fn get_fallback_texture(&self, character: &char) -> &Sprite {
-        return self.fallback_textures.get(&format!("{}_WHITE", &character.to_string().to_uppercase())).unwrap_or(
-            self.fallback_textures.get("?_WHITE").unwrap()
-        );
+        return get_fallback_glyph(&self.fallback_textures, character, &self.fallback_color);
+    }
+
+    fn set_fallback_color(&mut self, fallback_color: FallbackColor) {
+        self.fallback_color = fallback_color;
+    }
+
+    fn set_map_edge(&mut self, map_edge: MapEdge) {
+        self.map_edge = map_edge;
+    }
+
+    fn get_missing_texture(&self) -> &Sprite {
+        return &self.missing_texture;
     }
 }
 
+/// Every terrain and furniture id which is known from the loaded tileset and palettes, sorted
+#[derive(Resource, Default)]
+pub struct KnownTileIds {
+    pub ids: Vec<TileId>,
+}
+
+impl KnownTileIds {
+    pub fn contains(&self, id: &TileId) -> bool {
+        return self.ids.binary_search(id).is_ok();
+    }
+}
+
+/// How many tilesets are kept in memory next to the selected one, the least recently used one is evicted first
+pub const MAX_CACHED_TILESETS: usize = 2;
+
+/// A loaded tileset, kept in the cache so switching back to it does not load it from disk again
+pub struct CachedTileset {
+    pub name: String,
+    pub textures: Box<dyn GetTexture>,
+    pub info: TilesetInfo,
+}
+
 #[derive(Resource, Default)]
 pub struct GraphicsResource {
     pub textures: Option<Box<dyn GetTexture>>,
+    /// The name and info of the tileset in textures
+    pub selected: Option<(String, TilesetInfo)>,
+    /// Previously selected tilesets, the least recently used one comes first
+    pub cached: Vec<CachedTileset>,
 }
 
 impl GraphicsResource {
     pub fn new(tileset: Box<dyn GetTexture>) -> Self {
         return Self {
-            textures: Some(tileset)
+            textures: Some(tileset),
+            selected: None,
+            cached: Vec::new(),
+        };
+    }
+
+    /// Removes the tileset with the name from the cache
+    pub fn take_cached(&mut self, name: &str) -> Option<CachedTileset> {
+        let index = self.cached.iter().position(|c| c.name == name)?;
+        return Some(self.cached.remove(index));
+    }
+
+    /// Makes the tileset the selected one. The previously selected tileset is moved to the cache
+    pub fn select(&mut self, tileset: CachedTileset) {
+        if let (Some(textures), Some((name, info))) = (self.textures.take(), self.selected.take()) {
+            self.cached.push(CachedTileset { name, textures, info });
+        }
+
+        while self.cached.len() > MAX_CACHED_TILESETS {
+            let evicted = self.cached.remove(0);
+            log::info!("Unloaded the tileset {} from the cache", evicted.name);
+        }
+
+        self.textures = Some(tileset.textures);
+        self.selected = Some((tileset.name, tileset.info));
+    }
+
+    /// Frees the memory of every tileset except the selected one
+    pub fn unload_cached(&mut self) {
+        self.cached.clear();
+    }
+}
+
+/// Returns the glyph of the character in the fallback color, the fallback glyphs are stored as {CHARACTER}_{COLOR}
+fn get_fallback_glyph<'a, T>(fallback_textures: &'a HashMap<String, T>, character: &char, fallback_color: &FallbackColor) -> &'a T {
+    let character_name = character.to_string().to_uppercase();
+    let color_name = fallback_color.get_color_name();
+
+    // Not every tileset has a glyph of every color, so the white glyph is used in that case
+    return fallback_textures.get(&format!("{}_{}", character_name, color_name))
+        .or(fallback_textures.get(&format!("{}_WHITE", character_name)))
+        .unwrap_or(fallback_textures.get("?_WHITE").unwrap());
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use bevy::asset::Handle;
+    use bevy::prelude::Image;
+
+    use crate::graphics::{Corner, DEFAULT_ANIMATION_INTERVAL, Edge, FullCardinal, get_fallback_glyph, get_missing_texture_image, MultitileSubtile, Sprite, SpriteType};
+    use crate::graphics::tileset::{GetBackground, GetForeground};
+    use crate::settings::data::FallbackColor;
+
+    /// A foreground which always returns the same handle, so the sprite can be identified by its id
+    struct TestForeground(Handle<Image>);
+
+    impl GetForeground for TestForeground {
+        fn get_randomized_sprite(&self) -> &Handle<Image> {
+            return &self.0;
+        }
+
+        fn get_representative_sprite(&self) -> &Handle<Image> {
+            return &self.0;
+        }
+    }
+
+    fn create_foregrounds(first_id: u128, amount: u128) -> Vec<Arc<dyn GetForeground>> {
+        return (first_id..first_id + amount)
+            .map(|id| Arc::new(TestForeground(Handle::weak_from_u128(id))) as Arc<dyn GetForeground>)
+            .collect();
+    }
+
+    fn create_sprite(id: u128) -> Sprite {
+        return Sprite {
+            fg: create_foregrounds(id, 1).pop(),
+            fg_layers: vec![],
+            bg: None,
+            offset_x: 0,
+            offset_y: 0,
+            is_animated: false,
+            animation_interval: DEFAULT_ANIMATION_INTERVAL,
         };
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_missing_texture_is_a_checkerboard() {
+        let image = get_missing_texture_image(32, 32);
+
+        let get_pixel = |x: usize, y: usize| &image.data[(y * 32 + x) * 4..(y * 32 + x) * 4 + 4];
+
+        assert_eq!(get_pixel(0, 0), &[255, 0, 255, 255]);
+        assert_eq!(get_pixel(8, 0), &[0, 0, 0, 255]);
+        assert_eq!(get_pixel(8, 8), &[255, 0, 255, 255]);
+    }
+
+    #[test]
+    pub fn test_multitile_subtiles_match_cdda() {
+        let subtile = |connections: &str| {
+            let is_connected: Vec<bool> = connections.chars().map(|c| c == 'x').collect();
+            MultitileSubtile::from_connections(is_connected[0], is_connected[1], is_connected[2], is_connected[3])
+        };
+
+        // The connections are written in the order top, right, below, left
+        assert_eq!(subtile("xxxx"), MultitileSubtile::Center);
+        assert_eq!(subtile("...."), MultitileSubtile::Unconnected);
+        assert_eq!(subtile("x.x."), MultitileSubtile::Edge(0));
+        assert_eq!(subtile(".x.x"), MultitileSubtile::Edge(1));
+        assert_eq!(subtile(".xx."), MultitileSubtile::Corner(0));
+        assert_eq!(subtile("xx.."), MultitileSubtile::Corner(1));
+        assert_eq!(subtile("x..x"), MultitileSubtile::Corner(2));
+        assert_eq!(subtile("..xx"), MultitileSubtile::Corner(3));
+        assert_eq!(subtile(".xxx"), MultitileSubtile::TConnection(0));
+        assert_eq!(subtile("xxx."), MultitileSubtile::TConnection(1));
+        assert_eq!(subtile("xx.x"), MultitileSubtile::TConnection(2));
+        assert_eq!(subtile("x.xx"), MultitileSubtile::TConnection(3));
+        assert_eq!(subtile("..x."), MultitileSubtile::EndPiece(0));
+        assert_eq!(subtile(".x.."), MultitileSubtile::EndPiece(1));
+        assert_eq!(subtile("x..."), MultitileSubtile::EndPiece(2));
+        assert_eq!(subtile("...x"), MultitileSubtile::EndPiece(3));
+    }
+
+    #[test]
+    pub fn test_subtile_sprite_of_every_connection() {
+        type Group = (Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>, i32, i32, bool, Vec<Arc<dyn GetForeground>>, u64);
+        let group = |first_id: u128| -> Group { (create_foregrounds(first_id, 4), None, 0, 0, false, vec![], DEFAULT_ANIMATION_INTERVAL) };
+
+        // The ids are the group followed by the position of the sprite in the fg list of the tileset
+        let multitile = SpriteType::Multitile {
+            center: create_sprite(0),
+            corner: Corner::from(group(20)),
+            t_connection: FullCardinal::from(group(30)),
+            edge: Edge::from(group(10)),
+            end_piece: FullCardinal::from(group(40)),
+            unconnected: create_sprite(1),
+            is_rotating: false,
+        };
+
+        // The connections are written in the order top, right, below, left
+        let cases = [
+            ("xxxx", 0), ("....", 1),
+            ("x.x.", 10), (".x.x", 11),
+            (".xx.", 20), ("xx..", 21), ("x..x", 22), ("..xx", 23),
+            (".xxx", 30), ("xxx.", 31), ("xx.x", 32), ("x.xx", 33),
+            ("..x.", 40), (".x..", 41), ("x...", 42), ("...x", 43),
+        ];
+
+        for (connections, id) in cases {
+            let is_connected: Vec<bool> = connections.chars().map(|c| c == 'x').collect();
+            let subtile = MultitileSubtile::from_connections(is_connected[0], is_connected[1], is_connected[2], is_connected[3]);
+
+            let sprite = multitile.get_subtile_sprite(&subtile);
+            assert_eq!(sprite.fg.as_ref().unwrap().get_representative_sprite(), &Handle::weak_from_u128(id), "{}", connections);
+        }
+
+        // A single sprite is used for every subtile
+        let single = SpriteType::Single(create_sprite(50));
+        let sprite = single.get_subtile_sprite(&MultitileSubtile::TConnection(2));
+        assert_eq!(sprite.fg.as_ref().unwrap().get_representative_sprite(), &Handle::weak_from_u128(50));
+    }
+
+    #[test]
+    pub fn test_rotated_multitile_turns_the_whole_set() {
+        // A wall which runs from left to right
+        let connections = [false, true, false, true];
+
+        assert_eq!(MultitileSubtile::from_rotated_connections(connections, 0), MultitileSubtile::Edge(1));
+        // The north south edge turned by a quarter runs from left to right
+        assert_eq!(MultitileSubtile::from_rotated_connections(connections, 1), MultitileSubtile::Edge(0));
+
+        // A door with a wall on its left side
+        assert_eq!(MultitileSubtile::from_rotated_connections([false, false, false, true], 0), MultitileSubtile::EndPiece(3));
+        assert_eq!(MultitileSubtile::from_rotated_connections([false, false, false, true], 1), MultitileSubtile::EndPiece(0));
+    }
+
+    #[test]
+    pub fn test_fallback_glyph_of_character_in_color() {
+        let fallback_textures: HashMap<String, &str> = HashMap::from([
+            ("~_WHITE".to_string(), "white water"),
+            ("~_BLUE".to_string(), "blue water"),
+            ("T_WHITE".to_string(), "white tree"),
+            ("?_WHITE".to_string(), "unknown"),
+        ]);
+
+        assert_eq!(*get_fallback_glyph(&fallback_textures, &'~', &FallbackColor::Blue), "blue water");
+        assert_eq!(*get_fallback_glyph(&fallback_textures, &'~', &FallbackColor::White), "white water");
+        // The tileset has no blue tree, so the white one is drawn
+        assert_eq!(*get_fallback_glyph(&fallback_textures, &'T', &FallbackColor::Blue), "white tree");
+        // Lowercase characters use the glyph of the uppercase one
+        assert_eq!(*get_fallback_glyph(&fallback_textures, &'t', &FallbackColor::White), "white tree");
+        assert_eq!(*get_fallback_glyph(&fallback_textures, &'x', &FallbackColor::Red), "unknown");
+    }
+}
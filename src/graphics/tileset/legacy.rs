@@ -1,30 +1,35 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::fs;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
-use std::str::FromStr;
 use std::sync::Arc;
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use bevy::asset::{Assets, Handle};
 use bevy::prelude::{Image, ResMut, Vec2};
 use bevy::render::render_asset::RenderAssetUsages;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
-use image::{DynamicImage, EncodableLayout, GenericImageView, ImageBuffer, imageops, Rgba};
-use image::io::Reader;
+use image::{DynamicImage, GenericImageView, ImageBuffer, imageops, Rgba};
 use log::{debug, error, info, warn};
 use serde::Deserialize;
 use serde_json::Value;
 
 use crate::common::{GetRandom, MeabyMulti, MeabyWeighted, TileId, Weighted};
 use crate::common::io::{Load, LoadError};
-use crate::graphics::{Corner, Edge, FullCardinal, Sprite, SpriteType};
+use crate::graphics::{Corner, DEFAULT_ANIMATION_INTERVAL, Edge, FullCardinal, Sprite, SpriteType};
 use crate::graphics::tileset::{GetBackground, GetForeground};
-use crate::graphics::tileset::TilesetLoader;
+use crate::graphics::tileset::{TilesetLoader, TilesetLoadStatistics, TilesetSource};
 
 const TILESET_INFO_NAME: &'static str = "tileset.txt";
-const AMOUNT_OF_SPRITES_PER_ROW: u32 = 16;
+
+/// Ids of additional tiles that describe how a multitile connects to its neighbors.
+const MULTITILE_CONNECTION_IDS: &'static [&'static str] = &["center", "corner", "t_connection", "edge", "end_piece", "unconnected"];
+
+/// Ids of additional tiles that replace the sprite when the terrain or furniture is in another state.
+/// They are not shown in the editor, all other additional tiles are drawn as layers on top of the main foreground
+const MULTITILE_STATE_IDS: &'static [&'static str] = &["open", "broken"];
+
+/// CDDA draws animated tiles at roughly 60 frames per second and shows every sprite for as many frames as its weight
+const ANIMATION_FRAME_DURATION: u64 = 17;
 
 const FALLBACK_TILE_MAPPING: &'static [(&'static str, u32)] = &[
     // Ignore some textures at the start and end of each color
@@ -92,11 +97,19 @@ const FALLBACK_TILE_MAPPING: &'static [(&'static str, u32)] = &[
 
 #[derive(Debug)]
 pub struct TilesetInfo {
+    /// The factor by which every sprite of the sprite sheets is scaled up when it is displayed
     pub pixelscale: u32,
     pub tile_width: u32,
     pub tile_height: u32,
 }
 
+impl TilesetInfo {
+    /// Returns the width of a tile after the pixelscale was applied
+    pub fn get_scaled_tile_width(&self) -> u32 {
+        return self.tile_width * self.pixelscale.max(1);
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TileGroup {
     pub file: String,
@@ -156,7 +169,8 @@ pub struct LegacyTileset {
     pub tiles: Vec<TileGroup>,
 }
 
-fn get_image_from_tileset(image: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> Image {
+/// Cuts the sprite out of the sprite sheet and scales it up by the pixelscale of the tileset
+fn get_image_from_tileset(image: &DynamicImage, x: u32, y: u32, width: u32, height: u32, pixelscale: u32) -> Image {
     let tile_sprite = image.view(
         x,
         y,
@@ -164,14 +178,20 @@ fn get_image_from_tileset(image: &DynamicImage, x: u32, y: u32, width: u32, heig
         height,
     );
 
+    // Nearest neighbour keeps the pixel art sharp
+    let tile_sprite = match pixelscale {
+        0 | 1 => tile_sprite.to_image(),
+        _ => imageops::resize(&tile_sprite.to_image(), width * pixelscale, height * pixelscale, imageops::FilterType::Nearest)
+    };
+
     let image = Image::new(
         Extent3d {
-            width: width,
-            height: height,
+            width: tile_sprite.width(),
+            height: tile_sprite.height(),
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
-        tile_sprite.to_image().to_vec(),
+        tile_sprite.to_vec(),
         TextureFormat::Rgba8UnormSrgb,
         RenderAssetUsages::all(),
     );
@@ -179,15 +199,80 @@ fn get_image_from_tileset(image: &DynamicImage, x: u32, y: u32, width: u32, heig
     return image;
 }
 
-fn get_xy_from_index(index: &i32, last_group_index: i32) -> Vec2 {
+/// Returns how many sprites fit into a single row of a sprite sheet
+fn get_sprites_per_row(sheet_width: u32, sprite_width: u32) -> u32 {
+    return (sheet_width / sprite_width).max(1);
+}
+
+fn get_xy_from_index(index: &i32, last_group_index: i32, sprites_per_row: u32) -> Vec2 {
     let local_tile_index: u32 = (index - last_group_index) as u32 - 1;
 
     return Vec2::new(
-        (local_tile_index % AMOUNT_OF_SPRITES_PER_ROW) as f32,
-        ((local_tile_index / AMOUNT_OF_SPRITES_PER_ROW) as f32).floor(),
+        (local_tile_index % sprites_per_row) as f32,
+        ((local_tile_index / sprites_per_row) as f32).floor(),
     );
 }
 
+fn get_bg_indices(groups: &Vec<TileGroup>) -> Vec<i32> {
+    let mut indices = Vec::new();
+
+    for tile in groups.iter().flat_map(|g| g.tiles.iter()) {
+        let mut bgs = vec![&tile.bg];
+
+        if let Some(additional_tiles) = &tile.additional_tiles {
+            bgs.extend(additional_tiles.iter().map(|t| &t.bg));
+        }
+
+        for bg in bgs.into_iter().flatten() {
+            match bg {
+                MeabyMulti::Single(bg) => indices.push(*bg.value()),
+                MeabyMulti::Multi(multi) => indices.extend(multi.iter().map(|bg| *bg.value()))
+            }
+        }
+    }
+
+    return indices;
+}
+
+/// Returns every sprite index which is used as a foreground or background of a tile
+/// Returns the milliseconds between two frames of an animated tile, from the average weight of its sprites
+fn get_animation_interval(fg: &Option<MeabyMulti<MeabyWeighted<i32>>>) -> u64 {
+    let frames = match fg {
+        Some(MeabyMulti::Multi(frames)) if !frames.is_empty() => frames,
+        _ => return DEFAULT_ANIMATION_INTERVAL
+    };
+
+    let total_weight: u64 = frames.iter()
+        .map(|f| match f {
+            MeabyWeighted::NotWeighted(_) => 1,
+            MeabyWeighted::Weighted(w) => w.weight as u64
+        })
+        .sum();
+
+    return total_weight * ANIMATION_FRAME_DURATION / frames.len() as u64;
+}
+
+fn get_sprite_indices(groups: &Vec<TileGroup>) -> Vec<i32> {
+    let mut indices = Vec::new();
+
+    for tile in groups.iter().flat_map(|g| g.tiles.iter()) {
+        let mut sprites = vec![&tile.fg, &tile.bg];
+
+        if let Some(additional_tiles) = &tile.additional_tiles {
+            sprites.extend(additional_tiles.iter().flat_map(|t| [&t.fg, &t.bg]));
+        }
+
+        for sprite in sprites.into_iter().flatten() {
+            match sprite {
+                MeabyMulti::Single(sprite) => indices.push(*sprite.value()),
+                MeabyMulti::Multi(multi) => indices.extend(multi.iter().map(|sprite| *sprite.value()))
+            }
+        }
+    }
+
+    return indices;
+}
+
 fn get_sprite_trait_from_single_fg(
     fg: &MeabyWeighted<i32>,
     loaded_sprites: &HashMap<i32, Handle<Image>>,
@@ -207,6 +292,7 @@ fn get_sprite_trait_from_single_fg(
 fn get_sprite_trait_from_multi_fg(
     fg: &Vec<MeabyWeighted<i32>>,
     loaded_sprites: &HashMap<i32, Handle<Image>>,
+    log_details: bool,
 ) -> Option<Arc<dyn GetForeground>> {
     let mut textures: Vec<Weighted<Handle<Image>>> = Vec::new();
 
@@ -214,9 +300,7 @@ fn get_sprite_trait_from_multi_fg(
         match meaby_weighted {
             MeabyWeighted::NotWeighted(v) => {
                 match loaded_sprites.get(v) {
-                    None => {
-                        warn!("Could not find sprite for bg {:?}", v)
-                    }
+                    None => if log_details { warn!("Could not find sprite for fg {:?}", v) },
                     Some(sprite) => {
                         textures.push(Weighted {
                             value: sprite.clone(),
@@ -229,12 +313,20 @@ fn get_sprite_trait_from_multi_fg(
                 }
             }
             MeabyWeighted::Weighted(w) => {
-                textures.push(Weighted::new(loaded_sprites.get(&w.value).unwrap().clone(), w.weight))
+                match loaded_sprites.get(&w.value) {
+                    None => if log_details { warn!("Could not find sprite for fg {:?}", w.value) },
+                    Some(sprite) => {
+                        textures.push(Weighted::new(sprite.clone(), w.weight))
+                    }
+                }
             }
         }
     }
 
-    return Some(Arc::new(WeightedForeground { weighted_sprites: textures }));
+    return match WeightedForeground::new(textures) {
+        None => None,
+        Some(foreground) => Some(Arc::new(foreground))
+    };
 }
 
 fn get_sprite_trait_from_single_bg(
@@ -262,6 +354,7 @@ fn get_sprite_trait_from_single_bg(
 fn get_sprite_trait_from_multi_bg(
     bg: &Vec<MeabyWeighted<i32>>,
     loaded_sprites: &HashMap<i32, Handle<Image>>,
+    log_details: bool,
 ) -> Option<Arc<dyn GetBackground>> {
     let mut textures: Vec<Weighted<Handle<Image>>> = Vec::new();
 
@@ -269,9 +362,7 @@ fn get_sprite_trait_from_multi_bg(
         match meaby_weighted {
             MeabyWeighted::NotWeighted(v) => {
                 match loaded_sprites.get(v) {
-                    None => {
-                        warn!("Could not find sprite for bg {:?}", v)
-                    }
+                    None => if log_details { warn!("Could not find sprite for bg {:?}", v) },
                     Some(sprite) => {
                         textures.push(Weighted {
                             value: sprite.clone(),
@@ -285,9 +376,7 @@ fn get_sprite_trait_from_multi_bg(
             }
             MeabyWeighted::Weighted(w) => {
                 match loaded_sprites.get(&w.value) {
-                    None => {
-                        warn!("Could not find sprite for bg {:?}", bg);
-                    }
+                    None => if log_details { warn!("Could not find sprite for bg {:?}", w.value) },
                     Some(sprite) => {
                         textures.push(Weighted {
                             value: sprite.clone(),
@@ -299,19 +388,38 @@ fn get_sprite_trait_from_multi_bg(
         }
     }
 
-    return Some(Arc::new(WeightedBackground { weighted_sprites: textures }));
+    return match WeightedBackground::new(textures) {
+        None => None,
+        Some(background) => Some(Arc::new(background))
+    };
+}
+
+/// Returns None if there is no bg or if it is an empty array, which some tilesets use instead of leaving it out
+fn get_sprite_trait_from_bg(
+    bg: &Option<MeabyMulti<MeabyWeighted<i32>>>,
+    loaded_sprites: &HashMap<i32, Handle<Image>>,
+    log_details: bool,
+) -> Option<Arc<dyn GetBackground>> {
+    return match bg {
+        None => None,
+        Some(MeabyMulti::Multi(multi)) if multi.is_empty() => None,
+        Some(MeabyMulti::Multi(multi)) => get_sprite_trait_from_multi_bg(multi, loaded_sprites, log_details),
+        Some(MeabyMulti::Single(bg)) => get_sprite_trait_from_single_bg(bg, loaded_sprites)
+    };
 }
 
 fn get_single_fg_and_bg(
     loaded_sprites: &HashMap<i32, Handle<Image>>,
     fg: &MeabyMulti<MeabyWeighted<i32>>,
     bg: &Option<MeabyMulti<MeabyWeighted<i32>>>,
+    log_details: bool,
 ) -> (Option<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>) {
     let get_fg = match fg {
         MeabyMulti::Multi(multi) => {
             get_sprite_trait_from_multi_fg(
                 multi,
                 loaded_sprites,
+                log_details,
             )
         }
         MeabyMulti::Single(fg) => {
@@ -322,34 +430,29 @@ fn get_single_fg_and_bg(
         }
     };
 
-    let get_bg = match bg {
-        Some(bg) => {
-            match bg {
-                MeabyMulti::Multi(multi) => {
-                    get_sprite_trait_from_multi_bg(
-                        multi,
-                        loaded_sprites,
-                    )
-                }
-                MeabyMulti::Single(bg) => {
-                    get_sprite_trait_from_single_bg(
-                        &bg,
-                        loaded_sprites,
-                    )
-                }
-            }
-        }
-        None => None
-    };
+    let get_bg = get_sprite_trait_from_bg(bg, loaded_sprites, log_details);
 
     return (get_fg, get_bg);
 }
 
+/// Returns the four rotations of a single multitile sprite in the order NW, SW, SE, NE.
+/// The same order is used when a tileset defines all four sprites itself.
+/// Like CDDA, the sprite is rotated counterclockwise by 90 degrees for every step
+fn get_rotated_corner_images(image: &DynamicImage) -> Vec<DynamicImage> {
+    return vec![
+        image.clone(),
+        DynamicImage::from(imageops::rotate270(image)),
+        DynamicImage::from(imageops::rotate180(image)),
+        DynamicImage::from(imageops::rotate90(image)),
+    ];
+}
+
 fn get_multi_fg_and_bg(
     loaded_sprites: &HashMap<i32, Handle<Image>>,
     assets: &mut ResMut<Assets<Image>>,
     fg: &MeabyMulti<MeabyWeighted<i32>>,
     bg: &Option<MeabyMulti<MeabyWeighted<i32>>>,
+    log_details: bool,
 ) -> (Vec<Arc<dyn GetForeground>>, Option<Arc<dyn GetBackground>>) {
     let get_fg = match fg {
         MeabyMulti::Single(fg) => {
@@ -363,38 +466,20 @@ fn get_multi_fg_and_bg(
             let sprite = loaded_sprites.get(&fg).unwrap();
 
             let image = assets.get(sprite).unwrap();
-            let mut rotated = Vec::new();
-
-            let image_width = image.width();
-            let image_height = image.height();
-
-            let dyn_image = DynamicImage::from(ImageBuffer::<Rgba<u8>, Vec<u8>>::from_vec(image_width, image_height, image.data.clone()).unwrap());
+            let dyn_image = DynamicImage::from(ImageBuffer::<Rgba<u8>, Vec<u8>>::from_vec(image.width(), image.height(), image.data.clone()).unwrap());
 
-            for i in 0..4 {
-                let new_image = match i {
-                    0 => {
-                        dyn_image.as_bytes().to_vec()
-                    }
-                    1 => {
-                        imageops::rotate270(&dyn_image).to_vec()
-                    }
-                    2 => {
-                        imageops::rotate180(&dyn_image).to_vec()
-                    }
-                    3 => {
-                        imageops::rotate90(&dyn_image).to_vec()
-                    }
-                    _ => { panic!() }
-                };
+            let mut rotated = Vec::new();
 
+            for rotated_image in get_rotated_corner_images(&dyn_image) {
                 let image = Image::new(
                     Extent3d {
-                        width: 32,
-                        height: 32,
+                        // Rotating by 90 degrees swaps the width and height of sprites which are not square
+                        width: rotated_image.width(),
+                        height: rotated_image.height(),
                         depth_or_array_layers: 1,
                     },
                     TextureDimension::D2,
-                    new_image,
+                    rotated_image.to_rgba8().into_raw(),
                     TextureFormat::Rgba8UnormSrgb,
                     RenderAssetUsages::all(),
                 );
@@ -427,53 +512,54 @@ fn get_multi_fg_and_bg(
         }
     };
 
-    let get_bg = match bg {
-        Some(bg) => {
-            match bg {
-                MeabyMulti::Multi(multi) => {
-                    get_sprite_trait_from_multi_bg(
-                        multi,
-                        loaded_sprites,
-                    )
-                }
-                MeabyMulti::Single(bg) => {
-                    get_sprite_trait_from_single_bg(
-                        bg,
-                        loaded_sprites,
-                    )
-                }
-            }
-        }
-        None => None
-    };
+    let get_bg = get_sprite_trait_from_bg(bg, loaded_sprites, log_details);
 
     return (get_fg, get_bg);
 }
 
 
 pub struct LegacyTilesetLoader {
-    pub path: PathBuf,
+    pub source: TilesetSource,
+    /// If every skipped or missing sprite is logged, otherwise they are only counted in the statistics
+    pub log_details: bool,
+    statistics: RefCell<TilesetLoadStatistics>,
 }
 
 impl LegacyTilesetLoader {
+    /// Creates a loader for the tileset at the path, which can either be a folder or a zip archive
     pub fn new(path: PathBuf) -> Self {
         return Self {
-            path
+            source: TilesetSource::from_path(path),
+            log_details: false,
+            statistics: RefCell::new(TilesetLoadStatistics::default()),
         };
     }
 }
 
+/// Always contains at least one sprite
 pub struct WeightedForeground {
     weighted_sprites: Vec<Weighted<Handle<Image>>>,
 }
 
+impl WeightedForeground {
+    /// Returns None if there are no sprites, which happens if none of the referenced sprites could be loaded
+    pub fn new(weighted_sprites: Vec<Weighted<Handle<Image>>>) -> Option<Self> {
+        if weighted_sprites.is_empty() { return None; }
+
+        return Some(Self { weighted_sprites });
+    }
+}
+
 impl GetForeground for WeightedForeground {
     fn get_randomized_sprite(&self) -> &Handle<Image> {
-        return self.weighted_sprites.get_random_weighted().unwrap();
+        return match self.weighted_sprites.get_random_weighted() {
+            None => self.get_representative_sprite(),
+            Some(s) => s
+        };
     }
 
     fn get_representative_sprite(&self) -> &Handle<Image> {
-        return &self.weighted_sprites.first().unwrap().value;
+        return &self.weighted_sprites[0].value;
     }
 }
 
@@ -500,17 +586,30 @@ impl GetForeground for SingleForeground {
 }
 
 
+/// Always contains at least one sprite
 pub struct WeightedBackground {
     weighted_sprites: Vec<Weighted<Handle<Image>>>,
 }
 
+impl WeightedBackground {
+    /// Returns None if there are no sprites, which happens if none of the referenced sprites could be loaded
+    pub fn new(weighted_sprites: Vec<Weighted<Handle<Image>>>) -> Option<Self> {
+        if weighted_sprites.is_empty() { return None; }
+
+        return Some(Self { weighted_sprites });
+    }
+}
+
 impl GetBackground for WeightedBackground {
     fn get_randomized_sprite(&self) -> &Handle<Image> {
-        return self.weighted_sprites.get_random_weighted().unwrap();
+        return match self.weighted_sprites.get_random_weighted() {
+            None => self.get_representative_sprite(),
+            Some(s) => s
+        };
     }
 
     fn get_representative_sprite(&self) -> &Handle<Image> {
-        return &self.weighted_sprites.first().unwrap().value
+        return &self.weighted_sprites[0].value;
     }
 }
 
@@ -528,76 +627,78 @@ impl GetBackground for SingleBackground {
     }
 }
 
-impl Load<LegacyTileset> for LegacyTilesetLoader {
-    fn load(&self) -> Result<LegacyTileset, LoadError> {
-        let info_file = File::open(self.path.join(PathBuf::from_str(TILESET_INFO_NAME).unwrap())).unwrap();
+/// Returns the name of the tileset and the name of its config file.
+/// The keys can be on any line, so comments, blank lines and a different order are allowed
+fn parse_tileset_info(content: &str) -> Result<(String, String), LoadError> {
+    let mut tileset_name = None;
+    let mut config_file_name = None;
+
+    for line in content.lines().map(|l| l.trim()) {
+        if let Some(name) = line.strip_prefix("NAME:") {
+            tileset_name = Some(name.trim().to_string());
+        }
 
-        let mut buf_reader = BufReader::new(info_file);
-        let mut line_content = String::new();
+        if let Some(file_name) = line.strip_prefix("JSON:") {
+            config_file_name = Some(file_name.trim().to_string());
+        }
+    }
 
-        let mut iteration = 0;
+    let config_file_name = match config_file_name {
+        None => return Err(LoadError::Other(anyhow!("{} does not contain the JSON: key", TILESET_INFO_NAME))),
+        Some(n) => n
+    };
 
-        let mut tileset_name = String::new();
-        let mut config_file_name = String::new();
+    return Ok((tileset_name.unwrap_or("Unknown Name".to_string()), config_file_name));
+}
 
-        while let Ok(bytes_read) = buf_reader.read_line(&mut line_content) {
-            if bytes_read == 0 { break; }
+impl Load<LegacyTileset> for LegacyTilesetLoader {
+    fn load(&self) -> Result<LegacyTileset, LoadError> {
+        let info_content = match self.source.read_to_string(TILESET_INFO_NAME) {
+            Ok(c) => c,
+            Err(e) => return Err(LoadError::Other(e))
+        };
 
-            match iteration {
-                // Name of Tileset
-                0 => {
-                    tileset_name = line_content.split("NAME: ")
-                        .last()
-                        .unwrap_or("Unknown Name")
-                        .to_string()
-                        .replace("\n", "");
-                }
-                // Config file name
-                2 => {
-                    config_file_name = line_content
-                        .split("JSON: ")
-                        .last()
-                        .unwrap()
-                        .to_string()
-                        .replace("\n", "");
-                }
-                _ => {}
-            }
+        let (tileset_name, config_file_name) = parse_tileset_info(info_content.as_str())?;
 
-            line_content = String::new();
-            iteration += 1;
-        }
+        let config_file_content = match self.source.read_to_string(config_file_name.as_str()) {
+            Ok(c) => c,
+            Err(e) => return Err(LoadError::Other(e))
+        };
+        let tileset_file_value = match serde_json::from_str::<Value>(&config_file_content) {
+            Ok(v) => v,
+            Err(e) => return Err(LoadError::Other(anyhow!("Could not parse {}: {}", config_file_name, e)))
+        };
 
-        let config_file_path_buf = PathBuf::from_str(config_file_name.as_str()).unwrap();
-        let tileset_file_value = serde_json::from_str::<Value>(&*fs::read_to_string(self.path.join(config_file_path_buf)).unwrap()).unwrap();
-        let tileset_object = tileset_file_value.as_object().unwrap();
+        let tile_info = match tileset_file_value.get("tile_info").and_then(|t| t.as_array()).and_then(|t| t.first()) {
+            None => return Err(LoadError::Other(anyhow!("{} does not contain the tile_info", config_file_name))),
+            Some(t) => t
+        };
 
-        let tile_info = tileset_object
-            .get("tile_info")
-            .unwrap()
-            .as_array()
-            .unwrap()
-            .first()
-            .unwrap()
-            .as_object()
-            .unwrap();
+        let get_tile_info_value = |key: &str| {
+            return match tile_info.get(key).and_then(|v| v.as_u64()) {
+                None => Err(LoadError::Other(anyhow!("The tile_info of {} does not contain the {}", config_file_name, key))),
+                Some(v) => Ok(v as u32)
+            };
+        };
 
         let tileset_info = TilesetInfo {
-            pixelscale: tile_info.get("pixelscale").unwrap().as_u64().unwrap() as u32,
-            tile_width: tile_info.get("width").unwrap().as_u64().unwrap() as u32,
-            tile_height: tile_info.get("height").unwrap().as_u64().unwrap() as u32,
+            pixelscale: get_tile_info_value("pixelscale")?,
+            tile_width: get_tile_info_value("width")?,
+            tile_height: get_tile_info_value("height")?,
         };
 
-        let tiles_new = tileset_object
-            .get("tiles-new")
-            .unwrap()
-            .as_array()
-            .unwrap();
+        let tiles_new = match tileset_file_value.get("tiles-new").and_then(|t| t.as_array()) {
+            None => return Err(LoadError::Other(anyhow!("{} does not contain the tiles-new", config_file_name))),
+            Some(t) => t
+        };
 
         let mut tiles = Vec::new();
 
         for tile_group in tiles_new.iter() {
-            tiles.push(serde_json::from_value::<TileGroup>(tile_group.clone()).unwrap());
+            match serde_json::from_value::<TileGroup>(tile_group.clone()) {
+                Ok(g) => tiles.push(g),
+                Err(e) => return Err(LoadError::Other(anyhow!("Could not parse a sprite sheet of {}: {}", config_file_name, e)))
+            }
         }
 
         return Ok(LegacyTileset {
@@ -611,19 +712,22 @@ impl Load<LegacyTileset> for LegacyTilesetLoader {
 
 impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
     fn load_textures(&self) -> Result<HashMap<i32, Image>, Error> {
-        let tileset = self.load().unwrap();
+        let tileset = match self.load() {
+            Ok(t) => t,
+            Err(e) => return Err(anyhow!("{}", e))
+        };
         let mut textures: HashMap<i32, Image> = HashMap::new();
         let mut out_of_range = Vec::new();
 
+        // A background can be defined in a different file than the tile it belongs to
+        let bg_indices = get_bg_indices(&tileset.tiles);
+
         for group in tileset.tiles.iter() {
             if group.file == "fallback.png".to_string() {
                 continue;
             }
 
-            let image = Reader::open(self.path.join(PathBuf::from_str(group.file.as_str()).unwrap()))
-                .unwrap()
-                .decode()
-                .unwrap();
+            let image = self.source.read_image(group.file.as_str())?;
 
             // TODO Revisit
             // Not a good way to do this, but i just couldn't for the life of me figure out how to get the range
@@ -635,13 +739,17 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
 
             let end: u32 = range_vec.last().unwrap().parse().unwrap();
 
+            let group_width: u32 = group.sprite_width.unwrap_or(tileset.info.tile_width);
+            let group_height: u32 = group.sprite_height.unwrap_or(tileset.info.tile_height);
+
+            // Sprite sheets can have any width, so the amount of sprites per row has to be calculated for every sheet
+            let sprites_per_row = get_sprites_per_row(image.width(), group_width);
+
             for tile in group.tiles.iter() {
-                let group_width: u32 = group.sprite_width.unwrap_or(tileset.info.tile_width);
-                let group_height: u32 = group.sprite_height.unwrap_or(tileset.info.tile_height);
 
                 match &tile.fg {
                     None => {
-                        info!("No fg for tile {:?}", tile.id);
+                        if self.log_details { info!("No fg for tile {:?}", tile.id); }
                     }
                     Some(fg) => {
                         match fg {
@@ -653,7 +761,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                 continue;
                                             }
 
-                                            let xy = get_xy_from_index(fg, start as i32);
+                                            let xy = get_xy_from_index(fg, start as i32, sprites_per_row);
 
                                             let image = get_image_from_tileset(
                                                 &image,
@@ -661,6 +769,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                 xy.y as u32 * group_height,
                                                 group_width,
                                                 group_height,
+                                                tileset.info.pixelscale,
                                             );
 
                                             textures.insert(*fg, image);
@@ -670,7 +779,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                 continue;
                                             }
 
-                                            let xy = get_xy_from_index(&w.value, start as i32);
+                                            let xy = get_xy_from_index(&w.value, start as i32, sprites_per_row);
 
                                             let image = get_image_from_tileset(
                                                 &image,
@@ -678,6 +787,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                 xy.y as u32 * group_height,
                                                 group_width,
                                                 group_height,
+                                                tileset.info.pixelscale,
                                             );
 
                                             textures.insert(w.value, image);
@@ -692,11 +802,11 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                             continue;
                                         }
 
-                                        let xy = get_xy_from_index(fg, start as i32);
+                                        let xy = get_xy_from_index(fg, start as i32, sprites_per_row);
 
                                         if fg < &(start as i32) || fg > &(end as i32) {
-                                            warn!("fg {} out of range", fg);
-                                            out_of_range.push((fg, xy));
+                                            if self.log_details { warn!("fg {} out of range", fg); }
+                                            out_of_range.push(*fg);
                                             continue;
                                         }
 
@@ -706,6 +816,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                             xy.y as u32 * group_height,
                                             group_width,
                                             group_height,
+                                            tileset.info.pixelscale,
                                         );
 
                                         textures.insert(*fg, image);
@@ -715,9 +826,10 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                             continue;
                                         }
 
-                                        let xy = get_xy_from_index(&w.value, start as i32);
+                                        let xy = get_xy_from_index(&w.value, start as i32, sprites_per_row);
 
                                         if w.value < start as i32 || w.value > end as i32 {
+                                            out_of_range.push(w.value);
                                             continue;
                                         }
 
@@ -727,6 +839,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                             xy.y as u32 * group_height,
                                             group_width,
                                             group_height,
+                                            tileset.info.pixelscale,
                                         );
 
                                         textures.insert(w.value, image);
@@ -743,7 +856,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                         for additional_tile in tiles {
                             match &additional_tile.fg {
                                 None => {
-                                    info!("Additional Tile with parent id {:?} has no fg", tile.fg);
+                                    if self.log_details { info!("Additional Tile with parent id {:?} has no fg", tile.fg); }
                                 }
                                 Some(fg) => {
                                     match fg {
@@ -755,7 +868,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                             continue;
                                                         }
 
-                                                        let xy = get_xy_from_index(fg, start as i32);
+                                                        let xy = get_xy_from_index(fg, start as i32, sprites_per_row);
 
                                                         let image = get_image_from_tileset(
                                                             &image,
@@ -763,6 +876,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                             xy.y as u32 * group_height,
                                                             group_width,
                                                             group_height,
+                                                            tileset.info.pixelscale,
                                                         );
 
                                                         textures.insert(*fg, image);
@@ -772,7 +886,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                             continue;
                                                         }
 
-                                                        let xy = get_xy_from_index(&w.value, start as i32);
+                                                        let xy = get_xy_from_index(&w.value, start as i32, sprites_per_row);
 
                                                         let image = get_image_from_tileset(
                                                             &image,
@@ -780,6 +894,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                             xy.y as u32 * group_height,
                                                             group_width,
                                                             group_height,
+                                                            tileset.info.pixelscale,
                                                         );
 
                                                         textures.insert(w.value, image);
@@ -794,7 +909,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                         continue;
                                                     }
 
-                                                    let xy = get_xy_from_index(fg, start as i32);
+                                                    let xy = get_xy_from_index(fg, start as i32, sprites_per_row);
 
                                                     // For some fucking reason the
                                                     // Grass tiles in the UndeadPeopleTileset specify a fg id which isn't
@@ -802,6 +917,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                     // TODO FIX
 
                                                     if fg < &(start as i32) || fg > &(end as i32) {
+                                                        out_of_range.push(*fg);
                                                         continue;
                                                     }
 
@@ -811,6 +927,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                         xy.y as u32 * group_height,
                                                         group_width,
                                                         group_height,
+                                                        tileset.info.pixelscale,
                                                     );
 
                                                     textures.insert(*fg, image);
@@ -820,9 +937,10 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                         continue;
                                                     }
 
-                                                    let xy = get_xy_from_index(&w.value, start as i32);
+                                                    let xy = get_xy_from_index(&w.value, start as i32, sprites_per_row);
 
                                                     if w.value < start as i32 || w.value > end as i32 {
+                                                        out_of_range.push(w.value);
                                                         continue;
                                                     }
 
@@ -832,6 +950,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                                         xy.y as u32 * group_height,
                                                         group_width,
                                                         group_height,
+                                                        tileset.info.pixelscale,
                                                     );
 
                                                     textures.insert(w.value, image);
@@ -845,26 +964,55 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                     }
                 }
             }
+
+            for bg in bg_indices.iter() {
+                if textures.contains_key(bg) || *bg <= start as i32 || *bg > end as i32 {
+                    continue;
+                }
+
+                let xy = get_xy_from_index(bg, start as i32, sprites_per_row);
+
+                let image = get_image_from_tileset(
+                    &image,
+                    xy.x as u32 * group_width,
+                    xy.y as u32 * group_height,
+                    group_width,
+                    group_height,
+                    tileset.info.pixelscale,
+                );
+
+                textures.insert(*bg, image);
+            }
         }
 
+        // An index can be out of range in another sprite sheet and still be loaded from the sheet it belongs to
+        out_of_range.retain(|i| !textures.contains_key(i));
+        out_of_range.sort();
+        out_of_range.dedup();
+
+        let mut statistics = self.statistics.borrow_mut();
+        statistics.textures = textures.len();
+        statistics.out_of_range = out_of_range.len();
+
         return Ok(textures);
     }
     fn load_fallback_textures(&self) -> Result<HashMap<String, Image>, Error> {
-        let tileset = self.load().unwrap();
+        let tileset = match self.load() {
+            Ok(t) => t,
+            Err(e) => return Err(anyhow!("{}", e))
+        };
         let mut fallback_textures: HashMap<String, Image> = HashMap::new();
 
         for group in tileset.tiles.iter() {
             if group.file != "fallback.png".to_string() { continue; }
 
-            let image = Reader::open(self.path.join(PathBuf::from_str(group.file.as_str()).unwrap()))
-                .unwrap()
-                .decode()
-                .unwrap();
+            let image = self.source.read_image(group.file.as_str())?;
+            let sprites_per_row = get_sprites_per_row(image.width(), tileset.info.tile_width);
 
             for color in group.ascii.as_ref().unwrap().iter() {
                 for (character, index) in FALLBACK_TILE_MAPPING {
-                    let x = (color.offset + 1 + index) % AMOUNT_OF_SPRITES_PER_ROW * tileset.info.tile_width;
-                    let y = (color.offset + 1 + index) / AMOUNT_OF_SPRITES_PER_ROW * tileset.info.tile_height;
+                    let x = (color.offset + 1 + index) % sprites_per_row * tileset.info.tile_width;
+                    let y = (color.offset + 1 + index) / sprites_per_row * tileset.info.tile_height;
 
                     let fallback_image = get_image_from_tileset(
                         &image,
@@ -872,6 +1020,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                         y,
                         tileset.info.tile_width,
                         tileset.info.tile_height,
+                        tileset.info.pixelscale,
                     );
 
                     fallback_textures.insert(format!("{}_{}", character, color.color), fallback_image);
@@ -879,11 +1028,16 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
             };
         }
 
+        self.statistics.borrow_mut().fallback_textures = fallback_textures.len();
+
         return Ok(fallback_textures);
     }
 
     fn load_sprite_handles(&self, image_resource: &mut ResMut<Assets<Image>>) -> Result<HashMap<TileId, SpriteType>, Error> {
-        let tileset = self.load().unwrap();
+        let tileset = match self.load() {
+            Ok(t) => t,
+            Err(e) => return Err(anyhow!("{}", e))
+        };
         let mut loaded_sprites: HashMap<i32, Handle<Image>> = HashMap::new();
 
         self.load_textures()
@@ -895,12 +1049,14 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
         let mut assigned_textures: HashMap<TileId, SpriteType> = HashMap::new();
 
         for group in tileset.tiles.iter() {
-            let offset_x = group.sprite_offset_x.unwrap_or(0);
-            let offset_y = group.sprite_offset_y.unwrap_or(0);
+            // The offsets are defined in pixels of the sprite sheet, so they are scaled with the sprites
+            let offset_x = group.sprite_offset_x.unwrap_or(0) * tileset.info.pixelscale.max(1) as i32;
+            let offset_y = group.sprite_offset_y.unwrap_or(0) * tileset.info.pixelscale.max(1) as i32;
 
             for tile in group.tiles.iter() {
                 let get_main_fg: Option<Arc<dyn GetForeground>> = match &tile.fg {
-                    None => { continue; }
+                    // Some floors only define a background
+                    None => None,
                     Some(fg) => {
                         match fg {
                             MeabyMulti::Single(fg) => {
@@ -908,7 +1064,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                     MeabyWeighted::NotWeighted(fg) => {
                                         match loaded_sprites.get(fg) {
                                             None => {
-                                                error!("No Sprite found for fg {}", fg);
+                                                if self.log_details { error!("No Sprite found for fg {}", fg); }
                                                 None
                                             }
                                             Some(sprite) => {
@@ -931,77 +1087,53 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                     match fg {
                                         MeabyWeighted::NotWeighted(fg) => {
                                             // TODO: Check how to handle weights
-                                            sprites.push(Weighted::new(loaded_sprites.get(fg).unwrap().clone(), 0));
+                                            match loaded_sprites.get(fg) {
+                                                None => if self.log_details { warn!("Could not find sprite for fg {:?}", fg) },
+                                                Some(sprite) => sprites.push(Weighted::new(sprite.clone(), 0))
+                                            };
                                         }
                                         MeabyWeighted::Weighted(w) => {
-                                            sprites.push(Weighted::new(loaded_sprites.get(&w.value).unwrap().clone(), w.weight));
+                                            match loaded_sprites.get(&w.value) {
+                                                None => if self.log_details { warn!("Could not find sprite for fg {:?}", w.value) },
+                                                Some(sprite) => sprites.push(Weighted::new(sprite.clone(), w.weight))
+                                            };
                                         }
                                     }
                                 }
 
-                                Some(Arc::new(WeightedForeground { weighted_sprites: sprites }))
+                                match WeightedForeground::new(sprites) {
+                                    None => None,
+                                    Some(foreground) => Some(Arc::new(foreground))
+                                }
                             }
                         }
                     }
                 };
 
-                let get_main_bg: Option<Arc<dyn GetBackground>> = match &tile.bg {
-                    None => None,
-                    Some(bg) => {
-                        match bg {
-                            MeabyMulti::Single(bg) => {
-                                match bg {
-                                    MeabyWeighted::NotWeighted(bg) => {
-                                        match loaded_sprites.get(bg) {
-                                            None => None,
-                                            Some(sprite) => Some(Arc::new(SingleBackground { sprite: sprite.clone() }))
-                                        }
-                                    }
-                                    MeabyWeighted::Weighted(w) => {
-                                        // TODO: Revisit
-                                        // Not sure what to do here
-                                        match loaded_sprites.get(&w.value) {
-                                            None => None,
-                                            Some(sprite) => Some(Arc::new(SingleBackground { sprite: sprite.clone() }))
-                                        }
-                                    }
-                                }
-                            }
-                            MeabyMulti::Multi(bg) => {
-                                // Handle weird case when bg is an empty array
-                                if bg.len() > 0 {
-                                    let mut sprites: Vec<Weighted<Handle<Image>>> = Vec::new();
-
-                                    for bg in bg.iter() {
-                                        match bg {
-                                            MeabyWeighted::NotWeighted(bg) => {
-                                                // TODO: Revisit
-                                                // Not sure what to do here
-                                                match loaded_sprites.get(bg) {
-                                                    None => {}
-                                                    Some(sprite) => sprites.push(Weighted::new(sprite.clone(), 0))
-                                                };
-                                            }
-                                            MeabyWeighted::Weighted(w) => {
-                                                match loaded_sprites.get(&w.value) {
-                                                    None => {}
-                                                    Some(sprite) => sprites.push(Weighted::new(sprite.clone(), w.weight))
-                                                };
-                                            }
-                                        }
-                                    }
+                let get_main_bg = get_sprite_trait_from_bg(&tile.bg, &loaded_sprites, self.log_details);
 
-                                    Some(Arc::new(WeightedBackground { weighted_sprites: sprites }))
-                                } else {
-                                    None
-                                }
-                            }
-                        }
-                    }
+                let fg_layers: Vec<Arc<dyn GetForeground>> = match &tile.additional_tiles {
+                    None => vec![],
+                    Some(additional_tiles) => additional_tiles.iter()
+                        .filter(|a| !MULTITILE_CONNECTION_IDS.contains(&a.id.as_str()) && !MULTITILE_STATE_IDS.contains(&a.id.as_str()))
+                        .filter_map(|a| match &a.fg {
+                            None => None,
+                            Some(fg) => get_single_fg_and_bg(&loaded_sprites, fg, &None, self.log_details).0
+                        })
+                        .collect()
+                };
+
+                let animation_interval = get_animation_interval(&tile.fg);
+
+                let has_connections = match &tile.additional_tiles {
+                    None => false,
+                    Some(additional_tiles) => additional_tiles.iter()
+                        .any(|a| MULTITILE_CONNECTION_IDS.contains(&a.id.as_str()))
                 };
 
                 match &tile.additional_tiles {
-                    None => {
+                    // Tiles that only have foreground layers are still single tiles
+                    None | Some(_) if !has_connections => {
                         match &tile.id {
                             MeabyMulti::Single(v) => {
                                 debug!("Loaded tile {:?}", v);
@@ -1009,10 +1141,12 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                     v.clone(),
                                     SpriteType::Single(Sprite {
                                         fg: get_main_fg.clone(),
+                                        fg_layers: fg_layers.clone(),
                                         bg: get_main_bg.clone(),
                                         offset_x,
                                         offset_y,
                                         is_animated: tile.is_animated.unwrap_or(false),
+                                        animation_interval,
                                     }),
                                 );
                             }
@@ -1023,10 +1157,12 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                         value.clone(),
                                         SpriteType::Single(Sprite {
                                             fg: get_main_fg.clone(),
+                                            fg_layers: fg_layers.clone(),
                                             bg: get_main_bg.clone(),
                                             offset_x,
                                             offset_y,
                                             is_animated: tile.is_animated.unwrap_or(false),
+                                            animation_interval,
                                         }),
                                     );
                                 }
@@ -1055,21 +1191,23 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
 
                                 let bg = &additional_tile.bg;
 
-                                // TODO: Figure out what a id of 'broken' means
                                 match additional_tile.id.as_str() {
                                     "center" => {
                                         let v = get_single_fg_and_bg(
                                             &loaded_sprites,
                                             fg,
                                             bg,
+                                            self.log_details,
                                         );
 
                                         center = Some(Sprite {
                                             fg: v.0,
+                                            fg_layers: fg_layers.clone(),
                                             bg: v.1,
                                             offset_x,
                                             offset_y,
                                             is_animated: tile.is_animated.unwrap_or(false),
+                                            animation_interval,
                                         })
                                     }
                                     "corner" => {
@@ -1078,9 +1216,9 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                             image_resource,
                                             fg,
                                             bg,
+                                            self.log_details,
                                         );
-                                        corner = Some(Corner::from((v.0, v.1, offset_x, offset_y, tile.is_animated.unwrap_or(false)
-                                        )))
+                                        corner = Some(Corner::from((v.0, v.1, offset_x, offset_y, tile.is_animated.unwrap_or(false), fg_layers.clone(), animation_interval)))
                                     }
                                     "t_connection" => {
                                         let v = get_multi_fg_and_bg(
@@ -1088,8 +1226,9 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                             image_resource,
                                             fg,
                                             bg,
+                                            self.log_details,
                                         );
-                                        t_connection = Some(FullCardinal::from((v.0, v.1, offset_x, offset_y, tile.is_animated.unwrap_or(false))));
+                                        t_connection = Some(FullCardinal::from((v.0, v.1, offset_x, offset_y, tile.is_animated.unwrap_or(false), fg_layers.clone(), animation_interval)));
                                     }
                                     "edge" => {
                                         let v = get_multi_fg_and_bg(
@@ -1097,8 +1236,9 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                             image_resource,
                                             fg,
                                             bg,
+                                            self.log_details,
                                         );
-                                        edge = Some(Edge::from((v.0, v.1, offset_x, offset_y, tile.is_animated.unwrap_or(false))));
+                                        edge = Some(Edge::from((v.0, v.1, offset_x, offset_y, tile.is_animated.unwrap_or(false), fg_layers.clone(), animation_interval)));
                                     }
                                     "end_piece" => {
                                         let v = get_multi_fg_and_bg(
@@ -1106,33 +1246,40 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                             image_resource,
                                             fg,
                                             bg,
+                                            self.log_details,
                                         );
-                                        end_piece = Some(FullCardinal::from((v.0, v.1, offset_x, offset_y, tile.is_animated.unwrap_or(false))));
+                                        end_piece = Some(FullCardinal::from((v.0, v.1, offset_x, offset_y, tile.is_animated.unwrap_or(false), fg_layers.clone(), animation_interval)));
                                     }
                                     "unconnected" => {
                                         let (get_fg, get_bg) = get_single_fg_and_bg(
                                             &loaded_sprites,
                                             fg,
                                             bg,
+                                            self.log_details,
                                         );
                                         unconnected = Some(Sprite {
                                             fg: get_fg,
+                                            fg_layers: fg_layers.clone(),
                                             bg: get_bg,
                                             offset_x,
                                             offset_y,
                                             is_animated: tile.is_animated.unwrap_or(false),
+                                            animation_interval,
                                         });
                                     }
-                                    _ => { warn!("Got Unexpected id {} for fg {:?}", additional_tile.id, fg) }
+                                    "open" | "broken" => {}
+                                    _ => { if self.log_details { warn!("Got Unexpected id {} for fg {:?}, drawing it as a foreground layer", additional_tile.id, fg) } }
                                 }
                             }
 
                             let default_sprite = Sprite {
                                 fg: get_main_fg.clone(),
+                                fg_layers: fg_layers.clone(),
                                 bg: get_main_bg.clone(),
                                 offset_x: 0,
                                 offset_y: 0,
                                 is_animated: false,
+                                animation_interval: DEFAULT_ANIMATION_INTERVAL,
                             };
 
                             assigned_textures.insert(
@@ -1162,6 +1309,7 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
                                         west: default_sprite.clone(),
                                     }),
                                     unconnected: unconnected.unwrap_or(default_sprite.clone()),
+                                    is_rotating: tile.is_rotate_allowed.unwrap_or(false),
                                 },
                             );
                         }
@@ -1170,19 +1318,57 @@ impl TilesetLoader<LegacyTileset, i32> for LegacyTilesetLoader {
             }
         };
 
+        let mut missing_sprites: Vec<i32> = get_sprite_indices(&tileset.tiles).into_iter()
+            .filter(|i| !loaded_sprites.contains_key(i))
+            .collect();
+        missing_sprites.sort();
+        missing_sprites.dedup();
+
+        let mut statistics = self.statistics.borrow_mut();
+        statistics.missing_sprites = missing_sprites.len();
+        statistics.multitiles = assigned_textures.values()
+            .filter(|s| matches!(s, SpriteType::Multitile { .. }))
+            .count();
+
         return Ok(assigned_textures);
     }
+
+    fn get_statistics(&self) -> TilesetLoadStatistics {
+        return self.statistics.borrow().clone();
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
+    use std::collections::HashMap;
     use std::path::PathBuf;
 
+    use bevy::asset::Assets;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Image, ResMut, World};
+    use image::GenericImageView;
     use image::io::Reader;
 
+    use crate::common::{MeabyMulti, MeabyWeighted, TileId, Weighted};
     use crate::common::io::Load;
-    use crate::graphics::tileset::legacy::{get_image_from_tileset, LegacyTilesetLoader};
+    use crate::graphics::{DEFAULT_ANIMATION_INTERVAL, SpriteType};
+    use crate::graphics::tileset::legacy::{ANIMATION_FRAME_DURATION, get_animation_interval, get_image_from_tileset, get_rotated_corner_images, get_sprite_trait_from_multi_bg, get_sprite_trait_from_multi_fg, get_sprites_per_row, get_xy_from_index, LegacyTilesetLoader, parse_tileset_info, WeightedForeground};
     use crate::graphics::tileset::TilesetLoader;
+    use crate::map::systems::Animated;
+
+    /// Loads the sprites of the tileset in testing_data
+    fn load_test_tileset() -> (LegacyTilesetLoader, HashMap<TileId, SpriteType>) {
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+
+        let mut state: SystemState<ResMut<Assets<Image>>> = SystemState::new(&mut world);
+        let mut images = state.get_mut(&mut world);
+
+        let loader = LegacyTilesetLoader::new(PathBuf::from("./testing_data"));
+        let sprites = loader.load_sprite_handles(&mut images).unwrap();
+
+        return (loader, sprites);
+    }
 
     #[test]
     pub fn test_load_legacy_tileset() {
@@ -1224,9 +1410,201 @@ pub mod tests {
             0,
             32,
             32,
+            1,
         );
 
         assert_eq!(item.data, supposed_data.data);
     }
-}
 
+    #[test]
+    pub fn test_rotated_corner_order() {
+        // The corner connects to the east and to the south, so it is the north west corner
+        let image = Reader::open(PathBuf::from("./testing_data/asymmetric_corner.png"))
+            .unwrap()
+            .decode()
+            .unwrap();
+
+        let rotated = get_rotated_corner_images(&image);
+
+        let is_filled = |index: usize, x: u32, y: u32| rotated[index].get_pixel(x, y).0[3] != 0;
+
+        // North west connects to the east and south
+        assert!(is_filled(0, 2, 1) && is_filled(0, 1, 2));
+        // South west connects to the north and east
+        assert!(is_filled(1, 1, 0) && is_filled(1, 2, 1));
+        assert!(!is_filled(1, 1, 2));
+        // South east connects to the north and west
+        assert!(is_filled(2, 1, 0) && is_filled(2, 0, 1));
+        assert!(!is_filled(2, 2, 1));
+        // North east connects to the south and west
+        assert!(is_filled(3, 1, 2) && is_filled(3, 0, 1));
+        assert!(!is_filled(3, 1, 0));
+    }
+
+    #[test]
+    pub fn test_xy_from_index_8_wide_sheet() {
+        let sprites_per_row = get_sprites_per_row(8 * 32, 32);
+        assert_eq!(sprites_per_row, 8);
+
+        // Indices start at 1 in the tileset config
+        let xy = get_xy_from_index(&10, 0, sprites_per_row);
+        assert_eq!((xy.x, xy.y), (1., 1.));
+    }
+
+    #[test]
+    pub fn test_xy_from_index_32_wide_sheet() {
+        let sprites_per_row = get_sprites_per_row(32 * 32, 32);
+        assert_eq!(sprites_per_row, 32);
+
+        let xy = get_xy_from_index(&10, 0, sprites_per_row);
+        assert_eq!((xy.x, xy.y), (9., 0.));
+
+        let xy = get_xy_from_index(&40, 5, sprites_per_row);
+        assert_eq!((xy.x, xy.y), (2., 1.));
+    }
+
+    #[test]
+    pub fn test_load_sprite_offsets() {
+        let data = LegacyTilesetLoader::new(PathBuf::from("./testing_data")).load().unwrap();
+
+        let offset_group = data.tiles.get(1).unwrap();
+
+        assert_eq!(offset_group.sprite_offset_x, Some(-16));
+        assert_eq!(offset_group.sprite_offset_y, Some(-8));
+    }
+
+    #[test]
+    pub fn test_animation_interval_reaches_component() {
+        let (_, sprites) = load_test_tileset();
+
+        let animated = |id: &str| match sprites.get(id).unwrap() {
+            SpriteType::Single(sprite) => Animated::from_sprite(sprite),
+            SpriteType::Multitile { .. } => panic!("Expected {} to be a single sprite", id)
+        };
+
+        // Both sprites of animated_item are shown for 15 frames
+        assert_eq!(animated("animated_item").unwrap().cooldown, 15 * ANIMATION_FRAME_DURATION);
+        assert!(animated("offset_item").is_none());
+    }
+
+    #[test]
+    pub fn test_animation_interval_of_sprite_weights() {
+        let frames = vec![MeabyWeighted::Weighted(Weighted::new(10, 4)), MeabyWeighted::NotWeighted(11), MeabyWeighted::Weighted(Weighted::new(12, 7))];
+
+        assert_eq!(get_animation_interval(&Some(MeabyMulti::Multi(frames))), 4 * ANIMATION_FRAME_DURATION);
+        assert_eq!(get_animation_interval(&Some(MeabyMulti::Single(MeabyWeighted::NotWeighted(10)))), DEFAULT_ANIMATION_INTERVAL);
+        assert_eq!(get_animation_interval(&None), DEFAULT_ANIMATION_INTERVAL);
+    }
+
+    #[test]
+    pub fn test_rotating_multitile_door() {
+        let (_, sprites) = load_test_tileset();
+
+        match sprites.get("t_door_rotating").unwrap() {
+            SpriteType::Multitile { is_rotating, end_piece, .. } => {
+                assert!(is_rotating);
+                assert!(end_piece.north.fg.is_some());
+            }
+            SpriteType::Single(_) => panic!("Expected the door to be a multitile")
+        }
+    }
+
+    #[test]
+    pub fn test_empty_bg_of_additional_tiles_is_none() {
+        let (_, sprites) = load_test_tileset();
+
+        match sprites.get("t_fence_empty_bg").unwrap() {
+            SpriteType::Multitile { center, corner, .. } => {
+                assert!(center.bg.is_none());
+                assert!(corner.north_west.bg.is_none());
+                assert!(corner.north_west.fg.is_some());
+            }
+            SpriteType::Single(_) => panic!("Expected the fence to be a multitile")
+        }
+    }
+
+    #[test]
+    pub fn test_only_overlays_are_foreground_layers() {
+        let (_, sprites) = load_test_tileset();
+
+        match sprites.get("t_window_overlay").unwrap() {
+            // 'broken' and 'open' are other states of the window, only 'curtains' is drawn on top of it
+            SpriteType::Single(sprite) => assert_eq!(sprite.fg_layers.len(), 1),
+            SpriteType::Multitile { .. } => panic!("Expected the window to be a single tile")
+        }
+    }
+
+    #[test]
+    pub fn test_load_tile_without_fg() {
+        let (_, sprites) = load_test_tileset();
+
+        match sprites.get("bg_only_floor") {
+            Some(SpriteType::Single(sprite)) => {
+                assert!(sprite.fg.is_none());
+                assert!(sprite.bg.is_some());
+            }
+            _ => panic!("Expected bg_only_floor to be loaded as a single sprite")
+        }
+    }
+
+    #[test]
+    pub fn test_load_tall_sprite_with_native_size() {
+        let data = LegacyTilesetLoader::new(PathBuf::from("./testing_data")).load_textures().unwrap();
+
+        // The sprite is not squished into the 32x32 tile
+        let tall_sprite = data.get(&5).unwrap();
+        assert_eq!(tall_sprite.width(), 32);
+        assert_eq!(tall_sprite.height(), 64);
+    }
+
+    #[test]
+    pub fn test_parse_reordered_tileset_info() {
+        let (name, config_file_name) = parse_tileset_info(include_str!("../../../testing_data/reordered_tileset.txt")).unwrap();
+
+        assert_eq!(name, "REORDERED");
+        assert_eq!(config_file_name, "tile_config.json");
+        assert!(parse_tileset_info("NAME: NO_JSON\nVIEW: NO_JSON").is_err());
+    }
+
+    #[test]
+    pub fn test_foreground_of_missing_sprites_is_not_created() {
+        let fg = vec![MeabyWeighted::NotWeighted(98), MeabyWeighted::Weighted(Weighted::new(99, 10))];
+
+        assert!(get_sprite_trait_from_multi_fg(&fg, &HashMap::new(), false).is_none());
+        assert!(get_sprite_trait_from_multi_bg(&fg, &HashMap::new(), false).is_none());
+        assert!(WeightedForeground::new(vec![]).is_none());
+    }
+
+    #[test]
+    pub fn test_sprites_are_scaled_by_pixelscale() {
+        let loader = LegacyTilesetLoader::new(PathBuf::from("./testing_data/pixelscale_tileset"));
+
+        let tileset = loader.load().unwrap();
+        assert_eq!(tileset.info.get_scaled_tile_width(), 32);
+
+        // The 16x16 sprites of the sheet are displayed at twice their size
+        let textures = loader.load_textures().unwrap();
+        let wall = textures.get(&2).unwrap();
+        assert_eq!(wall.width(), 32);
+        assert_eq!(wall.height(), 32);
+    }
+
+    #[test]
+    pub fn test_statistics_count_the_loaded_sprites() {
+        let (loader, sprites) = load_test_tileset();
+        let textures = loader.load_textures().unwrap();
+        loader.load_fallback_textures().unwrap();
+        let statistics = loader.get_statistics();
+
+        // The sprites 1 to 7, the background 9443 does not exist and out_of_range_item uses 8 in the range 6 to 7
+        assert_eq!(statistics.textures, 7);
+        assert_eq!(textures.len(), 7);
+        assert_eq!(statistics.out_of_range, 1);
+        assert_eq!(statistics.missing_sprites, 2);
+        // t_door_rotating and t_fence_empty_bg, t_window_overlay has no connections
+        assert_eq!(statistics.multitiles, 2);
+        assert_eq!(sprites.values().filter(|s| matches!(s, SpriteType::Multitile { .. })).count(), 2);
+        // The fixture has no fallback.png
+        assert_eq!(statistics.fallback_textures, 0);
+    }
+}
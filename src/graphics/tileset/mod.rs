@@ -1,14 +1,22 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
 
+use anyhow::anyhow;
 use bevy::asset::{Assets, Handle};
 use bevy::prelude::{Image, ResMut};
+use image::DynamicImage;
+use zip::ZipArchive;
 
 use crate::common::io::Load;
 use crate::common::TileId;
 use crate::graphics::SpriteType;
 
-pub(crate) mod current;
-pub(crate) mod legacy;
+pub mod current;
+pub mod legacy;
 
 pub trait GetForeground: Send + Sync {
     fn get_randomized_sprite(&self) -> &Handle<Image>;
@@ -24,10 +32,128 @@ pub trait GetBackground: Send + Sync {
     fn get_representative_sprite(&self) -> &Handle<Image>;
 }
 
+/// The place where the files of a tileset are read from
+#[derive(Debug)]
+pub enum TilesetSource {
+    /// An extracted tileset folder
+    Directory(PathBuf),
+    /// A zip archive which contains the tileset, either at the root or inside a single folder.
+    /// The archive is opened on the first read and kept open for the following reads
+    Archive(PathBuf, RefCell<Option<ZipArchive<File>>>),
+}
+
+impl TilesetSource {
+    pub fn from_path(path: PathBuf) -> Self {
+        return match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("zip") => TilesetSource::Archive(path, RefCell::new(None)),
+            _ => TilesetSource::Directory(path)
+        };
+    }
+
+    pub fn read_bytes(&self, name: &str) -> Result<Vec<u8>, anyhow::Error> {
+        return match self {
+            TilesetSource::Directory(path) => Ok(fs::read(path.join(name))?),
+            TilesetSource::Archive(path, opened) => {
+                let mut opened = opened.borrow_mut();
+
+                if opened.is_none() {
+                    *opened = Some(ZipArchive::new(File::open(path)?)?);
+                }
+
+                let archive = match opened.as_mut() {
+                    None => return Err(anyhow!("Could not open the archive {:?}", path)),
+                    Some(a) => a
+                };
+
+                // Tilepacks are usually distributed with a folder around the tileset
+                let entry_name = archive.file_names()
+                    .find(|n| *n == name || n.ends_with(&format!("/{}", name)))
+                    .map(|n| n.to_string())
+                    .ok_or(anyhow!("{} does not exist in the archive {:?}", name, path))?;
+
+                let mut bytes = Vec::new();
+                archive.by_name(&entry_name)?.read_to_end(&mut bytes)?;
+
+                Ok(bytes)
+            }
+        };
+    }
+
+    pub fn read_to_string(&self, name: &str) -> Result<String, anyhow::Error> {
+        return Ok(String::from_utf8(self.read_bytes(name)?)?);
+    }
+
+    pub fn read_image(&self, name: &str) -> Result<DynamicImage, anyhow::Error> {
+        return Ok(image::load_from_memory(&self.read_bytes(name)?)?);
+    }
+}
+
+/// Counts of what was loaded from a tileset, which are logged as a single summary instead of a line for every tile
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TilesetLoadStatistics {
+    pub textures: usize,
+    pub fallback_textures: usize,
+    pub multitiles: usize,
+    /// Sprite indices which are outside of the range of their sprite sheet
+    pub out_of_range: usize,
+    /// Sprite indices which are used by a tile but could not be loaded
+    pub missing_sprites: usize,
+}
+
+impl TilesetLoadStatistics {
+    pub fn summary(&self, tileset_name: &str) -> String {
+        return format!(
+            "Loaded the tileset {}: {} textures, {} fallback glyphs, {} multitiles, {} ids out of range, {} missing sprites",
+            tileset_name,
+            self.textures,
+            self.fallback_textures,
+            self.multitiles,
+            self.out_of_range,
+            self.missing_sprites
+        );
+    }
+}
+
 pub trait TilesetLoader<T, Id>: Load<T> {
     fn load_textures(&self) -> Result<HashMap<Id, Image>, anyhow::Error>;
     fn load_fallback_textures(&self) -> Result<HashMap<String, Image>, anyhow::Error>;
     fn load_sprite_handles(&self, image_resource: &mut ResMut<Assets<Image>>) -> Result<HashMap<TileId, SpriteType>, anyhow::Error>;
+
+    /// Returns the statistics of the textures which were loaded so far
+    fn get_statistics(&self) -> TilesetLoadStatistics;
 }
 
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
 
+    use crate::graphics::tileset::TilesetSource;
+
+    #[test]
+    pub fn test_archive_is_only_opened_once() {
+        let dir = std::env::temp_dir().join(format!("cdda_map_editor_test_archive_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join("tileset.zip");
+
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        writer.start_file("tileset/tileset.txt", FileOptions::default()).unwrap();
+        writer.write_all(b"NAME: test").unwrap();
+        writer.finish().unwrap();
+
+        let source = TilesetSource::from_path(path.clone());
+        assert_eq!(source.read_to_string("tileset.txt").unwrap(), "NAME: test");
+
+        // The archive was kept open, so the file on disk is not needed anymore
+        fs::remove_file(&path).unwrap();
+        assert_eq!(source.read_to_string("tileset.txt").unwrap(), "NAME: test");
+        assert!(source.read_to_string("tile_config.json").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
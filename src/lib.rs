@@ -0,0 +1,13 @@
+//! The editor itself, the binary in main.rs only sets up the bevy app.
+//! The modules are public so the benchmarks can use the tileset and map code without the app
+
+pub mod tiles;
+pub mod map;
+pub mod ui;
+pub mod project;
+pub mod graphics;
+pub mod palettes;
+pub mod common;
+pub mod region_settings;
+pub mod settings;
+pub mod program;
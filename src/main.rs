@@ -1,7 +1,7 @@
+use std::cell::RefCell;
 use std::default::Default;
 use std::io::Write;
 use std::ops::Deref;
-use std::path::PathBuf;
 use std::string::ToString;
 
 use bevy::{prelude::*, window::PrimaryWindow};
@@ -9,7 +9,7 @@ use bevy::app::{App, AppExit, PluginGroup};
 use bevy::asset::AsyncReadExt;
 use bevy::DefaultPlugins;
 use bevy::log::LogPlugin;
-use bevy::prelude::{Assets, Camera2dBundle, Commands, EventReader, NonSend, Query, Res, ResMut, Transform, Vec2, Window, With};
+use bevy::prelude::{Assets, Camera2dBundle, Commands, EventReader, NonSend, Query, Res, ResMut, Transform, Vec2, Visibility, Window, With};
 use bevy::sprite::Material2dPlugin;
 use bevy::utils::default;
 use bevy::window::{WindowMode, WindowPlugin};
@@ -26,47 +26,36 @@ use clap::builder::StyledStr;
 use color_print::cformat;
 use imageproc::drawing::Canvas;
 use lazy_static::lazy_static;
-use log::LevelFilter;
+use log::{error, LevelFilter, warn};
 use winit::window::Icon;
 
-use program::data::IntoColor32;
-use project::data::Project;
-use settings::data::Settings;
-use settings::io::{SettingsLoader, SettingsSaver};
-use tiles::data::{Offset, Tile};
-use ui::{CDDADirContents, IsCursorCaptured};
-
-use crate::common::{BufferedLogger, Coordinates, LogMessage};
-use crate::common::io::{Load, Save};
-use crate::graphics::GraphicsResource;
-use crate::map::data::MapEntity;
-use crate::map::io::MapEntityLoader;
-use crate::map::plugin::MapPlugin;
-use crate::map::systems::{clear_tiles_reader, set_tile_reader, spawn_map_entity_reader, spawn_sprite, tile_despawn_reader, tile_remove_reader, tile_spawn_reader, update_sprite_reader};
-use crate::program::data::{Menus, OpenedProject, Program, ProgramState};
-use crate::program::io::{ProgramdataLoader, ProgramdataSaver};
-use crate::program::plugin::ProgramPlugin;
-use crate::project::data::CreateProject;
-use crate::project::plugin::ProjectPlugin;
-use crate::tiles::plugin::TilePlugin;
-use crate::ui::grid::GridMaterial;
-use crate::ui::grid::GridPlugin;
-use crate::ui::grid::resources::Grid;
-use crate::ui::interaction::{cdda_folder_picked, CDDADirPicked, close_button_interaction, TilesetSelected};
-use crate::ui::minimap::plugin::MinimapPlugin;
-use crate::ui::tabs::events::SpawnTab;
-use crate::ui::UiPlugin;
-
-mod tiles;
-mod map;
-mod ui;
-mod project;
-mod graphics;
-mod palettes;
-mod common;
-mod region_settings;
-mod settings;
-mod program;
+use cdda_map_editor::program::data::IntoColor32;
+use cdda_map_editor::project::data::Project;
+use cdda_map_editor::settings::data::Settings;
+use cdda_map_editor::settings::io::{SettingsLoader, SettingsSaver};
+use cdda_map_editor::tiles::data::{InteractionMode, Offset, Tile};
+use cdda_map_editor::ui::{CDDADirContents, CDDAMapgenContents, CDDAPaletteContents, IsCursorCaptured};
+use cdda_map_editor::common::{BufferedLogger, Coordinates, LogMessage};
+use cdda_map_editor::common::io::{Load, Save};
+use cdda_map_editor::graphics::GraphicsResource;
+use cdda_map_editor::map::data::{MapEntity, Multi};
+use cdda_map_editor::map::io::MapEntityLoader;
+use cdda_map_editor::map::plugin::MapPlugin;
+use cdda_map_editor::map::systems::{bulk_tile_place_reader, clear_tiles_reader, set_tile_reader, spawn_map_entity_reader, spawn_sprite, tile_despawn_reader, tile_remove_reader, tile_spawn_reader, update_sprite_reader};
+use cdda_map_editor::program::data::{Menus, OpenedProject, Program, ProgramState};
+use cdda_map_editor::program::io::{ProgramdataLoader, ProgramdataSaver};
+use cdda_map_editor::program::plugin::ProgramPlugin;
+use cdda_map_editor::project::data::CreateProject;
+use cdda_map_editor::project::plugin::ProjectPlugin;
+use cdda_map_editor::tiles::plugin::TilePlugin;
+use cdda_map_editor::ui::grid::GridMaterial;
+use cdda_map_editor::ui::grid::GridPlugin;
+use cdda_map_editor::ui::grid::resources::Grid;
+use cdda_map_editor::ui::interaction::{cdda_folder_picked, CDDADirPicked, close_button_interaction, TilesetSelected};
+use cdda_map_editor::ui::minimap::plugin::MinimapPlugin;
+use cdda_map_editor::ui::style::{Style, ThemeChanged};
+use cdda_map_editor::ui::UiPlugin;
+use cdda_map_editor::ui::error_dialog::ShowError;
 
 lazy_static! {
     pub static ref LOGGER: BufferedLogger = BufferedLogger::new();
@@ -91,7 +80,8 @@ fn main() {
         }).disable::<LogPlugin>(),
         ConsolePlugin,
         FileDialogPlugin::new()
-            .with_save_file::<Project>()
+            .with_save_file::<CDDAMapgenContents>()
+            .with_save_file::<CDDAPaletteContents>()
             .with_load_file::<Project>()
             .with_pick_directory::<CDDADirContents>(),
         Material2dPlugin::<GridMaterial>::default(),
@@ -131,6 +121,7 @@ fn main() {
         apply_deferred,
         set_tile_reader,
         tile_spawn_reader,
+        bulk_tile_place_reader,
         apply_deferred,
         spawn_sprite,
         apply_deferred,
@@ -141,43 +132,67 @@ fn main() {
     );
 
     app.add_systems(Update, sys.chain());
+    app.add_systems(Update, apply_theme);
 
     app.run();
 }
 
 fn load(
     r_program: Res<Program>,
+    r_settings: Res<Settings>,
     mut e_create_project: EventWriter<CreateProject>,
-    mut e_spawn_tab: EventWriter<SpawnTab>
+    mut e_show_error: EventWriter<ShowError>,
+    mut e_log: EventWriter<LogMessage>,
 ) {
+    let (cdda_data, data_json_dir) = match (r_program.config.cdda_data.as_ref(), r_settings.data_json_dir()) {
+        (Some(d), Some(dir)) => (d, dir),
+        _ => {
+            warn!("Skipped loading the default Project, no CDDA directory is configured");
+            return;
+        }
+    };
+
+    // The default Project is the field of the configured CDDA directory
     let loader = MapEntityLoader {
-        path: PathBuf::from(r"C:\CDDA\testing\data\json\mapgen\basic\field.json"),
+        path: data_json_dir.join("mapgen").join("basic").join("field.json"),
         id: "field".into(),
-        cdda_data: r_program.config.cdda_data.as_ref().unwrap(),
+        cdda_data,
+        row_size_mismatch: r_settings.row_size_mismatch,
+        variant: None,
+        warnings: RefCell::default(),
+    };
+
+    let entity: MapEntity = match loader.load() {
+        Ok(s) => MapEntity::Single(s),
+        Err(e) => {
+            e_show_error.send(ShowError::load("Could not load the default Project", &e));
+            return;
+        }
     };
 
-    let entity: MapEntity = MapEntity::Single(loader.load().unwrap());
+    for warning in loader.take_warnings() {
+        e_log.send(LogMessage::warning(warning));
+    }
 
     let project = Project {
         name: "Field".into(),
         map_entity: entity,
-        save_state: Default::default(),
+        ..Default::default()
     };
 
     e_create_project.send(CreateProject {
         project
     });
-
-    e_spawn_tab.send(SpawnTab {
-        name: "Field".into(),
-        index: 0,
-    });
 }
 
 fn setup(
     mut commands: Commands,
     mut e_cdda_dir_picked: EventWriter<CDDADirPicked>,
     mut e_tileset_selected: EventWriter<TilesetSelected>,
+    mut e_show_error: EventWriter<ShowError>,
+    mut r_console_configuration: ResMut<ConsoleConfiguration>,
+    mut r_menus: ResMut<Menus>,
+    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
     win_windows: NonSend<WinitWindows>,
 ) {
     commands.spawn(Camera2dBundle::default());
@@ -198,11 +213,37 @@ fn setup(
 
             s
         }
-        Err(_) => Settings::default()
+        Err(e) => {
+            // The settings do not exist on the first run
+            if !e.is_not_found() {
+                e_show_error.send(ShowError::load("Could not load the Settings", &e));
+            }
+
+            Settings::default()
+        }
     };
 
+    for mut window in q_windows.iter_mut() {
+        window.mode = settings.get_window_mode();
+    }
+
+    // Without a CDDA directory nothing can be loaded, so show the user where to pick it
+    if settings.needs_onboarding() {
+        r_menus.is_settings_menu_open = true;
+        r_menus.is_onboarding = true;
+    }
+
     let program_loader = ProgramdataLoader {};
-    let program_data = program_loader.load().unwrap_or(Program::new(vec![], vec![]));
+    let program_data = match program_loader.load() {
+        Ok(p) => p,
+        Err(e) => {
+            if !e.is_not_found() {
+                e_show_error.send(ShowError::load("Could not load the open Projects", &e));
+            }
+
+            Program::new(vec![], vec![])
+        }
+    };
 
     let texture_resource = GraphicsResource::default();
 
@@ -218,10 +259,8 @@ fn setup(
 
     win_windows.windows.iter().for_each(|(_, w)| w.set_window_icon(Some(icon.clone())));
 
-    commands.insert_resource(ConsoleConfiguration {
-        keys: program_data.config.keybindings.open_console.clone(),
-        ..default()
-    });
+    // Only change the keys, replacing the configuration would drop the registered console commands
+    r_console_configuration.keys = program_data.config.keybindings.open_console.clone();
     commands.insert_resource(settings);
     commands.insert_resource(ClearColor(program_data.config.style.gray_dark.clone()));
     commands.insert_resource(program_data);
@@ -249,7 +288,12 @@ fn setup_egui(
         .insert(0, "unifont".to_string());
 
     contexts.ctx_mut().set_fonts(fonts);
-    contexts.ctx_mut().set_visuals(egui::Visuals {
+    contexts.ctx_mut().set_visuals(get_egui_visuals(&r_program.config.style));
+}
+
+/// Returns the egui visuals which use the colors of the style
+fn get_egui_visuals(style: &Style) -> egui::Visuals {
+    return egui::Visuals {
         window_rounding: 0.0.into(),
         window_shadow: Shadow {
             extrusion: 0.0,
@@ -258,44 +302,66 @@ fn setup_egui(
         widgets: Widgets {
             open: WidgetVisuals {
                 bg_fill: Default::default(),
-                weak_bg_fill: r_program.config.style.blue_dark.into_color32(),
+                weak_bg_fill: style.blue_dark.into_color32(),
                 bg_stroke: Default::default(),
                 rounding: Default::default(),
                 fg_stroke: Default::default(),
                 expansion: 0.0,
             },
             inactive: WidgetVisuals {
-                bg_fill: r_program.config.style.white.into_color32(),
-                weak_bg_fill: r_program.config.style.blue_dark.into_color32(),
+                bg_fill: style.white.into_color32(),
+                weak_bg_fill: style.blue_dark.into_color32(),
                 bg_stroke: Default::default(),
                 rounding: Default::default(),
-                fg_stroke: Stroke::new(1., r_program.config.style.white.into_color32()),
+                fg_stroke: Stroke::new(1., style.white.into_color32()),
                 expansion: 0.0,
             },
             hovered: WidgetVisuals {
                 bg_fill: Default::default(),
-                weak_bg_fill: r_program.config.style.selected.into_color32(),
+                weak_bg_fill: style.selected.into_color32(),
                 bg_stroke: Default::default(),
                 rounding: Default::default(),
-                fg_stroke: Stroke::new(1., r_program.config.style.white.into_color32()),
+                fg_stroke: Stroke::new(1., style.white.into_color32()),
                 expansion: 0.0,
             },
             active: WidgetVisuals {
                 bg_fill: Default::default(),
-                weak_bg_fill: r_program.config.style.selected.into_color32(),
+                weak_bg_fill: style.selected.into_color32(),
                 bg_stroke: Default::default(),
                 rounding: Default::default(),
-                fg_stroke: Stroke::new(1., r_program.config.style.white.into_color32()),
+                fg_stroke: Stroke::new(1., style.white.into_color32()),
                 expansion: 0.0,
             },
             ..Default::default()
         },
-        extreme_bg_color: r_program.config.style.blue_dark.into_color32(),
+        extreme_bg_color: style.blue_dark.into_color32(),
         window_stroke: Stroke::NONE,
-        override_text_color: Some(r_program.config.style.white.into_color32()),
-        window_fill: r_program.config.style.gray_darker.into_color32(),
+        override_text_color: Some(style.white.into_color32()),
+        window_fill: style.gray_darker.into_color32(),
         ..default()
-    });
+    };
+}
+
+/// Swaps the colors which are only set once to the colors of the new theme
+fn apply_theme(
+    mut e_theme_changed: EventReader<ThemeChanged>,
+    mut contexts: EguiContexts,
+    mut r_clear_color: ResMut<ClearColor>,
+    mut r_grid_material: ResMut<Assets<GridMaterial>>,
+    r_program: Res<Program>,
+) {
+    if e_theme_changed.is_empty() { return; }
+    e_theme_changed.clear();
+
+    let style = &r_program.config.style;
+
+    r_clear_color.0 = style.gray_dark;
+    contexts.ctx_mut().set_visuals(get_egui_visuals(style));
+
+    for (_, grid_material) in r_grid_material.iter_mut() {
+        grid_material.inside_grid_color = style.gray_light.rgb_to_vec3();
+        grid_material.outside_grid_color = style.gray_darker.rgb_to_vec3();
+    }
 }
 
 fn update(
@@ -303,13 +369,17 @@ fn update(
     r_cursor: Res<IsCursorCaptured>,
     r_program: Res<Program>,
     r_program_state: Res<State<ProgramState>>,
+    r_settings: Res<Settings>,
+    r_mode: Res<InteractionMode>,
     mut r_grid_material: ResMut<Assets<GridMaterial>>,
-    mut q_tiles: Query<(&mut Transform, &Coordinates, &Offset), With<Tile>>,
+    mut q_tiles: Query<(&mut Transform, &mut Visibility, &Coordinates, &Offset, Option<&Handle<Image>>), With<Tile>>,
+    r_images: Res<Assets<Image>>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
     mut e_write_line: EventWriter<PrintConsoleLine>,
+    mut e_log: EventReader<LogMessage>,
     q_opened_project: Query<(Entity, &OpenedProject)>,
 ) {
-    for log in LOGGER.log_queue.read().unwrap().iter() {
+    for log in LOGGER.log_queue.read().unwrap().iter().chain(e_log.read()) {
         e_write_line.send(PrintConsoleLine::new(StyledStr::from(cformat!(r#"<g>[{}] {}</g>"#, log.level.as_str(), log.message))));
     }
     LOGGER.log_queue.write().unwrap().clear();
@@ -327,20 +397,56 @@ fn update(
 
         let window = q_windows.single();
 
+        // The Preview Mode shows the map like CDDA places it, the editor shows the rows like they are written
+        let is_rotated = *r_mode == InteractionMode::Preview && project.map_entity.rotation() != 0;
+
         grid_material.1.offset = r_grid.offset;
         grid_material.1.tile_size = r_grid.tile_size;
         grid_material.1.mouse_pos = window.cursor_position().unwrap_or(Vec2::default());
-        grid_material.1.map_size = project.map_entity.size();
+        grid_material.1.map_size = match is_rotated {
+            true => project.map_entity.get_rotated_size(),
+            false => project.map_entity.size()
+        };
         // Weird way to do this but bevy does not let me pass a bool as a uniform for some reason
         grid_material.1.is_cursor_captured = match r_cursor.0 {
             true => 1,
             false => 0
         };
         grid_material.1.scale_factor = window.resolution.scale_factor();
+        grid_material.1.min_zoom = r_grid.min_zoom;
+        grid_material.1.grid_fade_start = r_settings.grid_fade_start;
+        // The boundaries between the overmap terrains of a Multi map are always shown
+        grid_material.1.overmap_guide_spacing = match (&project.map_entity, r_settings.show_overmap_guides) {
+            (MapEntity::Multi(_), _) => Multi::OVERMAP_TERRAIN_SIZE,
+            (_, true) => r_settings.overmap_guide_spacing as i32,
+            (_, false) => 0
+        };
 
-        for (mut transform, coordinates, sprite_offset) in q_tiles.iter_mut() {
-            transform.translation.x = (-window.resolution.width() / 2. + r_grid.tile_size / 2.) - (r_grid.offset.x - coordinates.x as f32 * r_grid.tile_size);
-            transform.translation.y = (window.resolution.height() / 2. - (r_grid.tile_size + (sprite_offset.y as f32 * (r_grid.tile_size / r_grid.default_tile_size))) / 2.) + (r_grid.offset.y - coordinates.y as f32 * r_grid.tile_size);
+        let window_size = Vec2::new(window.resolution.width(), window.resolution.height());
+
+        for (mut transform, mut visibility, coordinates, sprite_offset, handle) in q_tiles.iter_mut() {
+            let sprite_size = match handle.and_then(|h| r_images.get(h)) {
+                None => Vec2::splat(r_grid.default_tile_size),
+                Some(image) => Vec2::new(image.width() as f32, image.height() as f32)
+            };
+
+            let translation = match is_rotated {
+                true => r_grid.get_sprite_translation(window_size, &project.map_entity.get_rotated_coordinates(coordinates), sprite_offset, sprite_size),
+                false => r_grid.get_sprite_translation(window_size, coordinates, sprite_offset, sprite_size)
+            };
+            transform.translation.x = translation.x;
+            transform.translation.y = translation.y;
+
+            // Hide all tiles which are not on the screen so that they do not have to be rendered
+            let new_visibility = match r_grid.is_on_screen(translation, window_size) {
+                true => Visibility::Inherited,
+                false => Visibility::Hidden
+            };
+
+            // Only write when the visibility actually changes to not trigger change detection every frame
+            if *visibility != new_visibility {
+                *visibility = new_visibility;
+            }
         }
     }
 }
@@ -348,13 +454,25 @@ fn update(
 fn exit(
     e_exit: EventReader<AppExit>,
     r_settings: Res<Settings>,
-    r_editor_data: Res<Program>,
+    r_grid: Res<Grid>,
+    mut r_editor_data: ResMut<Program>,
+    q_opened_project: Query<&OpenedProject>,
 ) {
     if e_exit.is_empty() { return; }
 
+    // The view of the opened Project is only stored when another Project is opened
+    if let Some(project) = q_opened_project.iter().next().and_then(|o| r_editor_data.projects.get_mut(o.index)) {
+        project.view = Some(r_grid.get_view());
+    }
+
+    // The window is already closing, so the errors can only be logged
     let data_saver = ProgramdataSaver {};
-    data_saver.save(&r_editor_data).unwrap();
+    if let Err(e) = data_saver.save(&r_editor_data) {
+        error!("Could not save the open Projects: {}", e);
+    }
 
     let settings_saver = SettingsSaver {};
-    settings_saver.save(&r_settings).unwrap();
+    if let Err(e) = settings_saver.save(&r_settings) {
+        error!("Could not save the Settings: {}", e);
+    }
 }
\ No newline at end of file
@@ -1,16 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use bevy::math::{IVec2, Vec2};
 use bevy::prelude::{Event, Resource};
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-use crate::common::{Coordinates, MeabyWeighted, TileId};
+use crate::common::{Coordinates, MeabyMulti, MeabyWeighted, TileId};
 use crate::common::GetRandom;
+use crate::common::io::LoadError;
 use crate::common::Weighted;
 use crate::program::data::CDDAData;
-use crate::map::io::{Parameter, ParameterId};
-use crate::palettes::data::{MapObjectId, MeabyParam, PaletteId};
+use crate::map::io::{compute_palettes, Parameter, ParameterId};
+use crate::palettes::data::{Item, MapObjectId, MeabyParam, PaletteId, Switch};
+use crate::settings::data::MapEdge;
 use crate::tiles::data::Tile;
 
 #[derive(Default, Serialize, Deserialize, Debug, Resource, Clone)]
@@ -20,6 +26,11 @@ pub struct ComputedParameters {
 }
 
 impl ComputedParameters {
+    /// Returns true if the palette is attached to this map or to one of its palettes
+    pub fn contains_palette(&self, palette_id: &PaletteId) -> bool {
+        return self.palettes.contains_key(palette_id) || self.palettes.values().any(|p| p.contains_palette(palette_id));
+    }
+
     pub fn get_value(&self, parameter_id: &String) -> Option<&String> {
         match self.this.get(parameter_id) {
             None => {
@@ -35,6 +46,17 @@ impl ComputedParameters {
 
         return None;
     }
+
+    /// Sets the value of the parameter in this map and in every palette which defines it
+    pub fn set_value(&mut self, parameter_id: &ParameterId, value: &String) {
+        if let Some(v) = self.this.get_mut(parameter_id) {
+            *v = value.clone();
+        }
+
+        for (_, parameters) in self.palettes.iter_mut() {
+            parameters.set_value(parameter_id, value);
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -42,11 +64,269 @@ pub struct TileSelection {
     pub computed_parameters: ComputedParameters,
     pub parameters: HashMap<ParameterId, Parameter>,
 
-    pub fill_ter: Option<TileId>,
+    /// Can also be a parameter, a distribution or a region id, use get_fill_ter to get the terrain
+    pub fill_ter: Option<MapObjectId<MeabyWeighted<MeabyParam>>>,
+    /// The entry of a grouped or distributed fill terrain which was picked by weight, see [Self::roll_fill_ter]
+    #[serde(default)]
+    pub rolled_fill_ter: Option<TileId>,
 
-    pub palettes: Vec<MapObjectId<MeabyParam>>,
+    pub palettes: Vec<MapObjectId<MeabyWeighted<MeabyParam>>>,
     pub terrain: HashMap<char, MapObjectId<MeabyWeighted<MeabyParam>>>,
     pub furniture: HashMap<char, MapObjectId<MeabyWeighted<MeabyParam>>>,
+    #[serde(default)]
+    pub items: HashMap<char, MeabyMulti<Item>>,
+    /// Loot which is placed at coordinates instead of at a character
+    #[serde(default)]
+    pub place_loot: Vec<PlaceLoot>,
+
+    /// Cases of switches which are previewed for the character instead of the case selected by the parameter
+    #[serde(skip)]
+    pub switch_previews: HashMap<char, String>,
+}
+
+/// A coordinate of a placement, CDDA picks one of the values if it is a range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MeabyRange {
+    Single(i32),
+    Range([i32; 2]),
+}
+
+impl MeabyRange {
+    /// The value or the start of the range as it is written in the mapgen
+    pub fn first(&self) -> i32 {
+        return match self {
+            MeabyRange::Single(v) => *v,
+            MeabyRange::Range([start, _]) => *start
+        };
+    }
+
+    /// All values which could be picked
+    pub fn values(&self) -> RangeInclusive<i32> {
+        return match self {
+            MeabyRange::Single(v) => *v..=*v,
+            MeabyRange::Range([start, end]) => *start.min(end)..=*start.max(end)
+        };
+    }
+
+    /// Moves the values at or behind the index back by one, like the tiles of an inserted row or column
+    pub fn inserted(&self, index: i32) -> MeabyRange {
+        let shift = |v: &i32| match *v >= index {
+            true => *v + 1,
+            false => *v
+        };
+
+        return match self {
+            MeabyRange::Single(v) => MeabyRange::Single(shift(v)),
+            MeabyRange::Range([start, end]) => MeabyRange::Range([shift(start), shift(end)])
+        };
+    }
+
+    /// Moves the values behind the index forward by one, like the tiles of a deleted row or column.
+    /// Returns None if every value was inside of the deleted row or column
+    pub fn deleted(&self, index: i32) -> Option<MeabyRange> {
+        let values = self.values();
+
+        let start = match *values.start() > index {
+            true => *values.start() - 1,
+            false => *values.start()
+        };
+
+        let end = match *values.end() >= index {
+            true => *values.end() - 1,
+            false => *values.end()
+        };
+
+        if start > end {
+            return None;
+        }
+
+        return match self {
+            MeabyRange::Single(_) => Some(MeabyRange::Single(start)),
+            MeabyRange::Range(_) => Some(MeabyRange::Range([start, end]))
+        };
+    }
+}
+
+/// An entry of place_loot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaceLoot {
+    pub x: MeabyRange,
+    pub y: MeabyRange,
+    /// The item or group, the chance and the other keys of the entry, which are only kept for the export
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl PlaceLoot {
+    /// Returns every coordinate at which the loot could be placed
+    pub fn get_coordinates(&self) -> Vec<Coordinates> {
+        let mut coordinates = Vec::new();
+
+        for y in self.y.values() {
+            for x in self.x.values() {
+                coordinates.push(Coordinates::new(x, y));
+            }
+        }
+
+        return coordinates;
+    }
+}
+
+impl TileSelection {
+    /// Returns the case of the switch which is used for the character
+    pub fn get_active_switch_case(&self, character: &char, switch: &Switch) -> Option<String> {
+        if let Some(case) = self.switch_previews.get(character) {
+            return Some(case.clone());
+        }
+
+        return match self.computed_parameters.get_value(&switch.param) {
+            None => switch.fallback.clone(),
+            Some(v) => Some(v.clone())
+        };
+    }
+
+    /// Returns the current value of the parameter, or the fallback if the parameter has no value
+    pub fn get_param_value(&self, param: &ParameterId, fallback: &Option<String>) -> Option<String> {
+        return match self.computed_parameters.get_value(param) {
+            None => fallback.clone(),
+            Some(v) => Some(v.clone())
+        };
+    }
+
+    fn resolve_fill_value(&self, value: &MeabyParam) -> Option<TileId> {
+        return match value {
+            MeabyParam::TileId(id) => Some(id.clone()),
+            MeabyParam::Parameter(p) => self.get_param_value(&p.param, &Some(p.fallback.clone()))
+        };
+    }
+
+    /// Picks one entry of a grouped or distributed fill terrain by weight, which get_fill_ter returns until this is called again.
+    /// Has to be called every time the fill terrain changes
+    pub fn roll_fill_ter(&mut self) {
+        let rolled = match &self.fill_ter {
+            Some(MapObjectId::Grouped(g)) | Some(MapObjectId::Distribution { distribution: g }) => {
+                g.get_random_weighted().and_then(|value| self.resolve_fill_value(value))
+            }
+            _ => None
+        };
+
+        self.rolled_fill_ter = rolled;
+    }
+
+    /// Returns the terrain which fills the tiles without terrain. The result can be a region id.
+    /// Grouped entries and distributions return the entry which was picked by [Self::roll_fill_ter]
+    pub fn get_fill_ter(&self) -> Option<TileId> {
+        let resolve = |value: &MeabyParam| self.resolve_fill_value(value);
+
+        return match self.fill_ter.as_ref()? {
+            MapObjectId::Single(mw) => resolve(mw.value()),
+            MapObjectId::Grouped(_) | MapObjectId::Distribution { .. } => self.rolled_fill_ter.clone(),
+            MapObjectId::Param { param, fallback } => self.get_param_value(param, fallback),
+            MapObjectId::Switch { switch, cases } => {
+                let case = match self.computed_parameters.get_value(&switch.param) {
+                    None => switch.fallback.clone(),
+                    Some(v) => Some(v.clone())
+                };
+
+                case.and_then(|c| cases.get(&c)).and_then(|mw| resolve(mw.value()))
+            }
+            MapObjectId::Nested(_) => None
+        };
+    }
+
+    /// Returns the id of the palette which is referenced by the passed palette entry
+    /// Grouped entries return the palette which was chosen when the parameters were computed
+    pub fn get_palette_id(&self, palette: &MapObjectId<MeabyWeighted<MeabyParam>>) -> Option<PaletteId> {
+        return match palette {
+            MapObjectId::Single(mw) => match mw.value() {
+                MeabyParam::TileId(id) => Some(id.clone()),
+                MeabyParam::Parameter(p) => self.get_param_value(&p.param, &Some(p.fallback.clone()))
+            },
+            MapObjectId::Grouped(entries) | MapObjectId::Distribution { distribution: entries } => entries.iter()
+                .filter_map(|mw| match mw.value() {
+                    MeabyParam::TileId(id) => Some(id.clone()),
+                    MeabyParam::Parameter(p) => self.get_param_value(&p.param, &Some(p.fallback.clone()))
+                })
+                .find(|id| self.computed_parameters.contains_palette(id)),
+            MapObjectId::Param { param, fallback } => self.get_param_value(param, fallback),
+            _ => None
+        };
+    }
+
+    /// Writes the terrain, furniture, items and parameters of every attached palette into the map and detaches the palettes.
+    /// Like when the ids are resolved, definitions of the map win over palettes and earlier palettes win over later ones
+    pub fn flatten_palettes(&mut self, cdda_data: &CDDAData) {
+        fn add_palette(selection: &mut TileSelection, cdda_data: &CDDAData, palette: &MapObjectId<MeabyWeighted<MeabyParam>>) {
+            let palette = match selection.get_palette_id(palette).and_then(|id| cdda_data.palettes.get(&id)) {
+                None => return,
+                Some(p) => p
+            };
+
+            for (character, definition) in palette.terrain.iter() {
+                selection.terrain.entry(*character).or_insert_with(|| definition.clone());
+            }
+
+            for (character, definition) in palette.furniture.iter() {
+                selection.furniture.entry(*character).or_insert_with(|| definition.clone());
+            }
+
+            // The items of all palettes are placed, so they are combined instead of replaced
+            for (character, items) in palette.items.iter() {
+                let mut combined = match selection.items.remove(character) {
+                    None => vec![],
+                    Some(MeabyMulti::Single(s)) => vec![s],
+                    Some(MeabyMulti::Multi(m)) => m
+                };
+
+                match items {
+                    MeabyMulti::Single(s) => combined.push(s.clone()),
+                    MeabyMulti::Multi(m) => combined.extend(m.iter().cloned())
+                }
+
+                selection.items.insert(*character, MeabyMulti::Multi(combined));
+            }
+
+            for (id, parameter) in palette.parameters.iter() {
+                selection.parameters.entry(id.clone()).or_insert_with(|| Parameter {
+                    parameter_type: parameter.parameter_type.clone(),
+                    default: parameter.default.clone(),
+                });
+            }
+
+            for parent_palette in palette.palettes.iter() {
+                add_palette(selection, cdda_data, parent_palette);
+            }
+        }
+
+        for palette in self.palettes.clone().iter() {
+            add_palette(self, cdda_data, palette);
+        }
+
+        // Keep the values which were rolled for the palettes, so the map does not change
+        let parameter_ids: Vec<ParameterId> = self.parameters.keys().cloned().collect();
+        for id in parameter_ids {
+            if self.computed_parameters.this.contains_key(&id) { continue; }
+
+            if let Some(value) = self.computed_parameters.get_value(&id).cloned() {
+                self.computed_parameters.this.insert(id, value);
+            }
+        }
+
+        self.palettes.clear();
+        self.computed_parameters.palettes.clear();
+    }
+
+    /// Recomputes the parameters of all attached palettes
+    /// Has to be called every time the palettes list changes
+    pub fn recompute_palette_parameters(&mut self, cdda_data: &CDDAData) {
+        self.computed_parameters.palettes = compute_palettes(
+            cdda_data,
+            &self.computed_parameters.this,
+            &self.palettes,
+            &self.computed_parameters.palettes,
+        );
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,12 +336,77 @@ pub enum MapEntity {
     Nested(Nested),
 }
 
+#[derive(Debug, PartialEq)]
+pub enum TileIntegrityIssue {
+    /// A tile was stored at coordinates which are outside of the map size
+    OutOfBounds(Coordinates),
+    /// No tile was stored at coordinates which are inside of the map size
+    Missing(Coordinates),
+    /// A grouped or distributed definition of a character has no entries to pick from
+    EmptyGroup(char),
+    /// A definition of a character references a parameter which does not exist and has no fallback
+    UndefinedParameter { character: char, parameter: ParameterId },
+}
+
+/// A character which is placed on the map but not defined anywhere
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndefinedCharacter {
+    pub character: char,
+    /// How many tiles use the character
+    pub count: usize,
+}
+
+/// How many tiles of the map use the character
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterCount {
+    pub character: char,
+    pub count: usize,
+    /// The share of all tiles of the map, between 0 and 100
+    pub percentage: f32,
+}
+
+/// A terrain or furniture definition of a character which is not placed anywhere on the map
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnusedDefinition {
+    pub character: char,
+    pub kind: &'static str,
+    /// The palette which contains the definition, None if it is defined by the map itself
+    pub palette_id: Option<PaletteId>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CharacterUsageReport {
+    pub undefined_characters: Vec<UndefinedCharacter>,
+    pub unused_definitions: Vec<UnusedDefinition>,
+}
+
 #[derive(Debug, Default)]
 pub struct TileIdGroup {
     pub terrain: Option<TileId>,
     pub furniture: Option<TileId>,
     pub toilet: Option<TileId>,
     pub item: Option<TileId>,
+    /// The loot of the character which is defined in the map object and its palettes
+    pub items: Vec<Item>,
+    /// The palette which defines the terrain or None if it is defined by the map itself
+    pub terrain_palette: Option<PaletteId>,
+    /// The palette which defines the furniture or None if it is defined by the map itself
+    pub furniture_palette: Option<PaletteId>,
+}
+
+impl TileIdGroup {
+    /// Returns true if nothing is defined for the character, it is then drawn with its fallback
+    pub fn is_empty(&self) -> bool {
+        return self.terrain.is_none() && self.furniture.is_none() && self.toilet.is_none() && self.item.is_none() && self.items.is_empty();
+    }
+}
+
+/// Adds the items of the entry to the items of the group
+fn add_items(group: &mut TileIdGroup, items: &MeabyMulti<Item>) {
+    match items {
+        MeabyMulti::Multi(m) => group.items.extend(m.iter().cloned()),
+        MeabyMulti::Single(s) => group.items.push(s.clone())
+    }
 }
 
 impl Default for MapEntity {
@@ -104,18 +449,250 @@ impl MapEntity {
         }
     }
 
-    pub fn get_tiles_around(&self, coordinates: &Coordinates) -> Vec<(Option<&Tile>, Coordinates)> {
+    /// Returns the ids of all palettes which are attached to the map, including the palettes of palettes
+    pub fn get_attached_palette_ids(&self) -> Vec<PaletteId> {
+        fn collect_palette_ids(computed_parameters: &ComputedParameters, palette_ids: &mut Vec<PaletteId>) {
+            for (palette_id, palette_parameters) in computed_parameters.palettes.iter() {
+                palette_ids.push(palette_id.clone());
+                collect_palette_ids(palette_parameters, palette_ids);
+            }
+        }
+
+        let mut palette_ids = Vec::new();
+        collect_palette_ids(&self.object().computed_parameters, &mut palette_ids);
+
+        return palette_ids;
+    }
+
+    /// Returns true if the terrain or furniture of the character is defined by the map object or one of its palettes
+    pub fn is_character_defined(&self, cdda_data: &CDDAData, character: &char) -> bool {
+        if self.object().terrain.contains_key(character) || self.object().furniture.contains_key(character) {
+            return true;
+        }
+
+        return self.get_attached_palette_ids().iter()
+            .filter_map(|id| cdda_data.palettes.get(id))
+            .any(|p| p.terrain.contains_key(character) || p.furniture.contains_key(character));
+    }
+
+    /// Returns how often every character is used, the most used character comes first
+    pub fn get_character_counts(&self) -> Vec<CharacterCount> {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+
+        for tile in self.tiles().values() {
+            *counts.entry(tile.character).or_default() += 1;
+        }
+
+        let total = self.tiles().len().max(1) as f32;
+
+        let mut character_counts: Vec<CharacterCount> = counts.into_iter()
+            .map(|(character, count)| CharacterCount { character, count, percentage: count as f32 / total * 100. })
+            .collect();
+
+        character_counts.sort_by(|a, b| b.count.cmp(&a.count).then(a.character.cmp(&b.character)));
+
+        return character_counts;
+    }
+
+    /// Returns the coordinates of every tile which uses the character, sorted by row and then by column
+    pub fn get_coordinates_of_character(&self, character: &char) -> Vec<Coordinates> {
+        let mut coordinates: Vec<Coordinates> = self.tiles().iter()
+            .filter(|(_, tile)| tile.character == *character)
+            .map(|(coordinates, _)| coordinates.clone())
+            .collect();
+
+        coordinates.sort_by(|a, b| a.y.cmp(&b.y).then(a.x.cmp(&b.x)));
+
+        return coordinates;
+    }
+
+    /// Returns the coordinates of every tile whose terrain is the id, sorted by row and then by column.
+    /// Tiles without a terrain use the fill terrain. Terrain which is chosen from a distribution matches the id
+    /// which is resolved for this call, so another call can return other coordinates
+    pub fn coordinates_with_terrain(&self, cdda_data: &CDDAData, id: &TileId) -> Vec<Coordinates> {
+        let fill_ter = self.object().get_fill_ter();
+        let mut resolved: HashMap<char, bool> = HashMap::new();
+
+        let mut coordinates: Vec<Coordinates> = self.tiles().iter()
+            .filter(|(_, tile)| *resolved.entry(tile.character).or_insert_with(|| {
+                let terrain = self.get_ids(cdda_data, &tile.character).terrain.or(fill_ter.clone());
+                terrain.as_ref() == Some(id)
+            }))
+            .map(|(coordinates, _)| coordinates.clone())
+            .collect();
+
+        coordinates.sort_by(|a, b| a.y.cmp(&b.y).then(a.x.cmp(&b.x)));
+
+        return coordinates;
+    }
+
+    /// Cross references the placed characters with the terrain and furniture definitions of the map and its palettes
+    pub fn get_character_usage_report(&self, cdda_data: &CDDAData) -> CharacterUsageReport {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+
+        for tile in self.tiles().values() {
+            *counts.entry(tile.character).or_default() += 1;
+        }
+
+        let mut definitions: Vec<(&HashMap<char, MapObjectId<MeabyWeighted<MeabyParam>>>, &'static str, Option<PaletteId>)> = vec![
+            (&self.object().terrain, "terrain", None),
+            (&self.object().furniture, "furniture", None),
+        ];
+
+        for palette_id in self.get_attached_palette_ids() {
+            if let Some(palette) = cdda_data.palettes.get(&palette_id) {
+                definitions.push((&palette.terrain, "terrain", Some(palette_id.clone())));
+                definitions.push((&palette.furniture, "furniture", Some(palette_id)));
+            }
+        }
+
+        let mut report = CharacterUsageReport::default();
+
+        for (character, count) in counts.iter() {
+            // The space character is filled with the fill terrain and does not have to be defined
+            if *character == ' ' { continue; }
+
+            if !definitions.iter().any(|(d, _, _)| d.contains_key(character)) {
+                report.undefined_characters.push(UndefinedCharacter { character: *character, count: *count });
+            }
+        }
+
+        for (definitions, kind, palette_id) in definitions.iter() {
+            for character in definitions.keys() {
+                if counts.contains_key(character) { continue; }
+
+                report.unused_definitions.push(UnusedDefinition {
+                    character: *character,
+                    kind,
+                    palette_id: palette_id.clone(),
+                });
+            }
+        }
+
+        report.undefined_characters.sort_by_key(|u| u.character);
+        report.unused_definitions.sort_by_key(|u| u.character);
+
+        return report;
+    }
+
+    pub fn get_tile_integrity_issues(&self) -> Vec<TileIntegrityIssue> {
+        let mut issues = Vec::new();
+        let object = self.object();
+
+        for (character, id) in object.terrain.iter().chain(object.furniture.iter()) {
+            match id {
+                MapObjectId::Grouped(g) | MapObjectId::Distribution { distribution: g } => {
+                    if g.is_empty() {
+                        issues.push(TileIntegrityIssue::EmptyGroup(*character));
+                    }
+                }
+                MapObjectId::Param { param, fallback } => {
+                    if object.get_param_value(param, fallback).is_none() {
+                        issues.push(TileIntegrityIssue::UndefinedParameter { character: *character, parameter: param.clone() });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Only Single maps store their own size, the other variants derive it
+        let size = match self {
+            MapEntity::Single(s) => s.size,
+            _ => return issues
+        };
+
+        for coordinates in self.tiles().keys() {
+            if coordinates.x < 0 || coordinates.y < 0 || coordinates.x >= size.x || coordinates.y >= size.y {
+                issues.push(TileIntegrityIssue::OutOfBounds(coordinates.clone()));
+            }
+        }
+
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let coordinates = Coordinates::new(x, y);
+
+                if !self.tiles().contains_key(&coordinates) {
+                    issues.push(TileIntegrityIssue::Missing(coordinates));
+                }
+            }
+        }
+
+        return issues;
+    }
+
+    /// Checks that the tiles of the map are consistent with its size and repairs them if possible.
+    /// Out of bounds tiles are removed and missing tiles are filled with empty tiles.
+    /// Returns an Error if the tiles are too corrupted to be repaired or a definition can not be resolved
+    pub fn verify_tiles(&mut self) -> Result<(), LoadError> {
+        let issues = self.get_tile_integrity_issues();
+
+        if issues.is_empty() { return Ok(()); }
+
+        // Definitions can not be repaired without guessing which tiles were meant
+        for issue in issues.iter() {
+            match issue {
+                TileIntegrityIssue::EmptyGroup(character) => {
+                    return Err(LoadError::Other(anyhow!("The definition of the character '{}' has no entries", character)));
+                }
+                TileIntegrityIssue::UndefinedParameter { character, parameter } => {
+                    return Err(LoadError::Other(anyhow!("The definition of the character '{}' references the undefined parameter {}", character, parameter)));
+                }
+                _ => {}
+            }
+        }
+
+        let amount_out_of_bounds = issues.iter()
+            .filter(|i| matches!(i, TileIntegrityIssue::OutOfBounds(_)))
+            .count();
+
+        // If not a single tile is inside of the map, the size and the tiles do not belong together
+        if !self.tiles().is_empty() && amount_out_of_bounds == self.tiles().len() {
+            error!("None of the {} tiles are inside of the map with the size {:?}", amount_out_of_bounds, self.size());
+            return Err(LoadError::ParseError);
+        }
+
+        for issue in issues {
+            match issue {
+                TileIntegrityIssue::OutOfBounds(coordinates) => {
+                    warn!("Removing tile at {:?} because it is outside of the map", coordinates);
+                    self.tiles_mut().remove(&coordinates);
+                }
+                TileIntegrityIssue::Missing(coordinates) => {
+                    warn!("Inserting empty tile at {:?} because it is missing", coordinates);
+                    self.tiles_mut().insert(coordinates, Tile::from(' '));
+                }
+                TileIntegrityIssue::EmptyGroup(_) | TileIntegrityIssue::UndefinedParameter { .. } => {}
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the tiles above, right of, below and left of the coordinates, None if there is no tile.
+    /// With a connected map edge the tile itself is returned for the neighbors outside of the map,
+    /// so autotiled tiles connect to the edge
+    pub fn get_tiles_around(&self, coordinates: &Coordinates, map_edge: MapEdge) -> Vec<(Option<&Tile>, Coordinates)> {
         let tiles = self.tiles();
+        let size = self.size().as_ivec2();
+
+        let get_tile = move |around: &Coordinates| {
+            let is_outside = around.x < 0 || around.y < 0 || around.x >= size.x || around.y >= size.y;
+
+            return match (map_edge, is_outside) {
+                (MapEdge::Connected, true) => tiles.get(coordinates),
+                _ => tiles.get(around)
+            };
+        };
 
         let top_coordinates = Coordinates { x: coordinates.x, y: coordinates.y - 1 };
         let right_coordinates = Coordinates { x: coordinates.x + 1, y: coordinates.y };
         let below_coordinates = Coordinates { x: coordinates.x, y: coordinates.y + 1 };
         let left_coordinates = Coordinates { x: coordinates.x - 1, y: coordinates.y };
 
-        let tile_ontop = tiles.get(&top_coordinates);
-        let tile_right = tiles.get(&right_coordinates);
-        let tile_below = tiles.get(&below_coordinates);
-        let tile_left = tiles.get(&left_coordinates);
+        let tile_ontop = get_tile(&top_coordinates);
+        let tile_right = get_tile(&right_coordinates);
+        let tile_below = get_tile(&below_coordinates);
+        let tile_left = get_tile(&left_coordinates);
 
         return vec![
             (tile_ontop, top_coordinates),
@@ -125,19 +702,100 @@ impl MapEntity {
         ];
     }
 
+    /// Returns the tiles around the changed coordinates whose sprites depend on their neighbors.
+    /// Every tile is only returned once, even if it is next to multiple changed coordinates
+    pub fn get_neighbors_to_update(&self, changed: &Vec<Coordinates>, is_autotiled: impl Fn(&Tile) -> bool) -> Vec<(Tile, Coordinates)> {
+        let mut visited: HashSet<Coordinates> = HashSet::new();
+        let mut neighbors = Vec::new();
+
+        for coordinates in changed.iter() {
+            // Only tiles inside of the map are updated
+            for (tile, coordinates) in self.get_tiles_around(coordinates, MapEdge::Open) {
+                let tile = match tile {
+                    None => continue,
+                    Some(t) => t
+                };
+
+                if !visited.insert(coordinates.clone()) { continue; }
+                if !is_autotiled(tile) { continue; }
+
+                neighbors.push((*tile, coordinates));
+            }
+        }
+
+        return neighbors;
+    }
+
     pub fn size(&self) -> Vec2 {
         return match self {
             MapEntity::Single(s) => s.size.as_vec2(),
-            MapEntity::Multi(_) => Vec2::new(24., 24.),
-            MapEntity::Nested(n) => Vec2::new(n.om_terrain.len() as f32, n.row_size as f32)
+            MapEntity::Multi(m) => m.size().as_vec2(),
+            MapEntity::Nested(n) => match n.size {
+                None => Vec2::new(n.om_terrain.len() as f32, n.row_size as f32),
+                Some(size) => size.as_vec2()
+            }
+        };
+    }
+
+    /// The rotation of the whole map as it is written in the mapgen, None if the mapgen has no rotation
+    pub fn rotation_range(&self) -> Option<&MeabyRange> {
+        return match self {
+            MapEntity::Single(s) => s.rotation.as_ref(),
+            MapEntity::Multi(m) => m.rotation.as_ref(),
+            MapEntity::Nested(_) => None
+        };
+    }
+
+    /// The rotation of the whole map, different from the rotation of single tiles.
+    /// The editor can only show a single rotation, so the first value of a range is used
+    pub fn rotation(&self) -> u8 {
+        return match self.rotation_range() {
+            None => 0,
+            Some(rotation) => rotation.first().rem_euclid(4) as u8
+        };
+    }
+
+    /// Returns where the tile at the coordinates ends up once the rotation of the map is applied
+    pub fn get_rotated_coordinates(&self, coordinates: &Coordinates) -> Coordinates {
+        return coordinates.rotated(self.size().as_ivec2(), self.rotation());
+    }
+
+    /// The size of the map once its rotation is applied, odd rotations swap the width and height
+    pub fn get_rotated_size(&self) -> Vec2 {
+        let size = self.size();
+
+        return match self.rotation() % 2 {
+            1 => Vec2::new(size.y, size.x),
+            _ => size
         };
     }
 
+    /// Returns the coordinates where the characters of both Map Entities differ, including tiles which only exist in one of them.
+    /// Only the region where both Map Entities overlap is compared
+    pub fn get_differences(&self, other: &MapEntity) -> Vec<Coordinates> {
+        let size = self.size().min(other.size());
+        let mut differences = Vec::new();
+
+        for y in 0..size.y as i32 {
+            for x in 0..size.x as i32 {
+                let coordinates = Coordinates::new(x, y);
+                let character = self.tiles().get(&coordinates).map(|t| t.character);
+                let other_character = other.tiles().get(&coordinates).map(|t| t.character);
+
+                if character != other_character {
+                    differences.push(coordinates);
+                }
+            }
+        }
+
+        return differences;
+    }
+
     pub fn get_ids(&self, cdda_data: &CDDAData, character: &char) -> TileIdGroup {
         let mut group = TileIdGroup::default();
 
         macro_rules! match_id {
-            ($id: ident, $path: expr, $computed_parameters: expr) => {
+            ($id: ident, $path: expr, $tile_selection: expr, $character: expr) => {
                 match $id {
                     MapObjectId::Single(v) => {
                         match v {
@@ -151,7 +809,7 @@ impl MapEntity {
                             MeabyWeighted::Weighted(_) => todo!()
                         }
                     }
-                    MapObjectId::Grouped(g) => {
+                    MapObjectId::Grouped(g) | MapObjectId::Distribution { distribution: g } => {
                         let final_group: Vec<Weighted<MeabyParam>> = g.iter().map(|mw| {
                             match mw {
                                 MeabyWeighted::NotWeighted(v) => Weighted::new(v.clone(), 1),
@@ -169,24 +827,36 @@ impl MapEntity {
                     }
                     MapObjectId::Nested(_) => todo!(),
                     MapObjectId::Param { param, fallback } => {
-                        $path = Some($computed_parameters.get_value(param).expect(format!("Parameter {} to exist", param).as_str()).clone());
+                        $path = $tile_selection.get_param_value(param, fallback);
+                    }
+                    MapObjectId::Switch { switch, cases } => {
+                        let case = $tile_selection.get_active_switch_case($character, switch);
+
+                        if let Some(MeabyParam::TileId(id)) = case.and_then(|c| cases.get(&c)).map(|mw| mw.value()) {
+                            $path = Some(id.clone());
+                        }
                     }
-                    MapObjectId::Switch {switch, cases} => todo!(),
                 }
             }
         }
 
         if let Some(id) = self.object().terrain.get(character) {
-            match_id!(id, group.terrain, self.object().computed_parameters);
+            match_id!(id, group.terrain, self.object(), character);
         }
 
         if let Some(id) = self.object().furniture.get(character) {
-            match_id!(id, group.furniture, self.object().computed_parameters);
+            match_id!(id, group.furniture, self.object(), character);
         }
 
-        fn match_palette(map_entity: &MapEntity, cdda_data: &CDDAData, group: &mut TileIdGroup, character: &char, palette: &MapObjectId<MeabyParam>) {
+        fn match_palette(map_entity: &MapEntity, cdda_data: &CDDAData, group: &mut TileIdGroup, character: &char, palette: &MapObjectId<MeabyWeighted<MeabyParam>>) {
             let palette_id = match palette {
-                MapObjectId::Grouped(_) => { todo!() }
+                // The palette was already chosen when the parameters were computed, rolling again would change it every frame
+                MapObjectId::Grouped(_) | MapObjectId::Distribution { .. } => {
+                    match map_entity.object().get_palette_id(palette) {
+                        None => return,
+                        Some(id) => id
+                    }
+                }
                 MapObjectId::Nested(_) => { todo!() }
                 MapObjectId::Param { param, fallback } => {
                     match map_entity.object().computed_parameters.get_value(param) {
@@ -196,7 +866,7 @@ impl MapEntity {
                 }
                 MapObjectId::Switch { .. } => { todo!() }
                 MapObjectId::Single(mp) => {
-                    match mp {
+                    match mp.value() {
                         MeabyParam::TileId(id) => {
                             id.clone()
                         }
@@ -209,21 +879,31 @@ impl MapEntity {
 
             if let Some(id) = palette.furniture.get(character) {
                 if group.furniture.is_none() {
-                    match_id!(id, group.furniture, map_entity.object().computed_parameters);
+                    match_id!(id, group.furniture, map_entity.object(), character);
+                    group.furniture_palette = Some(palette_id.clone());
                 }
             }
 
             if let Some(id) = palette.terrain.get(character) {
                 if group.terrain.is_none() {
-                    match_id!(id, group.terrain, map_entity.object().computed_parameters);
+                    match_id!(id, group.terrain, map_entity.object(), character);
+                    group.terrain_palette = Some(palette_id.clone());
                 }
             }
 
+            if let Some(items) = palette.items.get(character) {
+                add_items(group, items);
+            }
+
             for parent_palette in palette.palettes.iter() {
                 match_palette(map_entity, cdda_data, group, character, parent_palette);
             }
         }
 
+        if let Some(items) = self.object().items.get(character) {
+            add_items(&mut group, items);
+        }
+
         for palette_object_id in self.object().palettes.iter() {
             match_palette(self, cdda_data, &mut group, character, palette_object_id);
         }
@@ -238,24 +918,136 @@ pub struct Single {
     pub om_terrain: String,
     pub tile_selection: TileSelection,
     pub tiles: HashMap<Coordinates, Tile>,
-    pub size: IVec2
+    pub size: IVec2,
+    /// The top level "//" comment of the imported mapgen, which is written back on export
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// The weight of the imported mapgen, which is written back on export
+    #[serde(default)]
+    pub weight: Option<u32>,
+    /// Which of the mapgens with the same om_terrain in the imported file this is
+    #[serde(default)]
+    pub variant: usize,
+    /// The amount of clockwise quarter turns which CDDA applies to the whole map when placing it.
+    /// Kept as it was imported so ranges are exported again, see [MapEntity::rotation] for the shown rotation
+    #[serde(default)]
+    pub rotation: Option<MeabyRange>,
+}
+
+/// The om_terrain of a mapgen which is used for more than one overmap terrain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MultiOmTerrain {
+    /// Rows of overmap terrain ids, every id is placed on its own 24x24 part of the map
+    Grid(Vec<Vec<String>>),
+    /// A list of overmap terrain ids which all reuse the same 24x24 map
+    Shared(Vec<String>),
+}
+
+impl Default for MultiOmTerrain {
+    fn default() -> Self {
+        return MultiOmTerrain::Shared(Vec::new());
+    }
+}
+
+impl MultiOmTerrain {
+    /// Returns every id in the order of its rows together with its position in overmap terrains.
+    /// The ids of a shared list are all at the origin
+    pub fn get_positioned(&self) -> Vec<(IVec2, &String)> {
+        return match self {
+            MultiOmTerrain::Grid(rows) => rows.iter()
+                .enumerate()
+                .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, id)| (IVec2::new(x as i32, y as i32), id)))
+                .collect(),
+            MultiOmTerrain::Shared(ids) => ids.iter().map(|id| (IVec2::ZERO, id)).collect()
+        };
+    }
+
+    pub fn contains(&self, id: &String) -> bool {
+        return self.get_positioned().iter().any(|(_, i)| *i == id);
+    }
+
+    /// Returns the id at the index of [MultiOmTerrain::get_positioned]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut String> {
+        return match self {
+            MultiOmTerrain::Grid(rows) => rows.iter_mut().flatten().nth(index),
+            MultiOmTerrain::Shared(ids) => ids.get_mut(index)
+        };
+    }
+
+    /// Only the rows of a grid span multiple overmap terrains, a shared list is the size of one overmap terrain
+    pub fn size(&self) -> IVec2 {
+        let (width, height) = match self {
+            MultiOmTerrain::Grid(rows) => (rows.iter().map(|r| r.len()).max().unwrap_or(0).max(1), rows.len().max(1)),
+            MultiOmTerrain::Shared(_) => (1, 1)
+        };
+
+        return IVec2::new(Multi::OVERMAP_TERRAIN_SIZE * width as i32, Multi::OVERMAP_TERRAIN_SIZE * height as i32);
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Multi {
-    pub om_terrain: Vec<String>,
+    pub om_terrain: MultiOmTerrain,
     pub tile_selection: TileSelection,
     pub tiles: HashMap<Coordinates, Tile>,
+    /// See [Single::rotation]
+    #[serde(default)]
+    pub rotation: Option<MeabyRange>,
+}
+
+impl Multi {
+    /// The size of a single overmap terrain
+    pub const OVERMAP_TERRAIN_SIZE: i32 = 24;
+
+    pub fn size(&self) -> IVec2 {
+        return self.om_terrain.size();
+    }
+
+    /// Returns the ids of the overmap terrains which contain the coordinates and the coordinates relative to them
+    pub fn get_om_terrain_at(&self, coordinates: &Coordinates) -> Option<(Vec<&String>, Coordinates)> {
+        let size = self.size();
+
+        if coordinates.x < 0 || coordinates.y < 0 || coordinates.x >= size.x || coordinates.y >= size.y {
+            return None;
+        }
+
+        let position = IVec2::new(coordinates.x / Self::OVERMAP_TERRAIN_SIZE, coordinates.y / Self::OVERMAP_TERRAIN_SIZE);
+        let ids: Vec<&String> = self.om_terrain.get_positioned()
+            .into_iter()
+            .filter(|(p, _)| *p == position)
+            .map(|(_, id)| id)
+            .collect();
+
+        if ids.is_empty() { return None; }
+
+        return Some((ids, Coordinates::new(coordinates.x % Self::OVERMAP_TERRAIN_SIZE, coordinates.y % Self::OVERMAP_TERRAIN_SIZE)));
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Nested {
     pub row_size: usize,
     pub om_terrain: Vec<String>,
+    /// Set for chunks which are only placed by place_nested and have no om_terrain
+    #[serde(default)]
+    pub nested_mapgen_id: Option<String>,
+    /// The size of the rows
+    #[serde(default)]
+    pub size: Option<IVec2>,
     pub tile_selection: TileSelection,
     pub tiles: HashMap<Coordinates, Tile>,
 }
 
+impl Nested {
+    pub fn get_name(&self) -> String {
+        return match &self.nested_mapgen_id {
+            None => self.om_terrain.join("_"),
+            Some(id) => id.clone()
+        };
+    }
+}
+
 #[derive(Event)]
 pub struct UpdateSpriteEvent {
     pub tile: Tile,
@@ -269,6 +1061,13 @@ pub struct TilePlaceEvent {
     pub should_update_sprites: bool,
 }
 
+/// Places many tiles at once without going through a [TilePlaceEvent] for every tile.
+/// Used when spawning a whole map because all tiles are known before any sprite is resolved
+#[derive(Event, Debug)]
+pub struct BulkTilePlaceEvent {
+    pub tiles: Vec<(Coordinates, Tile)>,
+}
+
 #[derive(Event, Debug)]
 pub struct TileDeleteEvent {
     pub tile: Tile,
@@ -282,3 +1081,429 @@ pub struct SpawnMapEntity {
 
 #[derive(Event)]
 pub struct ClearTiles;
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use bevy::math::IVec2;
+
+    use crate::common::{Coordinates, MeabyMulti, MeabyWeighted};
+    use crate::map::data::{CharacterCount, MapEntity, Multi, MultiOmTerrain, Single, TileIntegrityIssue, UndefinedCharacter};
+    use crate::palettes::data::{Item, MapObjectId, MeabyParam, Palette, Switch};
+    use crate::program::data::CDDAData;
+    use crate::region_settings::data::{RegionSettings, TerrainAndFurniture};
+    use crate::settings::data::MapEdge;
+    use crate::tiles::data::Tile;
+
+    fn create_map(size: IVec2, tiles: Vec<Coordinates>) -> MapEntity {
+        let mut map_tiles = HashMap::new();
+
+        for coordinates in tiles {
+            map_tiles.insert(coordinates, Tile::from('.'));
+        }
+
+        return MapEntity::Single(Single {
+            om_terrain: "test".into(),
+            tile_selection: Default::default(),
+            tiles: map_tiles,
+            size,
+            comment: None,
+            weight: None,
+            variant: 0,
+            rotation: None,
+        });
+    }
+
+    #[test]
+    pub fn test_verify_tiles_repairs_mismatched_entry() {
+        let mut map = create_map(
+            IVec2::new(2, 1),
+            vec![Coordinates::new(0, 0), Coordinates::new(5, 5)],
+        );
+
+        let issues = map.get_tile_integrity_issues();
+
+        assert_eq!(issues.len(), 2);
+        assert!(issues.contains(&TileIntegrityIssue::OutOfBounds(Coordinates::new(5, 5))));
+        assert!(issues.contains(&TileIntegrityIssue::Missing(Coordinates::new(1, 0))));
+
+        map.verify_tiles().unwrap();
+
+        assert!(map.get_tile_integrity_issues().is_empty());
+        assert_eq!(map.tiles().get(&Coordinates::new(1, 0)).unwrap().character, ' ');
+        assert!(map.tiles().get(&Coordinates::new(5, 5)).is_none());
+    }
+
+    #[test]
+    pub fn test_verify_tiles_fails_when_no_tile_is_inside() {
+        let mut map = create_map(
+            IVec2::new(1, 1),
+            vec![Coordinates::new(3, 3), Coordinates::new(4, 4)],
+        );
+
+        assert!(map.verify_tiles().is_err());
+    }
+
+    #[test]
+    pub fn test_verify_tiles_fails_on_unresolvable_definitions() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+
+        map.object_mut().terrain.insert('.', MapObjectId::Grouped(vec![]));
+        map.object_mut().furniture.insert('.', MapObjectId::Distribution { distribution: vec![] });
+        map.object_mut().terrain.insert('#', MapObjectId::Param { param: "wall_type".into(), fallback: None });
+        map.object_mut().terrain.insert('_', MapObjectId::Param { param: "floor_type".into(), fallback: Some("t_floor".into()) });
+
+        let issues = map.get_tile_integrity_issues();
+
+        assert_eq!(issues.len(), 3);
+        assert_eq!(issues.iter().filter(|i| **i == TileIntegrityIssue::EmptyGroup('.')).count(), 2);
+        assert!(issues.contains(&TileIntegrityIssue::UndefinedParameter { character: '#', parameter: "wall_type".into() }));
+        assert!(map.verify_tiles().is_err());
+
+        map.object_mut().computed_parameters.this.insert("wall_type".into(), "t_wall".into());
+        map.object_mut().terrain.insert('.', MapObjectId::Grouped(vec![MeabyWeighted::NotWeighted(MeabyParam::TileId("t_dirt".into()))]));
+        map.object_mut().furniture.remove(&'.');
+
+        assert!(map.get_tile_integrity_issues().is_empty());
+        assert!(map.verify_tiles().is_ok());
+    }
+
+    #[test]
+    pub fn test_character_usage_report() {
+        let mut map = create_map(
+            IVec2::new(2, 1),
+            vec![Coordinates::new(0, 0), Coordinates::new(1, 0)],
+        );
+
+        map.object_mut().terrain.insert(
+            '#',
+            MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_wall".into()))),
+        );
+
+        let report = map.get_character_usage_report(&CDDAData { palettes: HashMap::new() });
+
+        assert_eq!(report.undefined_characters, vec![UndefinedCharacter { character: '.', count: 2 }]);
+        assert_eq!(report.unused_definitions.len(), 1);
+        assert_eq!(report.unused_definitions[0].character, '#');
+        assert_eq!(report.unused_definitions[0].kind, "terrain");
+    }
+
+    #[test]
+    pub fn test_ids_of_undefined_character_are_empty() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+
+        map.object_mut().terrain.insert(
+            '#',
+            MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_wall".into()))),
+        );
+
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+
+        assert!(!map.get_ids(&cdda_data, &'#').is_empty());
+        assert!(map.get_ids(&cdda_data, &'.').is_empty());
+    }
+
+    #[test]
+    pub fn test_neighbors_of_drag_are_only_updated_if_autotiled() {
+        let mut coordinates = vec![];
+
+        for y in 0..3 {
+            for x in 0..100 {
+                coordinates.push(Coordinates::new(x, y));
+            }
+        }
+
+        let mut map = create_map(IVec2::new(100, 3), coordinates);
+
+        for x in 0..100 {
+            map.tiles_mut().insert(Coordinates::new(x, 0), Tile::from('#'));
+        }
+
+        // A drag over 100 tiles in the middle row used to update 4 neighbors per tile
+        let dragged: Vec<Coordinates> = (0..100).map(|x| Coordinates::new(x, 1)).collect();
+
+        assert_eq!(map.get_neighbors_to_update(&dragged, |_| false).len(), 0);
+        assert_eq!(map.get_neighbors_to_update(&dragged, |t| t.character == '#').len(), 100);
+        assert_eq!(map.get_neighbors_to_update(&dragged, |_| true).len(), 300);
+    }
+
+    #[test]
+    pub fn test_tiles_around_edge_tile() {
+        let mut coordinates = vec![];
+
+        for y in 0..2 {
+            for x in 0..2 {
+                coordinates.push(Coordinates::new(x, y));
+            }
+        }
+
+        let map = create_map(IVec2::new(2, 2), coordinates);
+        let corner = Coordinates::new(0, 0);
+
+        // Above and left of the corner is outside of the map
+        let open: Vec<Option<char>> = map.get_tiles_around(&corner, MapEdge::Open).iter().map(|(t, _)| t.map(|t| t.character)).collect();
+        assert_eq!(open, vec![None, Some('.'), Some('.'), None]);
+
+        let connected: Vec<Option<char>> = map.get_tiles_around(&corner, MapEdge::Connected).iter().map(|(t, _)| t.map(|t| t.character)).collect();
+        assert_eq!(connected, vec![Some('.'); 4]);
+
+        // The coordinates still point outside of the map
+        assert_eq!(map.get_tiles_around(&corner, MapEdge::Connected)[0].1, Coordinates::new(0, -1));
+    }
+
+    #[test]
+    pub fn test_param_value_uses_fallback() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+
+        map.object_mut().computed_parameters.this.insert("floor_type".into(), "t_floor".into());
+
+        assert_eq!(map.object().get_param_value(&"floor_type".into(), &Some("t_dirt".into())), Some("t_floor".into()));
+        assert_eq!(map.object().get_param_value(&"wall_type".into(), &Some("t_wall".into())), Some("t_wall".into()));
+        assert_eq!(map.object().get_param_value(&"wall_type".into(), &None), None);
+    }
+
+    #[test]
+    pub fn test_switch_uses_previewed_case() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+
+        let mut cases = HashMap::new();
+        cases.insert("wood".to_string(), MeabyWeighted::NotWeighted(MeabyParam::TileId("t_floor_wood".into())));
+        cases.insert("metal".to_string(), MeabyWeighted::NotWeighted(MeabyParam::TileId("t_floor_metal".into())));
+
+        map.object_mut().computed_parameters.this.insert("floor_type".into(), "wood".into());
+        map.object_mut().terrain.insert('.', MapObjectId::Switch {
+            switch: Switch { param: "floor_type".into(), fallback: None },
+            cases,
+        });
+
+        assert_eq!(map.get_ids(&cdda_data, &'.').terrain, Some("t_floor_wood".into()));
+
+        map.object_mut().switch_previews.insert('.', "metal".into());
+        assert_eq!(map.get_ids(&cdda_data, &'.').terrain, Some("t_floor_metal".into()));
+    }
+
+    #[test]
+    pub fn test_differences_only_compare_overlapping_region() {
+        let mut map = create_map(
+            IVec2::new(2, 2),
+            vec![Coordinates::new(0, 0), Coordinates::new(1, 0), Coordinates::new(0, 1)],
+        );
+        map.tiles_mut().insert(Coordinates::new(1, 0), Tile::from('#'));
+
+        let other = create_map(
+            IVec2::new(3, 1),
+            vec![Coordinates::new(0, 0), Coordinates::new(1, 0), Coordinates::new(2, 0)],
+        );
+
+        assert_eq!(map.get_differences(&other), vec![Coordinates::new(1, 0)]);
+
+        let other = create_map(IVec2::new(2, 2), vec![Coordinates::new(0, 0)]);
+        assert_eq!(map.get_differences(&other), vec![Coordinates::new(1, 0), Coordinates::new(0, 1)]);
+    }
+
+    #[test]
+    pub fn test_items_of_map_object_and_palette_are_collected() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+
+        let item = |id: &str| serde_json::from_value::<Item>(serde_json::json!({ "item": id, "chance": 50 })).unwrap();
+
+        let mut palette = Palette { id: "test_palette".into(), ..Palette::default() };
+        palette.items.insert('.', MeabyMulti::Multi(vec![item("log"), item("stick")]));
+
+        let mut palettes = HashMap::new();
+        palettes.insert(palette.id.clone(), palette);
+        let cdda_data = CDDAData { palettes };
+
+        map.object_mut().items.insert('.', MeabyMulti::Single(item("rock")));
+        map.object_mut().palettes.push(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("test_palette".into()))));
+
+        let items = map.get_ids(&cdda_data, &'.').items;
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].get_label(), "rock (50%)");
+        assert!(map.get_ids(&cdda_data, &'#').items.is_empty());
+    }
+
+    #[test]
+    pub fn test_flatten_palettes_keeps_definitions_of_the_map() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+        let id = |id: &str| MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId(id.into())));
+
+        let mut palette = Palette { id: "test_palette".into(), ..Palette::default() };
+        palette.terrain.insert('.', id("t_dirt"));
+        palette.terrain.insert('#', id("t_wall"));
+        palette.furniture.insert('c', id("f_chair"));
+
+        let mut palettes = HashMap::new();
+        palettes.insert(palette.id.clone(), palette);
+        let cdda_data = CDDAData { palettes };
+
+        map.object_mut().terrain.insert('.', id("t_floor"));
+        map.object_mut().palettes.push(id("test_palette"));
+        map.object_mut().recompute_palette_parameters(&cdda_data);
+
+        map.object_mut().flatten_palettes(&cdda_data);
+
+        assert!(map.object().palettes.is_empty());
+        assert!(map.object().computed_parameters.palettes.is_empty());
+        assert_eq!(map.get_ids(&cdda_data, &'.').terrain, Some("t_floor".into()));
+        assert_eq!(map.get_ids(&cdda_data, &'#').terrain, Some("t_wall".into()));
+        assert_eq!(map.get_ids(&cdda_data, &'c').furniture, Some("f_chair".into()));
+        assert_eq!(map.get_ids(&cdda_data, &'#').terrain_palette, None);
+    }
+
+    #[test]
+    pub fn test_fill_ter_parameter_resolves_to_region() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+
+        map.object_mut().fill_ter = Some(serde_json::from_value(serde_json::json!({ "param": "ground", "fallback": "t_region_groundcover" })).unwrap());
+
+        let fill_ter = map.object().get_fill_ter().unwrap();
+        assert_eq!(fill_ter, "t_region_groundcover");
+
+        let mut groundcover = HashMap::new();
+        groundcover.insert("t_grass".to_string(), 1);
+
+        let mut terrain = HashMap::new();
+        terrain.insert("t_region_groundcover".to_string(), groundcover);
+
+        let region_settings = RegionSettings {
+            id: "default".into(),
+            default_oter: vec![],
+            default_groundcover: vec![],
+            region_terrain_and_furniture: TerrainAndFurniture { terrain, furniture: HashMap::new() },
+        };
+
+        assert_eq!(region_settings.get_random_terrain_from_region(&fill_ter), Some(&"t_grass".to_string()));
+
+        map.object_mut().computed_parameters.this.insert("ground".into(), "t_dirt".into());
+        assert_eq!(map.object().get_fill_ter(), Some("t_dirt".into()));
+    }
+
+    #[test]
+    pub fn test_fill_ter_distribution_is_picked_by_weight() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+
+        // The first entry can never be picked
+        map.object_mut().fill_ter = Some(serde_json::from_value(serde_json::json!({ "distribution": [["t_dirt", 0], ["t_grass", 1]] })).unwrap());
+        assert!(matches!(map.object().fill_ter, Some(MapObjectId::Distribution { .. })));
+
+        for _ in 0..10 {
+            map.object_mut().roll_fill_ter();
+            assert_eq!(map.object().get_fill_ter(), Some("t_grass".into()));
+        }
+
+        map.object_mut().fill_ter = Some(serde_json::from_value(serde_json::json!([["t_dirt", 0], ["t_grass", 1]])).unwrap());
+
+        for _ in 0..10 {
+            map.object_mut().roll_fill_ter();
+            assert_eq!(map.object().get_fill_ter(), Some("t_grass".into()));
+        }
+    }
+
+    #[test]
+    pub fn test_fill_ter_distribution_is_not_rolled_again() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+
+        map.object_mut().fill_ter = Some(serde_json::from_value(serde_json::json!({ "distribution": [["t_dirt", 1], ["t_grass", 1]] })).unwrap());
+        map.object_mut().roll_fill_ter();
+
+        // Every sprite update asks for the fill terrain, so it has to stay the same until it is rolled again
+        let rolled = map.object().get_fill_ter();
+        assert!(rolled.is_some());
+
+        for _ in 0..50 {
+            assert_eq!(map.object().get_fill_ter(), rolled);
+        }
+    }
+
+    #[test]
+    pub fn test_coordinates_with_terrain() {
+        let mut map = create_map(
+            IVec2::new(2, 2),
+            vec![Coordinates::new(0, 0), Coordinates::new(1, 0), Coordinates::new(0, 1), Coordinates::new(1, 1)],
+        );
+        map.tiles_mut().insert(Coordinates::new(1, 0), Tile::from('#'));
+        map.tiles_mut().insert(Coordinates::new(0, 1), Tile::from('x'));
+
+        map.object_mut().fill_ter = Some(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_dirt".into()))));
+        map.object_mut().terrain.insert('.', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_grass".into()))));
+        map.object_mut().terrain.insert('#', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_wall".into()))));
+
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+
+        assert_eq!(map.coordinates_with_terrain(&cdda_data, &"t_grass".into()), vec![Coordinates::new(0, 0), Coordinates::new(1, 1)]);
+        assert_eq!(map.coordinates_with_terrain(&cdda_data, &"t_wall".into()), vec![Coordinates::new(1, 0)]);
+
+        // The undefined character is on the fill terrain
+        assert_eq!(map.coordinates_with_terrain(&cdda_data, &"t_dirt".into()), vec![Coordinates::new(0, 1)]);
+        assert!(map.coordinates_with_terrain(&cdda_data, &"t_water_sh".into()).is_empty());
+    }
+
+    #[test]
+    pub fn test_character_counts_are_sorted_by_usage() {
+        let mut map = create_map(
+            IVec2::new(2, 2),
+            vec![Coordinates::new(0, 0), Coordinates::new(1, 0), Coordinates::new(0, 1), Coordinates::new(1, 1)],
+        );
+        map.tiles_mut().insert(Coordinates::new(1, 1), Tile::from('#'));
+
+        let counts = map.get_character_counts();
+
+        assert_eq!(counts, vec![
+            CharacterCount { character: '.', count: 3, percentage: 75. },
+            CharacterCount { character: '#', count: 1, percentage: 25. },
+        ]);
+        assert_eq!(map.get_coordinates_of_character(&'.'), vec![Coordinates::new(0, 0), Coordinates::new(1, 0), Coordinates::new(0, 1)]);
+    }
+
+    #[test]
+    pub fn test_character_of_attached_palette_is_defined() {
+        let mut map = create_map(IVec2::new(1, 1), vec![Coordinates::new(0, 0)]);
+
+        let mut palette = Palette { id: "test_palette".into(), ..Palette::default() };
+        palette.furniture.insert('h', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("f_chair".into()))));
+
+        let mut palettes = HashMap::new();
+        palettes.insert(palette.id.clone(), palette);
+        let cdda_data = CDDAData { palettes };
+
+        map.object_mut().computed_parameters.palettes.insert("test_palette".into(), Default::default());
+
+        assert!(map.is_character_defined(&cdda_data, &'h'));
+        assert!(!map.is_character_defined(&cdda_data, &'x'));
+    }
+
+    #[test]
+    pub fn test_coordinates_are_mapped_to_their_om_terrain() {
+        let (west, east, south) = ("house_west".to_string(), "house_east".to_string(), "house_south".to_string());
+        let multi = Multi {
+            om_terrain: MultiOmTerrain::Grid(vec![vec![west.clone(), east.clone()], vec![south.clone()]]),
+            ..Default::default()
+        };
+
+        assert_eq!(multi.size(), IVec2::new(48, 48));
+        assert_eq!(multi.get_om_terrain_at(&Coordinates::new(3, 5)), Some((vec![&west], Coordinates::new(3, 5))));
+        assert_eq!(multi.get_om_terrain_at(&Coordinates::new(24, 0)), Some((vec![&east], Coordinates::new(0, 0))));
+        assert_eq!(multi.get_om_terrain_at(&Coordinates::new(47, 23)), Some((vec![&east], Coordinates::new(23, 23))));
+        assert_eq!(multi.get_om_terrain_at(&Coordinates::new(0, 24)), Some((vec![&south], Coordinates::new(0, 0))));
+        // The second row has no om_terrain in its second column
+        assert_eq!(multi.get_om_terrain_at(&Coordinates::new(24, 24)), None);
+        assert_eq!(multi.get_om_terrain_at(&Coordinates::new(48, 0)), None);
+    }
+
+    #[test]
+    pub fn test_shared_om_terrains_reuse_one_overmap_terrain() {
+        let (north, south) = ("house_north".to_string(), "house_south".to_string());
+        let multi = Multi {
+            om_terrain: MultiOmTerrain::Shared(vec![north.clone(), south.clone()]),
+            ..Default::default()
+        };
+
+        assert_eq!(multi.size(), IVec2::new(24, 24));
+        assert_eq!(multi.get_om_terrain_at(&Coordinates::new(23, 23)), Some((vec![&north, &south], Coordinates::new(23, 23))));
+        assert_eq!(multi.get_om_terrain_at(&Coordinates::new(24, 0)), None);
+    }
+}
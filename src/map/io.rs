@@ -1,25 +1,148 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
 use std::path::PathBuf;
+
+use anyhow::anyhow;
 use bevy::math::IVec2;
 use bevy::tasks::futures_lite::StreamExt;
-use log::info;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
 
-use crate::common::{Coordinates, MeabyWeighted, TileId};
+use crate::common::{Coordinates, GetRandom, MeabyMulti, MeabyWeighted, TileId, Weighted};
 use crate::common::io::{Load, LoadError};
 use crate::program::data::CDDAData;
-use crate::map::data::{ComputedParameters, Multi, Nested, Single, TileSelection};
-use crate::palettes::data::{MapGenValue, MapObjectId, MeabyParam, PaletteId, ParameterType};
+use crate::settings::data::RowSizeMismatch;
+use crate::map::data::{ComputedParameters, MapEntity, MeabyRange, Multi, MultiOmTerrain, Nested, PlaceLoot, Single, TileSelection};
+use crate::palettes::data::{Item, MapGenValue, MapObjectId, MeabyParam, PaletteId, ParameterType};
 use crate::tiles::data::Tile;
 
 pub type ParameterId = String;
 
+/// The kinds of maps a mapgen file can contain, see [MapEntityLoader::get_mapgen_kind]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapgenKind {
+    /// A mapgen with a single om_terrain
+    Single,
+    /// A mapgen with a list or rows of om_terrains
+    Multi,
+    /// A mapgen which is only placed by other mapgens
+    Nested,
+}
+
 pub struct MapEntityLoader<'a> {
     pub path: PathBuf,
     pub id: String,
     pub cdda_data: &'a CDDAData,
+    pub row_size_mismatch: RowSizeMismatch,
+    /// Which of the mapgens with the same om_terrain is loaded, if None one is chosen by their weight
+    pub variant: Option<usize>,
+    /// Problems of the loaded mapgen which did not stop it from loading, like rows of the wrong length
+    pub warnings: RefCell<Vec<String>>,
+}
+
+impl MapEntityLoader<'_> {
+    /// Returns the warnings of the previous loads and clears them
+    pub fn take_warnings(&self) -> Vec<String> {
+        return self.warnings.take();
+    }
+
+    /// Returns which kind of map the mapgen with the id is, decided by the shape of its om_terrain.
+    /// Returns None if no mapgen of the objects has the id
+    pub fn get_mapgen_kind(&self, objects: &Vec<Value>) -> Option<MapgenKind> {
+        for object in objects.iter() {
+            if object.get("nested_mapgen_id").and_then(|i| i.as_str()) == Some(self.id.as_str()) {
+                return Some(MapgenKind::Nested);
+            }
+
+            let kind = match object.get("om_terrain") {
+                Some(Value::String(id)) if *id == self.id => MapgenKind::Single,
+                Some(Value::Array(ids)) if ids.iter().any(|i| i.as_str() == Some(self.id.as_str())) => MapgenKind::Multi,
+                Some(Value::Array(rows)) if rows.iter().filter_map(|r| r.as_array()).flatten().any(|i| i.as_str() == Some(self.id.as_str())) => MapgenKind::Multi,
+                _ => continue
+            };
+
+            return Some(kind);
+        }
+
+        return None;
+    }
+
+    /// Turns the rows into tiles and returns them with the size of the map.
+    /// Rows which do not match the declared size are added to the warnings, the size is then expanded to fit them
+    /// or the rows are cropped. Rows which are too short are filled with spaces
+    fn load_tiles_of_rows(&self, rows: &Vec<String>, declared_size: IVec2, row_size_mismatch: RowSizeMismatch) -> (HashMap<Coordinates, Tile>, IVec2) {
+        let mut fitting_size = declared_size;
+        let mut warnings = Vec::new();
+
+        for (row, characters) in rows.iter().enumerate() {
+            let width = characters.chars().count() as i32;
+
+            if width != declared_size.x {
+                warnings.push(format!("Row {} {:?} of {} has {} characters but the map is declared to be {} wide", row, characters, self.id, width, declared_size.x));
+            }
+
+            fitting_size.x = fitting_size.x.max(width);
+        }
+
+        if rows.len() as i32 != declared_size.y {
+            warnings.push(format!("{} has {} rows but is declared to be {} high", self.id, rows.len(), declared_size.y));
+        }
+
+        fitting_size.y = fitting_size.y.max(rows.len() as i32);
+
+        let size = match row_size_mismatch {
+            RowSizeMismatch::Expand => fitting_size,
+            RowSizeMismatch::Crop => declared_size
+        };
+
+        let mut tiles = HashMap::new();
+
+        for (row, characters) in rows.iter().enumerate() {
+            // to_string returns quotes so we use as_str
+            for (column, char) in characters.as_str().chars().enumerate() {
+                let coordinates = Coordinates::new(column as i32, row as i32);
+
+                if coordinates.x >= size.x || coordinates.y >= size.y { continue; }
+
+                tiles.insert(coordinates, Tile::from(char));
+            }
+        }
+
+        // Short and missing rows would leave holes in the map
+        for y in 0..size.y {
+            for x in 0..size.x {
+                tiles.entry(Coordinates::new(x, y)).or_insert(Tile::from(' '));
+            }
+        }
+
+        for warning in warnings.iter() {
+            warn!("{}", warning);
+        }
+
+        self.warnings.borrow_mut().extend(warnings);
+
+        return (tiles, size);
+    }
+}
+
+/// The weight which CDDA uses for mapgens without a weight
+const DEFAULT_MAPGEN_WEIGHT: u32 = 1000;
+
+/// Reads the mapgen objects of the file at the path
+pub fn read_mapgen_file<T: DeserializeOwned>(path: &PathBuf) -> Result<Vec<T>, LoadError> {
+    let contents = read_to_string(path).map_err(|e| LoadError::Io(path.clone(), e))?;
+
+    return serde_json::from_str(contents.as_str())
+        .map_err(|e| LoadError::Other(anyhow!("Could not parse the mapgen file {:?} {}", path, e)));
+}
+
+/// Parses a field of the mapgen with the id, the error names the field which could not be parsed
+fn parse_mapgen_field<T: DeserializeOwned>(value: &Value, field: &str, id: &str) -> Result<T, LoadError> {
+    return serde_json::from_value(value.clone())
+        .map_err(|e| LoadError::Other(anyhow!("Could not parse the {} of the mapgen {} {}", field, id, e)));
 }
 
 #[derive(Deserialize, Clone, Serialize, Debug)]
@@ -29,26 +152,64 @@ pub struct Parameter {
     pub default: MapGenValue,
 }
 
-fn compute_palettes(
+/// Chooses one palette of the weighted entries.
+/// A palette which was already chosen before is kept, so the map does not change every time the parameters are computed
+fn choose_palette_id(
+    entries: &Vec<MeabyWeighted<MeabyParam>>,
+    parameters: &HashMap<String, String>,
+    previous: &HashMap<PaletteId, ComputedParameters>,
+) -> Option<PaletteId> {
+    let weighted_ids: Vec<Weighted<PaletteId>> = entries.iter()
+        .map(|entry| {
+            let id = match entry.value() {
+                MeabyParam::TileId(id) => id.clone(),
+                MeabyParam::Parameter(p) => parameters.get(&p.param).unwrap_or(&p.fallback).clone()
+            };
+
+            return match entry {
+                MeabyWeighted::NotWeighted(_) => Weighted::new(id, 1),
+                MeabyWeighted::Weighted(w) => Weighted::new(id, w.weight)
+            };
+        })
+        .collect();
+
+    if let Some(chosen) = weighted_ids.iter().find(|w| previous.contains_key(&w.value)) {
+        return Some(chosen.value.clone());
+    }
+
+    return weighted_ids.get_random_weighted().cloned();
+}
+
+pub fn compute_palettes(
     cdda_data: &CDDAData,
     parameters: &HashMap<String, String>,
-    palettes: &Vec<MapObjectId<MeabyParam>>,
+    palettes: &Vec<MapObjectId<MeabyWeighted<MeabyParam>>>,
+    previous: &HashMap<PaletteId, ComputedParameters>,
 ) -> HashMap<PaletteId, ComputedParameters> {
     let mut computed_palettes = HashMap::new();
 
     for palette in palettes.iter() {
         let palette_id: PaletteId = match palette {
-            MapObjectId::Grouped(_) => { todo!() }
+            MapObjectId::Grouped(entries) | MapObjectId::Distribution { distribution: entries } => {
+                match choose_palette_id(entries, parameters, previous) {
+                    None => continue,
+                    Some(id) => id
+                }
+            }
             MapObjectId::Nested(_) => { todo!() }
             MapObjectId::Param { param, fallback } => {
-                match parameters.get(param) {
-                    None => fallback.as_ref().unwrap().clone(),
-                    Some(v) => v.clone()
+                match (parameters.get(param), fallback) {
+                    (Some(v), _) => v.clone(),
+                    (None, Some(f)) => f.clone(),
+                    (None, None) => {
+                        warn!("The palette parameter {} has no value and no fallback", param);
+                        continue;
+                    }
                 }
             }
             MapObjectId::Switch { .. } => { todo!() }
             MapObjectId::Single(mp) => {
-                match mp {
+                match mp.value() {
                     MeabyParam::TileId(i) => {
                         i.clone()
                     }
@@ -57,7 +218,13 @@ fn compute_palettes(
             }
         };
 
-        let associated_palette = cdda_data.palettes.get(&palette_id).unwrap();
+        let associated_palette = match cdda_data.palettes.get(&palette_id) {
+            None => {
+                warn!("The palette {} does not exist in the CDDA data", palette_id);
+                continue;
+            }
+            Some(p) => p
+        };
 
         let mut this = HashMap::new();
 
@@ -67,7 +234,12 @@ fn compute_palettes(
 
         let computed_palette_parameters = ComputedParameters {
             this: this.clone(),
-            palettes: compute_palettes(cdda_data, &this, &associated_palette.palettes),
+            palettes: compute_palettes(
+                cdda_data,
+                &this,
+                &associated_palette.palettes,
+                previous.get(&palette_id).map(|p| &p.palettes).unwrap_or(&HashMap::new()),
+            ),
         };
 
         computed_palettes.insert(palette_id, computed_palette_parameters.clone());
@@ -80,11 +252,17 @@ fn compute_palettes(
 
 impl Load<Single> for MapEntityLoader<'_> {
     fn load(&self) -> Result<Single, LoadError> {
-        let objects = serde_json::from_str::<Vec<HashMap<String, Value>>>(read_to_string(&self.path).unwrap().as_str()).unwrap();
+        return self.load_single(read_mapgen_file(&self.path)?);
+    }
+}
 
-        let mapgen_entity = objects
+impl MapEntityLoader<'_> {
+    /// Loads the mapgen with the om_terrain from the already parsed objects of a mapgen file
+    pub fn load_single(&self, objects: Vec<Value>) -> Result<Single, LoadError> {
+        let mut mapgen_entities: Vec<HashMap<String, Value>> = objects
             .into_iter()
-            .find(|o| {
+            .filter_map(|o| serde_json::from_value::<HashMap<String, Value>>(o).ok())
+            .filter(|o| {
                 return match o.get("om_terrain") {
                     None => false,
                     Some(s) => match serde_json::from_value::<MapObjectId<String>>(s.clone()) {
@@ -97,35 +275,69 @@ impl Load<Single> for MapEntityLoader<'_> {
                                 _ => false
                             }
                         }
-                        Err(_) => { todo!() }
+                        Err(_) => false
                     }
                 };
             })
-            .unwrap();
+            .collect();
 
-        let om_terrain = mapgen_entity.get("om_terrain").unwrap();
-        let object = mapgen_entity.get("object").unwrap();
-        let rows: Vec<String> = serde_json::from_value(object.get("rows").unwrap().clone()).unwrap();
-        let parameters = match object.get("parameters") {
-            None => HashMap::new(),
-            Some(v) => serde_json::from_value::<HashMap<ParameterId, Parameter>>(v.clone()).unwrap()
+        let get_weight = |entity: &HashMap<String, Value>| entity.get("weight")
+            .and_then(|w| w.as_u64())
+            .map(|w| w as u32);
+
+        // CDDA picks one of the mapgens with the same om_terrain at random
+        let variant = match self.variant {
+            Some(v) => v,
+            None => mapgen_entities.iter()
+                .enumerate()
+                .map(|(i, e)| MeabyWeighted::Weighted(Weighted::new(i, get_weight(e).unwrap_or(DEFAULT_MAPGEN_WEIGHT))))
+                .collect::<Vec<MeabyWeighted<usize>>>()
+                .get_random_weighted()
+                .copied()
+                .unwrap_or(0)
         };
-        let palettes: Vec<MapObjectId<MeabyParam>> = serde_json::from_value(object.get("palettes")
-            .unwrap_or(&Value::Array(Vec::new())).clone())
-            .unwrap();
 
-        let mut tiles = HashMap::new();
+        let amount_of_variants = mapgen_entities.len();
 
-        for (row, tile) in rows.iter().enumerate() {
-            // to_string returns quotes so we use as_str
-            for (column, char) in tile.as_str().chars().enumerate() {
-                tiles.insert(
-                    Coordinates::new(column as i32, row as i32),
-                    Tile::from(char),
-                );
-            }
+        if amount_of_variants == 0 {
+            return Err(LoadError::Other(anyhow!("No mapgen with the om_terrain {} was found", self.id)));
         }
 
+        if variant >= amount_of_variants {
+            return Err(LoadError::Other(anyhow!("No mapgen with the om_terrain {} and the variant {} was found", self.id, variant)));
+        }
+
+        let mapgen_entity = mapgen_entities.swap_remove(variant);
+        let weight = get_weight(&mapgen_entity);
+
+        // The om_terrain exists, otherwise the mapgen would have been filtered out
+        let om_terrain = &mapgen_entity["om_terrain"];
+        let comment = mapgen_entity.get("//").and_then(|c| c.as_str()).map(|c| c.to_string());
+
+        let object = match mapgen_entity.get("object") {
+            None => return Err(LoadError::Other(anyhow!("The mapgen {} has no object", self.id))),
+            Some(o) => o
+        };
+
+        let rows: Vec<String> = match object.get("rows") {
+            None => return Err(LoadError::Other(anyhow!("The mapgen {} has no rows", self.id))),
+            Some(r) => parse_mapgen_field(r, "rows", &self.id)?
+        };
+        let declared_size = match object.get("mapgensize").map(|s| serde_json::from_value::<IVec2>(s.clone())) {
+            Some(Ok(s)) => s,
+            _ => IVec2::new(rows.get(0).map(|r| r.chars().count()).unwrap_or(0) as i32, rows.len() as i32)
+        };
+        let parameters = match object.get("parameters") {
+            None => HashMap::new(),
+            Some(v) => parse_mapgen_field::<HashMap<ParameterId, Parameter>>(v, "parameters", &self.id)?
+        };
+        let palettes: Vec<MapObjectId<MeabyWeighted<MeabyParam>>> = match object.get("palettes") {
+            None => Vec::new(),
+            Some(p) => parse_mapgen_field(p, "palettes", &self.id)?
+        };
+
+        let (tiles, size) = self.load_tiles_of_rows(&rows, declared_size, self.row_size_mismatch);
+
         let mut this = HashMap::new();
 
         for (parameter_id, parameter) in parameters.iter() {
@@ -137,63 +349,172 @@ impl Load<Single> for MapEntityLoader<'_> {
 
         let computed_parameters = ComputedParameters {
             this: this.clone(),
-            palettes: compute_palettes(self.cdda_data, &this, &palettes),
+            palettes: compute_palettes(self.cdda_data, &this, &palettes, &HashMap::new()),
         };
 
         let terrain = match object.get("terrain") {
             None => HashMap::new(),
-            Some(t) => serde_json::from_value(t.clone()).unwrap()
+            Some(t) => parse_mapgen_field(t, "terrain", &self.id)?
         };
 
         let furniture = match object.get("furniture") {
             None => HashMap::new(),
-            Some(f) => serde_json::from_value(f.clone()).unwrap()
+            Some(f) => parse_mapgen_field(f, "furniture", &self.id)?
         };
 
-        let fill: Option<TileId> = match object.get("fill_ter") {
+        let items = match object.get("items") {
+            None => HashMap::new(),
+            Some(i) => parse_mapgen_field(i, "items", &self.id)?
+        };
+
+        let place_loot = match object.get("place_loot").map(|p| serde_json::from_value(p.clone())) {
+            None => Vec::new(),
+            Some(Ok(p)) => p,
+            Some(Err(e)) => {
+                warn!("Could not parse the place_loot of {} {:?}", om_terrain, e);
+                Vec::new()
+            }
+        };
+
+        // A range of rotations is kept so it can be exported again
+        let rotation = match object.get("rotation") {
+            None => None,
+            Some(r) => Some(parse_mapgen_field(r, "rotation", &self.id)?)
+        };
+
+        // The fill terrain can also be a parameter or a distribution
+        let fill: Option<MapObjectId<MeabyWeighted<MeabyParam>>> = match object.get("fill_ter") {
             None => None,
-            Some(v) => Some(String::from(v.as_str().unwrap().to_string()))
-        };
-
-        info!("Loaded Single Mapgen Object {}", om_terrain);
-
-        return Ok(
-            Single {
-                om_terrain: om_terrain.to_string(),
-                tile_selection: TileSelection {
-                    fill_ter: fill,
-                    parameters,
-                    computed_parameters,
-                    palettes,
-                    terrain,
-                    furniture,
-                },
-                tiles,
-                size: IVec2::new(rows.get(0).unwrap().len() as i32, rows.len() as i32),
+            Some(v) => match serde_json::from_value(v.clone()) {
+                Ok(f) => Some(f),
+                Err(e) => {
+                    warn!("Could not parse the fill_ter of {} {:?}", om_terrain, e);
+                    None
+                }
             }
-        );
+        };
+
+        info!("Loaded Single Mapgen Object {} variant {} of {}", om_terrain, variant + 1, amount_of_variants);
+
+        let mut single = Single {
+            // to_string returns quotes so we use as_str
+            om_terrain: om_terrain.as_str().map(|s| s.to_string()).unwrap_or(om_terrain.to_string()),
+            tile_selection: TileSelection {
+                fill_ter: fill,
+                rolled_fill_ter: None,
+                parameters,
+                computed_parameters,
+                palettes,
+                terrain,
+                furniture,
+                items,
+                place_loot,
+                switch_previews: HashMap::new(),
+            },
+            tiles,
+            size,
+            comment,
+            weight,
+            variant,
+            rotation,
+        };
+
+        single.tile_selection.roll_fill_ter();
+
+        return Ok(single);
     }
 }
 
 impl Load<Multi> for MapEntityLoader<'_> {
     fn load(&self) -> Result<Multi, LoadError> {
-        todo!()
+        return self.load_multi(read_mapgen_file(&self.path)?);
+    }
+}
+
+impl MapEntityLoader<'_> {
+    /// Loads the mapgen with the om_terrain in its om_terrain list from the already parsed objects of a mapgen file
+    pub fn load_multi(&self, objects: Vec<Value>) -> Result<Multi, LoadError> {
+        let objects: Vec<CDDAMultiMapgenObject> = objects
+            .into_iter()
+            .filter_map(|hm| serde_json::from_value(hm).ok())
+            .collect();
+
+        let entity = match objects.iter().find(|o| o.om_terrain.contains(&self.id)) {
+            None => return Err(LoadError::Other(anyhow!("No mapgen with the om_terrain {} in its om_terrain list was found", self.id))),
+            Some(e) => e
+        };
+
+        // The size of the map is derived from its om_terrains, so rows which do not fit are always cropped
+        let declared_size = entity.om_terrain.size();
+        let (tiles, _) = self.load_tiles_of_rows(&entity.object.rows, declared_size, RowSizeMismatch::Crop);
+
+        let mut this = HashMap::new();
+        let parameters = entity.parameters.clone().unwrap_or(HashMap::new());
+
+        for (parameter_id, parameter) in parameters.iter() {
+            this.insert(
+                parameter_id.clone(),
+                parameter.default.get_value(),
+            );
+        }
+
+        let computed_parameters = ComputedParameters {
+            this: this.clone(),
+            palettes: compute_palettes(self.cdda_data, &this, &entity.object.palettes, &HashMap::new()),
+        };
+
+        info!("Loaded Multi Mapgen Object {:?}", entity.om_terrain);
+
+        let mut multi = Multi {
+            om_terrain: entity.om_terrain.clone(),
+            tile_selection: TileSelection {
+                fill_ter: entity.object.fill_ter.clone(),
+                rolled_fill_ter: None,
+                computed_parameters,
+                parameters,
+                palettes: entity.object.palettes.clone(),
+                terrain: entity.object.terrain.clone().unwrap_or(HashMap::new()),
+                furniture: entity.object.furniture.clone().unwrap_or(HashMap::new()),
+                items: entity.object.items.clone().unwrap_or(HashMap::new()),
+                place_loot: entity.object.place_loot.clone(),
+                switch_previews: HashMap::new(),
+            },
+            tiles,
+            rotation: entity.object.rotation.clone(),
+        };
+
+        multi.tile_selection.roll_fill_ter();
+
+        return Ok(multi);
     }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct CDDAMapgenObject {
-    fill_ter: Option<String>,
+    fill_ter: Option<MapObjectId<MeabyWeighted<MeabyParam>>>,
     rows: Vec<String>,
-    palettes: Vec<MapObjectId<MeabyParam>>,
+    #[serde(default)]
+    mapgensize: Option<IVec2>,
+    #[serde(default)]
+    rotation: Option<MeabyRange>,
+    /// CDDA defines the parameters in the object, older files define them next to it
+    #[serde(default)]
+    parameters: Option<HashMap<ParameterId, Parameter>>,
+    #[serde(default)]
+    palettes: Vec<MapObjectId<MeabyWeighted<MeabyParam>>>,
 
     terrain: Option<HashMap<char, MapObjectId<MeabyWeighted<MeabyParam>>>>,
     furniture: Option<HashMap<char, MapObjectId<MeabyWeighted<MeabyParam>>>>,
+    items: Option<HashMap<char, MeabyMulti<Item>>>,
+    #[serde(default)]
+    place_loot: Vec<PlaceLoot>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct CDDANestedMapgenObject {
+    #[serde(default)]
     om_terrain: Vec<Vec<String>>,
+    nested_mapgen_id: Option<String>,
     method: String,
     #[serde(rename = "type")]
     om_type: String,
@@ -201,39 +522,51 @@ struct CDDANestedMapgenObject {
     object: CDDAMapgenObject,
 }
 
+/// A mapgen whose om_terrain is a list or rows of overmap terrain ids
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct CDDAMultiMapgenObject {
+    om_terrain: MultiOmTerrain,
+    parameters: Option<HashMap<ParameterId, Parameter>>,
+    object: CDDAMapgenObject,
+}
+
 impl Load<Nested> for MapEntityLoader<'_> {
     fn load(&self) -> Result<Nested, LoadError> {
-        let objects: Vec<CDDANestedMapgenObject> = serde_json::from_str::<Vec<Value>>(read_to_string(&self.path).unwrap().as_str())
-            .unwrap()
+        return self.load_nested(read_mapgen_file(&self.path)?);
+    }
+}
+
+impl MapEntityLoader<'_> {
+    /// Loads the nested mapgen with the id from the already parsed objects of a mapgen file
+    pub fn load_nested(&self, objects: Vec<Value>) -> Result<Nested, LoadError> {
+        let objects: Vec<CDDANestedMapgenObject> = objects
             .into_iter()
-            .filter_map(|hm| {
-                return match serde_json::from_value(hm) {
-                    Err(e) => None,
-                    Ok(v) => Some(v)
-                };
-            })
+            .filter_map(|hm| serde_json::from_value(hm).ok())
             .collect();
 
-        // TODO: Handle
-        let entity = objects.first().unwrap();
+        let entity = objects.iter().find(|o| {
+            return match &o.nested_mapgen_id {
+                None => o.om_terrain.iter().flatten().any(|id| id.clone() == self.id),
+                Some(id) => id.clone() == self.id
+            };
+        });
 
-        let mut tiles = HashMap::new();
+        let entity = match entity {
+            None => return Err(LoadError::Other(anyhow!("No nested mapgen with the id {} was found", self.id))),
+            Some(e) => e
+        };
 
-        for (row, tile) in entity.object.rows.iter().enumerate() {
-            // to_string returns quotes, so we use as_str
-            for (column, char) in tile.as_str().chars().enumerate() {
-                tiles.insert(
-                    Coordinates::new(column as i32, row as i32),
-                    Tile::from(char),
-                );
-            }
-        }
+        let rows = &entity.object.rows;
+        let declared_size = entity.object.mapgensize
+            .unwrap_or(IVec2::new(rows.get(0).map(|r| r.chars().count()).unwrap_or(0) as i32, rows.len() as i32));
+        let (tiles, size) = self.load_tiles_of_rows(rows, declared_size, self.row_size_mismatch);
 
         let mut this = HashMap::new();
 
         let terrain = entity.object.terrain.clone().unwrap_or(HashMap::new());
         let furniture = entity.object.furniture.clone().unwrap_or(HashMap::new());
-        let parameters = entity.parameters.clone().unwrap_or(HashMap::new());
+        let items = entity.object.items.clone().unwrap_or(HashMap::new());
+        let parameters = entity.object.parameters.clone().or(entity.parameters.clone()).unwrap_or(HashMap::new());
 
         for (parameter_id, parameter) in parameters.iter() {
             this.insert(
@@ -244,25 +577,684 @@ impl Load<Nested> for MapEntityLoader<'_> {
 
         let computed_parameters = ComputedParameters {
             this: this.clone(),
-            palettes: compute_palettes(self.cdda_data, &this, &entity.object.palettes),
-        };
-
-        info!("Loaded Nested Om Mapgen Object {:?}", entity.om_terrain);
-
-        return Ok(
-            Nested {
-                row_size: entity.om_terrain.get(0).unwrap().len(),
-                om_terrain: entity.om_terrain.iter().flatten().map(|s| s.clone()).collect(),
-                tile_selection: TileSelection {
-                    fill_ter: entity.object.fill_ter.clone(),
-                    computed_parameters,
-                    parameters,
-                    palettes: entity.object.palettes.clone(),
-                    terrain,
-                    furniture,
-                },
-                tiles,
+            palettes: compute_palettes(self.cdda_data, &this, &entity.object.palettes, &HashMap::new()),
+        };
+
+        info!("Loaded Nested Mapgen Object {:?} {:?}", entity.om_terrain, entity.nested_mapgen_id);
+
+        let mut nested = Nested {
+            row_size: entity.om_terrain.get(0).map(|r| r.len()).unwrap_or(0),
+            om_terrain: entity.om_terrain.iter().flatten().map(|s| s.clone()).collect(),
+            nested_mapgen_id: entity.nested_mapgen_id.clone(),
+            size: Some(size),
+            tile_selection: TileSelection {
+                fill_ter: entity.object.fill_ter.clone(),
+                rolled_fill_ter: None,
+                computed_parameters,
+                parameters,
+                palettes: entity.object.palettes.clone(),
+                terrain,
+                furniture,
+                items,
+                place_loot: entity.object.place_loot.clone(),
+                switch_previews: HashMap::new(),
+            },
+            tiles,
+        };
+
+        nested.tile_selection.roll_fill_ter();
+
+        return Ok(nested);
+    }
+}
+
+/// Inserts the entries of the map sorted by their key so that the output does not depend on the order of the HashMap
+pub(crate) fn sorted_json_object<K: ToString + Ord, V: Serialize>(map: &HashMap<K, V>) -> Map<String, Value> {
+    let mut entries: Vec<(&K, &V)> = map.iter().collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut object = Map::new();
+
+    for (key, value) in entries {
+        object.insert(key.to_string(), serde_json::to_value(value).unwrap());
+    }
+
+    return object;
+}
+
+/// Reconstructs the rows of the tiles between the two corners, both corners are included
+fn get_rows(map_entity: &MapEntity, start: &Coordinates, end: &Coordinates) -> Vec<String> {
+    let mut rows = Vec::new();
+
+    for y in start.y..=end.y {
+        let row: String = (start.x..=end.x)
+            .map(|x| match map_entity.tiles().get(&Coordinates::new(x, y)) {
+                None => ' ',
+                Some(t) => t.character
+            })
+            .collect();
+
+        rows.push(row);
+    }
+
+    return rows;
+}
+
+/// Converts the Map Entity into a CDDA mapgen object.
+/// The keys are inserted in the order which is used in the CDDA repository to keep diffs against it minimal
+pub fn map_entity_to_cdda_json(map_entity: &MapEntity) -> Value {
+    let tile_selection = map_entity.object();
+
+    let (id_key, id) = match map_entity {
+        MapEntity::Single(s) => ("om_terrain", Value::from(s.om_terrain.clone())),
+        MapEntity::Multi(m) => ("om_terrain", match &m.om_terrain {
+            MultiOmTerrain::Grid(rows) => Value::from(rows.clone()),
+            MultiOmTerrain::Shared(ids) => Value::from(ids.clone())
+        }),
+        MapEntity::Nested(Nested { nested_mapgen_id: Some(id), .. }) => ("nested_mapgen_id", Value::from(id.clone())),
+        MapEntity::Nested(n) => ("om_terrain", Value::from(n.om_terrain.clone()))
+    };
+
+    let size = map_entity.size();
+    let rows = get_rows(map_entity, &Coordinates::new(0, 0), &Coordinates::new(size.x as i32 - 1, size.y as i32 - 1));
+
+    let mut object = Map::new();
+
+    if !tile_selection.parameters.is_empty() {
+        object.insert("parameters".into(), Value::Object(sorted_json_object(&tile_selection.parameters)));
+    }
+
+    if let Some(fill_ter) = &tile_selection.fill_ter {
+        object.insert("fill_ter".into(), serde_json::to_value(fill_ter).unwrap());
+    }
+
+    if let Some(rotation) = map_entity.rotation_range() {
+        object.insert("rotation".into(), serde_json::to_value(rotation).unwrap());
+    }
+
+    object.insert("rows".into(), Value::from(rows));
+
+    if !tile_selection.palettes.is_empty() {
+        object.insert("palettes".into(), serde_json::to_value(&tile_selection.palettes).unwrap());
+    }
+
+    if !tile_selection.terrain.is_empty() {
+        object.insert("terrain".into(), Value::Object(sorted_json_object(&tile_selection.terrain)));
+    }
+
+    if !tile_selection.furniture.is_empty() {
+        object.insert("furniture".into(), Value::Object(sorted_json_object(&tile_selection.furniture)));
+    }
+
+    if !tile_selection.items.is_empty() {
+        object.insert("items".into(), Value::Object(sorted_json_object(&tile_selection.items)));
+    }
+
+    if !tile_selection.place_loot.is_empty() {
+        object.insert("place_loot".into(), serde_json::to_value(&tile_selection.place_loot).unwrap());
+    }
+
+    let mut mapgen = Map::new();
+    mapgen.insert("type".into(), Value::from("mapgen"));
+
+    if let MapEntity::Single(Single { comment: Some(comment), .. }) = map_entity {
+        mapgen.insert("//".into(), Value::from(comment.clone()));
+    }
+
+    mapgen.insert("method".into(), Value::from("json"));
+    mapgen.insert(id_key.into(), id);
+
+    if let MapEntity::Single(Single { weight: Some(weight), .. }) = map_entity {
+        mapgen.insert("weight".into(), Value::from(*weight));
+    }
+    mapgen.insert("object".into(), Value::Object(object));
+
+    return Value::Object(mapgen);
+}
+
+/// Converts the tiles between the two corners into a nested mapgen object which can be placed with place_nested.
+/// Only the definitions of the characters inside of the selection are included
+pub fn selection_to_nested_cdda_json(map_entity: &MapEntity, start: &Coordinates, end: &Coordinates, nested_mapgen_id: &str) -> Value {
+    let tile_selection = map_entity.object();
+    let rows = get_rows(map_entity, start, end);
+    let characters: HashSet<char> = rows.iter().flat_map(|r| r.chars()).collect();
+
+    let terrain: HashMap<char, _> = tile_selection.terrain.iter()
+        .filter(|(c, _)| characters.contains(c))
+        .collect();
+
+    let furniture: HashMap<char, _> = tile_selection.furniture.iter()
+        .filter(|(c, _)| characters.contains(c))
+        .collect();
+
+    let mut object = Map::new();
+
+    // The definitions of the selection can use the parameters and spaces are filled with the fill terrain
+    if !tile_selection.parameters.is_empty() {
+        object.insert("parameters".into(), Value::Object(sorted_json_object(&tile_selection.parameters)));
+    }
+
+    if let Some(fill_ter) = &tile_selection.fill_ter {
+        object.insert("fill_ter".into(), serde_json::to_value(fill_ter).unwrap());
+    }
+
+    object.insert("mapgensize".into(), Value::from(vec![end.x - start.x + 1, end.y - start.y + 1]));
+    object.insert("rows".into(), Value::from(rows));
+
+    if !tile_selection.palettes.is_empty() {
+        object.insert("palettes".into(), serde_json::to_value(&tile_selection.palettes).unwrap());
+    }
+
+    if !terrain.is_empty() {
+        object.insert("terrain".into(), Value::Object(sorted_json_object(&terrain)));
+    }
+
+    if !furniture.is_empty() {
+        object.insert("furniture".into(), Value::Object(sorted_json_object(&furniture)));
+    }
+
+    let mut mapgen = Map::new();
+    mapgen.insert("type".into(), Value::from("mapgen"));
+    mapgen.insert("method".into(), Value::from("json"));
+    mapgen.insert("nested_mapgen_id".into(), Value::from(nested_mapgen_id));
+    mapgen.insert("object".into(), Value::Object(object));
+
+    return Value::Object(mapgen);
+}
+
+/// Returns the place_nested entry which places the nested mapgen at the coordinates
+pub fn get_place_nested_reference(nested_mapgen_id: &str, coordinates: &Coordinates) -> Value {
+    let mut reference = Map::new();
+    reference.insert("chunks".into(), Value::from(vec![nested_mapgen_id]));
+    reference.insert("x".into(), Value::from(coordinates.x));
+    reference.insert("y".into(), Value::from(coordinates.y));
+
+    return Value::Object(reference);
+}
+
+/// Returns the Map Entity as a pretty printed mapgen file with two spaces of indentation
+pub fn map_entity_to_cdda_json_string(map_entity: &MapEntity) -> String {
+    let file = Value::Array(vec![map_entity_to_cdda_json(map_entity)]);
+    return serde_json::to_string_pretty(&file).unwrap();
+}
+
+/// Returns a markdown table which lists the definitions of every character used in the Map Entity
+/// together with the palette they are defined in
+pub fn map_entity_to_legend_markdown(map_entity: &MapEntity, cdda_data: &CDDAData) -> String {
+    let mut characters: Vec<char> = map_entity.tiles().values()
+        .map(|t| t.character)
+        // The space character is filled with the fill terrain
+        .filter(|c| *c != ' ')
+        .collect::<HashSet<char>>()
+        .into_iter()
+        .collect();
+    characters.sort();
+
+    let mut legend = String::from("| Character | Terrain | Furniture | Items |\n|---|---|---|---|\n");
+
+    for character in characters {
+        let ids = map_entity.get_ids(cdda_data, &character);
+        let items: Vec<String> = ids.items.iter().map(|i| i.get_label()).collect();
+
+        let character = match character {
+            '|' => "`\\|`".to_string(),
+            '`' => "`` ` ``".to_string(),
+            c => format!("`{}`", c)
+        };
+
+        let items = match items.is_empty() {
+            true => "-".to_string(),
+            false => items.join(", ")
+        };
+
+        legend.push_str(format!(
+            "| {} | {} | {} | {} |\n",
+            character,
+            get_legend_definition(&ids.terrain, &ids.terrain_palette),
+            get_legend_definition(&ids.furniture, &ids.furniture_palette),
+            items
+        ).as_str());
+    }
+
+    return legend;
+}
+
+fn get_legend_definition(id: &Option<TileId>, palette: &Option<PaletteId>) -> String {
+    return match (id, palette) {
+        (None, _) => "-".to_string(),
+        (Some(id), None) => id.clone(),
+        (Some(id), Some(palette)) => format!("{} (palette {})", id, palette)
+    };
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use bevy::math::{IVec2, Vec2};
+
+    use std::path::PathBuf;
+
+    use crate::common::{Coordinates, MeabyWeighted};
+    use crate::common::io::Load;
+    use crate::map::data::{MapEntity, MeabyRange, Multi, MultiOmTerrain, Nested, Single, TileSelection};
+    use crate::map::io::{map_entity_to_cdda_json, map_entity_to_cdda_json_string, map_entity_to_legend_markdown, MapEntityLoader, selection_to_nested_cdda_json};
+    use crate::program::data::CDDAData;
+    use crate::palettes::data::{MapObjectId, MeabyParam, Palette, Parameter};
+    use crate::settings::data::RowSizeMismatch;
+    use crate::tiles::data::Tile;
+
+    #[test]
+    pub fn test_export_matches_golden_file() {
+        let rows = ["..#", "#.."];
+        let mut tiles = HashMap::new();
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, character) in row.chars().enumerate() {
+                tiles.insert(Coordinates::new(x as i32, y as i32), Tile::from(character));
             }
+        }
+
+        let mut terrain = HashMap::new();
+        terrain.insert('.', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_grass".into()))));
+        terrain.insert('#', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_wall".into()))));
+
+        let mut furniture = HashMap::new();
+        furniture.insert('#', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("f_null".into()))));
+
+        let map_entity = MapEntity::Single(Single {
+            om_terrain: "test_export".into(),
+            tile_selection: TileSelection {
+                fill_ter: Some(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_dirt".into())))),
+                palettes: vec![MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("test_palette".into())))],
+                terrain,
+                furniture,
+                ..Default::default()
+            },
+            tiles,
+            size: IVec2::new(3, 2),
+            comment: None,
+            weight: None,
+            variant: 0,
+            rotation: None,
+        });
+
+        let expected = include_str!("../../testing_data/exported_mapgen.json");
+        assert_eq!(map_entity_to_cdda_json_string(&map_entity), expected.trim_end());
+    }
+
+    #[test]
+    pub fn test_comment_is_kept_on_export() {
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let loader = MapEntityLoader {
+            path: PathBuf::from("./testing_data/commented_mapgen.json"),
+            id: "test_comment".into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::default(),
+            variant: None,
+            warnings: RefCell::default(),
+        };
+
+        let single: Single = loader.load().unwrap();
+        assert_eq!(single.comment, Some("A map with a comment".into()));
+
+        let exported = map_entity_to_cdda_json(&MapEntity::Single(single));
+        assert_eq!(exported.get("//").unwrap().as_str(), Some("A map with a comment"));
+    }
+
+    #[test]
+    pub fn test_export_selection_as_nested_mapgen() {
+        let rows = ["..#", "#.~"];
+        let mut tiles = HashMap::new();
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, character) in row.chars().enumerate() {
+                tiles.insert(Coordinates::new(x as i32, y as i32), Tile::from(character));
+            }
+        }
+
+        let mut terrain = HashMap::new();
+        terrain.insert('.', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_grass".into()))));
+        terrain.insert('#', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_wall".into()))));
+        terrain.insert('~', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_water_sh".into()))));
+
+        let parameter: Parameter = serde_json::from_value(serde_json::json!({ "type": "ter_str_id", "default": "t_grass" })).unwrap();
+        let mut parameters = HashMap::new();
+        parameters.insert("floor_type".to_string(), parameter);
+
+        let map_entity = MapEntity::Single(Single {
+            om_terrain: "test_selection".into(),
+            tile_selection: TileSelection {
+                fill_ter: Some(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_dirt".into())))),
+                parameters,
+                terrain,
+                ..Default::default()
+            },
+            tiles,
+            size: IVec2::new(3, 2),
+            comment: None,
+            weight: None,
+            variant: 0,
+            rotation: None,
+        });
+
+        let chunk = selection_to_nested_cdda_json(&map_entity, &Coordinates::new(0, 0), &Coordinates::new(1, 1), "test_chunk");
+        let object = chunk.get("object").unwrap();
+
+        assert_eq!(chunk.get("nested_mapgen_id").unwrap(), "test_chunk");
+        assert_eq!(object.get("mapgensize").unwrap(), &serde_json::json!([2, 2]));
+        assert_eq!(object.get("rows").unwrap(), &serde_json::json!(["..", "#."]));
+        assert_eq!(object.get("fill_ter").unwrap(), "t_dirt");
+        assert_eq!(
+            object.get("parameters").unwrap(),
+            &serde_json::json!({ "floor_type": { "type": "ter_str_id", "default": "t_grass" } })
         );
+
+        // The water is not inside of the selection
+        assert_eq!(object.get("terrain").unwrap().as_object().unwrap().len(), 2);
+    }
+
+    #[test]
+    pub fn test_load_nested_mapgen_without_om_terrain() {
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let loader = MapEntityLoader {
+            path: PathBuf::from("./testing_data/nested_chunk.json"),
+            id: "test_chunk".into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::default(),
+            variant: None,
+            warnings: RefCell::default(),
+        };
+
+        let single: Result<Single, _> = loader.load();
+        assert!(single.is_err());
+
+        let nested: Nested = loader.load().unwrap();
+        assert_eq!(nested.get_name(), "test_chunk");
+        assert_eq!(nested.tiles.len(), 6);
+
+        let map_entity = MapEntity::Nested(nested);
+        assert_eq!(map_entity.size(), Vec2::new(3., 2.));
+
+        let exported = map_entity_to_cdda_json(&map_entity);
+        assert_eq!(exported.get("nested_mapgen_id").unwrap(), "test_chunk");
+        assert!(exported.get("om_terrain").is_none());
+    }
+
+    #[test]
+    pub fn test_legend_lists_the_palette_of_definitions() {
+        let mut tiles = HashMap::new();
+        tiles.insert(Coordinates::new(0, 0), Tile::from('.'));
+        tiles.insert(Coordinates::new(1, 0), Tile::from('#'));
+
+        let mut terrain = HashMap::new();
+        terrain.insert('.', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_grass".into()))));
+
+        let mut palette = Palette { id: "test_palette".into(), ..Palette::default() };
+        palette.terrain.insert('#', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_wall".into()))));
+
+        let mut palettes = HashMap::new();
+        palettes.insert(palette.id.clone(), palette);
+        let cdda_data = CDDAData { palettes };
+
+        let map_entity = MapEntity::Single(Single {
+            om_terrain: "test_legend".into(),
+            tile_selection: TileSelection {
+                palettes: vec![MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("test_palette".into())))],
+                terrain,
+                ..Default::default()
+            },
+            tiles,
+            size: IVec2::new(2, 1),
+            comment: None,
+            weight: None,
+            variant: 0,
+            rotation: None,
+        });
+
+        let legend = map_entity_to_legend_markdown(&map_entity, &cdda_data);
+        let lines: Vec<&str> = legend.lines().collect();
+
+        assert_eq!(lines[2], "| `#` | t_wall (palette test_palette) | - | - |");
+        assert_eq!(lines[3], "| `.` | t_grass | - | - |");
+    }
+
+    #[test]
+    pub fn test_multi_mapgen_size_spans_all_om_terrains() {
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let loader = MapEntityLoader {
+            path: PathBuf::from("./testing_data/multi_mapgen.json"),
+            id: "test_multi_center".into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::default(),
+            variant: None,
+            warnings: RefCell::default(),
+        };
+
+        let multi: Multi = loader.load().unwrap();
+        assert_eq!(multi.om_terrain.get_positioned().len(), 3);
+
+        let map_entity = MapEntity::Multi(multi);
+        assert_eq!(map_entity.size(), Vec2::new(72., 24.));
+        assert_eq!(map_entity.tiles().get(&Coordinates::new(71, 23)).unwrap().character, '~');
+
+        // The flat om_terrain list of the second mapgen reuses one overmap terrain for every id
+        let loader = MapEntityLoader { id: "test_shared_south".into(), ..loader };
+        let multi: Multi = loader.load().unwrap();
+        assert_eq!(multi.om_terrain, MultiOmTerrain::Shared(vec!["test_shared_north".into(), "test_shared_south".into()]));
+
+        let map_entity = MapEntity::Multi(multi);
+        assert_eq!(map_entity.size(), Vec2::new(24., 24.));
+        assert_eq!(map_entity.tiles().get(&Coordinates::new(23, 23)).unwrap().character, '#');
+        assert!(loader.take_warnings().is_empty());
+    }
+
+    #[test]
+    pub fn test_grouped_palettes_choose_one_palette() {
+        let mut palettes = HashMap::new();
+
+        for (id, wall) in [("test_wall_palette", "t_wall"), ("test_fence_palette", "t_fence")] {
+            let mut palette = Palette { id: id.into(), ..Palette::default() };
+            palette.terrain.insert('#', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId(wall.into()))));
+            palettes.insert(palette.id.clone(), palette);
+        }
+
+        let cdda_data = CDDAData { palettes };
+        let loader = MapEntityLoader {
+            path: PathBuf::from("./testing_data/grouped_palettes_mapgen.json"),
+            id: "test_grouped_palettes".into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::default(),
+            variant: None,
+            warnings: RefCell::default(),
+        };
+
+        let mut single: Single = loader.load().unwrap();
+        assert!(matches!(single.tile_selection.palettes[0], MapObjectId::Distribution { .. }));
+        assert_eq!(single.tile_selection.computed_parameters.palettes.len(), 1);
+
+        // The distribution is written back in the form CDDA reads
+        let exported = map_entity_to_cdda_json(&MapEntity::Single(single.clone()));
+        assert!(exported["object"]["palettes"][0].get("distribution").is_some());
+
+        let chosen = single.tile_selection.get_palette_id(&single.tile_selection.palettes[0]).unwrap();
+
+        // The choice is kept when the parameters are computed again
+        single.tile_selection.recompute_palette_parameters(&cdda_data);
+        assert_eq!(single.tile_selection.get_palette_id(&single.tile_selection.palettes[0]), Some(chosen.clone()));
+
+        let map_entity = MapEntity::Single(single);
+        let ids = map_entity.get_ids(&cdda_data, &'#');
+
+        assert_eq!(ids.terrain_palette, Some(chosen));
+        assert_eq!(map_entity.get_ids(&cdda_data, &'#').terrain, ids.terrain);
+    }
+
+    #[test]
+    pub fn test_row_longer_than_mapgensize_is_expanded_or_cropped() {
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let mut loader = MapEntityLoader {
+            path: PathBuf::from("./testing_data/oversized_row_mapgen.json"),
+            id: "test_oversized_row".into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::Expand,
+            variant: None,
+            warnings: RefCell::default(),
+        };
+
+        let expanded: Single = loader.load().unwrap();
+        assert_eq!(expanded.size, IVec2::new(5, 2));
+        assert_eq!(expanded.tiles.get(&Coordinates::new(4, 1)).unwrap().character, '#');
+        // The shorter row is filled with spaces
+        assert_eq!(expanded.tiles.get(&Coordinates::new(4, 0)).unwrap().character, ' ');
+        assert!(MapEntity::Single(expanded).get_tile_integrity_issues().is_empty());
+        assert_eq!(loader.take_warnings().len(), 1);
+
+        loader.row_size_mismatch = RowSizeMismatch::Crop;
+
+        let cropped: Single = loader.load().unwrap();
+        assert_eq!(cropped.size, IVec2::new(3, 2));
+        assert!(cropped.tiles.get(&Coordinates::new(3, 1)).is_none());
+        assert!(MapEntity::Single(cropped).get_tile_integrity_issues().is_empty());
+    }
+
+    #[test]
+    pub fn test_weighted_variants_of_one_om_terrain() {
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let mut loader = MapEntityLoader {
+            path: PathBuf::from("./testing_data/weighted_mapgen.json"),
+            id: "test_weighted".into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::default(),
+            variant: None,
+            warnings: RefCell::default(),
+        };
+
+        // A mapgen with a weight of 0 is never chosen
+        let chosen: Single = loader.load().unwrap();
+        assert_eq!(chosen.variant, 1);
+        assert_eq!(chosen.weight, Some(100));
+        assert_eq!(chosen.tiles.get(&Coordinates::new(0, 0)).unwrap().character, '#');
+
+        loader.variant = Some(0);
+
+        let browsed: Single = loader.load().unwrap();
+        assert_eq!(browsed.weight, Some(0));
+        assert_eq!(browsed.tiles.get(&Coordinates::new(0, 0)).unwrap().character, '.');
+
+        let exported = map_entity_to_cdda_json(&MapEntity::Single(chosen));
+        assert_eq!(exported.get("weight").unwrap(), 100);
+
+        loader.variant = Some(2);
+        assert!(Load::<Single>::load(&loader).is_err());
+    }
+
+    #[test]
+    pub fn test_rotation_of_the_object_turns_the_rendered_tiles() {
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let loader = MapEntityLoader {
+            path: PathBuf::from("./testing_data/rotated_mapgen.json"),
+            id: "test_rotated".into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::default(),
+            variant: None,
+            warnings: RefCell::default(),
+        };
+
+        let single: Single = loader.load().unwrap();
+        assert_eq!(single.rotation, Some(MeabyRange::Single(1)));
+
+        let map_entity = MapEntity::Single(single);
+
+        // A quarter turn moves the top left corner to the top right and swaps the width and height
+        assert_eq!(map_entity.get_rotated_size(), Vec2::new(2., 3.));
+        assert_eq!(map_entity.get_rotated_coordinates(&Coordinates::new(0, 0)), Coordinates::new(1, 0));
+        assert_eq!(map_entity.get_rotated_coordinates(&Coordinates::new(2, 1)), Coordinates::new(0, 2));
+
+        let exported = map_entity_to_cdda_json(&map_entity);
+        assert_eq!(exported.get("object").unwrap().get("rotation").unwrap(), 1);
+    }
+
+    #[test]
+    pub fn test_rotation_range_and_zero_are_exported() {
+        let mut map_entity = MapEntity::Single(Single { size: IVec2::new(1, 1), ..Default::default() });
+        map_entity.tiles_mut().insert(Coordinates::new(0, 0), Tile::from('.'));
+
+        let exported_rotation = |map_entity: &MapEntity| map_entity_to_cdda_json(map_entity)["object"].get("rotation").cloned();
+
+        if let MapEntity::Single(s) = &mut map_entity { s.rotation = Some(MeabyRange::Range([1, 3])); }
+        // Only the start of the range is shown
+        assert_eq!(map_entity.rotation(), 1);
+        assert_eq!(exported_rotation(&map_entity), Some(serde_json::json!([1, 3])));
+
+        if let MapEntity::Single(s) = &mut map_entity { s.rotation = Some(MeabyRange::Single(0)); }
+        assert_eq!(exported_rotation(&map_entity), Some(serde_json::json!(0)));
+
+        if let MapEntity::Single(s) = &mut map_entity { s.rotation = None; }
+        assert_eq!(exported_rotation(&map_entity), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_place_loot_is_loaded_and_exported() {
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let loader = MapEntityLoader {
+            path: PathBuf::from("./testing_data/place_loot_mapgen.json"),
+            id: "test_place_loot".into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::default(),
+            variant: None,
+            warnings: RefCell::default(),
+        };
+
+        let single: Single = loader.load().unwrap();
+        let place_loot = &single.tile_selection.place_loot;
+
+        assert_eq!(place_loot.len(), 2);
+        assert_eq!(place_loot[0].get_coordinates(), vec![Coordinates::new(0, 0)]);
+        assert_eq!(place_loot[1].get_coordinates(), vec![Coordinates::new(1, 2), Coordinates::new(2, 2)]);
+
+        let exported = map_entity_to_cdda_json(&MapEntity::Single(single));
+        let exported_place_loot = exported.get("object").unwrap().get("place_loot").unwrap();
+
+        assert_eq!(exported_place_loot, &serde_json::json!([
+            { "group": "test_loot", "x": 0, "y": 0, "chance": 50 },
+            { "item": "test_item", "x": [1, 2], "y": 2 }
+        ]));
+    }
+
+    #[test]
+    pub fn test_exported_definitions_are_sorted_by_character() {
+        let characters = ['z', '#', 'a', '.', 'M', '1', '~'];
+
+        let mut terrain = HashMap::new();
+        let mut furniture = HashMap::new();
+
+        for character in characters {
+            terrain.insert(character, MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_grass".into()))));
+            furniture.insert(character, MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("f_null".into()))));
+        }
+
+        let map_entity = MapEntity::Single(Single {
+            om_terrain: "test_sorted".into(),
+            tile_selection: TileSelection { terrain, furniture, ..Default::default() },
+            tiles: HashMap::new(),
+            size: IVec2::new(1, 1),
+            comment: None,
+            weight: None,
+            variant: 0,
+            rotation: None,
+        });
+
+        let exported = map_entity_to_cdda_json(&map_entity);
+        let object = exported.get("object").unwrap();
+
+        let mut expected: Vec<String> = characters.iter().map(|c| c.to_string()).collect();
+        expected.sort();
+
+        for key in ["terrain", "furniture"] {
+            let keys: Vec<String> = object.get(key).unwrap().as_object().unwrap().keys().cloned().collect();
+            assert_eq!(keys, expected);
+        }
+    }
+}
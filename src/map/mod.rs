@@ -1,5 +1,5 @@
-pub(crate) mod systems;
-pub(crate) mod io;
-pub(crate) mod plugin;
-pub(crate) mod data;
+pub mod systems;
+pub mod io;
+pub mod plugin;
+pub mod data;
 
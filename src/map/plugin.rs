@@ -1,7 +1,7 @@
 use bevy::app::{App, Plugin, Update};
 use bevy::prelude::{in_state, IntoSystemConfigs};
 
-use crate::map::data::{ClearTiles, SpawnMapEntity, TileDeleteEvent, TilePlaceEvent, UpdateSpriteEvent};
+use crate::map::data::{BulkTilePlaceEvent, ClearTiles, SpawnMapEntity, TileDeleteEvent, TilePlaceEvent, UpdateSpriteEvent};
 use crate::map::systems::{clear_tiles_reader, spawn_map_entity_reader, SpawnSprite, update_animated_sprites};
 use crate::program::data::ProgramState;
 
@@ -17,6 +17,7 @@ impl Plugin for MapPlugin {
         );
 
         app.add_event::<TilePlaceEvent>();
+        app.add_event::<BulkTilePlaceEvent>();
         app.add_event::<SpawnSprite>();
         app.add_event::<TileDeleteEvent>();
         app.add_event::<UpdateSpriteEvent>();
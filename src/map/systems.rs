@@ -3,17 +3,20 @@ use std::ops::Index;
 use std::sync::Arc;
 
 use bevy::asset::{Assets, Handle};
+use bevy::ecs::system::EntityCommands;
 use bevy::math::{Vec2, Vec3};
-use bevy::prelude::{Commands, Component, Cuboid, default, Entity, Event, EventReader, EventWriter, Image, Mesh, Meshable, Query, Res, ResMut, SpriteBundle, State, Transform, With};
+use bevy::prelude::{BuildChildren, Commands, Component, Cuboid, default, DespawnRecursiveExt, Entity, Event, EventReader, EventWriter, Image, Mesh, Meshable, Query, Res, ResMut, SpriteBundle, State, Transform, With};
 use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
 use bevy::window::{PrimaryWindow, Window};
-use log::warn;
+use log::{debug, warn};
 
 use crate::common::Coordinates;
-use crate::graphics::{GetTexture, GraphicsResource, Sprite, SpriteState, TileSprite};
+use crate::graphics::{get_fill_color, GetTexture, GraphicsResource, Sprite, SpriteState, TileSprite};
 use crate::graphics::tileset::{GetBackground, GetForeground};
-use crate::map::data::{ClearTiles, SpawnMapEntity, TileDeleteEvent, TilePlaceEvent, UpdateSpriteEvent};
-use crate::program::data::{OpenedProject, Program, ProgramState};
+use crate::map::data::{BulkTilePlaceEvent, ClearTiles, SpawnMapEntity, TileDeleteEvent, TilePlaceEvent, UpdateSpriteEvent};
+use crate::program::data::{CDDAData, OpenedProject, Program, ProgramState};
+use crate::project::data::Project;
+use crate::settings::data::Settings;
 use crate::tiles::data::{Offset, Tile};
 use crate::ui::grid::GridMaterial;
 use crate::ui::grid::resources::Grid;
@@ -37,8 +40,31 @@ pub enum SpriteKind {
 
 #[derive(Component, Debug)]
 pub struct Animated {
-    cooldown: u16,
-    last_update: u64,
+    /// Milliseconds until the next frame is shown
+    pub cooldown: u64,
+    pub last_update: u64,
+}
+
+impl Animated {
+    pub fn new(cooldown: u64) -> Self {
+        return Self {
+            cooldown,
+            last_update: chrono::prelude::Utc::now().timestamp_millis() as u64,
+        };
+    }
+
+    /// Returns the animation of the sprite, if the tileset marks it as animated
+    pub fn from_sprite(sprite: &Sprite) -> Option<Self> {
+        if !sprite.is_animated {
+            return None;
+        }
+
+        return Some(Animated::new(sprite.animation_interval));
+    }
+
+    pub fn is_due(&self) -> bool {
+        return (chrono::prelude::Utc::now().timestamp_millis() as u64).saturating_sub(self.last_update) >= self.cooldown;
+    }
 }
 
 impl SpriteKind {
@@ -52,6 +78,16 @@ impl SpriteKind {
         };
     }
 
+    pub fn get_fg_layers(&self) -> &Vec<Arc<dyn GetForeground>> {
+        return match self {
+            SpriteKind::Item(i) => &i.fg_layers,
+            SpriteKind::Terrain(t) => &t.fg_layers,
+            SpriteKind::Furniture(f) => &f.fg_layers,
+            SpriteKind::Toilet(t) => &t.fg_layers,
+            SpriteKind::Fallback(f) => &f.fg_layers
+        };
+    }
+
     pub fn get_bg(&self) -> &Option<Arc<dyn GetBackground>> {
         return match self {
             SpriteKind::Item(i) => &i.bg,
@@ -66,11 +102,21 @@ impl SpriteKind {
 #[derive(Component, Debug)]
 pub struct Layer(f32);
 
+/// Despawns the entity together with its children. Does nothing if the entity does not exist anymore,
+/// which can happen when the same entity is referenced twice or it was already despawned by a reload
+pub fn despawn_if_exists(commands: &mut Commands, entity: Entity) {
+    match commands.get_entity(entity) {
+        None => warn!("Tried to despawn entity {:?} which does not exist", entity),
+        Some(entity_commands) => entity_commands.despawn_recursive()
+    }
+}
+
 pub fn spawn_sprite(
     mut commands: Commands,
     r_grid: Res<Grid>,
     mut e_spawn_sprite: EventReader<SpawnSprite>,
     mut r_editor_data: ResMut<Program>,
+    r_settings: Res<Settings>,
     q_opened_project: Query<(Entity, &OpenedProject)>,
 ) {
     let index = match q_opened_project.iter().next() {
@@ -78,6 +124,8 @@ pub fn spawn_sprite(
         Some(o) => o.1.index
     };
 
+    let cdda_data = r_editor_data.config.cdda_data.clone();
+
     let project = match r_editor_data.projects.get_mut(index) {
         None => return,
         Some(p) => p
@@ -101,10 +149,12 @@ pub fn spawn_sprite(
                             z: layer,
                         },
                         scale: Vec3 {
-                            x: r_grid.tile_size / r_grid.default_tile_size,
-                            y: r_grid.tile_size / r_grid.default_tile_size,
-                            z: 0.,
+                            x: r_grid.get_sprite_scale(),
+                            y: r_grid.get_sprite_scale(),
+                            // Needs to be 1 so the z of the foreground layers is not collapsed
+                            z: 1.,
                         },
+                        rotation: e.tile.get_fg_rotation(),
                         ..default()
                     },
                     ..default()
@@ -114,12 +164,14 @@ pub fn spawn_sprite(
                 Offset::from(e.offset.clone())
             ));
 
+            spawn_fg_layers(&mut fg_entity_commands, e.sprite_kind.get_fg_layers());
+
             let tile = project.map_entity.tiles_mut().get_mut(&e.coordinates).unwrap();
             match &e.sprite_kind {
                 SpriteKind::Item(_) => { panic!("Not Implemented") }
                 SpriteKind::Terrain(sprite) => {
-                    if sprite.is_animated {
-                        fg_entity_commands.insert(Animated { cooldown: 1, last_update: (chrono::prelude::Utc::now().timestamp_millis() / 1000) as u64 });
+                    if let Some(animated) = Animated::from_sprite(sprite) {
+                        fg_entity_commands.insert(animated);
                     }
                     tile.terrain.fg_entity = Some(fg_entity_commands.id());
                 }
@@ -146,8 +198,8 @@ pub fn spawn_sprite(
                             z: e.z as f32,
                         },
                         scale: Vec3 {
-                            x: r_grid.tile_size / r_grid.default_tile_size,
-                            y: r_grid.tile_size / r_grid.default_tile_size,
+                            x: r_grid.get_sprite_scale(),
+                            y: r_grid.get_sprite_scale(),
                             z: 0.,
                         },
                         ..default()
@@ -172,6 +224,48 @@ pub fn spawn_sprite(
                 SpriteKind::Fallback(_) => { panic!("Not Implemented") }
             };
         }
+
+        // Fill terrain without a background with a solid color, the fill entity takes the place of the bg entity
+        if let (SpriteKind::Terrain(_), None, true) = (&e.sprite_kind, bg, r_settings.fill_missing_backgrounds) {
+            let terrain_id = match &cdda_data {
+                None => None,
+                Some(cdda_data) => project.map_entity.get_ids(cdda_data, &e.tile.character).terrain
+            };
+
+            if let Some(terrain_id) = terrain_id {
+                let fill_entity_commands = commands.spawn((
+                    e.tile.clone(),
+                    SpriteBundle {
+                        sprite: bevy::sprite::Sprite {
+                            color: get_fill_color(&terrain_id),
+                            custom_size: Some(Vec2::splat(r_grid.default_tile_size)),
+                            ..default()
+                        },
+                        transform: Transform {
+                            translation: Vec3 {
+                                // Spawn off screen
+                                x: -1000.0,
+                                y: -1000.0,
+                                z: e.z as f32,
+                            },
+                            scale: Vec3 {
+                                x: r_grid.get_sprite_scale(),
+                                y: r_grid.get_sprite_scale(),
+                                z: 0.,
+                            },
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    e.coordinates.clone(),
+                    Layer(e.z as f32),
+                    Offset::from(e.offset.clone())
+                ));
+
+                let tile = project.map_entity.tiles_mut().get_mut(&e.coordinates).unwrap();
+                tile.terrain.bg_entity = Some(fill_entity_commands.id());
+            }
+        }
     }
 }
 
@@ -215,13 +309,17 @@ pub fn update_animated_sprites(
         };
         match textures.get_terrain(current_project, &cdda_data, &tile.character, cords) {
             SpriteState::Defined(terrain) => {
-                if (chrono::prelude::Utc::now().timestamp_millis() / 1000) as u64 - animated.last_update < animated.cooldown as u64 {
-                    return;
+                // Every sprite has its own interval, so only skip this one
+                if !animated.is_due() {
+                    continue;
                 }
 
                 let fg = terrain.fg.as_ref().unwrap().get_randomized_sprite();
 
-                let mut entity_commands = commands.get_entity(entity).unwrap();
+                let mut entity_commands = match commands.get_entity(entity) {
+                    None => continue,
+                    Some(c) => c
+                };
                 let fg_entity_commands = entity_commands
                     .insert(
                         SpriteBundle {
@@ -235,8 +333,8 @@ pub fn update_animated_sprites(
                                     z: 5. as f32 + 1. + cords.y as f32 * 10.,
                                 },
                                 scale: Vec3 {
-                                    x: r_grid.tile_size / r_grid.default_tile_size,
-                                    y: r_grid.tile_size / r_grid.default_tile_size,
+                                    x: r_grid.get_sprite_scale(),
+                                    y: r_grid.get_sprite_scale(),
                                     z: layer.0,
                                 },
                                 ..default()
@@ -245,10 +343,7 @@ pub fn update_animated_sprites(
                         },
                     )
                     .remove::<Animated>()
-                    .insert(Animated {
-                        cooldown: animated.cooldown,
-                        last_update: (chrono::prelude::Utc::now().timestamp_millis() / 1000) as u64,
-                    });
+                    .insert(Animated::new(animated.cooldown));
 
                 fg_entities_to_set.insert(cords, fg_entity_commands.id());
             }
@@ -283,6 +378,7 @@ pub fn set_tile_reader(
     };
 
     for e in e_set_tile.read() {
+        project.is_dirty = true;
         project.map_entity.tiles_mut().insert(
             e.coordinates.clone(),
             e.tile,
@@ -306,10 +402,33 @@ pub fn tile_remove_reader(
     };
 
     for e in e_delete_tile.read() {
+        project.is_dirty = true;
         project.map_entity.tiles_mut().remove(&e.coordinates);
     }
 }
 
+/// Replaces the foreground layers of a sprite. The layers are children of the foreground so they move,
+/// scale and despawn together with it
+fn spawn_fg_layers(entity_commands: &mut EntityCommands, fg_layers: &Vec<Arc<dyn GetForeground>>) {
+    entity_commands
+        .despawn_descendants()
+        .with_children(|parent| {
+            for (i, fg_layer) in fg_layers.iter().enumerate() {
+                parent.spawn(SpriteBundle {
+                    texture: fg_layer.get_randomized_sprite().clone(),
+                    transform: Transform::from_xyz(0., 0., 0.01 * (i + 1) as f32),
+                    ..default()
+                });
+            }
+        });
+}
+
+/// Returns the entity if its sprite still exists. The entities of a tile are stale after the sprites were
+/// despawned by a ClearTiles event while the tiles of the Project still reference them
+fn get_existing_sprite(entity: Option<Entity>, q_sprite: &Query<&mut Handle<Image>, With<Tile>>) -> Option<Entity> {
+    return entity.filter(|e| q_sprite.contains(*e));
+}
+
 pub fn update_sprite_reader(
     mut commands: Commands,
     mut e_update_sprite: EventReader<UpdateSpriteEvent>,
@@ -317,6 +436,7 @@ pub fn update_sprite_reader(
     mut r_program: ResMut<Program>,
     r_textures: Res<GraphicsResource>,
     r_grid: Res<Grid>,
+    r_settings: Res<Settings>,
     q_opened_project: Query<(Entity, &OpenedProject)>,
 ) {
     let index = match q_opened_project.iter().next() {
@@ -339,16 +459,18 @@ pub fn update_sprite_reader(
         Some(t) => t
     };
 
+    let floor_under_furniture = r_settings.get_floor_under_furniture(project);
+
     for e in e_update_sprite.read() {
-        let tile_sprite = textures.get_textures(&project, &cdda_data, &e.tile.character, &e.coordinates);
+        let tile_sprite = textures.get_textures(&project, &cdda_data, &e.tile.character, &e.coordinates, floor_under_furniture.as_ref(), r_settings.show_missing_textures);
 
         macro_rules! spawn_sprite {
             ($sprite: expr, $tile_path: expr, $sprite_type: ident) => {
                 if let Some(fg) = &$sprite.fg {
-                    match $tile_path.fg_entity {
+                    match get_existing_sprite($tile_path.fg_entity, &q_sprite) {
                         None => {
                             // Spawn the Sprite
-                            let fg_entity_commands = commands.spawn((
+                            let mut fg_entity_commands = commands.spawn((
                                 e.tile,
                                 SpriteBundle {
                                     texture: fg.get_randomized_sprite().clone(),
@@ -360,38 +482,39 @@ pub fn update_sprite_reader(
                                                z: 2.0,
                                          },
                                          scale: Vec3 {
-                                                 x: r_grid.tile_size / r_grid.default_tile_size,
-                                                 y: r_grid.tile_size / r_grid.default_tile_size,
+                                                 x: r_grid.get_sprite_scale(),
+                                                 y: r_grid.get_sprite_scale(),
                                                  z: 0.,
                                                  },
+                                         rotation: e.tile.get_fg_rotation(),
                                          ..default()
                                      },
                                     ..default()
                                 },
-                                Animated {
-                                    cooldown: 1,
-                                    last_update: (chrono::prelude::Utc::now().timestamp_millis() / 1000) as u64 
-                                },
+                                Animated::new($sprite.animation_interval),
                                 e.coordinates.clone(),
                                 Offset {x: $sprite.offset_x, y: $sprite.offset_y }
                             ));
 
-                            let tile = project.map_entity.tiles_mut().get_mut(&e.coordinates).unwrap();
-                            tile.$sprite_type.fg_entity = Some(fg_entity_commands.id());
+                            spawn_fg_layers(&mut fg_entity_commands, &$sprite.fg_layers);
+
+                            // The tile can be removed by an event which is read in the same frame
+                            if let Some(tile) = project.map_entity.tiles_mut().get_mut(&e.coordinates) {
+                                tile.$sprite_type.fg_entity = Some(fg_entity_commands.id());
+                            }
                         }
                         Some(i) => {
-                            match q_sprite.get_mut(i) {
-                               Ok(mut i) => {
-                                   *i = fg.get_randomized_sprite().clone()
-                               }
-                                   Err(_) => {}
-                               }
+                            if let Ok(mut handle) = q_sprite.get_mut(i) {
+                                *handle = fg.get_randomized_sprite().clone();
+                            }
+
+                            spawn_fg_layers(&mut commands.entity(i), &$sprite.fg_layers);
                         }
                     }
                 }
 
                 if let Some(bg) = &$sprite.bg {
-                    match $tile_path.bg_entity {
+                    match get_existing_sprite($tile_path.bg_entity, &q_sprite) {
                         None => {
                             let bg_entity_commands = commands.spawn((
                                 e.tile,
@@ -405,8 +528,8 @@ pub fn update_sprite_reader(
                                               z: 1.0,
                                           },
                                           scale: Vec3 {
-                                              x: r_grid.tile_size / r_grid.default_tile_size,
-                                              y: r_grid.tile_size / r_grid.default_tile_size,
+                                              x: r_grid.get_sprite_scale(),
+                                              y: r_grid.get_sprite_scale(),
                                               z: 0.,
                                           },
                                           ..default()
@@ -417,21 +540,14 @@ pub fn update_sprite_reader(
                                 Offset {x: $sprite.offset_x, y: $sprite.offset_y }
                             ));
 
-                            let tile = project.map_entity.tiles_mut().get_mut(&e.coordinates).unwrap();
-                            tile.$sprite_type.bg_entity = Some(bg_entity_commands.id());
+                            if let Some(tile) = project.map_entity.tiles_mut().get_mut(&e.coordinates) {
+                                tile.$sprite_type.bg_entity = Some(bg_entity_commands.id());
+                            }
                         }
-                        Some(i) => match q_sprite.get_mut(i) {
-                            Ok(mut i) => {
-                                match $sprite.bg.as_ref() {
-                                    None => {
-                                      // Sprite was deleted
-                                    }
-                                    Some(s) => {
-                                      *i = s.get_randomized_sprite().clone();
-                                    }
-                                }
+                        Some(i) => {
+                            if let Ok(mut handle) = q_sprite.get_mut(i) {
+                                *handle = bg.get_randomized_sprite().clone();
                             }
-                            Err(_) => {}
                         }
                     }
                 }
@@ -456,12 +572,60 @@ pub fn update_sprite_reader(
     }
 }
 
+fn send_spawn_sprites(
+    e_spawn_sprite: &mut EventWriter<SpawnSprite>,
+    sprites: TileSprite,
+    tile: &Tile,
+    coordinates: &Coordinates,
+) {
+    match sprites {
+        TileSprite::Exists { terrain, furniture, .. } => {
+            if let Some(terrain) = terrain {
+                e_spawn_sprite.send(
+                    SpawnSprite {
+                        coordinates: coordinates.clone(),
+                        sprite_kind: SpriteKind::Terrain(terrain.clone()),
+                        tile: tile.clone(),
+                        z: 1,
+                        offset: Offset { x: terrain.offset_x, y: terrain.offset_y },
+                    }
+                );
+            }
+
+            if let Some(furniture) = furniture {
+                e_spawn_sprite.send(
+                    SpawnSprite {
+                        coordinates: coordinates.clone(),
+                        sprite_kind: SpriteKind::Furniture(furniture.clone()),
+                        tile: tile.clone(),
+                        z: 3,
+                        offset: Offset { x: furniture.offset_x, y: furniture.offset_y },
+                    }
+                );
+            }
+        }
+        TileSprite::Fallback(default) => {
+            e_spawn_sprite.send(
+                SpawnSprite {
+                    coordinates: coordinates.clone(),
+                    sprite_kind: SpriteKind::Fallback(default.clone()),
+                    tile: tile.clone(),
+                    z: 1,
+                    offset: Offset::default(),
+                }
+            );
+        }
+        TileSprite::Empty => {}
+    }
+}
+
 pub fn tile_spawn_reader(
     mut e_tile_place: EventReader<TilePlaceEvent>,
     mut e_spawn_sprite: EventWriter<SpawnSprite>,
     mut e_update_sprite: EventWriter<UpdateSpriteEvent>,
     r_textures: Res<GraphicsResource>,
     mut r_program: ResMut<Program>,
+    r_settings: Res<Settings>,
     q_opened_project: Query<(Entity, &OpenedProject)>,
 ) {
     let index = match q_opened_project.iter().next() {
@@ -484,67 +648,90 @@ pub fn tile_spawn_reader(
         Some(t) => t
     };
 
-    for e in e_tile_place.read() {
-        let sprites = textures.get_textures(project, &cdda_data, &e.tile.character, &e.coordinates);
+    let floor_under_furniture = r_settings.get_floor_under_furniture(project);
 
-        match sprites {
-            TileSprite::Exists { terrain, furniture, .. } => {
-                if let Some(terrain) = terrain {
-                    e_spawn_sprite.send(
-                        SpawnSprite {
-                            coordinates: e.coordinates.clone(),
-                            sprite_kind: SpriteKind::Terrain(terrain.clone()),
-                            tile: e.tile.clone(),
-                            z: 1,
-                            offset: Offset { x: terrain.offset_x, y: terrain.offset_y },
-                        }
-                    );
-                }
+    let mut changed = vec![];
 
-                if let Some(furniture) = furniture {
-                    e_spawn_sprite.send(
-                        SpawnSprite {
-                            coordinates: e.coordinates.clone(),
-                            sprite_kind: SpriteKind::Furniture(furniture.clone()),
-                            tile: e.tile.clone(),
-                            z: 3,
-                            offset: Offset { x: furniture.offset_x, y: furniture.offset_y },
-                        }
-                    );
-                }
-            }
-            TileSprite::Fallback(default) => {
-                e_spawn_sprite.send(
-                    SpawnSprite {
-                        coordinates: e.coordinates.clone(),
-                        sprite_kind: SpriteKind::Fallback(default.clone()),
-                        tile: e.tile.clone(),
-                        z: 1,
-                        offset: Offset::default(),
-                    }
-                );
-            }
-            TileSprite::Empty => {}
-        }
+    for e in e_tile_place.read() {
+        let sprites = textures.get_textures(project, &cdda_data, &e.tile.character, &e.coordinates, floor_under_furniture.as_ref(), r_settings.show_missing_textures);
+        send_spawn_sprites(&mut e_spawn_sprite, sprites, &e.tile, &e.coordinates);
 
         // Check here because i couldn't figure out why the sprites were not correct when spawning a saved map
         if e.should_update_sprites {
-            let tiles_around = project.map_entity.get_tiles_around(&e.coordinates);
+            changed.push(e.coordinates.clone());
+        }
+    }
 
-            for (tile, coordinates) in tiles_around {
-                match tile {
-                    None => {}
-                    Some(t) => {
-                        e_update_sprite.send(
-                            UpdateSpriteEvent {
-                                tile: *t,
-                                coordinates,
-                            }
-                        );
-                    }
-                }
-            }
+    if changed.is_empty() { return; }
+
+    send_neighbor_updates(&mut e_update_sprite, textures.as_ref(), project, &cdda_data, &changed);
+}
+
+/// Updates the neighbors of the changed tiles, but only those whose sprites depend on their neighbors
+fn send_neighbor_updates(
+    e_update_sprite: &mut EventWriter<UpdateSpriteEvent>,
+    textures: &dyn GetTexture,
+    project: &Project,
+    cdda_data: &CDDAData,
+    changed: &Vec<Coordinates>,
+) {
+    let neighbors = project.map_entity.get_neighbors_to_update(
+        changed,
+        |tile| textures.is_autotiled(project, cdda_data, &tile.character),
+    );
+
+    debug!("Updating the sprites of {} neighbors of {} changed tiles", neighbors.len(), changed.len());
+
+    for (tile, coordinates) in neighbors {
+        e_update_sprite.send(UpdateSpriteEvent { tile, coordinates });
+    }
+}
+
+pub fn bulk_tile_place_reader(
+    mut e_bulk_tile_place: EventReader<BulkTilePlaceEvent>,
+    mut e_spawn_sprite: EventWriter<SpawnSprite>,
+    r_textures: Res<GraphicsResource>,
+    mut r_program: ResMut<Program>,
+    r_settings: Res<Settings>,
+    q_opened_project: Query<(Entity, &OpenedProject)>,
+) {
+    let index = match q_opened_project.iter().next() {
+        None => return,
+        Some(o) => o.1.index
+    };
+
+    let cdda_data = match r_program.config.cdda_data.clone() {
+        None => return,
+        Some(d) => d
+    };
+
+    let project = match r_program.projects.get_mut(index) {
+        None => { return; }
+        Some(p) => { p }
+    };
+
+    let textures = match &r_textures.textures {
+        None => return,
+        Some(t) => t
+    };
+
+    let floor_under_furniture = r_settings.get_floor_under_furniture(project);
+
+    for e in e_bulk_tile_place.read() {
+        // Insert every tile first so that the connections to the neighbors are already correct
+        // when resolving the sprites and no tile has to be updated afterwards
+        // The sprites of the passed tiles might already be despawned, so only keep the character and rotation
+        for (coordinates, tile) in e.tiles.iter() {
+            project.map_entity.tiles_mut().insert(coordinates.clone(), Tile::with_rotation(tile.character, tile.rotation));
+        }
+
+        for (coordinates, tile) in e.tiles.iter() {
+            let tile = Tile::with_rotation(tile.character, tile.rotation);
+            let sprites = textures.get_textures(project, &cdda_data, &tile.character, coordinates, floor_under_furniture.as_ref(), r_settings.show_missing_textures);
+            send_spawn_sprites(&mut e_spawn_sprite, sprites, &tile, coordinates);
         }
+
+        debug!("Placed {} tiles", e.tiles.len());
     }
 }
 
@@ -553,6 +740,7 @@ pub fn tile_despawn_reader(
     mut e_tile_delete: EventReader<TileDeleteEvent>,
     mut e_update_sprite: EventWriter<UpdateSpriteEvent>,
     r_program: Res<Program>,
+    r_textures: Res<GraphicsResource>,
     q_opened_project: Query<(Entity, &OpenedProject)>,
 ) {
     let index = match q_opened_project.iter().next() {
@@ -565,20 +753,23 @@ pub fn tile_despawn_reader(
         Some(p) => { p }
     };
 
+    let mut changed = vec![];
+
     for e in e_tile_delete.read() {
         macro_rules! despawn {
             ($path: expr) => {
                 match $path.fg_entity {
                     None => {}
                     Some(entity) => {
-                        commands.get_entity(entity).unwrap().despawn()
+                        // Recursive to also despawn the foreground layers
+                        despawn_if_exists(&mut commands, entity)
                     }
                 }
 
                 match $path.bg_entity {
                     None => {}
                     Some(entity) => {
-                        commands.get_entity(entity).unwrap().despawn()
+                        despawn_if_exists(&mut commands, entity)
                     }
                 }
             }
@@ -590,27 +781,22 @@ pub fn tile_despawn_reader(
         despawn!(e.tile.items);
         despawn!(e.tile.fallback);
 
-        let tiles_around = project.map_entity.get_tiles_around(&e.coordinates);
-
-        for (tile, coordinates) in tiles_around {
-            match tile {
-                None => {}
-                Some(t) => {
-                    e_update_sprite.send(
-                        UpdateSpriteEvent {
-                            tile: *t,
-                            coordinates,
-                        }
-                    );
-                }
-            }
-        }
+        changed.push(e.coordinates.clone());
     }
+
+    let (cdda_data, textures) = match (&r_program.config.cdda_data, &r_textures.textures) {
+        (Some(d), Some(t)) => (d, t),
+        _ => return
+    };
+
+    if changed.is_empty() { return; }
+
+    send_neighbor_updates(&mut e_update_sprite, textures.as_ref(), project, cdda_data, &changed);
 }
 
 pub fn spawn_map_entity_reader(
     mut e_spawn_map_entity: EventReader<SpawnMapEntity>,
-    mut e_tile_place: EventWriter<TilePlaceEvent>,
+    mut e_bulk_tile_place: EventWriter<BulkTilePlaceEvent>,
     mut commands: Commands,
     mut materials: ResMut<Assets<GridMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -641,6 +827,9 @@ pub fn spawn_map_entity_reader(
                         scale_factor: 1.,
                         inside_grid_color: r_program.config.style.gray_light.rgb_to_vec3(),
                         outside_grid_color: r_program.config.style.gray_darker.rgb_to_vec3(),
+                        overmap_guide_spacing: 0,
+                        min_zoom: r_grid.min_zoom,
+                        grid_fade_start: 0.,
                     }),
                     ..default()
                 },
@@ -648,15 +837,13 @@ pub fn spawn_map_entity_reader(
             )).id());
         }
 
-        for (coords, tile) in event.map_entity.tiles().iter() {
-            e_tile_place.send(
-                TilePlaceEvent {
-                    tile: tile.clone(),
-                    coordinates: coords.clone(),
-                    should_update_sprites: false,
-                }
-            );
-        }
+        e_bulk_tile_place.send(
+            BulkTilePlaceEvent {
+                tiles: event.map_entity.tiles().iter()
+                    .map(|(coords, tile)| (coords.clone(), tile.clone()))
+                    .collect()
+            }
+        );
     }
 }
 
@@ -667,8 +854,106 @@ pub fn clear_tiles_reader(
 ) {
     for _ in e_clear_tiles.read() {
         for entity in q_tiles.iter_mut() {
-            let mut entity_commands = commands.get_entity(entity).unwrap();
-            entity_commands.despawn();
+            despawn_if_exists(&mut commands, entity);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use bevy::asset::Assets;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::{CommandQueue, RunSystemOnce, SystemState};
+    use bevy::math::{IVec2, Vec2};
+    use bevy::prelude::{Commands, Handle, Image, ResMut, World};
+
+    use crate::common::{Coordinates, MeabyWeighted};
+    use crate::graphics::{GraphicsResource, LegacyTextures};
+    use crate::graphics::tileset::legacy::LegacyTilesetLoader;
+    use crate::map::data::{MapEntity, Single, UpdateSpriteEvent};
+    use crate::map::systems::{despawn_if_exists, update_sprite_reader};
+    use crate::palettes::data::{MapObjectId, MeabyParam};
+    use crate::program::data::{CDDAData, OpenedProject, Program};
+    use crate::project::data::Project;
+    use crate::region_settings::io::RegionSettingsLoader;
+    use crate::settings::data::Settings;
+    use crate::tiles::data::Tile;
+    use crate::ui::grid::resources::Grid;
+
+    #[test]
+    pub fn test_double_despawn_is_noop() {
+        let mut world = World::new();
+        let entity = world.spawn_empty().id();
+
+        for _ in 0..2 {
+            let mut queue = CommandQueue::default();
+            {
+                let mut commands = Commands::new(&mut queue, &world);
+                despawn_if_exists(&mut commands, entity);
+            }
+            queue.apply(&mut world);
         }
+
+        assert!(world.get_entity(entity).is_none());
+    }
+
+    #[test]
+    pub fn test_stale_sprite_entity_is_respawned() {
+        let mut world = World::new();
+        world.init_resource::<Assets<Image>>();
+        world.init_resource::<Events<UpdateSpriteEvent>>();
+
+        let mut state: SystemState<ResMut<Assets<Image>>> = SystemState::new(&mut world);
+        let mut images = state.get_mut(&mut world);
+
+        let textures = LegacyTextures::new(
+            &LegacyTilesetLoader::new(PathBuf::from("./testing_data/bench_tileset")),
+            RegionSettingsLoader::new(PathBuf::from("./testing_data/bench_tileset/regional_map_settings.json"), "default".into()),
+            &mut images,
+        );
+        world.insert_resource(GraphicsResource::new(Box::new(textures)));
+
+        // The sprite was despawned by a ClearTiles event while the tile still references it
+        let stale = world.spawn((Tile::from('.'), Handle::<Image>::default())).id();
+        world.despawn(stale);
+
+        let coordinates = Coordinates::new(0, 0);
+        let mut tile = Tile::from('.');
+        tile.terrain.fg_entity = Some(stale);
+
+        let mut project = Project::default();
+        project.map_entity = MapEntity::Single(Single { size: IVec2::new(1, 1), ..Default::default() });
+        project.map_entity.tiles_mut().insert(coordinates.clone(), tile);
+        project.map_entity.object_mut().terrain.insert('.', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_floor".into()))));
+
+        let mut program = Program::new(vec![project], vec![]);
+        program.config.cdda_data = Some(Arc::new(CDDAData { palettes: HashMap::new() }));
+        world.insert_resource(program);
+
+        world.insert_resource(Grid {
+            tile_size: 32.,
+            default_tile_size: 32.,
+            offset: Vec2::ZERO,
+            min_zoom: 6.,
+            max_zoom: 128.,
+            instantiated_grid: None,
+        });
+        world.insert_resource(Settings::default());
+        world.spawn(OpenedProject { index: 0 });
+
+        world.send_event(UpdateSpriteEvent { tile, coordinates: coordinates.clone() });
+        world.run_system_once(update_sprite_reader);
+
+        let fg_entity = world.resource::<Program>().projects[0].map_entity.tiles().get(&coordinates).unwrap().terrain.fg_entity;
+
+        // Updating the stale entity would have done nothing, so a new sprite has to be spawned and stored on the tile
+        let fg_entity = fg_entity.unwrap();
+        assert_ne!(fg_entity, stale);
+        assert!(world.get::<Tile>(fg_entity).is_some());
+        assert!(world.get::<Handle<Image>>(fg_entity).is_some());
     }
-}
\ No newline at end of file
+}
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 
-use crate::common::{GetRandom, ItemId, MeabyMulti, MeabyNumberRange, MeabyWeighted, TileId};
+use crate::common::{GetRandom, ItemId, MeabyMulti, MeabyNumberRange, MeabyWeighted, TileId, Weighted};
+use crate::project::data::DefinitionKind;
 
 pub type PaletteId = String;
 
@@ -29,7 +30,7 @@ pub enum MapGenValue {
 impl MapGenValue {
     pub fn get_value(&self) -> TileId {
         match self {
-            MapGenValue::Simple(_) => { panic!() }
+            MapGenValue::Simple(v) => v.clone(),
             MapGenValue::Distribution { distribution } => {
                 return distribution.get_random_weighted().unwrap().to_string();
             }
@@ -37,6 +38,18 @@ impl MapGenValue {
             MapGenValue::Switch { .. } => { panic!() }
         }
     }
+
+    /// Returns every value which this MapGenValue could resolve to
+    pub fn get_possible_values(&self) -> Vec<String> {
+        return match self {
+            MapGenValue::Simple(v) => vec![v.clone()],
+            MapGenValue::Distribution { distribution } => {
+                distribution.iter().map(|d| d.value().clone()).collect()
+            }
+            MapGenValue::Param { fallback, .. } => fallback.iter().cloned().collect(),
+            MapGenValue::Switch { cases, .. } => cases.values().cloned().collect(),
+        };
+    }
 }
 
 #[derive(Deserialize, Clone, Serialize, Debug)]
@@ -54,6 +67,19 @@ pub enum ParameterType {
     PaletteId,
 }
 
+impl ParameterType {
+    /// Returns the name of the type as it is written in the CDDA json files
+    pub fn name(&self) -> &'static str {
+        return match self {
+            ParameterType::TerStrId => "ter_str_id",
+            ParameterType::FurnStrId => "furn_str_id",
+            ParameterType::NestedMapgenId => "nested_mapgen_id",
+            ParameterType::String => "string",
+            ParameterType::PaletteId => "palette_id",
+        };
+    }
+}
+
 #[derive(Deserialize, Clone, Serialize, Debug)]
 pub struct Parameter {
     #[serde(rename = "type")]
@@ -87,9 +113,31 @@ pub enum MapObjectId<T> {
         switch: Switch,
         cases: HashMap<String, T>,
     },
+    /// The weighted list CDDA writes for palettes and the fill terrain, {"distribution": [[id, weight], ...]}
+    Distribution { distribution: Vec<T> },
     Single(T),
 }
 
+impl MapObjectId<MeabyWeighted<MeabyParam>> {
+    /// Returns every tile id which is directly referenced, ids behind parameters are not included
+    pub fn get_tile_ids(&self) -> Vec<TileId> {
+        let values: Vec<&MeabyWeighted<MeabyParam>> = match self {
+            MapObjectId::Grouped(g) | MapObjectId::Distribution { distribution: g } => g.iter().collect(),
+            MapObjectId::Nested(n) => n.iter().flatten().collect(),
+            MapObjectId::Param { .. } => vec![],
+            MapObjectId::Switch { cases, .. } => cases.values().collect(),
+            MapObjectId::Single(s) => vec![s],
+        };
+
+        return values.into_iter()
+            .filter_map(|v| match v.value() {
+                MeabyParam::TileId(id) => Some(id.clone()),
+                MeabyParam::Parameter(_) => None
+            })
+            .collect();
+    }
+}
+
 #[derive(Deserialize, Clone, Serialize, Debug, Eq, Hash, PartialEq)]
 pub struct ItemCollectionGroup {
     group: String,
@@ -154,10 +202,20 @@ pub enum Item {
     },
 }
 
+impl Item {
+    /// Returns a short description of the item and its chance to spawn
+    pub fn get_label(&self) -> String {
+        return match self {
+            Item::Default { item, chance, .. } => format!("{} ({}%)", item.0, chance),
+            Item::Distribution { item, chance, .. } => format!("{} with {} entries ({}%)", item.subtype, item.entries.len(), chance)
+        };
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum ParentPalette {
-    NotComputed(MapObjectId<MeabyParam>),
+    NotComputed(MapObjectId<MeabyWeighted<MeabyParam>>),
     Computed(Palette),
 }
 
@@ -167,7 +225,7 @@ pub struct Palette {
 
     #[serde(default)]
     #[serde(skip_serializing)]
-    pub palettes: Vec<MapObjectId<MeabyParam>>,
+    pub palettes: Vec<MapObjectId<MeabyWeighted<MeabyParam>>>,
 
     #[serde(default)]
     #[serde(skip_serializing)]
@@ -189,6 +247,11 @@ pub struct Palette {
     #[serde(skip_serializing)]
     // TODO: Figure out what the value is here
     pub toilets: HashMap<char, Value>,
+
+    /// Every key which the editor does not know, it is written back unchanged when the palette is saved
+    #[serde(flatten)]
+    #[serde(skip_serializing)]
+    pub other: Map<String, Value>,
 }
 
 impl Default for Palette {
@@ -201,6 +264,105 @@ impl Default for Palette {
             furniture: HashMap::new(),
             items: HashMap::new(),
             toilets: HashMap::new(),
+            other: Map::new(),
+        };
+    }
+}
+
+impl Palette {
+    pub fn get_definitions_mut(&mut self, kind: &DefinitionKind) -> &mut HashMap<char, MapObjectId<MeabyWeighted<MeabyParam>>> {
+        return match kind {
+            DefinitionKind::Terrain => &mut self.terrain,
+            DefinitionKind::Furniture => &mut self.furniture,
         };
     }
+
+    /// Adds the id to the definition of the character. A character with a single id becomes a group,
+    /// other kinds of definitions are replaced
+    pub fn add_entry(&mut self, kind: &DefinitionKind, character: char, id: TileId) {
+        let definitions = self.get_definitions_mut(kind);
+        let entry = MeabyWeighted::NotWeighted(MeabyParam::TileId(id));
+
+        let definition = match definitions.remove(&character) {
+            Some(MapObjectId::Single(existing)) => MapObjectId::Grouped(vec![existing, entry]),
+            Some(MapObjectId::Grouped(mut entries)) => {
+                entries.push(entry);
+                MapObjectId::Grouped(entries)
+            }
+            _ => MapObjectId::Single(entry)
+        };
+
+        definitions.insert(character, definition);
+    }
+
+    /// Removes the entry at the index. A group with one entry left becomes a single id
+    /// and the character is removed once it has no entries
+    pub fn remove_entry(&mut self, kind: &DefinitionKind, character: char, index: usize) {
+        let definitions = self.get_definitions_mut(kind);
+
+        let mut entries = match definitions.remove(&character) {
+            Some(MapObjectId::Grouped(entries)) => entries,
+            _ => return
+        };
+
+        if index < entries.len() {
+            entries.remove(index);
+        }
+
+        match entries.len() {
+            0 => {}
+            1 => { definitions.insert(character, MapObjectId::Single(entries.remove(0))); }
+            _ => { definitions.insert(character, MapObjectId::Grouped(entries)); }
+        };
+    }
+
+    /// Sets the weight of the entry at the index, entries with a weight of 1 are written without one
+    pub fn set_entry_weight(&mut self, kind: &DefinitionKind, character: char, index: usize, weight: u32) {
+        let entry = match self.get_definitions_mut(kind).get_mut(&character) {
+            Some(MapObjectId::Single(entry)) if index == 0 => entry,
+            Some(MapObjectId::Grouped(entries)) | Some(MapObjectId::Distribution { distribution: entries }) => match entries.get_mut(index) {
+                None => return,
+                Some(e) => e
+            },
+            _ => return
+        };
+
+        let value = entry.value().clone();
+
+        *entry = match weight {
+            1 => MeabyWeighted::NotWeighted(value),
+            _ => MeabyWeighted::Weighted(Weighted { value, weight })
+        };
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::common::MeabyWeighted;
+    use crate::palettes::data::{MapObjectId, MeabyParam, Palette};
+    use crate::project::data::DefinitionKind;
+
+    #[test]
+    pub fn test_edit_palette_entries() {
+        let mut palette = Palette::default();
+
+        palette.add_entry(&DefinitionKind::Terrain, '.', "t_grass".into());
+        palette.add_entry(&DefinitionKind::Terrain, '.', "t_dirt".into());
+        palette.set_entry_weight(&DefinitionKind::Terrain, '.', 1, 5);
+
+        match palette.terrain.get(&'.') {
+            Some(MapObjectId::Grouped(entries)) => {
+                assert_eq!(entries.len(), 2);
+                assert!(matches!(&entries[1], MeabyWeighted::Weighted(w) if w.weight == 5));
+            }
+            _ => panic!("Expected '.' to be a group")
+        }
+
+        palette.remove_entry(&DefinitionKind::Terrain, '.', 1);
+
+        match palette.terrain.get(&'.') {
+            Some(MapObjectId::Single(entry)) => assert_eq!(entry.value(), &MeabyParam::TileId("t_grass".into())),
+            _ => panic!("Expected '.' to be a single id")
+        }
+    }
 }
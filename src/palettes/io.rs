@@ -3,9 +3,10 @@ use std::fs;
 use std::path::PathBuf;
 
 use log::{info, warn};
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 use crate::common::io::{Load, LoadError, recurse_files};
+use crate::map::io::sorted_json_object;
 use crate::palettes::data::Palette;
 
 pub struct PalettesLoader {
@@ -22,11 +23,19 @@ impl PalettesLoader {
 
 impl Load<HashMap<String, Palette>> for PalettesLoader {
     fn load(&self) -> Result<HashMap<String, Palette>, LoadError> {
-        let files = recurse_files(&self.parent_dir).unwrap();
+        let files = match recurse_files(&self.parent_dir) {
+            Ok(f) => f,
+            Err(e) => return Err(LoadError::Io(self.parent_dir.clone(), e))
+        };
         let mut palettes = HashMap::new();
 
         for path in files.iter() {
-            match serde_json::from_str::<Vec<Value>>(&fs::read_to_string(path).unwrap()) {
+            let contents = match fs::read_to_string(path) {
+                Ok(c) => c,
+                Err(e) => return Err(LoadError::Io(path.clone(), e))
+            };
+
+            match serde_json::from_str::<Vec<Value>>(&contents) {
                 Err(_) => {
                     warn!("Failed to deserialize {:?} to Vec of Values", path);
                     continue;
@@ -50,4 +59,126 @@ impl Load<HashMap<String, Palette>> for PalettesLoader {
 
         return Ok(palettes);
     }
-}
\ No newline at end of file
+}
+/// Converts the palette into the palette object of CDDA
+pub fn palette_to_cdda_json(palette: &Palette) -> Value {
+    let mut object = Map::new();
+    object.insert("type".into(), Value::from("palette"));
+    object.insert("id".into(), Value::from(palette.id.clone()));
+
+    if !palette.parameters.is_empty() {
+        object.insert("parameters".into(), Value::Object(sorted_json_object(&palette.parameters)));
+    }
+
+    if !palette.palettes.is_empty() {
+        object.insert("palettes".into(), serde_json::to_value(&palette.palettes).unwrap());
+    }
+
+    if !palette.terrain.is_empty() {
+        object.insert("terrain".into(), Value::Object(sorted_json_object(&palette.terrain)));
+    }
+
+    if !palette.furniture.is_empty() {
+        object.insert("furniture".into(), Value::Object(sorted_json_object(&palette.furniture)));
+    }
+
+    if !palette.items.is_empty() {
+        object.insert("items".into(), Value::Object(sorted_json_object(&palette.items)));
+    }
+
+    if !palette.toilets.is_empty() {
+        object.insert("toilets".into(), Value::Object(sorted_json_object(&palette.toilets)));
+    }
+
+    for (key, value) in palette.other.iter() {
+        // The type is already written above
+        if key == "type" { continue; }
+
+        object.insert(key.clone(), value.clone());
+    }
+
+    return Value::Object(object);
+}
+
+pub fn palette_to_cdda_json_string(palette: &Palette) -> String {
+    let file = Value::Array(vec![palette_to_cdda_json(palette)]);
+    return serde_json::to_string_pretty(&file).unwrap();
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+
+    use serde_json::{json, Value};
+
+    use crate::common::MeabyMulti;
+    use crate::palettes::data::{Item, Palette};
+    use crate::palettes::io::palette_to_cdda_json_string;
+    use crate::project::data::DefinitionKind;
+
+    #[test]
+    pub fn test_exported_palette_can_be_loaded() {
+        let mut palette = Palette::default();
+        palette.id = "test_palette".into();
+        palette.add_entry(&DefinitionKind::Terrain, '.', "t_grass".into());
+        palette.add_entry(&DefinitionKind::Furniture, 'c', "f_chair".into());
+
+        let exported = palette_to_cdda_json_string(&palette);
+        let values: Vec<Value> = serde_json::from_str(exported.as_str()).unwrap();
+
+        assert_eq!(values[0]["type"], "palette");
+
+        let loaded: Palette = serde_json::from_value(values[0].clone()).unwrap();
+        assert_eq!(loaded.id, "test_palette");
+        assert!(loaded.terrain.contains_key(&'.'));
+        assert!(loaded.furniture.contains_key(&'c'));
+    }
+
+    #[test]
+    pub fn test_exported_palette_keeps_items_and_toilets() {
+        let palette: Palette = serde_json::from_value(json!({
+            "type": "palette",
+            "id": "test_item_palette",
+            "items": {
+                "c": { "item": "SUS_dresser_mens", "chance": 50 },
+                "d": [{ "item": "SUS_desk" }, { "item": "SUS_office_desk", "chance": 20 }]
+            },
+            "toilets": { "&": {} }
+        })).unwrap();
+
+        let exported = palette_to_cdda_json_string(&palette);
+        let values: Vec<Value> = serde_json::from_str(exported.as_str()).unwrap();
+
+        let loaded: Palette = serde_json::from_value(values[0].clone()).unwrap();
+        assert_eq!(loaded.items.len(), 2);
+        assert!(matches!(loaded.items.get(&'c'), Some(MeabyMulti::Single(Item::Default { chance: 50, .. }))));
+        assert!(matches!(loaded.items.get(&'d'), Some(MeabyMulti::Multi(items)) if items.len() == 2));
+        assert!(loaded.toilets.contains_key(&'&'));
+    }
+
+    #[test]
+    pub fn test_cdda_palette_round_trip() {
+        let contents = fs::read_to_string("./testing_data/domestic_palette.json").unwrap();
+        let original: Vec<Value> = serde_json::from_str(contents.as_str()).unwrap();
+
+        let palette: Palette = serde_json::from_value(original[0].clone()).unwrap();
+        let exported: Vec<Value> = serde_json::from_str(palette_to_cdda_json_string(&palette).as_str()).unwrap();
+
+        let original = original[0].as_object().unwrap();
+        let exported = exported[0].as_object().unwrap();
+
+        // No key of the palette may get lost, even the ones which can not be edited
+        let mut original_keys: Vec<&String> = original.keys().collect();
+        let mut exported_keys: Vec<&String> = exported.keys().collect();
+        original_keys.sort();
+        exported_keys.sort();
+        assert_eq!(original_keys, exported_keys);
+
+        for key in ["terrain", "furniture", "toilets", "vendingmachines", "liquids", "signs", "monster", "gaspumps", "sealed_item", "//"] {
+            assert_eq!(original.get(key), exported.get(key), "{} differs", key);
+        }
+
+        let loaded: Palette = serde_json::from_value(Value::Object(exported.clone())).unwrap();
+        assert_eq!(loaded.items.len(), 1);
+    }
+}
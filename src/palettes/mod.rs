@@ -1,2 +1,2 @@
-pub(crate) mod io;
-pub(crate) mod data;
\ No newline at end of file
+pub mod io;
+pub mod data;
\ No newline at end of file
@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::{Commands, EventWriter, Query, Res};
+use bevy_console::ConsoleCommand;
+use clap::Parser;
+use serde_json::Value;
+
+use crate::common::io::Load;
+use crate::common::LogMessage;
+use crate::graphics::{get_sprite_mapping_report, GraphicsResource};
+use crate::map::io::{get_place_nested_reference, selection_to_nested_cdda_json};
+use crate::program::data::{OpenedProject, Program};
+use crate::project::data::{CreateProject, Project};
+use crate::project::io::ProjectFileLoader;
+use crate::palettes::data::Palette;
+use crate::settings::data::Settings;
+use crate::tiles::data::Selection;
+use crate::ui::error_dialog::ShowError;
+use crate::ui::palette_editor::PaletteEditorData;
+
+/// Writes how every tile of the opened Project resolves to sprites into a json file
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "dump_sprites")]
+pub struct DumpSpritesCommand {
+    /// The file to write the report to
+    path: PathBuf,
+}
+
+pub fn dump_sprites_command(
+    mut command: ConsoleCommand<DumpSpritesCommand>,
+    r_program: Res<Program>,
+    r_graphics: Res<GraphicsResource>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let path = match command.take() {
+        Some(Ok(DumpSpritesCommand { path })) => path,
+        _ => return
+    };
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => {
+            command.reply_failed("No Project is opened");
+            return;
+        }
+        Some(p) => p
+    };
+
+    let (cdda_data, textures) = match (&r_program.config.cdda_data, &r_graphics.textures) {
+        (Some(d), Some(t)) => (d, t),
+        _ => {
+            command.reply_failed("The CDDA directory and a tileset have to be selected");
+            return;
+        }
+    };
+
+    let report = get_sprite_mapping_report(textures.as_ref(), project, cdda_data);
+
+    match fs::write(&path, serde_json::to_string_pretty(&Value::Object(report)).unwrap()) {
+        Ok(_) => command.reply_ok(format!("Wrote the sprite mapping to {:?}", path)),
+        Err(e) => command.reply_failed(format!("Failed to write the sprite mapping to {:?}: {}", path, e))
+    };
+}
+
+/// Writes the selected rectangle of the opened Project into a json file as a nested mapgen
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "export_selection")]
+pub struct ExportSelectionCommand {
+    /// The nested_mapgen_id of the exported chunk
+    id: String,
+    /// The file to write the nested mapgen to
+    path: PathBuf,
+}
+
+pub fn export_selection_command(
+    mut command: ConsoleCommand<ExportSelectionCommand>,
+    r_program: Res<Program>,
+    r_selection: Res<Selection>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let (id, path) = match command.take() {
+        Some(Ok(ExportSelectionCommand { id, path })) => (id, path),
+        _ => return
+    };
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => {
+            command.reply_failed("No Project is opened");
+            return;
+        }
+        Some(p) => p
+    };
+
+    let (start, end) = match r_selection.get_bounds() {
+        None => {
+            command.reply_failed("Nothing is selected");
+            return;
+        }
+        Some(b) => b
+    };
+
+    let chunk = selection_to_nested_cdda_json(&project.map_entity, &start, &end, id.as_str());
+    let file = Value::Array(vec![chunk]);
+
+    match fs::write(&path, serde_json::to_string_pretty(&file).unwrap()) {
+        Ok(_) => {
+            command.reply(format!("Wrote the selection to {:?}", path));
+            command.reply_ok(format!("Place it with {}", get_place_nested_reference(id.as_str(), &start)));
+        }
+        Err(e) => command.reply_failed(format!("Failed to write the selection to {:?}: {}", path, e))
+    };
+}
+
+/// Opens the palette editor
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "edit_palette")]
+pub struct EditPaletteCommand {
+    /// The id of the palette, a new palette is created if no palette with this id is loaded
+    id: String,
+}
+
+pub fn edit_palette_command(
+    mut command: ConsoleCommand<EditPaletteCommand>,
+    mut commands: Commands,
+    r_program: Res<Program>,
+) {
+    let id = match command.take() {
+        Some(Ok(EditPaletteCommand { id })) => id,
+        _ => return
+    };
+
+    let loaded_palette = r_program.config.cdda_data.as_ref().and_then(|d| d.palettes.get(&id));
+
+    let palette = match loaded_palette {
+        None => {
+            command.reply(format!("Created the new palette {}", id));
+            Palette { id, ..Palette::default() }
+        }
+        Some(p) => p.clone()
+    };
+
+    commands.insert_resource(PaletteEditorData::new(palette));
+    command.ok();
+}
+
+/// Opens a mapgen json file as a new Project.
+/// The id is either the om_terrain or the nested_mapgen_id of the mapgen
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "import_mapgen")]
+pub struct ImportMapgenCommand {
+    /// The mapgen json file
+    path: PathBuf,
+    /// The om_terrain or nested_mapgen_id of the mapgen in the file
+    id: String,
+    /// Which of the mapgens with the same om_terrain is imported, starting at 0. One is chosen by weight if omitted
+    #[arg(long)]
+    variant: Option<usize>,
+}
+
+pub fn import_mapgen_command(
+    mut command: ConsoleCommand<ImportMapgenCommand>,
+    mut e_create_project: EventWriter<CreateProject>,
+    mut e_show_error: EventWriter<ShowError>,
+    mut e_log: EventWriter<LogMessage>,
+    r_program: Res<Program>,
+    r_settings: Res<Settings>,
+) {
+    let (path, id, variant) = match command.take() {
+        Some(Ok(ImportMapgenCommand { path, id, variant })) => (path, id, variant),
+        _ => return
+    };
+
+    let cdda_data = match &r_program.config.cdda_data {
+        None => {
+            command.reply_failed("The CDDA directory has to be selected");
+            return;
+        }
+        Some(d) => d
+    };
+
+    let loader = ProjectFileLoader {
+        path: path.clone(),
+        om_terrain: id.clone(),
+        cdda_data,
+        row_size_mismatch: r_settings.row_size_mismatch,
+        variant,
+        warnings: RefCell::default(),
+    };
+
+    let map_entity = match loader.load() {
+        Ok(m) => m,
+        Err(e) => {
+            command.reply_failed(format!("Failed to import {} from {:?}: {}", id, path, e));
+            e_show_error.send(ShowError::load(format!("Could not import {}", id), &e));
+            return;
+        }
+    };
+
+    for warning in loader.take_warnings() {
+        e_log.send(LogMessage::warning(warning));
+    }
+
+    e_create_project.send(CreateProject {
+        project: Project {
+            name: id,
+            map_entity,
+            ..Default::default()
+        }
+    });
+
+    command.ok();
+}
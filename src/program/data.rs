@@ -1,17 +1,18 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
 use std::sync::Arc;
 
+use bevy::input::ButtonInput;
 use bevy::prelude::{Color, Component, KeyCode, Resource, States};
 use bevy_egui::egui::Color32;
 use num::ToPrimitive;
 use serde::{Deserialize, Serialize};
 
-use crate::common::io::Load;
+use crate::common::io::{Load, LoadError};
 use crate::palettes::data::Palette;
 use crate::palettes::io::PalettesLoader;
 use crate::project::data::{Project, ProjectSaveState};
-use crate::ui::style::Style;
+use crate::settings::data::Settings;
+use crate::ui::style::{Style, Theme};
 
 #[derive(Default, States, Clone, Hash, Debug, Eq, PartialEq)]
 pub enum ProgramState {
@@ -40,6 +41,49 @@ impl Program {
             config: Config::default(),
         };
     }
+
+    /// Adds the Project and returns the index at which it is stored
+    pub fn add_project(&mut self, project: Project) -> usize {
+        self.projects.push(project);
+        return self.projects.len() - 1;
+    }
+
+    /// Removes the Projects at the indices, the Projects behind them move to the front
+    pub fn remove_projects(&mut self, indices: &Vec<usize>) {
+        let mut index = 0;
+
+        self.projects.retain(|_| {
+            let is_kept = !indices.contains(&index);
+            index += 1;
+            is_kept
+        });
+    }
+
+    /// Moves the Project at the from index to the to index, the Projects between them move by one
+    pub fn move_project(&mut self, from: usize, to: usize) {
+        if from >= self.projects.len() || to >= self.projects.len() { return; }
+
+        let project = self.projects.remove(from);
+        self.projects.insert(to, project);
+    }
+
+    /// Returns the index which the Project at the index has after the Project at the from index was moved to the to index
+    pub fn get_moved_index(index: usize, from: usize, to: usize) -> usize {
+        if index == from { return to; }
+
+        if from < to && index > from && index <= to { return index - 1; }
+        if from > to && index >= to && index < from { return index + 1; }
+
+        return index;
+    }
+
+    /// Returns the index which the Project at the index has after the Projects at the removed indices were removed.
+    /// Returns None if the Project itself was removed
+    pub fn get_shifted_index(index: usize, removed: &Vec<usize>) -> Option<usize> {
+        if removed.contains(&index) { return None; }
+
+        return Some(index - removed.iter().filter(|r| **r < index).count());
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,17 +95,30 @@ pub struct CDDAData {
 pub struct Config {
     pub cdda_data: Option<Arc<CDDAData>>,
     pub keybindings: Keybinds,
+    pub theme: Theme,
     pub style: Style,
 }
 
 impl Config {
-    pub fn load_cdda_dir(&mut self, cdda_dir: PathBuf) {
-        let palettes_folder = PathBuf::from(format!("{}/data/json/mapgen_palettes", cdda_dir.to_str().unwrap()));
-        let palettes = PalettesLoader::new(palettes_folder).load().unwrap();
+    /// Replaces the style with the colors of the theme
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.style = Style::from_theme(theme);
+    }
+
+    pub fn load_cdda_data(&mut self, settings: &Settings) -> Result<(), LoadError> {
+        let palettes_folder = match settings.mapgen_palettes_dir() {
+            None => return Ok(()),
+            Some(dir) => dir
+        };
+
+        let palettes = PalettesLoader::new(palettes_folder).load()?;
 
         self.cdda_data = Some(Arc::new(CDDAData {
             palettes,
         }));
+
+        return Ok(());
     }
 }
 
@@ -70,6 +127,7 @@ impl Default for Config {
         return Self {
             cdda_data: Default::default(),
             keybindings: Default::default(),
+            theme: Theme::Dark,
             style: Style::dark(),
         };
     }
@@ -94,12 +152,65 @@ pub struct Menus {
     pub is_settings_menu_open: bool,
     pub is_create_project_menu_open: bool,
     pub is_define_terrain_menu_open: bool,
+    pub is_project_settings_menu_open: bool,
+    pub is_tile_statistics_open: bool,
+    pub is_bookmarks_open: bool,
+    /// Set on the first run, guides the user through picking the CDDA directory and a tileset
+    pub is_onboarding: bool,
 }
 
 #[derive(Debug)]
 pub struct Keybinds {
     pub open_console: Vec<KeyCode>,
     pub save_project: Vec<KeyCode>,
+    pub undo: Vec<KeyCode>,
+    pub redo: Vec<KeyCode>,
+
+    /// Turns the placed tiles clockwise by a quarter
+    pub rotate_brush: Vec<KeyCode>,
+
+    /// Closes the frontmost open menu
+    pub close_menu: Vec<KeyCode>,
+
+    /// Switches between editing the opened Project and only viewing it
+    pub toggle_preview_mode: Vec<KeyCode>,
+
+    /// Holding any of these keys while scrolling changes the brush size instead of zooming
+    pub brush_size_modifier: Vec<KeyCode>,
+
+    /// Holding any of these keys while scrolling changes the active edit layer instead of zooming
+    pub brush_layer_modifier: Vec<KeyCode>,
+
+    /// Holding any of these keys while clicking a tab adds it to or removes it from the selection
+    pub tab_selection_modifier: Vec<KeyCode>,
+
+    /// Holding any of these keys while dragging selects a rectangle of tiles instead of painting
+    pub selection_modifier: Vec<KeyCode>,
+
+    /// Holding any of these keys while dragging with the left mouse button pans the view instead of painting
+    pub pan_modifier: Vec<KeyCode>,
+
+    /// Holding any of these keys while typing a character which is defined in the opened Project makes it the brush character
+    pub character_entry_modifier: Vec<KeyCode>,
+
+    /// Moves the view to the next bookmark of the opened Project
+    pub next_bookmark: Vec<KeyCode>,
+
+    /// Switches between a windowed and a borderless fullscreen window
+    pub toggle_fullscreen: Vec<KeyCode>,
+
+    /// Switches between the current and the previous brush character
+    pub swap_brush_characters: Vec<KeyCode>,
+
+    /// Holding any of these keys while right clicking a tile opens its context menu instead of erasing it
+    pub context_menu_modifier: Vec<KeyCode>,
+}
+
+impl Keybinds {
+    /// Returns true in the frame in which all keys of the keybind are held down
+    pub fn is_just_pressed(keybind: &Vec<KeyCode>, keys: &ButtonInput<KeyCode>) -> bool {
+        return keybind.iter().all(|k| keys.pressed(*k)) && keybind.iter().any(|k| keys.just_pressed(*k));
+    }
 }
 
 impl Default for Keybinds {
@@ -107,6 +218,21 @@ impl Default for Keybinds {
         return Self {
             open_console: vec![KeyCode::F1],
             save_project: vec![KeyCode::ControlLeft, KeyCode::KeyS],
+            undo: vec![KeyCode::ControlLeft, KeyCode::KeyZ],
+            redo: vec![KeyCode::ControlLeft, KeyCode::KeyY],
+            rotate_brush: vec![KeyCode::KeyR],
+            close_menu: vec![KeyCode::Escape],
+            toggle_preview_mode: vec![KeyCode::F2],
+            brush_size_modifier: vec![KeyCode::ControlLeft, KeyCode::ControlRight],
+            brush_layer_modifier: vec![KeyCode::AltLeft, KeyCode::AltRight],
+            tab_selection_modifier: vec![KeyCode::ControlLeft, KeyCode::ControlRight],
+            selection_modifier: vec![KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            pan_modifier: vec![KeyCode::Space],
+            character_entry_modifier: vec![KeyCode::Tab],
+            next_bookmark: vec![KeyCode::KeyB],
+            toggle_fullscreen: vec![KeyCode::F11],
+            swap_brush_characters: vec![KeyCode::KeyX],
+            context_menu_modifier: vec![KeyCode::ControlLeft, KeyCode::ControlRight],
         };
     }
 }
\ No newline at end of file
@@ -1,17 +1,19 @@
+use std::collections::HashMap;
 use std::fs;
-use std::fs::File;
-use std::io::Write;
+use std::path::PathBuf;
 
-use bevy::log::info;
+use anyhow::anyhow;
+use bevy::log::{error, info};
 use bevy::prelude::default;
 use directories::ProjectDirs;
 use serde_json::{Map, Value};
 
-use crate::common::io::{Load, LoadError, Save, SaveError};
+use crate::common::io::{Load, LoadError, read_file, Save, SaveError, write_json_file};
 use crate::common::io::LoadError::ParseError;
 use crate::program::data::{Menus, Program, ProgramState};
-use crate::project::data::{Project, ProjectSaveState};
+use crate::project::data::{Project, ProjectSaveState, ProjectView};
 use crate::project::io::ProjectSaver;
+use crate::ui::style::Theme;
 
 pub struct ProgramdataLoader {}
 
@@ -24,15 +26,16 @@ impl Load<Program> for ProgramdataLoader {
 
         let data_dir = dir.data_local_dir();
 
-        if !data_dir.exists() { fs::create_dir_all(data_dir).unwrap(); }
+        if let Err(e) = fs::create_dir_all(data_dir) {
+            return Err(LoadError::Io(data_dir.to_path_buf(), e));
+        }
 
-        let contents = match fs::read_to_string(data_dir.join("data.json")) {
-            Err(_) => return Err(ParseError),
-            Ok(f) => f
-        };
+        let contents = read_file(&data_dir.join("data.json"))?;
 
-        let value: Map<String, Value> = serde_json::from_str(contents.as_str())
-            .expect("Valid Json");
+        let value: Map<String, Value> = match serde_json::from_str(contents.as_str()) {
+            Ok(v) => v,
+            Err(e) => return Err(LoadError::Other(e.into()))
+        };
 
         // let history_array: Vec<ProjectSaveState> = value
         //     .get("history")
@@ -43,23 +46,49 @@ impl Load<Program> for ProgramdataLoader {
         //     .map(|v| serde_json::from_value::<ProjectSaveState>(v.clone()).unwrap())
         //     .collect();
 
-        let projects_array: Vec<Project> = value
-            .get("open_projects")
-            .expect("open_projects field")
-            .as_array()
-            .expect("Valid array")
+        let open_projects = match value.get("open_projects").and_then(|p| p.as_array()) {
+            None => return Err(ParseError),
+            Some(p) => p
+        };
+
+        // Older program data has no views, the Projects are then opened with the default view
+        let views: HashMap<PathBuf, ProjectView> = value.get("views")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let projects_array: Vec<Project> = open_projects
             .iter()
             .map(|v| {
-                let state = serde_json::from_value::<ProjectSaveState>(v.clone()).unwrap();
+                let state = match serde_json::from_value::<ProjectSaveState>(v.clone()) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        log::warn!("Skipped an open Project with an invalid save state {:?}", e);
+                        return None;
+                    }
+                };
 
                 return match state {
                     ProjectSaveState::Saved(path) => {
                         match fs::read_to_string(path.clone()) {
                             Ok(s) => {
-                                let project: Project = serde_json::from_str(s.as_str()).expect("Valid Project");
+                                let mut project: Project = match serde_json::from_str(s.as_str()) {
+                                    Ok(p) => p,
+                                    Err(e) => {
+                                        log::warn!("Could not parse Saved Project at path {:?} {:?}", path, e);
+                                        return None;
+                                    }
+                                };
+
+                                if let Err(e) = project.map_entity.verify_tiles() {
+                                    log::warn!("Could not Load Saved Project at path {:?} because its tiles are corrupted {:?}", path, e);
+                                    return None;
+                                }
 
                                 info!("Loaded Saved Project at Path {:?}", path);
 
+                                project.view = views.get(&path).copied();
+                                project.apply_parameter_overrides();
+
                                 Some(project)
                             }
                             Err(_) => {
@@ -71,10 +100,24 @@ impl Load<Program> for ProgramdataLoader {
                     ProjectSaveState::AutoSaved(path) => {
                         match fs::read_to_string(path.clone()) {
                             Ok(s) => {
-                                let project: Project = serde_json::from_str(s.as_str()).expect("Valid Project");
+                                let mut project: Project = match serde_json::from_str(s.as_str()) {
+                                    Ok(p) => p,
+                                    Err(e) => {
+                                        log::warn!("Could not parse Auto saved Project at path {:?} {:?}", path, e);
+                                        return None;
+                                    }
+                                };
+
+                                if let Err(e) = project.map_entity.verify_tiles() {
+                                    log::warn!("Could not Load Auto saved Project at path {:?} because its tiles are corrupted {:?}", path, e);
+                                    return None;
+                                }
 
                                 info!("Loaded Auto saved Project at Path {:?}", path);
 
+                                project.view = views.get(&path).copied();
+                                project.apply_parameter_overrides();
+
                                 Some(project)
                             }
                             Err(_) => {
@@ -93,7 +136,14 @@ impl Load<Program> for ProgramdataLoader {
             .map(|v| v.unwrap())
             .collect();
 
-        return Ok(Program::new(projects_array, vec![]));
+        let theme = value.get("theme")
+            .and_then(|t| serde_json::from_value::<Theme>(t.clone()).ok())
+            .unwrap_or_default();
+
+        let mut program = Program::new(projects_array, vec![]);
+        program.config.set_theme(theme);
+
+        return Ok(program);
     }
 }
 
@@ -108,33 +158,51 @@ impl Save<Program> for ProgramdataSaver {
 
         let data_dir = dir.data_local_dir();
 
-        if !data_dir.exists() { fs::create_dir_all(data_dir).unwrap(); }
-
-        let mut file = File::options()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(data_dir.join("data.json"))
-            .unwrap();
-
         let mut data: Map<String, Value> = Map::new();
+        let mut open_projects: Vec<ProjectSaveState> = Vec::new();
+        let mut views: HashMap<PathBuf, ProjectView> = HashMap::new();
+        // A Project which could not be auto saved must not stop the other Projects from being saved
+        let mut failed_projects: Vec<String> = Vec::new();
 
-        let open_projects: Vec<ProjectSaveState> = value.projects.iter().map(|project| {
-            match &project.save_state {
+        for project in value.projects.iter() {
+            let state = match &project.save_state {
                 ProjectSaveState::AutoSaved(val) => ProjectSaveState::AutoSaved(val.clone()),
                 ProjectSaveState::Saved(val) => ProjectSaveState::Saved(val.clone()),
                 ProjectSaveState::NotSaved => {
                     info!("autosaving {}", project.name);
                     let project_saver = ProjectSaver { directory: Box::from(data_dir) };
-                    project_saver.save(project).unwrap();
+
+                    if let Err(e) = project_saver.save(project) {
+                        error!("Could not auto save the Project {}: {}", project.name, e);
+                        failed_projects.push(format!("{}: {}", project.name, e));
+                        continue;
+                    }
+
                     ProjectSaveState::AutoSaved(data_dir.join(format!("auto_save_{}.map", project.name)))
                 }
+            };
+
+            if let (Some(path), Some(view)) = (state.path(), &project.view) {
+                views.insert(path.clone(), *view);
+            }
+
+            open_projects.push(state);
+        }
+
+        match (serde_json::to_value(open_projects), serde_json::to_value(value.config.theme), serde_json::to_value(views)) {
+            (Ok(open_projects), Ok(theme), Ok(views)) => {
+                data.insert("open_projects".into(), open_projects);
+                data.insert("theme".into(), theme);
+                data.insert("views".into(), views);
             }
-        }).collect();
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return Err(SaveError::Other(e.into()))
+        }
 
-        data.insert("open_projects".into(), serde_json::to_value(open_projects).unwrap());
+        write_json_file(&data_dir.join("data.json"), &data)?;
 
-        file.write_all(serde_json::to_string(&data).unwrap().as_bytes()).unwrap();
+        if !failed_projects.is_empty() {
+            return Err(SaveError::Other(anyhow!("Could not auto save {} Project(s):\n{}", failed_projects.len(), failed_projects.join("\n"))));
+        }
 
         return Ok(());
     }
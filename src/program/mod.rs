@@ -1,3 +1,4 @@
-pub(crate) mod data;
-pub(crate) mod io;
-pub(crate) mod plugin;
\ No newline at end of file
+pub mod data;
+pub mod io;
+pub mod plugin;
+pub mod commands;
\ No newline at end of file
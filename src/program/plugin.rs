@@ -1,5 +1,8 @@
 use bevy::app::App;
 use bevy::prelude::Plugin;
+use bevy_console::AddConsoleCommand;
+
+use crate::program::commands::{dump_sprites_command, DumpSpritesCommand, edit_palette_command, EditPaletteCommand, export_selection_command, ExportSelectionCommand, import_mapgen_command, ImportMapgenCommand};
 use crate::common::io::Load;
 use crate::program::data::{ProgramState};
 use crate::program::io::ProgramdataLoader;
@@ -9,5 +12,9 @@ pub struct ProgramPlugin;
 impl Plugin for ProgramPlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<ProgramState>();
+        app.add_console_command::<DumpSpritesCommand, _>(dump_sprites_command);
+        app.add_console_command::<ExportSelectionCommand, _>(export_selection_command);
+        app.add_console_command::<EditPaletteCommand, _>(edit_palette_command);
+        app.add_console_command::<ImportMapgenCommand, _>(import_mapgen_command);
     }
 }
\ No newline at end of file
@@ -1,15 +1,550 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
+use std::time::SystemTime;
 
-use bevy::prelude::{Event, Resource};
+use bevy::prelude::{Event, IVec2, Resource, Timer, Vec2};
 use serde::{Deserialize, Serialize};
 
-use crate::map::data::MapEntity;
+use crate::common::{Coordinates, MeabyWeighted};
+use crate::map::data::{MapEntity, MeabyRange, PlaceLoot, TileSelection};
+use crate::map::io::ParameterId;
+use crate::palettes::data::{MapObjectId, MeabyParam};
+use crate::program::data::CDDAData;
+use crate::tiles::data::Tile;
+
+/// A position and zoom of the view which was saved by the user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    pub offset: Vec2,
+    pub tile_size: f32,
+}
+
+/// The position and zoom of the view when the Project was last shown
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ProjectView {
+    pub offset: Vec2,
+    pub tile_size: f32,
+}
+
+impl ProjectView {
+    /// Keeps the map in reach of the view if it was resized since the view was stored
+    pub fn clamped(&self, map_size: Vec2) -> Self {
+        let max_offset = map_size * self.tile_size;
+
+        return Self {
+            offset: self.offset.clamp(-max_offset, max_offset),
+            tile_size: self.tile_size,
+        };
+    }
+}
 
 #[derive(Debug, Default, Clone, Resource, Serialize, Deserialize)]
 pub struct Project {
     pub name: String,
     pub map_entity: MapEntity,
     pub save_state: ProjectSaveState,
+
+    /// If true, the space character will not be filled with the fill terrain and stays empty
+    #[serde(default)]
+    pub treat_space_as_empty: bool,
+
+    /// Values of parameters which were picked by the user instead of being rolled from their default
+    #[serde(default)]
+    pub parameter_overrides: HashMap<ParameterId, String>,
+
+    /// Named positions of the view which can be jumped back to
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+
+    /// The view which is restored when the Project is opened, stored in the program data by the path of the Project
+    #[serde(skip)]
+    pub view: Option<ProjectView>,
+
+    /// If the Project has changes which have not been saved yet
+    #[serde(skip)]
+    pub is_dirty: bool,
+
+    #[serde(skip)]
+    pub history: History,
+}
+
+impl Project {
+    /// Returns the index of the bookmark after the last one, starts again at the first bookmark after the last one
+    pub fn get_next_bookmark_index(&self, last: Option<usize>) -> Option<usize> {
+        if self.bookmarks.is_empty() { return None; }
+
+        return match last {
+            None => Some(0),
+            Some(i) => Some((i + 1) % self.bookmarks.len())
+        };
+    }
+
+    /// Reverts the last change. Returns false if there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        let entry = match self.history.undo_stack.pop_back() {
+            None => return false,
+            Some(e) => e
+        };
+
+        self.apply_history_entry(&entry, true);
+        self.history.redo_stack.push(entry);
+
+        return true;
+    }
+
+    /// Applies the last undone change again. Returns false if there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        let entry = match self.history.redo_stack.pop() {
+            None => return false,
+            Some(e) => e
+        };
+
+        self.apply_history_entry(&entry, false);
+        self.history.undo_stack.push_back(entry);
+
+        return true;
+    }
+
+    /// Writes the overridden parameter values into the computed parameters of the map.
+    /// Has to be called after the parameters were recomputed, otherwise the overrides are lost
+    pub fn apply_parameter_overrides(&mut self) {
+        for (parameter_id, value) in self.parameter_overrides.iter() {
+            self.map_entity.object_mut().computed_parameters.set_value(parameter_id, value);
+        }
+    }
+
+    /// Renames the overmap terrain of the map, which is also used as the name of the tab and the save file.
+    /// Only maps with a single om_terrain can be renamed, see [Project::set_multi_om_terrain] for the others
+    pub fn set_om_terrain(&mut self, om_terrain: &str) -> Result<(), String> {
+        let om_terrain = validate_om_terrain(om_terrain)?;
+
+        let single = match &mut self.map_entity {
+            MapEntity::Single(s) => s,
+            _ => return Err("Only maps with a single om_terrain can be renamed".into())
+        };
+
+        single.om_terrain = om_terrain.to_string();
+        self.name = om_terrain.to_string();
+        self.is_dirty = true;
+
+        return Ok(());
+    }
+
+    /// Renames one of the overmap terrains of a map which spans multiple overmap terrains,
+    /// the index is the one of [crate::map::data::MultiOmTerrain::get_positioned].
+    /// The name of the Project is kept so the save file does not change
+    pub fn set_multi_om_terrain(&mut self, index: usize, om_terrain: &str) -> Result<(), String> {
+        let om_terrain = validate_om_terrain(om_terrain)?;
+
+        let multi = match &mut self.map_entity {
+            MapEntity::Multi(m) => m,
+            _ => return Err("Only maps with multiple om_terrains can be renamed by index".into())
+        };
+
+        let entry = match multi.om_terrain.get_mut(index) {
+            None => return Err(format!("The map has no om_terrain at index {}", index)),
+            Some(e) => e
+        };
+
+        *entry = om_terrain.to_string();
+        self.is_dirty = true;
+
+        return Ok(());
+    }
+
+    /// Replaces every tile with an empty tile as a single step which can be undone.
+    /// The palettes and definitions of the map are kept
+    pub fn clear_tiles(&mut self) {
+        let size = self.map_entity.size();
+
+        let mut changes = Vec::new();
+        let mut tiles = HashMap::new();
+
+        for y in 0..size.y as i32 {
+            for x in 0..size.x as i32 {
+                let coordinates = Coordinates::new(x, y);
+                let before = self.map_entity.tiles().get(&coordinates);
+
+                if before.map(|t| t.character) != Some(' ') {
+                    changes.push(TileChange {
+                        coordinates: coordinates.clone(),
+                        before: before.map(|t| t.character),
+                        after: Some(' '),
+                        before_rotation: before.map(|t| t.rotation).unwrap_or(0),
+                        after_rotation: 0,
+                    });
+                }
+
+                tiles.insert(coordinates, Tile::from(' '));
+            }
+        }
+
+        for (coordinates, tile) in self.map_entity.tiles().iter() {
+            if !tiles.contains_key(coordinates) {
+                changes.push(TileChange {
+                    coordinates: coordinates.clone(),
+                    before: Some(tile.character),
+                    after: None,
+                    before_rotation: tile.rotation,
+                    after_rotation: 0,
+                });
+            }
+        }
+
+        *self.map_entity.tiles_mut() = tiles;
+
+        self.history.push(HistoryEntry::Tiles(changes));
+        self.is_dirty = true;
+    }
+
+    /// Inserts an empty row or column at the index as a single step which can be undone.
+    /// The tiles at and behind the index are moved back by one and the map grows by one
+    pub fn insert_line(&mut self, line: MapLine, index: i32) -> Result<(), String> {
+        let before = self.get_resizable_layout()?;
+
+        if index < 0 || index > line.get_count(before.size) {
+            return Err(format!("Can not insert a {} at index {}, the map only has {}", line.name(), index, line.get_count(before.size)));
+        }
+
+        let mut tiles = HashMap::new();
+
+        for (coordinates, tile) in before.tiles.iter() {
+            let coordinates = match line.get_index(coordinates) >= index {
+                true => line.moved(coordinates, 1),
+                false => coordinates.clone()
+            };
+
+            tiles.insert(coordinates, *tile);
+        }
+
+        let size = line.resized(before.size, 1);
+
+        for i in 0..line.get_length(size) {
+            tiles.insert(line.get_coordinates(index, i), Tile::from(' '));
+        }
+
+        let place_loot = before.place_loot.iter()
+            .map(|p| {
+                let mut place_loot = p.clone();
+                let range = line.get_range_mut(&mut place_loot);
+                *range = range.inserted(index);
+                place_loot
+            })
+            .collect();
+
+        self.apply_layout(&MapLayout { tiles, size, place_loot });
+        self.history.push(HistoryEntry::Layout { before: Box::new(before), after: Box::new(self.get_layout()) });
+        self.is_dirty = true;
+
+        return Ok(());
+    }
+
+    /// Removes the row or column at the index as a single step which can be undone.
+    /// The tiles behind the index are moved forward by one and the map shrinks by one
+    pub fn delete_line(&mut self, line: MapLine, index: i32) -> Result<(), String> {
+        let before = self.get_resizable_layout()?;
+
+        if line.get_count(before.size) <= 1 {
+            return Err(format!("The map needs at least one {}", line.name()));
+        }
+
+        if index < 0 || index >= line.get_count(before.size) {
+            return Err(format!("The map has no {} at index {}", line.name(), index));
+        }
+
+        let mut tiles = HashMap::new();
+
+        for (coordinates, tile) in before.tiles.iter() {
+            let coordinates = match line.get_index(coordinates).cmp(&index) {
+                Ordering::Less => coordinates.clone(),
+                Ordering::Equal => continue,
+                Ordering::Greater => line.moved(coordinates, -1)
+            };
+
+            tiles.insert(coordinates, *tile);
+        }
+
+        // Loot which could only be placed in the deleted row or column is removed together with it
+        let place_loot = before.place_loot.iter()
+            .filter_map(|p| {
+                let mut place_loot = p.clone();
+                let range = line.get_range_mut(&mut place_loot);
+                *range = range.deleted(index)?;
+                Some(place_loot)
+            })
+            .collect();
+
+        self.apply_layout(&MapLayout { tiles, size: line.resized(before.size, -1), place_loot });
+        self.history.push(HistoryEntry::Layout { before: Box::new(before), after: Box::new(self.get_layout()) });
+        self.is_dirty = true;
+
+        return Ok(());
+    }
+
+    fn get_layout(&self) -> MapLayout {
+        return MapLayout {
+            tiles: self.map_entity.tiles().clone(),
+            size: self.map_entity.size().as_ivec2(),
+            place_loot: self.map_entity.object().place_loot.clone(),
+        };
+    }
+
+    /// Returns the layout of the map or an error if the size of the map can not be changed
+    fn get_resizable_layout(&self) -> Result<MapLayout, String> {
+        return match &self.map_entity {
+            MapEntity::Multi(_) => Err("Maps with multiple om_terrains always have the size of their om_terrains".into()),
+            _ => Ok(self.get_layout())
+        };
+    }
+
+    fn apply_layout(&mut self, layout: &MapLayout) {
+        *self.map_entity.tiles_mut() = layout.tiles.clone();
+        self.map_entity.object_mut().place_loot = layout.place_loot.clone();
+
+        match &mut self.map_entity {
+            MapEntity::Single(s) => s.size = layout.size,
+            MapEntity::Nested(n) => n.size = Some(layout.size),
+            // The size of these maps is derived from their om_terrains
+            MapEntity::Multi(_) => {}
+        }
+    }
+
+    /// Replaces the terrain or furniture definition of the character as a single step which can be undone.
+    /// Passing None removes the definition
+    pub fn set_definition(&mut self, kind: DefinitionKind, character: char, definition: Option<Definition>) {
+        let before = self.get_definitions_mut(&kind).get(&character).cloned();
+
+        self.history.push(HistoryEntry::Definition {
+            kind: kind.clone(),
+            character,
+            before,
+            after: definition.clone(),
+        });
+
+        self.apply_definition(&kind, character, definition);
+        self.is_dirty = true;
+    }
+
+    /// Inlines the definitions of all palettes into the map as a single step which can be undone.
+    /// The map no longer changes with the palettes afterwards
+    pub fn flatten_palettes(&mut self, cdda_data: &CDDAData) {
+        let before = self.map_entity.object().clone();
+        self.map_entity.object_mut().flatten_palettes(cdda_data);
+
+        self.history.push(HistoryEntry::TileSelection {
+            before: Box::new(before),
+            after: Box::new(self.map_entity.object().clone()),
+        });
+        self.is_dirty = true;
+    }
+
+    fn get_definitions_mut(&mut self, kind: &DefinitionKind) -> &mut HashMap<char, Definition> {
+        let object = self.map_entity.object_mut();
+
+        return match kind {
+            DefinitionKind::Terrain => &mut object.terrain,
+            DefinitionKind::Furniture => &mut object.furniture,
+        };
+    }
+
+    fn apply_definition(&mut self, kind: &DefinitionKind, character: char, definition: Option<Definition>) {
+        let definitions = self.get_definitions_mut(kind);
+
+        match definition {
+            None => { definitions.remove(&character); }
+            Some(d) => { definitions.insert(character, d); }
+        }
+    }
+
+    fn apply_history_entry(&mut self, entry: &HistoryEntry, is_undo: bool) {
+        match entry {
+            HistoryEntry::Tiles(changes) => {
+                for change in changes.iter() {
+                    let (character, rotation) = match is_undo {
+                        true => (change.before, change.before_rotation),
+                        false => (change.after, change.after_rotation)
+                    };
+
+                    match character {
+                        None => { self.map_entity.tiles_mut().remove(&change.coordinates); }
+                        Some(c) => { self.map_entity.tiles_mut().insert(change.coordinates.clone(), Tile::with_rotation(c, rotation)); }
+                    }
+                }
+            }
+            HistoryEntry::Definition { kind, character, before, after } => {
+                let definition = match is_undo {
+                    true => before.clone(),
+                    false => after.clone()
+                };
+
+                self.apply_definition(kind, *character, definition);
+            }
+            HistoryEntry::TileSelection { before, after } => {
+                let tile_selection = match is_undo {
+                    true => before,
+                    false => after
+                };
+
+                *self.map_entity.object_mut() = *tile_selection.clone();
+            }
+            HistoryEntry::Layout { before, after } => {
+                let layout = match is_undo {
+                    true => before,
+                    false => after
+                };
+
+                self.apply_layout(layout);
+            }
+        }
+
+        self.is_dirty = true;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileChange {
+    pub coordinates: Coordinates,
+    /// The character before the change, None if there was no tile
+    pub before: Option<char>,
+    /// The character after the change, None if the tile was removed
+    pub after: Option<char>,
+    pub before_rotation: u8,
+    pub after_rotation: u8,
+}
+
+pub type Definition = MapObjectId<MeabyWeighted<MeabyParam>>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum DefinitionKind {
+    #[default]
+    Terrain,
+    Furniture,
+}
+
+/// A whole row or column of the map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapLine {
+    Row,
+    Column,
+}
+
+impl MapLine {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            MapLine::Row => "row",
+            MapLine::Column => "column",
+        };
+    }
+
+    /// Returns the index of the row or column which contains the coordinates
+    pub fn get_index(&self, coordinates: &Coordinates) -> i32 {
+        return match self {
+            MapLine::Row => coordinates.y,
+            MapLine::Column => coordinates.x,
+        };
+    }
+
+    /// Returns the coordinates of the tile at the position inside of the row or column at the index
+    fn get_coordinates(&self, index: i32, position: i32) -> Coordinates {
+        return match self {
+            MapLine::Row => Coordinates::new(position, index),
+            MapLine::Column => Coordinates::new(index, position),
+        };
+    }
+
+    /// The amount of rows or columns of the map
+    fn get_count(&self, size: IVec2) -> i32 {
+        return match self {
+            MapLine::Row => size.y,
+            MapLine::Column => size.x,
+        };
+    }
+
+    /// The amount of tiles in a single row or column
+    fn get_length(&self, size: IVec2) -> i32 {
+        return match self {
+            MapLine::Row => size.x,
+            MapLine::Column => size.y,
+        };
+    }
+
+    fn moved(&self, coordinates: &Coordinates, amount: i32) -> Coordinates {
+        return match self {
+            MapLine::Row => Coordinates::new(coordinates.x, coordinates.y + amount),
+            MapLine::Column => Coordinates::new(coordinates.x + amount, coordinates.y),
+        };
+    }
+
+    /// Returns the coordinate of the place_loot entry which runs across the rows or columns
+    fn get_range_mut<'a>(&self, place_loot: &'a mut PlaceLoot) -> &'a mut MeabyRange {
+        return match self {
+            MapLine::Row => &mut place_loot.y,
+            MapLine::Column => &mut place_loot.x,
+        };
+    }
+
+    fn resized(&self, size: IVec2, amount: i32) -> IVec2 {
+        return match self {
+            MapLine::Row => IVec2::new(size.x, size.y + amount),
+            MapLine::Column => IVec2::new(size.x + amount, size.y),
+        };
+    }
+}
+
+/// All tiles of the map together with its size and everything else which is placed at coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapLayout {
+    pub tiles: HashMap<Coordinates, Tile>,
+    pub size: IVec2,
+    pub place_loot: Vec<PlaceLoot>,
+}
+
+#[derive(Debug, Clone)]
+pub enum HistoryEntry {
+    /// Tiles that were changed in a single step, like one brush stroke
+    Tiles(Vec<TileChange>),
+    /// A terrain or furniture definition of a character which was added, changed or removed
+    Definition {
+        kind: DefinitionKind,
+        character: char,
+        /// The definition before the change, None if the character was not defined
+        before: Option<Definition>,
+        /// The definition after the change, None if the definition was removed
+        after: Option<Definition>,
+    },
+    /// All definitions, palettes and parameters of the map, for changes which touch many of them at once
+    TileSelection {
+        before: Box<TileSelection>,
+        after: Box<TileSelection>,
+    },
+    /// All tiles and the size of the map, for changes which move the tiles like inserting a row
+    Layout {
+        before: Box<MapLayout>,
+        after: Box<MapLayout>,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    /// The oldest entry is in the front, it is dropped once there are too many entries
+    undo_stack: VecDeque<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+}
+
+impl History {
+    const MAX_ENTRIES: usize = 100;
+
+    /// Adds a step that can be undone. Everything that could be redone is discarded
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.undo_stack.len() >= Self::MAX_ENTRIES {
+            self.undo_stack.pop_front();
+        }
+
+        self.undo_stack.push_back(entry);
+        self.redo_stack.clear();
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -26,6 +561,16 @@ pub enum ProjectSaveState {
     #[default] NotSaved,
 }
 
+impl ProjectSaveState {
+    /// Returns the path of the saved or auto saved file
+    pub fn path(&self) -> Option<&PathBuf> {
+        return match self {
+            ProjectSaveState::Saved(p) | ProjectSaveState::AutoSaved(p) => Some(p),
+            ProjectSaveState::NotSaved => None
+        };
+    }
+}
+
 #[derive(Event)]
 pub struct OpenProjectAtIndex {
     pub index: u32,
@@ -39,4 +584,218 @@ pub struct CreateProject {
 #[derive(Event)]
 pub struct CloseProject {}
 
+/// Moves the Project and its tab to another position
+#[derive(Event)]
+pub struct MoveProject {
+    pub from: usize,
+    pub to: usize,
+}
+
+/// Removes the Project at the index from the Program together with its tab
+#[derive(Event)]
+pub struct RemoveProject {
+    pub index: usize,
+}
+
+#[derive(Event)]
+pub struct ReloadProject {
+    pub index: usize,
+}
+
+/// Periodically checks if the file of the opened Project was changed outside of the editor
+#[derive(Resource)]
+pub struct ProjectFileWatcher {
+    pub timer: Timer,
+    pub last_modified: HashMap<PathBuf, SystemTime>,
+}
+
+fn validate_om_terrain(om_terrain: &str) -> Result<&str, String> {
+    let om_terrain = om_terrain.trim();
+
+    if om_terrain.is_empty() {
+        return Err("The id can not be empty".into());
+    }
 
+    if !om_terrain.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("The id can only contain letters, digits and underscores".into());
+    }
+
+    return Ok(om_terrain);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use bevy::prelude::{IVec2, Vec2};
+    use serde_json::Map;
+
+    use crate::common::{Coordinates, MeabyWeighted};
+    use crate::map::data::{MapEntity, MeabyRange, Multi, MultiOmTerrain, PlaceLoot, Single};
+    use crate::palettes::data::{MapObjectId, MeabyParam};
+    use crate::project::data::{Bookmark, DefinitionKind, HistoryEntry, MapLine, Project, ProjectView, TileChange};
+    use crate::tiles::data::Tile;
+
+    #[test]
+    pub fn test_restored_view_is_clamped_to_resized_map() {
+        let view = ProjectView { offset: Vec2::new(3000., -50.), tile_size: 32. };
+
+        // The map shrunk from 100x100 to 24x24 tiles
+        let clamped = view.clamped(Vec2::new(24., 24.));
+
+        assert_eq!(clamped.offset, Vec2::new(768., -50.));
+        assert_eq!(clamped.tile_size, 32.);
+    }
+
+    #[test]
+    pub fn test_rename_om_terrain_validates_the_id() {
+        let mut project = Project::default();
+
+        assert!(project.set_om_terrain("  ").is_err());
+        assert!(project.set_om_terrain("house 1").is_err());
+
+        assert_eq!(project.set_om_terrain("house_01"), Ok(()));
+        assert_eq!(project.name, "house_01");
+
+        match &project.map_entity {
+            MapEntity::Single(s) => assert_eq!(s.om_terrain, "house_01"),
+            _ => panic!("Expected a single map")
+        }
+    }
+
+    #[test]
+    pub fn test_rename_om_terrain_of_multi_map() {
+        let mut project = Project::default();
+        project.name = "house".into();
+        project.map_entity = MapEntity::Multi(Multi {
+            om_terrain: MultiOmTerrain::Grid(vec![vec!["house_north".into()], vec!["house_south".into()]]),
+            ..Default::default()
+        });
+
+        assert!(project.set_multi_om_terrain(2, "house_east").is_err());
+        assert!(project.set_multi_om_terrain(1, "house east").is_err());
+
+        assert_eq!(project.set_multi_om_terrain(1, "house_east"), Ok(()));
+        assert_eq!(project.name, "house");
+
+        match &project.map_entity {
+            MapEntity::Multi(m) => assert_eq!(m.om_terrain, MultiOmTerrain::Grid(vec![vec!["house_north".into()], vec!["house_east".into()]])),
+            _ => panic!("Expected a multi map")
+        }
+    }
+
+    #[test]
+    pub fn test_undo_and_redo_tile_change() {
+        let mut project = Project::default();
+        let coordinates = Coordinates::new(0, 0);
+
+        project.map_entity.tiles_mut().insert(coordinates.clone(), Tile::with_rotation('#', 2));
+        project.history.push(HistoryEntry::Tiles(vec![
+            TileChange { coordinates: coordinates.clone(), before: Some(' '), after: Some('#'), before_rotation: 0, after_rotation: 2 }
+        ]));
+
+        assert!(project.undo());
+        assert_eq!(project.map_entity.tiles().get(&coordinates).unwrap().character, ' ');
+        assert!(!project.undo());
+
+        assert!(project.redo());
+        assert_eq!(project.map_entity.tiles().get(&coordinates).unwrap().character, '#');
+        assert_eq!(project.map_entity.tiles().get(&coordinates).unwrap().rotation, 2);
+        assert!(!project.redo());
+    }
+
+    #[test]
+    pub fn test_undo_and_redo_definition_removal() {
+        let mut project = Project::default();
+
+        project.map_entity.object_mut().terrain.insert(
+            '.',
+            MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_grass".into()))),
+        );
+
+        project.set_definition(DefinitionKind::Terrain, '.', None);
+        assert!(project.map_entity.object().terrain.get(&'.').is_none());
+
+        assert!(project.undo());
+        assert!(project.map_entity.object().terrain.get(&'.').is_some());
+
+        assert!(project.redo());
+        assert!(project.map_entity.object().terrain.get(&'.').is_none());
+    }
+
+    #[test]
+    pub fn test_undo_furniture_removal_keeps_terrain() {
+        let mut project = Project::default();
+        let grass = MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_grass".into())));
+        let chair = MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("f_chair".into())));
+
+        project.map_entity.object_mut().terrain.insert('c', grass);
+        project.map_entity.object_mut().furniture.insert('c', chair);
+
+        project.set_definition(DefinitionKind::Furniture, 'c', None);
+        assert!(project.map_entity.object().furniture.get(&'c').is_none());
+        assert!(project.map_entity.object().terrain.get(&'c').is_some());
+
+        assert!(project.undo());
+        assert!(project.map_entity.object().furniture.get(&'c').is_some());
+        assert!(project.map_entity.object().terrain.get(&'c').is_some());
+    }
+
+    #[test]
+    pub fn test_next_bookmark_wraps_around() {
+        let mut project = Project::default();
+        assert_eq!(project.get_next_bookmark_index(None), None);
+
+        for name in ["West", "East"] {
+            project.bookmarks.push(Bookmark { name: name.into(), offset: Vec2::ZERO, tile_size: 32. });
+        }
+
+        assert_eq!(project.get_next_bookmark_index(None), Some(0));
+        assert_eq!(project.get_next_bookmark_index(Some(0)), Some(1));
+        assert_eq!(project.get_next_bookmark_index(Some(1)), Some(0));
+    }
+
+    #[test]
+    pub fn test_insert_and_delete_lines() {
+        let mut project = Project::default();
+        project.map_entity = MapEntity::Single(Single { size: IVec2::new(3, 2), ..Default::default() });
+
+        for (y, row) in ["abc", "def"].iter().enumerate() {
+            for (x, character) in row.chars().enumerate() {
+                project.map_entity.tiles_mut().insert(Coordinates::new(x as i32, y as i32), Tile::from(character));
+            }
+        }
+
+        let place_loot = vec![
+            PlaceLoot { x: MeabyRange::Single(2), y: MeabyRange::Single(1), other: Map::new() },
+            PlaceLoot { x: MeabyRange::Range([0, 2]), y: MeabyRange::Single(0), other: Map::new() },
+        ];
+        project.map_entity.object_mut().place_loot = place_loot.clone();
+
+        let character_at = |project: &Project, x: i32, y: i32| project.map_entity.tiles().get(&Coordinates::new(x, y)).map(|t| t.character);
+
+        assert!(project.insert_line(MapLine::Column, 4).is_err());
+        assert_eq!(project.insert_line(MapLine::Column, 1), Ok(()));
+        assert_eq!(project.map_entity.size(), Vec2::new(4., 2.));
+        assert_eq!(character_at(&project, 0, 1), Some('d'));
+        assert_eq!(character_at(&project, 1, 1), Some(' '));
+        assert_eq!(character_at(&project, 2, 1), Some('e'));
+        assert_eq!(project.map_entity.object().place_loot[0].x, MeabyRange::Single(3));
+        assert_eq!(project.map_entity.object().place_loot[1].x, MeabyRange::Range([0, 3]));
+
+        assert_eq!(project.delete_line(MapLine::Row, 0), Ok(()));
+        assert_eq!(project.map_entity.size(), Vec2::new(4., 1.));
+        assert_eq!(character_at(&project, 3, 0), Some('f'));
+        assert_eq!(character_at(&project, 0, 1), None);
+        // The loot which could only be placed in the deleted row is removed
+        assert_eq!(project.map_entity.object().place_loot.len(), 1);
+        assert_eq!(project.map_entity.object().place_loot[0].y, MeabyRange::Single(0));
+        assert!(project.delete_line(MapLine::Row, 0).is_err());
+
+        // Every operation is a single step
+        assert!(project.undo());
+        assert!(project.undo());
+        assert_eq!(project.map_entity.size(), Vec2::new(3., 2.));
+        assert_eq!(character_at(&project, 1, 1), Some('e'));
+        assert_eq!(project.map_entity.tiles().len(), 6);
+        assert_eq!(project.map_entity.object().place_loot, place_loot);
+    }
+}
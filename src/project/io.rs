@@ -1,14 +1,19 @@
+use std::cell::RefCell;
 use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
+use anyhow::anyhow;
 use directories::ProjectDirs;
 
-use crate::common::io::{Load, LoadError, Save, SaveError};
+use crate::common::io::{Load, LoadError, Save, SaveError, write_json_file};
 use crate::common::io::LoadError::NoAutoSave;
 use crate::map::data::MapEntity;
+use crate::map::io::{MapEntityLoader, MapgenKind, read_mapgen_file};
+use crate::program::data::CDDAData;
 use crate::project::data::Project;
+use crate::settings::data::RowSizeMismatch;
 
 pub struct ProjectSaver {
     pub directory: Box<Path>,
@@ -28,24 +33,51 @@ impl Save<Project> for ProjectSaver {
     fn save(&self, value: &Project) -> Result<(), SaveError> {
         let filename = match &value.map_entity {
             MapEntity::Single(s) => s.om_terrain.clone(),
-            _ => todo!()
+            MapEntity::Nested(n) => n.get_name(),
+            MapEntity::Multi(_) => value.name.clone()
         };
 
         let filename = format!("auto_save_{}.map", filename);
 
-        let mut file = match File::options()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(self.directory.join(filename)) {
-            Ok(f) => f,
-            Err(e) => return Err(SaveError::InvalidPath(e.into()))
-        };
+        return write_json_file(&self.directory.join(filename), value);
+    }
+}
 
-        file.write_all(serde_json::to_string(value).unwrap().as_bytes()).unwrap();
+/// Returns the path with the suffix appended to its file name, `map.json` becomes `map.json.bak`
+fn get_path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", suffix));
+    return PathBuf::from(name);
+}
+
+/// Saves the Project at the path.
+/// The Project is written to a temporary file which replaces the file at the path once it was written completely,
+/// so a failed save can't corrupt an existing file. The replaced file is kept as a `.bak` copy
+pub fn save_project_to_path(project: &Project, path: &Path) -> Result<(), SaveError> {
+    let project_json = match serde_json::to_string(project) {
+        Ok(j) => j,
+        Err(e) => return Err(SaveError::Other(e.into()))
+    };
 
-        return Ok(());
+    let temp_path = get_path_with_suffix(path, "tmp");
+
+    if let Err(e) = fs::write(&temp_path, project_json) {
+        return Err(SaveError::Io(temp_path, e));
     }
+
+    if path.exists() {
+        if let Err(e) = fs::copy(path, get_path_with_suffix(path, "bak")) {
+            fs::remove_file(&temp_path).ok();
+            return Err(SaveError::Io(path.to_path_buf(), e));
+        }
+    }
+
+    if let Err(e) = fs::rename(&temp_path, path) {
+        fs::remove_file(&temp_path).ok();
+        return Err(SaveError::Io(path.to_path_buf(), e));
+    }
+
+    return Ok(());
 }
 
 pub struct ProjectAutoSaveLoader {
@@ -62,7 +94,9 @@ impl ProjectAutoSaveLoader {
 
         let auto_save_dir = dir.data_local_dir();
 
-        if !auto_save_dir.exists() { fs::create_dir_all(auto_save_dir).unwrap(); }
+        if let Err(e) = fs::create_dir_all(auto_save_dir) {
+            return Err(LoadError::Io(auto_save_dir.to_path_buf(), e));
+        }
 
         return Ok(Self {
             directory: auto_save_dir.into(),
@@ -77,14 +111,186 @@ impl Load<Project> for ProjectAutoSaveLoader {
             return Err(LoadError::DirectoryNotFound);
         }
 
-        let mut file = match File::open(self.directory.join(format!("auto_save_{}.map", self.map_name))) {
+        let path = self.directory.join(format!("auto_save_{}.map", self.map_name));
+
+        let mut file = match File::open(&path) {
             Ok(f) => f,
             Err(_) => return Err(NoAutoSave)
         };
 
         let mut contents = Vec::new();
-        file.read_to_end(&mut contents).unwrap();
+        if let Err(e) = file.read_to_end(&mut contents) {
+            return Err(LoadError::Io(path, e));
+        }
+
+        let mut project: Project = match serde_json::from_slice(contents.as_slice()) {
+            Ok(p) => p,
+            Err(e) => return Err(LoadError::Other(e.into()))
+        };
+
+        project.map_entity.verify_tiles()?;
+
+        return Ok(project);
+    }
+}
+
+/// Loads the Map Entity of the file which backs a saved Project.
+/// Mapgen json files are loaded with the [MapEntityLoader], all other files are treated as saved Projects
+pub struct ProjectFileLoader<'a> {
+    pub path: PathBuf,
+    pub om_terrain: String,
+    pub cdda_data: &'a CDDAData,
+    pub row_size_mismatch: RowSizeMismatch,
+    /// See [MapEntityLoader::variant]
+    pub variant: Option<usize>,
+    /// See [MapEntityLoader::warnings]
+    pub warnings: RefCell<Vec<String>>,
+}
+
+impl ProjectFileLoader<'_> {
+    /// Returns the warnings of the previous loads and clears them
+    pub fn take_warnings(&self) -> Vec<String> {
+        return self.warnings.take();
+    }
+}
+
+impl Load<MapEntity> for ProjectFileLoader<'_> {
+    fn load(&self) -> Result<MapEntity, LoadError> {
+        let mut map_entity = match self.path.extension().and_then(|e| e.to_str()) {
+            Some("json") => {
+                let loader = MapEntityLoader {
+                    path: self.path.clone(),
+                    id: self.om_terrain.clone(),
+                    cdda_data: self.cdda_data,
+                    row_size_mismatch: self.row_size_mismatch,
+                    variant: self.variant,
+                    warnings: RefCell::default(),
+                };
+
+                // The file is only read once, the shape of the mapgen decides which kind of map is loaded,
+                // so the error of that kind is returned instead of the error of whichever kind was tried last
+                let objects = read_mapgen_file(&self.path)?;
+
+                let map_entity = match loader.get_mapgen_kind(&objects) {
+                    None => return Err(LoadError::Other(anyhow!("No mapgen with the id {} was found in {:?}", self.om_terrain, self.path))),
+                    Some(MapgenKind::Single) => MapEntity::Single(loader.load_single(objects)?),
+                    Some(MapgenKind::Multi) => MapEntity::Multi(loader.load_multi(objects)?),
+                    Some(MapgenKind::Nested) => MapEntity::Nested(loader.load_nested(objects)?)
+                };
+
+                self.warnings.borrow_mut().extend(loader.take_warnings());
+
+                map_entity
+            }
+            _ => {
+                let contents = match fs::read(&self.path) {
+                    Ok(c) => c,
+                    Err(e) => return Err(LoadError::Io(self.path.clone(), e))
+                };
+
+                match serde_json::from_slice::<Project>(contents.as_slice()) {
+                    Ok(p) => p.map_entity,
+                    Err(e) => return Err(LoadError::Other(e.into()))
+                }
+            }
+        };
+
+        map_entity.verify_tiles()?;
+
+        return Ok(map_entity);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::fs;
+
+    use crate::common::io::{Load, SaveError};
+    use crate::map::data::MapEntity;
+    use crate::program::data::CDDAData;
+    use crate::project::data::Project;
+    use crate::project::io::{ProjectFileLoader, save_project_to_path};
+    use crate::settings::data::RowSizeMismatch;
+
+    #[test]
+    pub fn test_mapgen_file_is_loaded_by_the_shape_of_its_om_terrain() {
+        let directory = std::env::temp_dir().join(format!("cdda_map_editor_test_mapgen_shape_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("mapgen.json");
+
+        fs::write(&path, r#"[
+            { "type": "mapgen", "method": "json", "om_terrain": "single", "object": { "rows": [ ".." ] } },
+            { "type": "mapgen", "method": "json", "om_terrain": "no_rows", "object": { } },
+            { "type": "mapgen", "method": "json", "om_terrain": [ [ "west", "east" ] ], "object": { "rows": [ "." ] } },
+            { "type": "mapgen", "method": "json", "nested_mapgen_id": "chunk", "object": { "mapgensize": [ 1, 1 ], "rows": [ "." ] } }
+        ]"#).unwrap();
+
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let load = |om_terrain: &str| ProjectFileLoader {
+            path: path.clone(),
+            om_terrain: om_terrain.into(),
+            cdda_data: &cdda_data,
+            row_size_mismatch: RowSizeMismatch::default(),
+            variant: Some(0),
+            warnings: RefCell::default(),
+        }.load();
+
+        assert!(matches!(load("single"), Ok(MapEntity::Single(_))));
+        assert!(matches!(load("chunk"), Ok(MapEntity::Nested(_))));
+        assert!(matches!(load("east"), Ok(MapEntity::Multi(_))));
+
+        // The error of the single mapgen is kept instead of the error of the nested mapgen which was tried last
+        assert_eq!(load("no_rows").unwrap_err().to_string(), "The mapgen no_rows has no rows");
+        assert_eq!(load("missing").unwrap_err().to_string(), format!("No mapgen with the id missing was found in {:?}", path));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    pub fn test_overwrite_keeps_backup() {
+        let directory = std::env::temp_dir().join(format!("cdda_map_editor_test_overwrite_keeps_backup_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("project.map");
+
+        fs::write(&path, "old contents").unwrap();
+
+        let mut project = Project::default();
+        project.name = "Overwritten".into();
+        save_project_to_path(&project, &path).unwrap();
+
+        let saved: Project = serde_json::from_str(fs::read_to_string(&path).unwrap().as_str()).unwrap();
+        assert_eq!(saved.name, "Overwritten");
+        assert_eq!(fs::read_to_string(directory.join("project.map.bak")).unwrap(), "old contents");
+        assert!(!directory.join("project.map.tmp").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    pub fn test_read_only_project_backup_is_a_save_error() {
+        let directory = std::env::temp_dir().join(format!("cdda_map_editor_test_read_only_project_backup_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("project.map");
+        let backup_path = directory.join("project.map.bak");
+
+        fs::write(&path, "old contents").unwrap();
+        fs::write(&backup_path, "old backup").unwrap();
+        let mut permissions = fs::metadata(&backup_path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&backup_path, permissions).unwrap();
+
+        // The backup can not be replaced, so the Project must not be overwritten either
+        match save_project_to_path(&Project::default(), &path) {
+            Err(SaveError::Io(error_path, _)) => assert_eq!(error_path, path),
+            result => panic!("Expected an Io SaveError but got {:?}", result)
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old contents");
+        assert_eq!(fs::read_to_string(&backup_path).unwrap(), "old backup");
+        assert!(!directory.join("project.map.tmp").exists());
 
-        return Ok(serde_json::from_slice(contents.as_slice()).unwrap());
+        fs::remove_dir_all(&directory).unwrap();
     }
 }
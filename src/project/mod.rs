@@ -1,6 +1,6 @@
-pub(crate) mod io;
-pub(crate) mod systems;
-pub(crate) mod data;
-pub(crate) mod plugin;
+pub mod io;
+pub mod systems;
+pub mod data;
+pub mod plugin;
 
 
@@ -1,9 +1,11 @@
+use std::collections::HashMap;
+
 use bevy::app::App;
-use bevy::prelude::{apply_deferred, in_state, IntoSystemConfigs, Plugin, Update};
+use bevy::prelude::{apply_deferred, in_state, IntoSystemConfigs, Plugin, Timer, TimerMode, Update};
 use crate::map::systems::spawn_map_entity_reader;
 use crate::program::data::ProgramState;
-use crate::project::data::{CloseProject, CreateProject, OpenProjectAtIndex};
-use crate::project::systems::{close_project, create_project, open_project};
+use crate::project::data::{CloseProject, CreateProject, MoveProject, OpenProjectAtIndex, ProjectFileWatcher, ReloadProject, RemoveProject};
+use crate::project::systems::{close_project, create_project, move_project, open_project, reload_project, remove_projects, undo_redo_system, watch_project_file};
 
 pub struct ProjectPlugin;
 
@@ -12,13 +14,26 @@ impl Plugin for ProjectPlugin {
         app.add_event::<OpenProjectAtIndex>();
         app.add_event::<CloseProject>();
         app.add_event::<CreateProject>();
+        app.add_event::<ReloadProject>();
+        app.add_event::<RemoveProject>();
+        app.add_event::<MoveProject>();
+
+        app.insert_resource(ProjectFileWatcher {
+            timer: Timer::from_seconds(1., TimerMode::Repeating),
+            last_modified: HashMap::new(),
+        });
 
         app.add_systems(
             Update,
             (
                 open_project,
+                remove_projects,
+                move_project,
                 close_project,
-                create_project
+                create_project,
+                watch_project_file,
+                reload_project,
+                undo_redo_system
             ).chain(),
         );
     }
@@ -1,33 +1,74 @@
+use std::cell::RefCell;
+use std::fs;
 use std::sync::Arc;
 
-use bevy::prelude::{Commands, Entity, EventReader, EventWriter, NextState, Query, ResMut, With};
+use bevy::input::ButtonInput;
+use bevy::prelude::{BuildChildren, Commands, DespawnRecursiveExt, Entity, EventReader, EventWriter, KeyCode, NextState, Query, Res, ResMut, Time, With};
+use log::info;
 
-use crate::map::data::{ClearTiles, SpawnMapEntity};
-use crate::program::data::{OpenedProject, Program, ProgramState};
-use crate::project::data::{CloseProject, CreateProject, OpenProjectAtIndex};
+use crate::common::io::Load;
+use crate::common::LogMessage;
+use crate::map::data::{ClearTiles, MapEntity, SpawnMapEntity};
+use crate::map::systems::despawn_if_exists;
+use crate::program::data::{Keybinds, OpenedProject, Program, ProgramState};
+use crate::project::data::{CloseProject, CreateProject, MoveProject, OpenProjectAtIndex, ProjectFileWatcher, ProjectSaveState, ReloadProject, RemoveProject};
+use crate::project::io::ProjectFileLoader;
+use crate::settings::data::Settings;
+use crate::tiles::data::{InteractionMode, ProjectDiffData, Selection};
 use crate::ui::grid::resources::Grid;
+use crate::ui::close_prompt::ClosePromptData;
+use crate::ui::error_dialog::ShowError;
+use crate::ui::overwrite_prompt::OverwritePromptData;
+use crate::ui::reload_prompt::{ReloadPromptData, ReloadReason};
+use crate::ui::tabs::components::{Tab, TabContainerMarker, TabNameMarker};
+use crate::ui::tabs::events::SpawnTab;
 
 pub fn open_project(
     mut e_open_project: EventReader<OpenProjectAtIndex>,
     mut e_clear_tiles: EventWriter<ClearTiles>,
     mut e_spawn_map_entity: EventWriter<SpawnMapEntity>,
     mut r_program: ResMut<Program>,
+    mut r_grid: ResMut<Grid>,
     mut s_next: ResMut<NextState<ProgramState>>,
     mut commands: Commands,
-    q_opened_project: Query<Entity, With<OpenedProject>>,
+    mut e_show_error: EventWriter<ShowError>,
+    q_opened_project: Query<(Entity, &OpenedProject)>,
 ) {
     for switch_project in e_open_project.read() {
-        let new_project = r_program.projects.get(switch_project.index as usize).unwrap();
+        // The opened Project is kept if the Project to open does not exist
+        if r_program.projects.get(switch_project.index as usize).is_none() {
+            e_show_error.send(ShowError::new("Could not open the Project", format!("There is no Project at index {}", switch_project.index)));
+            continue;
+        }
 
         s_next.set(ProgramState::ProjectOpen);
 
-        if let Some(o) = q_opened_project.iter().next() {
+        if let Some((entity, opened)) = q_opened_project.iter().next() {
+            // Remember where the previous Project was left off
+            if let Some(previous) = r_program.projects.get_mut(opened.index) {
+                previous.view = Some(r_grid.get_view());
+            }
+
             // Despawn the already existing entity
-            commands.get_entity(o).unwrap().despawn();
+            if let Some(mut entity) = commands.get_entity(entity) {
+                entity.despawn();
+            }
+        }
+
+        let new_project = match r_program.projects.get(switch_project.index as usize) {
+            None => continue,
+            Some(p) => p
+        };
+
+        if let Some(view) = &new_project.view {
+            r_grid.apply_view(&view.clamped(new_project.map_entity.size()));
         }
 
         commands.spawn(OpenedProject { index: switch_project.index as usize });
 
+        // The selection belongs to the previously opened Project
+        commands.insert_resource(Selection::default());
+
         e_clear_tiles.send(ClearTiles {});
 
         e_spawn_map_entity.send(SpawnMapEntity {
@@ -36,15 +77,165 @@ pub fn open_project(
     }
 }
 
+/// Adds the created Projects and spawns their tabs.
+/// The tab gets the index at which the Project was actually stored, so multiple Projects can be created in one frame
 pub fn create_project(
     mut e_create_project: EventReader<CreateProject>,
+    mut e_spawn_tab: EventWriter<SpawnTab>,
     mut r_program: ResMut<Program>,
 ) {
     for e in e_create_project.read() {
-        r_program.projects.push(e.project.clone());
+        let index = r_program.add_project(e.project.clone());
+
+        e_spawn_tab.send(SpawnTab {
+            name: e.project.name.clone(),
+            index: index as u32,
+        });
+    }
+}
+
+/// Removes the Projects and their tabs.
+/// Everything that stores the index of a Project is shifted, so it keeps pointing at the same Project.
+/// If the opened Project is removed, it is closed instead
+pub fn remove_projects(
+    mut e_remove_project: EventReader<RemoveProject>,
+    mut e_close_project: EventWriter<CloseProject>,
+    mut r_program: ResMut<Program>,
+    mut commands: Commands,
+    mut q_tabs: Query<(Entity, &mut Tab)>,
+    mut q_tab_names: Query<&mut TabNameMarker>,
+    mut q_opened_project: Query<&mut OpenedProject>,
+    r_close_prompt_data: Option<ResMut<ClosePromptData>>,
+    r_reload_prompt_data: Option<ResMut<ReloadPromptData>>,
+    r_overwrite_prompt_data: Option<ResMut<OverwritePromptData>>,
+    r_diff_data: Option<ResMut<ProjectDiffData>>,
+) {
+    let mut removed: Vec<usize> = e_remove_project.read().map(|e| e.index).collect();
+
+    if removed.is_empty() { return; }
+
+    removed.sort();
+    removed.dedup();
+
+    for (entity, mut tab) in q_tabs.iter_mut() {
+        match Program::get_shifted_index(tab.index as usize, &removed) {
+            None => commands.entity(entity).despawn_recursive(),
+            Some(i) => tab.index = i as u32
+        }
+    }
+
+    for mut tab_name in q_tab_names.iter_mut() {
+        if let Some(i) = Program::get_shifted_index(tab_name.index as usize, &removed) {
+            tab_name.index = i as u32;
+        }
+    }
+
+    for mut opened_project in q_opened_project.iter_mut() {
+        match Program::get_shifted_index(opened_project.index, &removed) {
+            None => { e_close_project.send(CloseProject {}); }
+            Some(i) => opened_project.index = i
+        }
+    }
+
+    if let Some(mut prompt_data) = r_close_prompt_data {
+        match Program::get_shifted_index(prompt_data.index, &removed) {
+            None => commands.remove_resource::<ClosePromptData>(),
+            Some(i) => prompt_data.index = i
+        }
     }
+
+    if let Some(mut prompt_data) = r_reload_prompt_data {
+        match Program::get_shifted_index(prompt_data.index, &removed) {
+            None => commands.remove_resource::<ReloadPromptData>(),
+            Some(i) => prompt_data.index = i
+        }
+    }
+
+    if let Some(mut prompt_data) = r_overwrite_prompt_data {
+        match Program::get_shifted_index(prompt_data.index, &removed) {
+            None => commands.remove_resource::<OverwritePromptData>(),
+            Some(i) => prompt_data.index = i
+        }
+    }
+
+    if let Some(mut diff_data) = r_diff_data {
+        match Program::get_shifted_index(diff_data.index, &removed) {
+            None => commands.remove_resource::<ProjectDiffData>(),
+            Some(i) => diff_data.index = i
+        }
+    }
+
+    r_program.remove_projects(&removed);
+
+    info!("Removed {} Projects", removed.len());
 }
 
+/// Moves the Projects and reorders their tabs.
+/// Everything that stores the index of a Project is updated, so it keeps pointing at the same Project
+pub fn move_project(
+    mut e_move_project: EventReader<MoveProject>,
+    mut r_program: ResMut<Program>,
+    mut commands: Commands,
+    mut q_tabs: Query<(Entity, &mut Tab)>,
+    mut q_tab_names: Query<&mut TabNameMarker>,
+    mut q_opened_project: Query<&mut OpenedProject>,
+    mut r_close_prompt_data: Option<ResMut<ClosePromptData>>,
+    mut r_reload_prompt_data: Option<ResMut<ReloadPromptData>>,
+    mut r_overwrite_prompt_data: Option<ResMut<OverwritePromptData>>,
+    mut r_diff_data: Option<ResMut<ProjectDiffData>>,
+    q_tab_container: Query<Entity, With<TabContainerMarker>>,
+) {
+    let mut has_moved = false;
+
+    for e in e_move_project.read() {
+        if e.from == e.to || e.from >= r_program.projects.len() || e.to >= r_program.projects.len() { continue; }
+
+        for (_, mut tab) in q_tabs.iter_mut() {
+            tab.index = Program::get_moved_index(tab.index as usize, e.from, e.to) as u32;
+        }
+
+        for mut tab_name in q_tab_names.iter_mut() {
+            tab_name.index = Program::get_moved_index(tab_name.index as usize, e.from, e.to) as u32;
+        }
+
+        for mut opened_project in q_opened_project.iter_mut() {
+            opened_project.index = Program::get_moved_index(opened_project.index, e.from, e.to);
+        }
+
+        if let Some(prompt_data) = r_close_prompt_data.as_mut() {
+            prompt_data.index = Program::get_moved_index(prompt_data.index, e.from, e.to);
+        }
+
+        if let Some(prompt_data) = r_reload_prompt_data.as_mut() {
+            prompt_data.index = Program::get_moved_index(prompt_data.index, e.from, e.to);
+        }
+
+        if let Some(prompt_data) = r_overwrite_prompt_data.as_mut() {
+            prompt_data.index = Program::get_moved_index(prompt_data.index, e.from, e.to);
+        }
+
+        if let Some(diff_data) = r_diff_data.as_mut() {
+            diff_data.index = Program::get_moved_index(diff_data.index, e.from, e.to);
+        }
+
+        r_program.move_project(e.from, e.to);
+        has_moved = true;
+    }
+
+    if !has_moved { return; }
+
+    let container = match q_tab_container.iter().next() {
+        None => return,
+        Some(c) => c
+    };
+
+    // The tabs are displayed in the order of the children of the container
+    let mut tabs: Vec<(Entity, u32)> = q_tabs.iter().map(|(entity, tab)| (entity, tab.index)).collect();
+    tabs.sort_by_key(|(_, index)| *index);
+
+    let tabs: Vec<Entity> = tabs.into_iter().map(|(entity, _)| entity).collect();
+    commands.entity(container).insert_children(0, &tabs);
+}
 
 pub fn close_project(
     mut s_next: ResMut<NextState<ProgramState>>,
@@ -63,14 +254,321 @@ pub fn close_project(
         s_next.set(ProgramState::NoneOpen);
 
         commands.get_entity(entity).unwrap().despawn();
+        commands.insert_resource(Selection::default());
 
         e_clear_tiles.send(ClearTiles {});
 
         match r_grid.instantiated_grid {
             None => {}
-            Some(g) => { commands.get_entity(g).unwrap().despawn() }
+            Some(g) => { despawn_if_exists(&mut commands, g) }
         }
 
         r_grid.instantiated_grid = None;
     }
-}
\ No newline at end of file
+}
+
+pub fn watch_project_file(
+    mut r_watcher: ResMut<ProjectFileWatcher>,
+    mut e_reload_project: EventWriter<ReloadProject>,
+    mut commands: Commands,
+    r_program: Res<Program>,
+    r_settings: Res<Settings>,
+    r_time: Res<Time>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !r_settings.watch_project_files { return; }
+
+    if !r_watcher.timer.tick(r_time.delta()).just_finished() { return; }
+
+    let opened_project = match q_opened_project.iter().next() {
+        None => return,
+        Some(o) => o
+    };
+
+    let project = match r_program.projects.get(opened_project.index) {
+        None => return,
+        Some(p) => p
+    };
+
+    let path = match &project.save_state {
+        ProjectSaveState::Saved(path) => path,
+        _ => return
+    };
+
+    let modified = match fs::metadata(path).and_then(|m| m.modified()) {
+        Ok(m) => m,
+        Err(_) => return
+    };
+
+    match r_watcher.last_modified.insert(path.clone(), modified) {
+        // The file was not watched before
+        None => {}
+        Some(last_modified) if last_modified == modified => {}
+        Some(_) => {
+            match project.is_dirty {
+                true => commands.insert_resource(ReloadPromptData { index: opened_project.index, reason: ReloadReason::FileChanged }),
+                false => { e_reload_project.send(ReloadProject { index: opened_project.index }); }
+            }
+        }
+    }
+}
+
+pub fn reload_project(
+    mut e_reload_project: EventReader<ReloadProject>,
+    mut e_clear_tiles: EventWriter<ClearTiles>,
+    mut e_spawn_map_entity: EventWriter<SpawnMapEntity>,
+    mut e_show_error: EventWriter<ShowError>,
+    mut e_log: EventWriter<LogMessage>,
+    mut r_program: ResMut<Program>,
+    r_settings: Res<Settings>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let opened_project = match q_opened_project.iter().next() {
+        None => return,
+        Some(o) => o
+    };
+
+    let cdda_data = match r_program.config.cdda_data.clone() {
+        None => return,
+        Some(d) => d
+    };
+
+    for e in e_reload_project.read() {
+        // Only the opened Project has spawned tiles which can be replaced
+        if e.index != opened_project.index { continue; }
+
+        let project = match r_program.projects.get_mut(e.index) {
+            None => continue,
+            Some(p) => p
+        };
+
+        let path = match &project.save_state {
+            ProjectSaveState::Saved(path) => path.clone(),
+            _ => continue
+        };
+
+        let om_terrain = match &project.map_entity {
+            MapEntity::Single(s) => s.om_terrain.clone(),
+            MapEntity::Nested(n) => n.nested_mapgen_id.clone()
+                .or(n.om_terrain.first().cloned())
+                .unwrap_or(project.name.clone()),
+            MapEntity::Multi(m) => m.om_terrain.get_positioned().first().map(|(_, id)| id.to_string()).unwrap_or(project.name.clone())
+        };
+
+        // The same variant is reloaded instead of choosing another one by weight
+        let variant = match &project.map_entity {
+            MapEntity::Single(s) => Some(s.variant),
+            _ => None
+        };
+
+        let loader = ProjectFileLoader {
+            path: path.clone(),
+            om_terrain,
+            cdda_data: &cdda_data,
+            row_size_mismatch: r_settings.row_size_mismatch,
+            variant,
+            warnings: RefCell::default(),
+        };
+
+        let map_entity = match loader.load() {
+            Ok(m) => m,
+            Err(err) => {
+                let mut show_error = ShowError::load(format!("Could not reload the Project {}", project.name), &err);
+                show_error.path = show_error.path.or(Some(path.clone()));
+                e_show_error.send(show_error);
+                continue;
+            }
+        };
+
+        for warning in loader.take_warnings() {
+            e_log.send(LogMessage::warning(warning));
+        }
+
+        project.map_entity = map_entity;
+        // The parameters were rolled again by the loader
+        project.apply_parameter_overrides();
+        project.is_dirty = false;
+
+        e_clear_tiles.send(ClearTiles {});
+        e_spawn_map_entity.send(SpawnMapEntity {
+            map_entity: Arc::new(project.map_entity.clone())
+        });
+
+        info!("Reloaded Project {} from {:?}", project.name, path);
+    }
+}
+
+pub fn undo_redo_system(
+    mut e_clear_tiles: EventWriter<ClearTiles>,
+    mut e_spawn_map_entity: EventWriter<SpawnMapEntity>,
+    mut r_program: ResMut<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+    r_mode: Res<InteractionMode>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    // Undoing and redoing would change the map while it is only being viewed
+    if *r_mode == InteractionMode::Preview { return; }
+
+    let is_undo = Keybinds::is_just_pressed(&r_program.config.keybindings.undo, &r_keys);
+    let is_redo = Keybinds::is_just_pressed(&r_program.config.keybindings.redo, &r_keys);
+
+    if !is_undo && !is_redo { return; }
+
+    let opened_project = match q_opened_project.iter().next() {
+        None => return,
+        Some(o) => o
+    };
+
+    let project = match r_program.projects.get_mut(opened_project.index) {
+        None => return,
+        Some(p) => p
+    };
+
+    let has_changed = match is_undo {
+        true => project.undo(),
+        false => project.redo()
+    };
+
+    if !has_changed { return; }
+
+    // Respawn the whole map because a single step can change any amount of tiles
+    e_clear_tiles.send(ClearTiles {});
+    e_spawn_map_entity.send(SpawnMapEntity {
+        map_entity: Arc::new(project.map_entity.clone())
+    });
+}
+
+#[cfg(test)]
+pub mod tests {
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{NextState, Vec2, World};
+
+    use crate::map::data::{ClearTiles, SpawnMapEntity};
+    use crate::program::data::{OpenedProject, Program, ProgramState};
+    use crate::project::data::{CloseProject, CreateProject, MoveProject, OpenProjectAtIndex, Project, RemoveProject};
+    use crate::project::systems::{create_project, move_project, open_project, remove_projects};
+    use crate::ui::error_dialog::ShowError;
+    use crate::ui::grid::resources::Grid;
+    use crate::ui::tabs::components::{Tab, TabNameMarker};
+    use crate::ui::tabs::events::SpawnTab;
+
+    #[test]
+    pub fn test_create_two_projects_in_one_update() {
+        let mut world = World::new();
+
+        world.insert_resource(Program::new(vec![Project::default()], vec![]));
+        world.init_resource::<Events<CreateProject>>();
+        world.init_resource::<Events<SpawnTab>>();
+
+        for name in ["first", "second"] {
+            world.send_event(CreateProject {
+                project: Project { name: name.into(), ..Default::default() }
+            });
+        }
+
+        world.run_system_once(create_project);
+
+        let events = world.resource::<Events<SpawnTab>>();
+        let tabs: Vec<&SpawnTab> = events.get_reader().read(events).collect();
+        let program = world.resource::<Program>();
+
+        assert_eq!(tabs.len(), 2);
+
+        for tab in tabs {
+            assert_eq!(program.projects.get(tab.index as usize).unwrap().name, tab.name);
+        }
+
+        assert_eq!(program.projects.get(2).unwrap().name, "second");
+    }
+
+    #[test]
+    pub fn test_remove_first_of_three_projects() {
+        let mut world = World::new();
+        let names = ["first", "second", "third"];
+
+        let projects = names.iter().map(|n| Project { name: n.to_string(), ..Default::default() }).collect();
+        world.insert_resource(Program::new(projects, vec![]));
+        world.init_resource::<Events<RemoveProject>>();
+        world.init_resource::<Events<CloseProject>>();
+
+        let tabs: Vec<_> = (0..3)
+            .map(|i| world.spawn((Tab { index: i }, TabNameMarker { index: i })).id())
+            .collect();
+        world.spawn(OpenedProject { index: 2 });
+
+        world.send_event(RemoveProject { index: 0 });
+        world.run_system_once(remove_projects);
+
+        assert!(world.get_entity(tabs[0]).is_none());
+
+        for (tab, name) in tabs.iter().zip(names.iter()).skip(1) {
+            let index = world.get::<Tab>(*tab).unwrap().index as usize;
+            let name_index = world.get::<TabNameMarker>(*tab).unwrap().index as usize;
+
+            assert_eq!(index, name_index);
+            assert_eq!(world.resource::<Program>().projects.get(index).unwrap().name, *name);
+        }
+
+        let opened_index = world.query::<&OpenedProject>().single(&world).index;
+        assert_eq!(world.resource::<Program>().projects.get(opened_index).unwrap().name, "third");
+        assert!(world.resource::<Events<CloseProject>>().is_empty());
+    }
+
+    #[test]
+    pub fn test_move_opened_project_to_the_end() {
+        let mut world = World::new();
+        let names = ["first", "second", "third"];
+
+        let projects = names.iter().map(|n| Project { name: n.to_string(), ..Default::default() }).collect();
+        world.insert_resource(Program::new(projects, vec![]));
+        world.init_resource::<Events<MoveProject>>();
+
+        let tabs: Vec<_> = (0..3)
+            .map(|i| world.spawn((Tab { index: i }, TabNameMarker { index: i })).id())
+            .collect();
+        world.spawn(OpenedProject { index: 0 });
+
+        world.send_event(MoveProject { from: 0, to: 2 });
+        world.run_system_once(move_project);
+
+        for (tab, name) in tabs.iter().zip(names.iter()) {
+            let index = world.get::<Tab>(*tab).unwrap().index as usize;
+            assert_eq!(world.resource::<Program>().projects.get(index).unwrap().name, *name);
+        }
+
+        let opened_index = world.query::<&OpenedProject>().single(&world).index;
+        assert_eq!(opened_index, 2);
+        assert_eq!(world.resource::<Program>().projects.get(opened_index).unwrap().name, "first");
+    }
+
+    #[test]
+    pub fn test_open_missing_project_shows_error() {
+        let mut world = World::new();
+
+        world.insert_resource(Program::new(vec![Project::default()], vec![]));
+        world.insert_resource(Grid {
+            tile_size: 32.,
+            default_tile_size: 32.,
+            offset: Vec2::ZERO,
+            min_zoom: 6.,
+            max_zoom: 128.,
+            instantiated_grid: None,
+        });
+        world.init_resource::<NextState<ProgramState>>();
+        world.init_resource::<Events<OpenProjectAtIndex>>();
+        world.init_resource::<Events<ClearTiles>>();
+        world.init_resource::<Events<SpawnMapEntity>>();
+        world.init_resource::<Events<ShowError>>();
+
+        let opened = world.spawn(OpenedProject { index: 0 }).id();
+
+        world.send_event(OpenProjectAtIndex { index: 3 });
+        world.run_system_once(open_project);
+
+        assert_eq!(world.resource::<Events<ShowError>>().len(), 1);
+        assert!(world.resource::<Events<SpawnMapEntity>>().is_empty());
+        assert_eq!(world.query::<&OpenedProject>().single(&world).index, 0);
+        assert!(world.get_entity(opened).is_some());
+    }
+}
@@ -6,6 +6,9 @@ use crate::common::{GetRandom, TileId, Weighted};
 
 type RegionId = String;
 
+/// The region which is covered with the default groundcover if the region settings do not define it
+pub const REGION_GROUNDCOVER_ID: &'static str = "t_region_groundcover";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TerrainAndFurniture {
     // Example "t_region_groundcover": { "t_grass": 12000, "t_grass_dead": 2000, "t_dirt": 1000 },
@@ -56,4 +59,68 @@ impl RegionSettings {
 
         return None;
     }
+
+    /// Picks a random terrain of the default groundcover, which is used for terrain that was not filled by the mapgen.
+    /// Returns None if the region has no default groundcover
+    pub fn get_random_default_groundcover(&self) -> Option<&TileId> {
+        let picked = self.default_groundcover.get_random_weighted()?;
+
+        return match self.get_random_terrain_from_region(picked) {
+            Some(t) => Some(t),
+            None => Some(picked)
+        };
+    }
+
+    /// Picks a random terrain for a fill terrain which is a region id.
+    /// Returns None if the id is not a region id
+    pub fn get_random_terrain_from_fill(&self, id: &TileId) -> Option<&TileId> {
+        if let Some(terrain) = self.get_random_terrain_from_region(id) {
+            return Some(terrain);
+        }
+
+        return match id.as_str() == REGION_GROUNDCOVER_ID {
+            true => self.get_random_default_groundcover(),
+            false => None
+        };
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use crate::common::Weighted;
+    use crate::region_settings::data::{RegionSettings, TerrainAndFurniture};
+
+    #[test]
+    pub fn test_default_groundcover_resolves_regions() {
+        let mut groundcover = HashMap::new();
+        groundcover.insert("t_grass".to_string(), 1);
+
+        let mut terrain = HashMap::new();
+        terrain.insert("t_region_groundcover".to_string(), groundcover);
+
+        let region_settings = RegionSettings {
+            id: "default".into(),
+            default_oter: vec![],
+            default_groundcover: vec![Weighted::new("t_region_groundcover".to_string(), 1)],
+            region_terrain_and_furniture: TerrainAndFurniture { terrain, furniture: HashMap::new() },
+        };
+
+        assert_eq!(region_settings.get_random_default_groundcover(), Some(&"t_grass".to_string()));
+    }
+
+    #[test]
+    pub fn test_only_region_fill_terrain_is_replaced() {
+        let region_settings = RegionSettings {
+            id: "default".into(),
+            default_oter: vec![],
+            default_groundcover: vec![Weighted::new("t_dirt".to_string(), 1)],
+            region_terrain_and_furniture: TerrainAndFurniture { terrain: HashMap::new(), furniture: HashMap::new() },
+        };
+
+        // The region groundcover is not defined by the region settings, so the default groundcover is used
+        assert_eq!(region_settings.get_random_terrain_from_fill(&"t_region_groundcover".to_string()), Some(&"t_dirt".to_string()));
+        assert_eq!(region_settings.get_random_terrain_from_fill(&"t_floor".to_string()), None);
+    }
 }
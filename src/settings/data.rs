@@ -1,13 +1,263 @@
 use std::path::PathBuf;
 
-use bevy::prelude::Resource;
+use bevy::prelude::{IVec2, Resource};
+use bevy::window::WindowMode;
 use serde::{Deserialize, Serialize};
 
+use crate::common::{Coordinates, TileId};
+use crate::program::data::CDDAData;
+use crate::project::data::Project;
+
 #[derive(Debug, Resource, Clone, Serialize, Deserialize)]
 pub struct Settings {
     pub selected_cdda_dir: Option<PathBuf>,
     pub selectable_tilesets: Vec<String>,
     pub selected_tileset: Option<String>,
+
+    /// If the opened Project should be reloaded when its file changes on disk
+    #[serde(default)]
+    pub watch_project_files: bool,
+
+    /// If terrain without a background sprite should be filled with a solid color
+    #[serde(default)]
+    pub fill_missing_backgrounds: bool,
+
+    /// If a floor should be rendered beneath furniture which is placed on undefined terrain
+    #[serde(default)]
+    pub render_floor_under_furniture: bool,
+
+    /// If the background sprites of furniture are drawn, hiding them keeps the terrain beneath visible
+    #[serde(default = "default_render_furniture_backgrounds")]
+    pub render_furniture_backgrounds: bool,
+
+    /// The floor which is rendered beneath furniture if the map has no fill terrain
+    #[serde(default = "default_floor")]
+    pub default_floor: TileId,
+
+    /// If thicker guide lines should be drawn at the boundaries of overmap tiles
+    #[serde(default)]
+    pub show_overmap_guides: bool,
+
+    /// The amount of tiles between two overmap guide lines
+    #[serde(default = "default_overmap_guide_spacing")]
+    pub overmap_guide_spacing: u32,
+
+    /// If ids which are missing in the tileset are drawn with a checkerboard instead of the fallback of their character
+    #[serde(default = "default_show_missing_textures")]
+    pub show_missing_textures: bool,
+
+    /// The size which is prefilled when a new Project is created
+    #[serde(default = "default_map_size")]
+    pub default_map_size: IVec2,
+
+    /// The fill terrain which is prefilled when a new Project is created
+    #[serde(default)]
+    pub default_fill_ter: Option<TileId>,
+
+    /// If the window is shown as a borderless fullscreen window
+    #[serde(default)]
+    pub fullscreen: bool,
+
+    /// If a marker is drawn at the origin of the map with the directions in which the coordinates increase
+    #[serde(default = "default_show_origin_marker")]
+    pub show_origin_marker: bool,
+
+    /// Below this tile size the grid lines fade out until they are gone at the smallest zoom
+    #[serde(default = "default_grid_fade_start")]
+    pub grid_fade_start: f32,
+
+    /// The corner from which displayed coordinates are counted, the map itself always starts at the top left
+    #[serde(default)]
+    pub origin_corner: OriginCorner,
+
+    /// If characters without any terrain, furniture or item definition cannot be painted
+    #[serde(default)]
+    pub strict_painting: bool,
+
+    /// What happens with rows of an imported mapgen which do not match its declared size
+    #[serde(default)]
+    pub row_size_mismatch: RowSizeMismatch,
+
+    /// If every skipped or missing sprite of a loaded tileset is logged instead of only a summary
+    #[serde(default)]
+    pub log_tileset_details: bool,
+
+    /// The color of the fallback glyphs which are drawn for ids without a sprite
+    #[serde(default)]
+    pub fallback_color: FallbackColor,
+
+    /// If autotiled tiles like walls connect to the edge of the map or end there
+    #[serde(default)]
+    pub map_edge: MapEdge,
+}
+
+/// How autotiled tiles at the edge of the map treat the neighbors outside of the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MapEdge {
+    /// Nothing is outside of the map, so walls at the edge are drawn as end pieces
+    #[default]
+    Open,
+    /// The neighbors outside of the map are of the same type, for maps which continue in the maps next to them
+    Connected,
+}
+
+impl MapEdge {
+    pub const ALL: [MapEdge; 2] = [MapEdge::Open, MapEdge::Connected];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            MapEdge::Open => "Open",
+            MapEdge::Connected => "Connected",
+        };
+    }
+}
+
+/// Picks which of the colored glyphs of the fallback sprite sheet is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FallbackColor {
+    #[default]
+    White,
+    Red,
+    Green,
+    Blue,
+    Cyan,
+    Magenta,
+    Yellow,
+}
+
+impl FallbackColor {
+    pub const ALL: [FallbackColor; 7] = [
+        FallbackColor::White,
+        FallbackColor::Red,
+        FallbackColor::Green,
+        FallbackColor::Blue,
+        FallbackColor::Cyan,
+        FallbackColor::Magenta,
+        FallbackColor::Yellow,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            FallbackColor::White => "White",
+            FallbackColor::Red => "Red",
+            FallbackColor::Green => "Green",
+            FallbackColor::Blue => "Blue",
+            FallbackColor::Cyan => "Cyan",
+            FallbackColor::Magenta => "Magenta",
+            FallbackColor::Yellow => "Yellow",
+        };
+    }
+
+    /// Returns the color of the fallback glyph in the tileset, as it is written in the ascii entries of the tileset config
+    pub fn get_color_name(&self) -> &'static str {
+        return match self {
+            FallbackColor::White => "WHITE",
+            FallbackColor::Red => "RED",
+            FallbackColor::Green => "GREEN",
+            FallbackColor::Blue => "BLUE",
+            FallbackColor::Cyan => "CYAN",
+            FallbackColor::Magenta => "MAGENTA",
+            FallbackColor::Yellow => "YELLOW",
+        };
+    }
+}
+
+/// How the size of an imported mapgen is chosen if its rows do not match the declared size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RowSizeMismatch {
+    /// The size grows until every row fits inside of it
+    #[default]
+    Expand,
+    /// The declared size is kept and characters outside of it are dropped
+    Crop,
+}
+
+impl RowSizeMismatch {
+    pub const ALL: [RowSizeMismatch; 2] = [RowSizeMismatch::Expand, RowSizeMismatch::Crop];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            RowSizeMismatch::Expand => "Expand the size",
+            RowSizeMismatch::Crop => "Crop the rows"
+        };
+    }
+}
+
+/// The corner of the map which is shown as (0, 0).
+/// CDDA counts the rows from the top left, the other corners only change how coordinates are displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OriginCorner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OriginCorner {
+    pub const ALL: [OriginCorner; 4] = [OriginCorner::TopLeft, OriginCorner::TopRight, OriginCorner::BottomLeft, OriginCorner::BottomRight];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            OriginCorner::TopLeft => "Top Left",
+            OriginCorner::TopRight => "Top Right",
+            OriginCorner::BottomLeft => "Bottom Left",
+            OriginCorner::BottomRight => "Bottom Right"
+        };
+    }
+
+    /// Converts the stored coordinates to the displayed ones. Converting the result again returns the stored coordinates
+    pub fn to_display(&self, coordinates: &Coordinates, map_size: IVec2) -> Coordinates {
+        let flipped_x = map_size.x - 1 - coordinates.x;
+        let flipped_y = map_size.y - 1 - coordinates.y;
+
+        return match self {
+            OriginCorner::TopLeft => coordinates.clone(),
+            OriginCorner::TopRight => Coordinates::new(flipped_x, coordinates.y),
+            OriginCorner::BottomLeft => Coordinates::new(coordinates.x, flipped_y),
+            OriginCorner::BottomRight => Coordinates::new(flipped_x, flipped_y)
+        };
+    }
+
+    /// Returns the arrows which point in the directions the displayed x and y coordinates increase
+    pub fn axis_arrows(&self) -> (&'static str, &'static str) {
+        return match self {
+            OriginCorner::TopLeft => ("→", "↓"),
+            OriginCorner::TopRight => ("←", "↓"),
+            OriginCorner::BottomLeft => ("→", "↑"),
+            OriginCorner::BottomRight => ("←", "↑")
+        };
+    }
+}
+
+fn default_floor() -> TileId {
+    "t_floor".into()
+}
+
+fn default_show_missing_textures() -> bool {
+    true
+}
+
+fn default_render_furniture_backgrounds() -> bool {
+    true
+}
+
+fn default_show_origin_marker() -> bool {
+    true
+}
+
+fn default_grid_fade_start() -> f32 {
+    return 16.;
+}
+
+fn default_map_size() -> IVec2 {
+    // The size of a single overmap tile
+    IVec2::new(24, 24)
+}
+
+fn default_overmap_guide_spacing() -> u32 {
+    // Overmap tiles in CDDA are 24x24 tiles big
+    24
 }
 
 impl Default for Settings {
@@ -16,22 +266,182 @@ impl Default for Settings {
             selected_cdda_dir: None,
             selectable_tilesets: vec![],
             selected_tileset: None,
+            watch_project_files: false,
+            fill_missing_backgrounds: false,
+            render_floor_under_furniture: false,
+            render_furniture_backgrounds: default_render_furniture_backgrounds(),
+            default_floor: default_floor(),
+            show_overmap_guides: false,
+            overmap_guide_spacing: default_overmap_guide_spacing(),
+            show_missing_textures: default_show_missing_textures(),
+            default_map_size: default_map_size(),
+            default_fill_ter: None,
+            fullscreen: false,
+            show_origin_marker: default_show_origin_marker(),
+            grid_fade_start: default_grid_fade_start(),
+            origin_corner: OriginCorner::default(),
+            strict_painting: false,
+            row_size_mismatch: RowSizeMismatch::default(),
+            log_tileset_details: false,
+            fallback_color: FallbackColor::default(),
+            map_edge: MapEdge::default(),
         };
     }
 }
 
 impl Settings {
+    /// Returns the mode of the window which matches the fullscreen setting
+    pub fn get_window_mode(&self) -> WindowMode {
+        return match self.fullscreen {
+            true => WindowMode::BorderlessFullscreen,
+            false => WindowMode::Windowed
+        };
+    }
+
+    /// Returns the terrain which is rendered beneath furniture without terrain, None if this is disabled
+    pub fn get_floor_under_furniture(&self, project: &Project) -> Option<TileId> {
+        if !self.render_floor_under_furniture { return None; }
+
+        return match project.map_entity.object().get_fill_ter() {
+            None => Some(self.default_floor.clone()),
+            Some(fill_ter) => Some(fill_ter)
+        };
+    }
+
+    /// Returns true if strict painting is enabled and the character is not defined for the Project.
+    /// Without the CDDA data the palettes are unknown, so nothing is blocked
+    pub fn is_brush_blocked(&self, project: &Project, cdda_data: Option<&CDDAData>, character: &char) -> bool {
+        if !self.strict_painting { return false; }
+
+        // Spaces erase tiles if they are treated as empty
+        if *character == ' ' && project.treat_space_as_empty { return false; }
+
+        return match cdda_data {
+            None => false,
+            Some(d) => !project.map_entity.is_character_defined(d, character)
+        };
+    }
+
+    /// Returns true if the CDDA directory was never picked, which is the case on the first run
+    pub fn needs_onboarding(&self) -> bool {
+        return match &self.selected_cdda_dir {
+            None => true,
+            Some(dir) => dir.as_os_str().is_empty()
+        };
+    }
+
     pub fn data_json_dir(&self) -> Option<PathBuf> {
         return match &self.selected_cdda_dir {
             None => None,
-            Some(dir) => Some(dir.join(r"data\json"))
+            Some(dir) => Some(dir.join("data").join("json"))
         };
     }
 
     pub fn gfx_dir(&self) -> Option<PathBuf> {
         return match &self.selected_cdda_dir {
             None => None,
-            Some(dir) => Some(dir.join(r"gfx"))
+            Some(dir) => Some(dir.join("gfx"))
+        };
+    }
+
+    pub fn mapgen_palettes_dir(&self) -> Option<PathBuf> {
+        return match self.data_json_dir() {
+            None => None,
+            Some(dir) => Some(dir.join("mapgen_palettes"))
+        };
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::PathBuf;
+
+    use bevy::prelude::IVec2;
+
+    use crate::common::{Coordinates, MeabyWeighted};
+    use crate::palettes::data::{MapObjectId, MeabyParam};
+    use crate::project::data::Project;
+    use crate::settings::data::{OriginCorner, Settings};
+
+    fn settings_with_dir(dir: &str) -> Settings {
+        return Settings {
+            selected_cdda_dir: Some(PathBuf::from(dir)),
+            ..Settings::default()
         };
     }
+
+    #[test]
+    pub fn test_gfx_dir() {
+        let settings = settings_with_dir("cdda");
+        assert_eq!(settings.gfx_dir(), Some(PathBuf::from("cdda").join("gfx")));
+    }
+
+    #[test]
+    pub fn test_data_json_dir() {
+        let settings = settings_with_dir("cdda");
+        assert_eq!(settings.data_json_dir(), Some(PathBuf::from("cdda").join("data").join("json")));
+    }
+
+    #[test]
+    pub fn test_dirs_without_cdda_dir() {
+        let settings = Settings::default();
+        assert_eq!(settings.gfx_dir(), None);
+        assert_eq!(settings.data_json_dir(), None);
+    }
+
+    #[test]
+    pub fn test_onboarding_only_without_cdda_dir() {
+        assert!(Settings::default().needs_onboarding());
+        assert!(settings_with_dir("").needs_onboarding());
+        assert!(!settings_with_dir("cdda").needs_onboarding());
+    }
+
+    #[test]
+    pub fn test_floor_under_furniture() {
+        let mut settings = Settings::default();
+        let mut project = Project::default();
+
+        assert_eq!(settings.get_floor_under_furniture(&project), None);
+
+        settings.render_floor_under_furniture = true;
+        assert_eq!(settings.get_floor_under_furniture(&project), Some("t_floor".to_string()));
+
+        project.map_entity.object_mut().fill_ter = Some(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_grass".into()))));
+        assert_eq!(settings.get_floor_under_furniture(&project), Some("t_grass".to_string()));
+    }
+
+    #[test]
+    pub fn test_new_project_defaults_of_old_settings() {
+        let settings: Settings = serde_json::from_str(r#"{
+            "selected_cdda_dir": null,
+            "selectable_tilesets": [],
+            "selected_tileset": null
+        }"#).unwrap();
+
+        assert_eq!(settings.default_map_size, IVec2::new(24, 24));
+        assert_eq!(settings.default_fill_ter, None);
+    }
+
+    #[test]
+    pub fn test_origin_corner_only_changes_displayed_coordinates() {
+        let size = IVec2::new(24, 12);
+        let coordinates = Coordinates::new(0, 0);
+
+        assert_eq!(OriginCorner::TopLeft.to_display(&coordinates, size), Coordinates::new(0, 0));
+        assert_eq!(OriginCorner::TopRight.to_display(&coordinates, size), Coordinates::new(23, 0));
+        assert_eq!(OriginCorner::BottomLeft.to_display(&coordinates, size), Coordinates::new(0, 11));
+        assert_eq!(OriginCorner::BottomRight.to_display(&coordinates, size), Coordinates::new(23, 11));
+
+        for corner in OriginCorner::ALL {
+            let displayed = corner.to_display(&Coordinates::new(5, 3), size);
+            assert_eq!(corner.to_display(&displayed, size), Coordinates::new(5, 3));
+        }
+    }
+
+    #[test]
+    pub fn test_fallback_color_names() {
+        assert_eq!(FallbackColor::White.get_color_name(), "WHITE");
+        assert_eq!(FallbackColor::Red.get_color_name(), "RED");
+        assert_eq!(FallbackColor::Yellow.get_color_name(), "YELLOW");
+    }
 }
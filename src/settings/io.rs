@@ -1,10 +1,8 @@
 use std::fs;
-use std::fs::File;
-use std::io::Write;
 
 use directories::ProjectDirs;
 
-use crate::common::io::{Load, LoadError, Save, SaveError};
+use crate::common::io::{Load, LoadError, read_file, Save, SaveError, write_json_file};
 use crate::settings::data::Settings;
 
 pub struct SettingsLoader {}
@@ -18,12 +16,11 @@ impl Load<Settings> for SettingsLoader {
 
         let data_dir = dir.data_local_dir();
 
-        if !data_dir.exists() { fs::create_dir_all(data_dir).unwrap(); }
+        if let Err(e) = fs::create_dir_all(data_dir) {
+            return Err(LoadError::Io(data_dir.to_path_buf(), e));
+        }
 
-        let contents = match fs::read_to_string(data_dir.join("settings.json")) {
-            Err(e) => return Err(LoadError::Other(e.into())),
-            Ok(f) => f
-        };
+        let contents = read_file(&data_dir.join("settings.json"))?;
 
         let settings: Settings = match serde_json::from_str::<Settings>(contents.as_str()) {
             Ok(s) => s,
@@ -43,22 +40,6 @@ impl Save<Settings> for SettingsSaver {
             Some(d) => d
         };
 
-        let data_dir = dir.data_local_dir();
-
-        if !data_dir.exists() { fs::create_dir_all(data_dir).unwrap(); }
-
-        let mut file = File::options()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(data_dir.join("settings.json"))
-            .unwrap();
-
-        match file.write_all(serde_json::to_string(&value).unwrap().as_bytes()) {
-            Ok(_) => {}
-            Err(e) => return Err(SaveError::Other(e.into()))
-        }
-
-        Ok(())
+        return write_json_file(&dir.data_local_dir().join("settings.json"), value);
     }
 }
@@ -1,8 +1,14 @@
-use bevy::math::Vec2;
-use bevy::prelude::{Component, Entity, Resource};
+use std::collections::HashSet;
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::math::Quat;
+use bevy::prelude::{Added, Component, Entity, Query, Res, Resource};
 use serde::{Deserialize, Serialize};
 
-use crate::common::Coordinates;
+use crate::common::{Coordinates, TileId};
+use crate::map::data::MapEntity;
+use crate::program::data::CDDAData;
+use crate::project::data::TileChange;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, Component, PartialEq)]
 pub struct SpriteRepresentation {
@@ -13,6 +19,98 @@ pub struct SpriteRepresentation {
     pub bg_entity: Option<Entity>,
 }
 
+/// Decides if the opened Project can be edited or only viewed
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub enum InteractionMode {
+    #[default]
+    Editor,
+    /// Painting and other edits are disabled, only panning and zooming works
+    Preview,
+}
+
+impl InteractionMode {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            InteractionMode::Editor => "Editor",
+            InteractionMode::Preview => "Preview"
+        };
+    }
+}
+
+/// Run condition which only passes while the opened Project can be edited
+pub fn in_editor_mode(r_mode: Res<InteractionMode>) -> bool {
+    return *r_mode == InteractionMode::Editor;
+}
+
+/// Run condition which passes if sprites were spawned since the system last ran, they still have to be dimmed by the layer focus
+pub fn sprites_added(q_sprites: Query<(), Added<bevy::sprite::Sprite>>) -> bool {
+    return !q_sprites.is_empty();
+}
+
+/// The rectangle which is selected by dragging with the selection modifier held
+#[derive(Resource, Default, Debug)]
+pub struct Selection {
+    pub start: Option<Coordinates>,
+    pub end: Option<Coordinates>,
+}
+
+impl Selection {
+    /// Returns the top left and the bottom right corner of the selection, both are inside of it
+    pub fn get_bounds(&self) -> Option<(Coordinates, Coordinates)> {
+        let (start, end) = match (&self.start, &self.end) {
+            (Some(s), Some(e)) => (s, e),
+            _ => return None
+        };
+
+        return Some((
+            Coordinates::new(start.x.min(end.x), start.y.min(end.y)),
+            Coordinates::new(start.x.max(end.x), start.y.max(end.y)),
+        ));
+    }
+}
+
+/// Marks the sprite which highlights the selected rectangle
+#[derive(Debug, Component)]
+pub struct SelectionMarker;
+
+/// Inserted while the opened Project is compared with another Project
+#[derive(Resource, Debug)]
+pub struct ProjectDiffData {
+    /// The index of the Project which the opened Project is compared with
+    pub index: usize,
+    /// The coordinates which are currently tinted
+    pub differences: Vec<Coordinates>,
+}
+
+/// Marks the sprites which tint the tiles that differ from the compared Project
+#[derive(Debug, Component)]
+pub struct DiffMarker;
+
+/// The tiles which are highlighted by the [CharacterHighlight]
+#[derive(Debug, Clone, PartialEq)]
+pub enum HighlightTarget {
+    /// Every tile which uses the character
+    Character(char),
+    /// Every tile whose terrain resolves to the id, see MapEntity::coordinates_with_terrain
+    Terrain(TileId),
+}
+
+/// Inserted while every tile of the opened Project which matches the target is highlighted
+#[derive(Resource, Debug)]
+pub struct CharacterHighlight {
+    pub target: HighlightTarget,
+    /// The coordinates which are currently highlighted
+    pub highlighted: Vec<Coordinates>,
+}
+
+/// Marks the sprites which highlight the tiles of the highlighted character
+#[derive(Debug, Component)]
+pub struct CharacterHighlightMarker;
+
+/// Marks the sprite which previews how the brush character would look at the cursor
+#[derive(Debug, Component)]
+pub struct BrushPreviewMarker;
+
 #[derive(Default, Debug, Component, Clone)]
 pub struct Offset {
     pub x: i32,
@@ -56,6 +154,30 @@ pub struct Tile {
     #[serde(skip)]
     pub toilets: SpriteRepresentation,
     // TODO: Add missing representations
+
+    /// Clockwise quarter turns of the foreground sprite, from 0 to 3.
+    /// CDDA can only rotate whole maps, so this is not exported
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_not_rotated")]
+    pub rotation: u8,
+}
+
+fn is_not_rotated(rotation: &u8) -> bool {
+    return *rotation == 0;
+}
+
+impl Tile {
+    pub fn with_rotation(character: char, rotation: u8) -> Self {
+        return Self {
+            rotation: rotation % 4,
+            ..Self::from(character)
+        };
+    }
+
+    /// Returns the rotation of the foreground sprite
+    pub fn get_fg_rotation(&self) -> Quat {
+        return Quat::from_rotation_z(-FRAC_PI_2 * self.rotation as f32);
+    }
 }
 
 impl From<char> for Tile {
@@ -67,11 +189,236 @@ impl From<char> for Tile {
             furniture: SpriteRepresentation::default(),
             items: SpriteRepresentation::default(),
             toilets: SpriteRepresentation::default(),
+            rotation: 0,
         };
     }
 }
 
 #[derive(Resource, Debug)]
 pub struct PlaceInfo {
-    pub last_place_position: Option<Vec2>,
+    /// The tile the cursor was on when the brush was last used, fast drags are filled from here
+    pub last_place_position: Option<Coordinates>,
+    /// The changes of the brush stroke which is currently being painted
+    pub stroke: Vec<TileChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EditLayer {
+    Terrain,
+    Furniture,
+}
+
+impl EditLayer {
+    /// All layers in the order they are cycled through
+    pub const ALL: [EditLayer; 2] = [EditLayer::Terrain, EditLayer::Furniture];
+
+    fn index(&self) -> usize {
+        return Self::ALL.iter().position(|l| l == self).unwrap_or_default();
+    }
+
+    pub fn next(&self) -> Self {
+        return Self::ALL[(self.index() + 1) % Self::ALL.len()];
+    }
+
+    pub fn previous(&self) -> Self {
+        return Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()];
+    }
+}
+
+/// If enabled, the layer which is currently edited is drawn at full opacity and the other layers are dimmed
+#[derive(Resource, Default, Debug)]
+pub struct LayerFocus {
+    pub is_enabled: bool,
+}
+
+impl LayerFocus {
+    pub const DIMMED_ALPHA: f32 = 0.25;
+
+    /// Returns the opacity of the sprites of the layer
+    pub fn get_alpha(&self, layer: EditLayer, edited_layer: EditLayer) -> f32 {
+        if !self.is_enabled || layer == edited_layer {
+            return 1.;
+        }
+
+        return Self::DIMMED_ALPHA;
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct Brush {
+    pub character: char,
+    /// The width and height of the square that is painted around the cursor
+    pub size: u32,
+    pub layer: EditLayer,
+    /// The rotation of the placed tiles, see Tile::rotation
+    pub rotation: u8,
+    /// The character which was used before the current one, see Brush::swap_characters
+    pub previous_character: Option<char>,
+}
+
+impl Brush {
+    pub const MIN_SIZE: u32 = 1;
+    pub const MAX_SIZE: u32 = 16;
+
+    pub fn change_size(&mut self, amount: i32) {
+        self.size = (self.size as i32 + amount).clamp(Self::MIN_SIZE as i32, Self::MAX_SIZE as i32) as u32;
+    }
+
+    /// Turns the brush clockwise by a quarter
+    pub fn rotate(&mut self) {
+        self.rotation = (self.rotation + 1) % 4;
+    }
+
+    /// Switches back to the previous character, the current one becomes the previous character
+    pub fn swap_characters(&mut self) {
+        let previous = match self.previous_character {
+            None => return,
+            Some(c) => c
+        };
+
+        self.previous_character = Some(self.character);
+        self.character = previous;
+    }
+
+    /// Picks the most used defined character of the map if the brush character is not defined in it
+    pub fn pick_defined_character(&mut self, map_entity: &MapEntity, cdda_data: &CDDAData) {
+        if map_entity.is_character_defined(cdda_data, &self.character) { return; }
+
+        let character = map_entity.get_character_counts().into_iter()
+            .map(|c| c.character)
+            .find(|c| map_entity.is_character_defined(cdda_data, c));
+
+        if let Some(character) = character {
+            self.character = character;
+        }
+    }
+
+    /// Returns all coordinates that are painted when the brush is used at the passed coordinates
+    pub fn get_coordinates(&self, center: &Coordinates) -> Vec<Coordinates> {
+        let start = -((self.size as i32 - 1) / 2);
+        let end = start + self.size as i32;
+
+        let mut coordinates = Vec::new();
+
+        for y in start..end {
+            for x in start..end {
+                coordinates.push(Coordinates::new(center.x + x, center.y + y));
+            }
+        }
+
+        return coordinates;
+    }
+
+    /// Returns all coordinates that are painted when the brush is used at every coordinate of the line.
+    /// Every coordinate is only returned once, in the order in which it is first painted
+    pub fn get_coordinates_along(&self, line: &Vec<Coordinates>) -> Vec<Coordinates> {
+        let mut seen = HashSet::new();
+        let mut coordinates = Vec::new();
+
+        for center in line.iter() {
+            for tile_coordinates in self.get_coordinates(center) {
+                if seen.insert(tile_coordinates.clone()) {
+                    coordinates.push(tile_coordinates);
+                }
+            }
+        }
+
+        return coordinates;
+    }
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        return Self {
+            // Replaced by a character of the map when a Project is opened, see Brush::pick_defined_character
+            character: ' ',
+            size: 1,
+            layer: EditLayer::Terrain,
+            rotation: 0,
+            previous_character: None,
+        };
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use bevy::math::IVec2;
+
+    use crate::common::{Coordinates, MeabyWeighted};
+    use crate::map::data::{MapEntity, Single};
+    use crate::palettes::data::{MapObjectId, MeabyParam};
+    use crate::program::data::CDDAData;
+    use crate::tiles::data::{Brush, EditLayer, Tile};
+
+    #[test]
+    pub fn test_edit_layer_cycles_in_both_directions() {
+        assert_eq!(EditLayer::Terrain.next(), EditLayer::Furniture);
+        assert_eq!(EditLayer::Furniture.next(), EditLayer::Terrain);
+        assert_eq!(EditLayer::Terrain.previous(), EditLayer::Furniture);
+        assert_eq!(EditLayer::Furniture.previous(), EditLayer::Terrain);
+
+        for layer in EditLayer::ALL {
+            assert_eq!(layer.next().previous(), layer);
+            assert_eq!(layer.previous().next(), layer);
+        }
+    }
+
+    #[test]
+    pub fn test_brush_picks_most_used_defined_character() {
+        let mut tiles = HashMap::new();
+        tiles.insert(Coordinates::new(0, 0), Tile::from('x'));
+        tiles.insert(Coordinates::new(1, 0), Tile::from('x'));
+        tiles.insert(Coordinates::new(2, 0), Tile::from('.'));
+
+        let mut map = MapEntity::Single(Single {
+            om_terrain: "test".into(),
+            tile_selection: Default::default(),
+            tiles,
+            size: IVec2::new(3, 1),
+            comment: None,
+            weight: None,
+            variant: 0,
+            rotation: None,
+        });
+        map.object_mut().terrain.insert('.', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_dirt".into()))));
+
+        let cdda_data = CDDAData { palettes: HashMap::new() };
+        let mut brush = Brush::default();
+
+        // 'x' is used more often but not defined
+        brush.pick_defined_character(&map, &cdda_data);
+        assert_eq!(brush.character, '.');
+
+        map.object_mut().terrain.insert('#', MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId("t_wall".into()))));
+        brush.character = '#';
+
+        // A defined character is kept
+        brush.pick_defined_character(&map, &cdda_data);
+        assert_eq!(brush.character, '#');
+    }
+
+    #[test]
+    pub fn test_brush_coordinates_along_a_line_are_unique() {
+        let brush = Brush { size: 3, ..Brush::default() };
+        let line = Coordinates::new(0, 0).line_to(&Coordinates::new(2, 0));
+
+        let coordinates = brush.get_coordinates_along(&line);
+
+        // The squares of the three centers overlap to a 5x3 rectangle
+        assert_eq!(coordinates.len(), 15);
+        assert_eq!(coordinates.iter().collect::<HashSet<&Coordinates>>().len(), 15);
+        assert_eq!(coordinates.first(), Some(&Coordinates::new(-1, -1)));
+        assert!(coordinates.contains(&Coordinates::new(3, 1)));
+    }
+
+    #[test]
+    pub fn test_brush_coordinates_of_a_single_tile_line() {
+        let brush = Brush::default();
+
+        assert_eq!(brush.get_coordinates_along(&vec![Coordinates::new(4, 2)]), vec![Coordinates::new(4, 2)]);
+        assert_eq!(brush.get_coordinates_along(&vec![Coordinates::new(4, 2), Coordinates::new(4, 2)]), vec![Coordinates::new(4, 2)]);
+        assert!(brush.get_coordinates_along(&Vec::new()).is_empty());
+    }
 }
@@ -1,4 +1,4 @@
-pub(crate) mod systems;
-pub(crate) mod plugin;
-pub(crate) mod data;
+pub mod systems;
+pub mod plugin;
+pub mod data;
 
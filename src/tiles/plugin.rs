@@ -1,9 +1,10 @@
 use bevy::app::{App, Plugin, Update};
-use bevy::prelude::{in_state, IntoSystemConfigs};
+use bevy::prelude::{Condition, in_state, IntoSystemConfigs, resource_changed};
 
 use crate::program::data::ProgramState;
-use crate::tiles::data::PlaceInfo;
-use crate::tiles::systems::{tile_delete_system, tile_place_system, tile_resize_system, window_tile_resize_system};
+use crate::settings::data::Settings;
+use crate::tiles::data::{Brush, in_editor_mode, InteractionMode, LayerFocus, PlaceInfo, Selection, sprites_added};
+use crate::tiles::systems::{brush_character_entry_system, brush_preview_system, character_highlight_system, diff_overlay_system, layer_focus_system, swap_brush_characters_system, tile_delete_system, tile_place_system, pick_brush_character_system, tile_resize_system, rotate_brush_system, selection_preview_system, selection_system, toggle_interaction_mode, track_brush_character_system, window_tile_resize_system};
 
 pub struct TilePlugin;
 
@@ -11,18 +12,50 @@ impl Plugin for TilePlugin {
     fn build(&self, app: &mut App) {
         let place_info: PlaceInfo = PlaceInfo {
             last_place_position: None,
+            stroke: vec![],
         };
 
         app.insert_resource(place_info);
+        app.insert_resource(Brush::default());
+        app.insert_resource(InteractionMode::default());
+        app.insert_resource(LayerFocus::default());
+        app.insert_resource(Selection::default());
 
         app.add_systems(
             Update,
             (
                 window_tile_resize_system,
                 tile_resize_system,
-                tile_place_system,
-                tile_delete_system
+                toggle_interaction_mode,
             ).run_if(in_state(ProgramState::ProjectOpen)),
         );
+
+        // Only update the sprites when the focus, the edited layer or the sprites themselves changed
+        app.add_systems(
+            Update,
+            layer_focus_system.run_if(
+                in_state(ProgramState::ProjectOpen).and_then(
+                    resource_changed::<LayerFocus>
+                        .or_else(resource_changed::<Brush>)
+                        .or_else(resource_changed::<Settings>)
+                        .or_else(sprites_added)
+                )
+            ),
+        );
+
+        app.add_systems(
+            Update,
+            (
+                tile_place_system,
+                tile_delete_system,
+                rotate_brush_system,
+                selection_system,
+                brush_character_entry_system,
+                swap_brush_characters_system
+            ).run_if(in_state(ProgramState::ProjectOpen).and_then(in_editor_mode)),
+        );
+
+        // Not limited to an open Project so the preview is hidden again after the Project was closed
+        app.add_systems(Update, (brush_preview_system, selection_preview_system, diff_overlay_system, character_highlight_system, pick_brush_character_system, track_brush_character_system));
     }
 }
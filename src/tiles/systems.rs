@@ -1,12 +1,16 @@
 use bevy::input::ButtonInput;
-use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
-use bevy::prelude::{Entity, EventReader, EventWriter, MouseButton, Query, Res, ResMut, State, Transform, Vec2Swizzles, Window, With, Without};
-use bevy::window::{PrimaryWindow, WindowResized};
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::{Added, Assets, Changed, Color, Commands, Component, default, Entity, EventReader, EventWriter, Handle, Image, KeyCode, Local, MouseButton, Query, Res, ResMut, SpriteBundle, State, Transform, Vec2, Vec2Swizzles, Vec3, Visibility, Window, With, Without};
+use bevy::window::{PrimaryWindow, ReceivedCharacter, WindowResized};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
 
-use crate::common::Coordinates;
+use crate::common::{Coordinates, LogMessage};
+use crate::graphics::{GraphicsResource, TileSprite};
 use crate::map::data::{TileDeleteEvent, TilePlaceEvent};
-use crate::program::data::{OpenedProject, Program, ProgramState};
-use crate::tiles::data::PlaceInfo;
+use crate::program::data::{Keybinds, OpenedProject, Program, ProgramState};
+use crate::project::data::{HistoryEntry, Project, TileChange};
+use crate::tiles::data::{Brush, BrushPreviewMarker, CharacterHighlight, CharacterHighlightMarker, DiffMarker, EditLayer, HighlightTarget, InteractionMode, LayerFocus, Offset, PlaceInfo, ProjectDiffData, Selection, SelectionMarker};
+use crate::settings::data::Settings;
 use crate::tiles::data::Tile;
 use crate::ui::grid::GridMarker;
 use crate::ui::grid::resources::Grid;
@@ -30,16 +34,12 @@ pub fn tile_resize_system(
     r_grid: ResMut<Grid>,
     mut q_tiles: Query<(&mut Tile, &mut Transform), Without<GridMarker>>,
 ) {
-    for event in e_scroll.read() {
-        match event.unit {
-            MouseScrollUnit::Line => {
-                for (_, mut transform) in q_tiles.iter_mut() {
-                    transform.scale.x = r_grid.tile_size / r_grid.default_tile_size;
-                    transform.scale.y = r_grid.tile_size / r_grid.default_tile_size;
-                }
-            }
-            MouseScrollUnit::Pixel => panic!("Not Implemented")
-        }
+    // Line and pixel scrolling both change the zoom
+    if e_scroll.read().count() == 0 { return; }
+
+    for (_, mut transform) in q_tiles.iter_mut() {
+        transform.scale.x = r_grid.get_sprite_scale();
+        transform.scale.y = r_grid.get_sprite_scale();
     }
 }
 
@@ -47,28 +47,39 @@ pub fn tile_place_system(
     mut e_set_tile: EventWriter<TilePlaceEvent>,
     mut e_delete_tile: EventWriter<TileDeleteEvent>,
     mut r_place_info: ResMut<PlaceInfo>,
-    r_program: Res<Program>,
+    mut r_program: ResMut<Program>,
+    r_brush: Res<Brush>,
     r_buttons: Res<ButtonInput<MouseButton>>,
+    r_keys: Res<ButtonInput<KeyCode>>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
     r_grid: Res<Grid>,
     r_captured: Res<IsCursorCaptured>,
+    r_settings: Res<Settings>,
+    mut e_log: EventWriter<LogMessage>,
     q_opened_project: Query<(Entity, &OpenedProject)>,
 ) {
-    if r_buttons.just_released(MouseButton::Left) {
-        r_place_info.last_place_position = None
-    }
+    let index = match q_opened_project.iter().next() {
+        None => return,
+        Some(o) => o.1.index
+    };
 
-    if r_buttons.pressed(MouseButton::Left) {
-        let index = match q_opened_project.iter().next() {
-            None => return,
-            Some(o) => o.1.index
-        };
+    let cdda_data = r_program.config.cdda_data.clone();
 
-        let project = match r_program.projects.get(index) {
-            None => return,
-            Some(p) => p
-        };
+    // Dragging selects a rectangle or pans the view instead of painting
+    let is_selecting = r_keys.any_pressed(r_program.config.keybindings.selection_modifier.clone());
+    let is_panning = r_keys.any_pressed(r_program.config.keybindings.pan_modifier.clone());
+
+    let project = match r_program.projects.get_mut(index) {
+        None => return,
+        Some(p) => p
+    };
+
+    if r_buttons.just_released(MouseButton::Left) {
+        r_place_info.last_place_position = None;
+        finish_stroke(&mut r_place_info, project);
+    }
 
+    if r_buttons.pressed(MouseButton::Left) && !is_selecting && !is_panning {
         let xy = match q_windows.single().cursor_position() {
             None => return,
             Some(p) => p.xy()
@@ -78,44 +89,71 @@ pub fn tile_place_system(
             return;
         }
 
-        // TODO - REPLACE
-        let tile_to_place: char = 'w';
+        if r_settings.is_brush_blocked(project, cdda_data.as_deref(), &r_brush.character) {
+            // Only tell the user once per stroke instead of every frame
+            if r_buttons.just_pressed(MouseButton::Left) {
+                e_log.send(LogMessage::warning(format!("Cannot paint '{}', it is not defined in the map or its palettes. Disable strict painting in the settings to paint it anyway", r_brush.character)));
+            }
+            return;
+        }
 
-        let tile_cords = Coordinates::new(
+        let cursor_cords = Coordinates::new(
             ((xy.x + r_grid.offset.x) / r_grid.tile_size).floor() as i32,
             ((xy.y + r_grid.offset.y) / r_grid.tile_size).floor() as i32,
         );
 
-        if tile_cords.x >= project.map_entity.size().x as i32 ||
-            tile_cords.y >= project.map_entity.size().y as i32 ||
-            tile_cords.x < 0 ||
-            tile_cords.y < 0 {
-            return;
-        }
+        // The cursor can skip tiles between two frames, so every tile between both positions is painted
+        let line = match &r_place_info.last_place_position {
+            None => vec![cursor_cords.clone()],
+            Some(last) => last.line_to(&cursor_cords)
+        };
 
-        let existing_tile = project.map_entity.tiles().get(&tile_cords);
-        if let Some(existing_tile) = existing_tile {
-            // Overwrite empty characters
-            if existing_tile.character != ' ' { return; }
+        let brush_coordinates = r_brush.get_coordinates_along(&line);
 
-            e_delete_tile.send(
-                TileDeleteEvent {
-                    tile: existing_tile.clone(),
-                    coordinates: tile_cords.clone(),
-                }
-            );
-        }
+        r_place_info.last_place_position = Some(cursor_cords);
+
+        for tile_cords in brush_coordinates {
+            if tile_cords.x >= project.map_entity.size().x as i32 ||
+                tile_cords.y >= project.map_entity.size().y as i32 ||
+                tile_cords.x < 0 ||
+                tile_cords.y < 0 {
+                continue;
+            }
+
+            let existing_tile = project.map_entity.tiles().get(&tile_cords);
+            if let Some(existing_tile) = existing_tile {
+                // Overwrite empty characters
+                if existing_tile.character != ' ' { continue; }
+
+                e_delete_tile.send(
+                    TileDeleteEvent {
+                        tile: existing_tile.clone(),
+                        coordinates: tile_cords.clone(),
+                    }
+                );
+            }
+
+            r_place_info.stroke.push(TileChange {
+                coordinates: tile_cords.clone(),
+                before: existing_tile.map(|t| t.character),
+                after: Some(r_brush.character),
+                before_rotation: existing_tile.map(|t| t.rotation).unwrap_or(0),
+                after_rotation: r_brush.rotation,
+            });
 
-        let tile = Tile::from(tile_to_place);
-        e_set_tile.send(TilePlaceEvent { tile, coordinates: tile_cords, should_update_sprites: true });
+            let tile = Tile::with_rotation(r_brush.character, r_brush.rotation);
+            e_set_tile.send(TilePlaceEvent { tile, coordinates: tile_cords, should_update_sprites: true });
+        }
     }
 }
 
 pub fn tile_delete_system(
     mut r_program: ResMut<Program>,
+    mut r_place_info: ResMut<PlaceInfo>,
     mut e_delete_tile: EventWriter<TileDeleteEvent>,
     mut e_spawn_tile: EventWriter<TilePlaceEvent>,
     r_buttons: Res<ButtonInput<MouseButton>>,
+    r_keys: Res<ButtonInput<KeyCode>>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
     r_grid: Res<Grid>,
     r_captured: Res<IsCursorCaptured>,
@@ -127,12 +165,19 @@ pub fn tile_delete_system(
         Some(o) => o.1.index
     };
 
-    let project = match r_program.projects.get(index) {
+    // Right clicking with the modifier opens the context menu of the tile instead
+    let is_opening_context_menu = r_keys.any_pressed(r_program.config.keybindings.context_menu_modifier.clone());
+
+    let project = match r_program.projects.get_mut(index) {
         None => return,
         Some(p) => p
     };
 
-    if r_buttons.pressed(MouseButton::Right) {
+    if r_buttons.just_released(MouseButton::Right) {
+        finish_stroke(&mut r_place_info, project);
+    }
+
+    if r_buttons.pressed(MouseButton::Right) && !is_opening_context_menu {
         let xy = match q_windows.single().cursor_position() {
             None => return,
             Some(p) => p.xy()
@@ -157,6 +202,14 @@ pub fn tile_delete_system(
             return;
         }
 
+        r_place_info.stroke.push(TileChange {
+            coordinates: tile_cords.clone(),
+            before: Some(tile.character),
+            after: Some(' '),
+            before_rotation: tile.rotation,
+            after_rotation: 0,
+        });
+
         e_delete_tile.send(TileDeleteEvent {
             tile: *tile,
             coordinates: tile_cords.clone(),
@@ -170,4 +223,509 @@ pub fn tile_delete_system(
             }
         );
     }
-}
\ No newline at end of file
+}
+
+/// Adds the changes of the current brush stroke to the history of the Project as a single step
+fn finish_stroke(place_info: &mut PlaceInfo, project: &mut Project) {
+    if place_info.stroke.is_empty() { return; }
+
+    project.history.push(HistoryEntry::Tiles(std::mem::take(&mut place_info.stroke)));
+}
+
+/// Switches between the Editor and the Preview Mode when the toggle_preview_mode keybinding is pressed
+pub fn toggle_interaction_mode(
+    mut r_mode: ResMut<InteractionMode>,
+    r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !Keybinds::is_just_pressed(&r_program.config.keybindings.toggle_preview_mode, &r_keys) { return; }
+
+    *r_mode = match *r_mode {
+        InteractionMode::Editor => InteractionMode::Preview,
+        InteractionMode::Preview => InteractionMode::Editor
+    };
+}
+
+pub fn layer_focus_system(
+    mut q_sprites: Query<&mut bevy::sprite::Sprite, Without<BrushPreviewMarker>>,
+    r_focus: Res<LayerFocus>,
+    r_brush: Res<Brush>,
+    r_program: Res<Program>,
+    r_settings: Res<Settings>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    for tile in project.map_entity.tiles().values() {
+        // The last value decides if the background of the layer is visible
+        let layers = [
+            (EditLayer::Terrain, &tile.terrain, true),
+            (EditLayer::Terrain, &tile.fallback, true),
+            (EditLayer::Furniture, &tile.furniture, r_settings.render_furniture_backgrounds),
+        ];
+
+        for (layer, representation, show_bg) in layers {
+            let alpha = r_focus.get_alpha(layer, r_brush.layer);
+            let bg_alpha = match show_bg {
+                true => alpha,
+                false => 0.
+            };
+
+            for (entity, alpha) in [(representation.fg_entity, alpha), (representation.bg_entity, bg_alpha)] {
+                let entity = match entity {
+                    None => continue,
+                    Some(e) => e
+                };
+
+                let mut sprite = match q_sprites.get_mut(entity) {
+                    Ok(s) => s,
+                    // The solid background fills are no sprites
+                    Err(_) => continue
+                };
+
+                // Only set the color if it changed, so the sprites are not marked as changed every frame
+                if sprite.color.a() != alpha {
+                    sprite.color.set_a(alpha);
+                }
+            }
+        }
+    }
+}
+
+/// Selects the rectangle between the tile where the drag started and the tile under the cursor
+pub fn selection_system(
+    mut r_selection: ResMut<Selection>,
+    r_program: Res<Program>,
+    r_buttons: Res<ButtonInput<MouseButton>>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    r_grid: Res<Grid>,
+    r_captured: Res<IsCursorCaptured>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !r_keys.any_pressed(r_program.config.keybindings.selection_modifier.clone()) { return; }
+    if !r_buttons.pressed(MouseButton::Left) || r_captured.0 { return; }
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let xy = match q_windows.single().cursor_position() {
+        None => return,
+        Some(p) => p.xy()
+    };
+
+    let size = project.map_entity.size();
+
+    // The selection can not leave the map, even if the cursor does
+    let coordinates = Coordinates::new(
+        (((xy.x + r_grid.offset.x) / r_grid.tile_size).floor() as i32).clamp(0, size.x as i32 - 1),
+        (((xy.y + r_grid.offset.y) / r_grid.tile_size).floor() as i32).clamp(0, size.y as i32 - 1),
+    );
+
+    if r_buttons.just_pressed(MouseButton::Left) || r_selection.start.is_none() {
+        r_selection.start = Some(coordinates.clone());
+    }
+
+    r_selection.end = Some(coordinates);
+}
+
+/// Highlights the selected rectangle
+pub fn selection_preview_system(
+    mut commands: Commands,
+    mut q_preview: Query<(&mut bevy::sprite::Sprite, &mut Transform, &mut Visibility), With<SelectionMarker>>,
+    r_selection: Res<Selection>,
+    r_grid: Res<Grid>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let (mut sprite, mut transform, mut visibility) = match q_preview.get_single_mut() {
+        Ok(p) => p,
+        Err(_) => {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: bevy::sprite::Sprite {
+                        color: Color::rgba(0.3, 0.6, 1., 0.3),
+                        ..default()
+                    },
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                SelectionMarker
+            ));
+            return;
+        }
+    };
+
+    let (start, end) = match (q_opened_project.iter().next(), r_selection.get_bounds()) {
+        (Some(_), Some(bounds)) => bounds,
+        _ => {
+            *visibility = Visibility::Hidden;
+            return;
+        }
+    };
+
+    let window = q_windows.single();
+    let window_size = Vec2::new(window.resolution.width(), window.resolution.height());
+
+    let start_translation = r_grid.get_tile_translation(window_size, &start);
+    let end_translation = r_grid.get_tile_translation(window_size, &end);
+    let center = (start_translation + end_translation) / 2.;
+
+    sprite.custom_size = Some(Vec2::new(
+        (end.x - start.x + 1) as f32 * r_grid.tile_size,
+        (end.y - start.y + 1) as f32 * r_grid.tile_size,
+    ));
+
+    // Above every tile, but below the brush preview
+    transform.translation = Vec3::new(center.x, center.y, 9_999.);
+    *visibility = Visibility::Visible;
+}
+
+/// Respawns the overlay markers if the coordinates changed, otherwise moves the markers onto their tiles
+fn update_overlay_markers<M: Component>(
+    commands: &mut Commands,
+    q_markers: &mut Query<(Entity, &Coordinates, &mut bevy::sprite::Sprite, &mut Transform, &mut Visibility), With<M>>,
+    shown: &mut Vec<Coordinates>,
+    coordinates: Vec<Coordinates>,
+    get_marker: impl Fn() -> M,
+    color: Color,
+    z: f32,
+    grid: &Grid,
+    window_size: Vec2,
+) {
+    if coordinates != *shown {
+        for (entity, _, _, _, _) in q_markers.iter() {
+            commands.entity(entity).despawn();
+        }
+
+        for coordinates in coordinates.iter() {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: bevy::sprite::Sprite {
+                        color,
+                        ..default()
+                    },
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                coordinates.clone(),
+                get_marker()
+            ));
+        }
+
+        *shown = coordinates;
+        return;
+    }
+
+    for (_, coordinates, mut sprite, mut transform, mut visibility) in q_markers.iter_mut() {
+        let translation = grid.get_tile_translation(window_size, coordinates);
+
+        sprite.custom_size = Some(Vec2::splat(grid.tile_size));
+        transform.translation = Vec3::new(translation.x, translation.y, z);
+        *visibility = Visibility::Visible;
+    }
+}
+
+/// Tints the tiles of the opened Project which differ from the Project it is compared with
+pub fn diff_overlay_system(
+    mut commands: Commands,
+    mut q_markers: Query<(Entity, &Coordinates, &mut bevy::sprite::Sprite, &mut Transform, &mut Visibility), With<DiffMarker>>,
+    r_diff_data: Option<ResMut<ProjectDiffData>>,
+    r_program: Res<Program>,
+    r_grid: Res<Grid>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+    q_changed_project: Query<(), Changed<OpenedProject>>,
+) {
+    let mut diff_data = match r_diff_data {
+        None => {
+            for (entity, _, _, _, _) in q_markers.iter() {
+                commands.entity(entity).despawn();
+            }
+            return;
+        }
+        Some(d) => d
+    };
+
+    let opened_project = q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index));
+
+    // Comparing every tile is expensive, so the differences are only collected again when one of the Projects
+    // was edited, undone or moved, or when another Project was opened
+    let should_compare = r_program.is_changed() || diff_data.is_added() || !q_changed_project.is_empty();
+
+    let differences = match (opened_project, r_program.projects.get(diff_data.index), should_compare) {
+        (_, _, false) => diff_data.differences.clone(),
+        (Some(project), Some(other), true) => project.map_entity.get_differences(&other.map_entity),
+        _ => Vec::new()
+    };
+
+    let window = q_windows.single();
+    let window_size = Vec2::new(window.resolution.width(), window.resolution.height());
+
+    // Above every tile, but below the selection
+    update_overlay_markers(
+        &mut commands,
+        &mut q_markers,
+        &mut diff_data.differences,
+        differences,
+        || DiffMarker,
+        Color::rgba(1., 0.3, 0.2, 0.4),
+        9_998.,
+        &r_grid,
+        window_size,
+    );
+}
+
+/// Highlights every tile of the opened Project which uses the highlighted character
+pub fn character_highlight_system(
+    mut commands: Commands,
+    mut q_markers: Query<(Entity, &Coordinates, &mut bevy::sprite::Sprite, &mut Transform, &mut Visibility), With<CharacterHighlightMarker>>,
+    r_highlight: Option<ResMut<CharacterHighlight>>,
+    r_program: Res<Program>,
+    r_grid: Res<Grid>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let mut highlight = match r_highlight {
+        None => {
+            for (entity, _, _, _, _) in q_markers.iter() {
+                commands.entity(entity).despawn();
+            }
+            return;
+        }
+        Some(h) => h
+    };
+
+    let project = q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index));
+
+    let coordinates = match (project, &highlight.target, &r_program.config.cdda_data) {
+        (None, _, _) => Vec::new(),
+        (Some(p), HighlightTarget::Character(character), _) => p.map_entity.get_coordinates_of_character(character),
+        // Terrain from a distribution is resolved again every time, so it is only collected when the map changed
+        (Some(p), HighlightTarget::Terrain(id), Some(cdda_data)) => match r_program.is_changed() || highlight.is_added() {
+            true => p.map_entity.coordinates_with_terrain(cdda_data, id),
+            false => highlight.highlighted.clone()
+        },
+        (Some(_), HighlightTarget::Terrain(_), None) => Vec::new()
+    };
+
+    let window = q_windows.single();
+    let window_size = Vec2::new(window.resolution.width(), window.resolution.height());
+
+    update_overlay_markers(
+        &mut commands,
+        &mut q_markers,
+        &mut highlight.highlighted,
+        coordinates,
+        || CharacterHighlightMarker,
+        Color::rgba(1., 0.85, 0.2, 0.35),
+        9_997.,
+        &r_grid,
+        window_size,
+    );
+}
+
+pub fn rotate_brush_system(
+    mut contexts: EguiContexts,
+    mut r_brush: ResMut<Brush>,
+    r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !Keybinds::is_just_pressed(&r_program.config.keybindings.rotate_brush, &r_keys) { return; }
+
+    // The key could be typed into a text field
+    if contexts.ctx_mut().wants_keyboard_input() { return; }
+
+    r_brush.rotate();
+}
+
+/// Gives the brush a character which is defined in the Project that was just opened
+pub fn pick_brush_character_system(
+    mut r_brush: ResMut<Brush>,
+    r_program: Res<Program>,
+    q_opened_project: Query<&OpenedProject, Added<OpenedProject>>,
+) {
+    let (project, cdda_data) = match (q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)), &r_program.config.cdda_data) {
+        (Some(p), Some(d)) => (p, d),
+        _ => return
+    };
+
+    r_brush.pick_defined_character(&project.map_entity, cdda_data);
+}
+
+/// Remembers the previous brush character, no matter where the brush character was changed
+pub fn track_brush_character_system(
+    mut r_brush: ResMut<Brush>,
+    mut last_character: Local<Option<char>>,
+) {
+    let character = r_brush.character;
+
+    match *last_character {
+        Some(last) if last != character => r_brush.previous_character = Some(last),
+        _ => {}
+    }
+
+    *last_character = Some(character);
+}
+
+pub fn swap_brush_characters_system(
+    mut contexts: EguiContexts,
+    mut r_brush: ResMut<Brush>,
+    r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !Keybinds::is_just_pressed(&r_program.config.keybindings.swap_brush_characters, &r_keys) { return; }
+
+    // While the character entry modifier is held the key selects its own character instead
+    if r_keys.any_pressed(r_program.config.keybindings.character_entry_modifier.clone()) { return; }
+
+    if contexts.ctx_mut().wants_keyboard_input() { return; }
+
+    r_brush.swap_characters();
+}
+
+/// Makes the typed character the brush character while the character entry modifier is held
+pub fn brush_character_entry_system(
+    mut contexts: EguiContexts,
+    mut e_character: EventReader<ReceivedCharacter>,
+    mut r_brush: ResMut<Brush>,
+    r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    // Typing into a text field of a menu should not change the brush
+    if !r_keys.any_pressed(r_program.config.keybindings.character_entry_modifier.clone()) || contexts.ctx_mut().wants_keyboard_input() {
+        e_character.clear();
+        return;
+    }
+
+    let (project, cdda_data) = match (q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)), &r_program.config.cdda_data) {
+        (Some(p), Some(d)) => (p, d),
+        _ => {
+            e_character.clear();
+            return;
+        }
+    };
+
+    for event in e_character.read() {
+        let character = match event.char.chars().next() {
+            None => continue,
+            Some(c) => c
+        };
+
+        if !project.map_entity.is_character_defined(cdda_data, &character) { continue; }
+
+        r_brush.character = character;
+    }
+}
+
+/// Shows the sprite that the brush character would get at the cursor, including the connections to its neighbors
+pub fn brush_preview_system(
+    mut commands: Commands,
+    mut q_preview: Query<(&mut Handle<Image>, &mut Transform, &mut Visibility), With<BrushPreviewMarker>>,
+    r_brush: Res<Brush>,
+    r_program: Res<Program>,
+    r_textures: Res<GraphicsResource>,
+    r_images: Res<Assets<Image>>,
+    r_grid: Res<Grid>,
+    r_captured: Res<IsCursorCaptured>,
+    r_mode: Res<InteractionMode>,
+    r_settings: Res<Settings>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let (mut texture, mut transform, mut visibility) = match q_preview.get_single_mut() {
+        Ok(p) => p,
+        Err(_) => {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: bevy::sprite::Sprite {
+                        color: Color::rgba(1., 1., 1., 0.6),
+                        ..default()
+                    },
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                BrushPreviewMarker
+            ));
+            return;
+        }
+    };
+
+    // Hidden until every check below passes
+    *visibility = Visibility::Hidden;
+
+    if *r_mode == InteractionMode::Preview { return; }
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let (cdda_data, textures) = match (&r_program.config.cdda_data, &r_textures.textures) {
+        (Some(d), Some(t)) => (d, t),
+        _ => return
+    };
+
+    let window = q_windows.single();
+
+    let xy = match window.cursor_position() {
+        None => return,
+        Some(p) => p.xy()
+    };
+
+    if r_captured.0 { return; }
+
+    let coordinates = Coordinates::new(
+        ((xy.x + r_grid.offset.x) / r_grid.tile_size).floor() as i32,
+        ((xy.y + r_grid.offset.y) / r_grid.tile_size).floor() as i32,
+    );
+
+    let size = project.map_entity.size();
+
+    if coordinates.x < 0 || coordinates.y < 0 || coordinates.x >= size.x as i32 || coordinates.y >= size.y as i32 {
+        return;
+    }
+
+    // The sprite is resolved against the current neighbors as if the brush character was already placed
+    let sprite = match textures.get_textures(project, cdda_data, &r_brush.character, &coordinates, r_settings.get_floor_under_furniture(project).as_ref(), r_settings.show_missing_textures) {
+        TileSprite::Exists { terrain, furniture, .. } => furniture.or(terrain),
+        TileSprite::Fallback(s) => Some(s),
+        TileSprite::Empty => None
+    };
+
+    let (fg, offset) = match sprite.and_then(|s| s.fg.as_ref().map(|fg| (fg, Offset { x: s.offset_x, y: s.offset_y }))) {
+        None => return,
+        Some(v) => v
+    };
+
+    *texture = fg.get_representative_sprite().clone();
+
+    let sprite_size = match r_images.get(&*texture) {
+        None => Vec2::splat(r_grid.default_tile_size),
+        Some(image) => Vec2::new(image.width() as f32, image.height() as f32)
+    };
+
+    let window_size = Vec2::new(window.resolution.width(), window.resolution.height());
+    let translation = r_grid.get_sprite_translation(window_size, &coordinates, &offset, sprite_size);
+
+    transform.translation = Vec3::new(
+        translation.x,
+        translation.y,
+        // Above every tile
+        10_000.,
+    );
+    transform.scale = Vec3::new(
+        r_grid.get_sprite_scale(),
+        r_grid.get_sprite_scale(),
+        1.,
+    );
+    transform.rotation = Tile::with_rotation(r_brush.character, r_brush.rotation).get_fg_rotation();
+
+    *visibility = Visibility::Inherited;
+}
@@ -0,0 +1,127 @@
+use bevy::input::ButtonInput;
+use bevy::prelude::{KeyCode, Local, Query, Res, ResMut, Transform, With, Without};
+use bevy_egui::egui::{Grid as EguiGrid, RichText, TextEdit, Window};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::program::data::{Keybinds, Menus, OpenedProject, Program};
+use crate::project::data::Bookmark;
+use crate::tiles::data::Tile;
+use crate::ui::grid::GridMarker;
+use crate::ui::grid::resources::Grid;
+
+/// Moves the view to the bookmark and scales the sprites to its zoom
+fn jump_to_bookmark(
+    grid: &mut Grid,
+    bookmark: &Bookmark,
+    q_tiles: &mut Query<&mut Transform, (With<Tile>, Without<GridMarker>)>,
+) {
+    grid.offset = bookmark.offset;
+    grid.tile_size = bookmark.tile_size.clamp(grid.min_zoom, grid.max_zoom);
+
+    for mut transform in q_tiles.iter_mut() {
+        transform.scale.x = grid.get_sprite_scale();
+        transform.scale.y = grid.get_sprite_scale();
+    }
+}
+
+pub fn bookmarks_menu(
+    mut contexts: EguiContexts,
+    mut r_menus: ResMut<Menus>,
+    mut r_program: ResMut<Program>,
+    mut r_grid: ResMut<Grid>,
+    mut q_tiles: Query<&mut Transform, (With<Tile>, Without<GridMarker>)>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !r_menus.is_bookmarks_open { return; }
+
+    let next_keys = r_program.config.keybindings.next_bookmark.iter()
+        .map(|k| format!("{:?}", k))
+        .collect::<Vec<String>>()
+        .join(" + ");
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get_mut(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let mut jump_to = None;
+    let mut delete = None;
+    let mut is_changed = false;
+
+    Window::new("Bookmarks")
+        .open(&mut r_menus.is_bookmarks_open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(RichText::new(format!("Press {} to jump to the next bookmark", next_keys)).weak());
+
+            EguiGrid::new("bookmarks_grid").striped(true).show(ui, |ui| {
+                for (i, bookmark) in project.bookmarks.iter_mut().enumerate() {
+                    is_changed |= ui.add(TextEdit::singleline(&mut bookmark.name).desired_width(160.)).changed();
+
+                    if ui.button("Go to").clicked() {
+                        jump_to = Some(i);
+                    }
+
+                    if ui.button("Delete").clicked() {
+                        delete = Some(i);
+                    }
+
+                    ui.end_row();
+                }
+            });
+
+            if ui.button("Add bookmark at the current view").clicked() {
+                project.bookmarks.push(Bookmark {
+                    name: format!("Bookmark {}", project.bookmarks.len() + 1),
+                    offset: r_grid.offset,
+                    tile_size: r_grid.tile_size,
+                });
+                is_changed = true;
+            }
+        });
+
+    if let Some(bookmark) = jump_to.and_then(|i| project.bookmarks.get(i)) {
+        jump_to_bookmark(&mut r_grid, bookmark, &mut q_tiles);
+    }
+
+    if let Some(i) = delete {
+        project.bookmarks.remove(i);
+        is_changed = true;
+    }
+
+    // Bookmarks are saved with the Project
+    if is_changed {
+        project.is_dirty = true;
+    }
+}
+
+pub fn next_bookmark_system(
+    mut contexts: EguiContexts,
+    mut r_grid: ResMut<Grid>,
+    mut last_bookmark: Local<Option<usize>>,
+    mut q_tiles: Query<&mut Transform, (With<Tile>, Without<GridMarker>)>,
+    r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !Keybinds::is_just_pressed(&r_program.config.keybindings.next_bookmark, &r_keys) { return; }
+
+    // Typing the key into a text field should not move the view
+    if contexts.ctx_mut().wants_keyboard_input() { return; }
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    // The last bookmark might have been deleted in the meantime
+    let last = last_bookmark.filter(|i| *i < project.bookmarks.len());
+
+    let index = match project.get_next_bookmark_index(last) {
+        None => return,
+        Some(i) => i
+    };
+
+    jump_to_bookmark(&mut r_grid, &project.bookmarks[index], &mut q_tiles);
+    *last_bookmark = Some(index);
+}
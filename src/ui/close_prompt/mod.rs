@@ -0,0 +1,77 @@
+use bevy::prelude::{Commands, EventWriter, Res, Resource};
+use bevy_egui::egui::{Align2, Vec2, Window};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::program::data::Program;
+use crate::project::data::{CloseProject, RemoveProject};
+use crate::ui::interaction::open_save_dialog;
+
+/// Inserted when a Project with unsaved changes is about to be closed
+#[derive(Resource)]
+pub struct ClosePromptData {
+    pub index: usize,
+    /// If the tab of the Project is removed as well, otherwise only the opened Project is closed
+    pub remove: bool,
+}
+
+pub fn close_prompt(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut e_close_project: EventWriter<CloseProject>,
+    mut e_remove_project: EventWriter<RemoveProject>,
+    r_program: Res<Program>,
+    r_close_prompt_data: Option<Res<ClosePromptData>>,
+) {
+    let prompt_data = match r_close_prompt_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let project = match r_program.projects.get(prompt_data.index) {
+        None => {
+            commands.remove_resource::<ClosePromptData>();
+            return;
+        }
+        Some(p) => p
+    };
+
+    let mut should_save = false;
+    let mut should_close = false;
+
+    Window::new("Unsaved Changes")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("The Project '{}' has unsaved changes.", project.name));
+            ui.label("Do you want to save them before closing it?");
+
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    should_save = true;
+                }
+
+                if ui.button("Close without saving").clicked() {
+                    match prompt_data.remove {
+                        true => { e_remove_project.send(RemoveProject { index: prompt_data.index }); }
+                        false => { e_close_project.send(CloseProject {}); }
+                    }
+                    should_close = true;
+                }
+
+                if ui.button("Cancel").clicked() {
+                    should_close = true;
+                }
+            });
+        });
+
+    if should_save {
+        // The Project stays open, so it can be closed after it was saved
+        open_save_dialog(&mut commands, prompt_data.index, project);
+        should_close = true;
+    }
+
+    if should_close {
+        commands.remove_resource::<ClosePromptData>();
+    }
+}
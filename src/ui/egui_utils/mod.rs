@@ -1,6 +1,39 @@
 use bevy::prelude::Commands;
 use bevy_egui::egui;
-use bevy_egui::egui::{Align, Color32, Margin, Ui, WidgetText};
+use bevy_egui::egui::{Align, Color32, Margin, RichText, ScrollArea, Ui, WidgetText};
+
+use crate::common::TileId;
+use crate::graphics::{GraphicsResource, KnownTileIds};
+use crate::program::data::Program;
+
+const MAX_TILE_ID_SUGGESTIONS: usize = 50;
+
+pub const CDDA_DIR_NOT_CONFIGURED: &str = "No CDDA directory is configured. Select it in the Settings to load the palettes and tilesets.";
+pub const TILESET_NOT_LOADED: &str = "No tileset is loaded. Select one in the Settings.";
+
+/// Returns why menus which depend on the CDDA data and a tileset can not be shown yet
+pub fn get_missing_cdda_requirement(program: &Program, graphics: &GraphicsResource) -> Option<&'static str> {
+    if program.config.cdda_data.is_none() {
+        return Some(CDDA_DIR_NOT_CONFIGURED);
+    }
+
+    if graphics.textures.is_none() {
+        return Some(TILESET_NOT_LOADED);
+    }
+
+    return None;
+}
+
+/// Shows why the content of a menu can not be displayed
+pub fn requirement_banner(ui: &mut Ui, message: &str, fill: Color32) {
+    egui::Frame::none()
+        .fill(fill)
+        .inner_margin(Margin::same(8.))
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            ui.label(RichText::new(message).strong());
+        });
+}
 
 pub fn add_settings_frame(
     name: impl Into<WidgetText>,
@@ -38,4 +71,59 @@ pub fn input_group(
 
         ui.label(label);
     });
-}
\ No newline at end of file
+}
+
+/// A text input for tile ids which suggests known ids while typing.
+/// Ids which are not known are shown in the error color. Returns true if the id was changed
+pub fn tile_id_input(
+    ui: &mut Ui,
+    id: &mut String,
+    known_tile_ids: &KnownTileIds,
+    error_color: Color32,
+) -> bool {
+    let mut text_edit = egui::widgets::TextEdit::singleline(id)
+        .vertical_align(Align::Center);
+
+    if !id.is_empty() && !known_tile_ids.contains(id) {
+        text_edit = text_edit.text_color(error_color);
+    }
+
+    let response = ui.add(text_edit);
+    let mut is_changed = response.changed();
+
+    let popup_id = response.id.with("tile_id_suggestions");
+
+    if response.has_focus() && !id.is_empty() {
+        ui.memory_mut(|m| m.open_popup(popup_id));
+    }
+
+    let suggestions: Vec<&TileId> = known_tile_ids.ids.iter()
+        .filter(|known_id| known_id.contains(id.as_str()) && known_id.as_str() != id.as_str())
+        .take(MAX_TILE_ID_SUGGESTIONS)
+        .collect();
+
+    if suggestions.is_empty() {
+        return is_changed;
+    }
+
+    let mut selected_id: Option<TileId> = None;
+
+    egui::popup_below_widget(ui, popup_id, &response, |ui| {
+        ScrollArea::vertical()
+            .max_height(200.)
+            .show(ui, |ui| {
+                for suggestion in suggestions {
+                    if ui.selectable_label(false, suggestion).clicked() {
+                        selected_id = Some(suggestion.clone());
+                    }
+                }
+            });
+    });
+
+    if let Some(selected_id) = selected_id {
+        *id = selected_id;
+        is_changed = true;
+    }
+
+    return is_changed;
+}
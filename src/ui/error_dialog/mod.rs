@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use bevy::prelude::{Event, EventReader, Local};
+use bevy_egui::egui::{Align2, Id, RichText, Vec2, Window};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::common::io::{LoadError, SaveError};
+
+/// Sent when loading or saving a file failed, shows the error in a modal dialog
+#[derive(Event, Debug, Clone)]
+pub struct ShowError {
+    pub title: String,
+    pub message: String,
+    pub path: Option<PathBuf>,
+}
+
+impl ShowError {
+    pub fn load(title: impl Into<String>, error: &LoadError) -> Self {
+        return Self {
+            title: title.into(),
+            message: error.to_string(),
+            path: error.path().map(|p| p.to_path_buf()),
+        };
+    }
+
+    pub fn save(title: impl Into<String>, error: &SaveError) -> Self {
+        return Self {
+            title: title.into(),
+            message: error.to_string(),
+            path: error.path().map(|p| p.to_path_buf()),
+        };
+    }
+
+    /// Used for errors which do not belong to a file
+    pub fn new(title: impl Into<String>, message: impl Into<String>) -> Self {
+        return Self {
+            title: title.into(),
+            message: message.into(),
+            path: None,
+        };
+    }
+
+    /// Used for errors which do not come from a loader or saver, like the result of a file dialog
+    pub fn with_path(title: impl Into<String>, message: impl Into<String>, path: PathBuf) -> Self {
+        return Self {
+            title: title.into(),
+            message: message.into(),
+            path: Some(path),
+        };
+    }
+}
+
+/// Shows the received errors one after another until each of them is dismissed
+pub fn error_dialog(
+    mut contexts: EguiContexts,
+    mut e_show_error: EventReader<ShowError>,
+    mut queue: Local<Vec<ShowError>>,
+) {
+    for event in e_show_error.read() {
+        log::error!("{}: {}", event.title, event.message);
+        queue.push(event.clone());
+    }
+
+    let error = match queue.first() {
+        None => return,
+        Some(e) => e.clone()
+    };
+
+    let mut is_dismissed = false;
+
+    Window::new(error.title.as_str())
+        .id(Id::new("error_dialog"))
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(error.message.as_str());
+
+            if let Some(path) = &error.path {
+                ui.label(RichText::new(format!("{}", path.display())).weak());
+            }
+
+            if queue.len() > 1 {
+                ui.label(RichText::new(format!("{} more errors", queue.len() - 1)).weak());
+            }
+
+            if ui.button("OK").clicked() {
+                is_dismissed = true;
+            }
+        });
+
+    if is_dismissed {
+        queue.remove(0);
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs;
+
+use bevy::prelude::{Commands, EventReader, EventWriter, Query, Res, ResMut, Resource};
+use bevy_egui::egui::{Align2, Checkbox, Vec2, Window};
+use bevy_file_dialog::{DialogFileSaved, FileDialogExt};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::common::LogMessage;
+use crate::map::io::{map_entity_to_cdda_json_string, map_entity_to_legend_markdown};
+use crate::program::data::{OpenedProject, Program};
+use crate::ui::CDDAMapgenContents;
+use crate::ui::egui_utils::CDDA_DIR_NOT_CONFIGURED;
+use crate::ui::error_dialog::ShowError;
+
+/// Inserted when the export button was pressed, holds the options of the export
+#[derive(Resource, Default)]
+pub struct ExportMenuData {
+    pub include_legend: bool,
+}
+
+/// The legend which is written next to the mapgen file once its path was picked
+#[derive(Resource)]
+pub struct PendingLegend {
+    pub legend: String,
+}
+
+pub fn export_menu(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    r_program: Res<Program>,
+    r_export_menu_data: Option<ResMut<ExportMenuData>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let mut menu_data = match r_export_menu_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => {
+            commands.remove_resource::<ExportMenuData>();
+            return;
+        }
+        Some(p) => p
+    };
+
+    let mut is_open = true;
+    let mut should_export = false;
+
+    Window::new("Export")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .open(&mut is_open)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("Export '{}' as a CDDA mapgen file", project.name));
+
+            ui.add_enabled(
+                r_program.config.cdda_data.is_some(),
+                Checkbox::new(&mut menu_data.include_legend, "Also write a legend of the characters"),
+            ).on_disabled_hover_text(CDDA_DIR_NOT_CONFIGURED);
+
+            if ui.button("Export").clicked() {
+                should_export = true;
+            }
+        });
+
+    if should_export {
+        match (menu_data.include_legend, &r_program.config.cdda_data) {
+            (true, Some(cdda_data)) => commands.insert_resource(PendingLegend {
+                legend: map_entity_to_legend_markdown(&project.map_entity, cdda_data)
+            }),
+            _ => commands.remove_resource::<PendingLegend>()
+        }
+
+        let mapgen_json = map_entity_to_cdda_json_string(&project.map_entity);
+        commands.dialog()
+            .add_filter("", vec!["json"].as_slice())
+            .set_file_name(format!("{}.json", project.name))
+            .save_file::<CDDAMapgenContents>(mapgen_json.into_bytes());
+
+        is_open = false;
+    }
+
+    if !is_open {
+        commands.remove_resource::<ExportMenuData>();
+    }
+}
+
+/// Writes the pending legend next to the exported mapgen file
+pub fn mapgen_saved_reader(
+    mut commands: Commands,
+    mut e_file_saved: EventReader<DialogFileSaved<CDDAMapgenContents>>,
+    mut e_log: EventWriter<LogMessage>,
+    mut e_show_error: EventWriter<ShowError>,
+    r_pending_legend: Option<Res<PendingLegend>>,
+) {
+    for event in e_file_saved.read() {
+        let pending_legend = match &r_pending_legend {
+            None => return,
+            Some(l) => l
+        };
+
+        commands.remove_resource::<PendingLegend>();
+
+        if event.result.is_err() { continue; }
+
+        let path = event.path.with_extension("legend.md");
+
+        match fs::write(&path, pending_legend.legend.as_bytes()) {
+            Ok(_) => { e_log.send(LogMessage::info(format!("Wrote the legend to {:?}", path))); }
+            Err(e) => { e_show_error.send(ShowError::with_path("Could not write the legend", e.to_string(), path.clone())); }
+        };
+    }
+}
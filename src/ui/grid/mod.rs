@@ -11,8 +11,8 @@ use crate::ui::grid::systems::{
     drag_system, grid_resize_system, window_grid_resize_system,
 };
 
-pub(crate) mod systems;
-pub(crate) mod resources;
+pub mod systems;
+pub mod resources;
 
 pub struct GridPlugin;
 
@@ -66,6 +66,14 @@ pub struct GridMaterial {
     pub inside_grid_color: Vec3,
     #[uniform(7)]
     pub outside_grid_color: Vec3,
+    #[uniform(8)]
+    // The amount of tiles between the overmap guide lines, 0 disables them
+    pub overmap_guide_spacing: i32,
+    #[uniform(9)]
+    pub min_zoom: f32,
+    #[uniform(10)]
+    // The grid lines start to fade out below this tile size and are gone at the min zoom
+    pub grid_fade_start: f32,
 }
 
 
@@ -1,15 +1,35 @@
 use bevy::math::Vec2;
 use bevy::prelude::{Entity, Resource};
 
+use crate::common::Coordinates;
+use crate::graphics::tileset::legacy::TilesetInfo;
+use crate::project::data::ProjectView;
+use crate::tiles::data::Offset;
+
+/// The distance in pixels the cursor has to move before a drag starts panning the view
+pub const DRAG_THRESHOLD: f32 = 4.;
+
 #[derive(Resource, Debug)]
 pub struct DragInfo {
     pub drag_started: Option<Vec2>,
+    /// Only set once the cursor moved further than the [DRAG_THRESHOLD] from where the drag started
     pub last_position: Option<Vec2>,
 }
 
+impl DragInfo {
+    /// Returns true if the cursor moved far enough from where the drag started to pan the view
+    pub fn is_past_threshold(&self, position: Vec2) -> bool {
+        return match self.drag_started {
+            None => false,
+            Some(s) => s.distance(position) > DRAG_THRESHOLD
+        };
+    }
+}
+
 #[derive(Resource, Debug)]
 pub struct Grid {
     pub tile_size: f32,
+    /// The width of a tile in the selected tileset, sprites are drawn at their native size at this zoom
     pub default_tile_size: f32,
     pub offset: Vec2,
 
@@ -17,4 +37,254 @@ pub struct Grid {
     pub max_zoom: f32,
 
     pub instantiated_grid: Option<Entity>,
-}
\ No newline at end of file
+}
+
+impl Grid {
+    /// Returns the scale of the sprites at the current zoom.
+    /// The scale is the same on both axes so sprites which are larger than a tile keep their aspect ratio
+    pub fn get_sprite_scale(&self) -> f32 {
+        return self.tile_size / self.default_tile_size;
+    }
+
+    /// Returns the current position and zoom, which is stored with the opened Project
+    pub fn get_view(&self) -> ProjectView {
+        return ProjectView {
+            offset: self.offset,
+            tile_size: self.tile_size,
+        };
+    }
+
+    /// Moves to the view of a Project, the zoom is kept in the allowed range
+    pub fn apply_view(&mut self, view: &ProjectView) {
+        self.offset = view.offset;
+        self.tile_size = view.tile_size.clamp(self.min_zoom, self.max_zoom);
+    }
+
+    /// Uses the tile width of the tileset as the default tile size and zooms to it, so the sprites are shown at their native size.
+    /// The grid only has square tiles, so the width is used for both axes
+    pub fn fit_to_tileset(&mut self, info: &TilesetInfo) {
+        let old_size = self.tile_size;
+
+        self.default_tile_size = info.get_scaled_tile_width() as f32;
+        self.tile_size = self.default_tile_size.clamp(self.min_zoom, self.max_zoom);
+
+        // Keep the same tiles in view
+        self.offset *= self.tile_size / old_size;
+    }
+
+    /// Changes the tile size by the amount while keeping the tile under the cursor in place
+    pub fn zoom(&mut self, amount: f32, cursor_position: Vec2) {
+        let old_size = self.tile_size;
+        self.tile_size = (old_size + amount).clamp(self.min_zoom, self.max_zoom);
+
+        let old_position = (cursor_position + self.offset) / old_size;
+        let new_position = (cursor_position + self.offset) / self.tile_size;
+        self.offset -= (new_position - old_position) * self.tile_size;
+    }
+
+    /// Moves the view so the position, in tiles, is at the center of the window
+    pub fn center_on(&mut self, position: Vec2, window_size: Vec2) {
+        self.offset = position * self.tile_size - window_size / 2.;
+    }
+
+    /// Returns the center of the tile at the coordinates, relative to the center of the window
+    pub fn get_tile_translation(&self, window_size: Vec2, coordinates: &Coordinates) -> Vec2 {
+        return Vec2::new(
+            (-window_size.x / 2. + self.tile_size / 2.) - (self.offset.x - coordinates.x as f32 * self.tile_size),
+            (window_size.y / 2. - self.tile_size / 2.) + (self.offset.y - coordinates.y as f32 * self.tile_size),
+        );
+    }
+
+    /// Returns true if a sprite at the translation, relative to the center of the window, can be seen.
+    /// Sprites can be larger than a single tile, so there is a margin of two tiles around the window
+    pub fn is_on_screen(&self, translation: Vec2, window_size: Vec2) -> bool {
+        let half_size = window_size / 2. + Vec2::splat(self.tile_size * 2.);
+
+        return translation.x.abs() <= half_size.x && translation.y.abs() <= half_size.y;
+    }
+
+    /// Returns the center of a sprite of the tile at the coordinates, relative to the center of the window.
+    /// Like in CDDA, the top left corner of the sprite is placed on the top left corner of the tile and then moved by the offset,
+    /// so negative sprite offsets push the sprite up and to the left
+    pub fn get_sprite_translation(&self, window_size: Vec2, coordinates: &Coordinates, sprite_offset: &Offset, sprite_size: Vec2) -> Vec2 {
+        let scale = self.get_sprite_scale();
+        let tile_translation = self.get_tile_translation(window_size, coordinates);
+
+        // Bevy places sprites at their center, so sprites which are larger than a tile reach further right and down
+        let overhang = (sprite_size * scale - Vec2::splat(self.tile_size)) / 2.;
+
+        return Vec2::new(
+            tile_translation.x + sprite_offset.x as f32 * scale + overhang.x,
+            tile_translation.y - sprite_offset.y as f32 * scale - overhang.y,
+        );
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::PathBuf;
+
+    use bevy::math::Vec2;
+
+    use crate::common::Coordinates;
+    use crate::common::io::Load;
+    use crate::graphics::tileset::TilesetLoader;
+    use crate::graphics::tileset::legacy::LegacyTilesetLoader;
+    use crate::tiles::data::Offset;
+    use crate::ui::grid::resources::{DragInfo, Grid};
+
+    #[test]
+    pub fn test_tile_translation_with_sprite_offset() {
+        let grid = Grid {
+            tile_size: 64.,
+            default_tile_size: 32.,
+            offset: Vec2::ZERO,
+            min_zoom: 6.,
+            max_zoom: 128.,
+            instantiated_grid: None,
+        };
+
+        let window_size = Vec2::new(640., 480.);
+        let coordinates = Coordinates::new(1, 1);
+        let sprite_size = Vec2::new(32., 32.);
+
+        let without_offset = grid.get_sprite_translation(window_size, &coordinates, &Offset::default(), sprite_size);
+        let with_offset = grid.get_sprite_translation(window_size, &coordinates, &Offset { x: -16, y: -8 }, sprite_size);
+
+        // A sprite with the size of a tile is centered on the tile
+        assert_eq!(without_offset, grid.get_tile_translation(window_size, &coordinates));
+        assert_eq!(without_offset, Vec2::new(-224., 144.));
+
+        // The offsets are scaled by the zoom and applied fully on both axes
+        assert_eq!(with_offset, Vec2::new(-256., 160.));
+    }
+
+    #[test]
+    pub fn test_tall_sprite_is_anchored_at_the_top_left() {
+        let grid = Grid {
+            tile_size: 64.,
+            default_tile_size: 32.,
+            offset: Vec2::ZERO,
+            min_zoom: 6.,
+            max_zoom: 128.,
+            instantiated_grid: None,
+        };
+
+        let window_size = Vec2::new(640., 480.);
+        let coordinates = Coordinates::new(1, 1);
+
+        // The size of tall_furniture in testing_data/tile_config.json
+        let sprite_size = Vec2::new(32., 64.);
+
+        // The tile spans from -256 to -192 on the x axis and from 176 to 112 on the y axis.
+        // The sprite starts at the top of the tile and reaches one tile further down
+        let translation = grid.get_sprite_translation(window_size, &coordinates, &Offset::default(), sprite_size);
+        assert_eq!(translation, Vec2::new(-224., 112.));
+
+        // With the sprite_offset_y of tall_furniture, it covers the tile above instead
+        let translation = grid.get_sprite_translation(window_size, &coordinates, &Offset { x: 0, y: -32 }, sprite_size);
+        assert_eq!(translation, Vec2::new(-224., 176.));
+    }
+
+    #[test]
+    pub fn test_tall_sprite_keeps_aspect_ratio() {
+        let grid = Grid {
+            tile_size: 48.,
+            default_tile_size: 32.,
+            offset: Vec2::ZERO,
+            min_zoom: 6.,
+            max_zoom: 128.,
+            instantiated_grid: None,
+        };
+
+        // The tall sprite of testing_data/tall_sprite.png is 32x64 pixels
+        let textures = LegacyTilesetLoader::new(PathBuf::from("./testing_data")).load_textures().unwrap();
+        let tall_sprite = textures.get(&5).unwrap();
+        let size = Vec2::new(tall_sprite.width() as f32, tall_sprite.height() as f32) * grid.get_sprite_scale();
+
+        // At a tile size of 48 it covers one tile in width and two tiles in height
+        assert_eq!(size, Vec2::new(48., 96.));
+    }
+
+    #[test]
+    pub fn test_only_tiles_around_the_window_are_on_screen() {
+        let grid = Grid {
+            tile_size: 32.,
+            default_tile_size: 32.,
+            offset: Vec2::ZERO,
+            min_zoom: 6.,
+            max_zoom: 128.,
+            instantiated_grid: None,
+        };
+
+        let window_size = Vec2::new(1280., 720.);
+        let mut on_screen = 0;
+
+        for y in 0..240 {
+            for x in 0..240 {
+                let translation = grid.get_tile_translation(window_size, &Coordinates::new(x, y));
+
+                if grid.is_on_screen(translation, window_size) {
+                    on_screen += 1;
+                }
+            }
+        }
+
+        // 40 columns and 22.5 rows fit into the window, plus a margin of two tiles to the right and below
+        assert_eq!(on_screen, 42 * 25);
+    }
+
+    #[test]
+    pub fn test_small_movement_does_not_start_drag() {
+        let drag_info = DragInfo {
+            drag_started: Some(Vec2::new(100., 100.)),
+            last_position: None,
+        };
+
+        assert!(!drag_info.is_past_threshold(Vec2::new(101., 102.)));
+        assert!(drag_info.is_past_threshold(Vec2::new(110., 100.)));
+    }
+
+    #[test]
+    pub fn test_zoom_keeps_tile_under_cursor() {
+        let mut grid = Grid {
+            tile_size: 32.,
+            default_tile_size: 32.,
+            offset: Vec2::new(16., 8.),
+            min_zoom: 6.,
+            max_zoom: 128.,
+            instantiated_grid: None,
+        };
+
+        let cursor = Vec2::new(100., 60.);
+        let tile_before = (cursor + grid.offset) / grid.tile_size;
+
+        grid.zoom(32., cursor);
+        assert_eq!(grid.tile_size, 64.);
+        assert_eq!((cursor + grid.offset) / grid.tile_size, tile_before);
+
+        grid.zoom(1000., cursor);
+        assert_eq!(grid.tile_size, grid.max_zoom);
+    }
+
+    #[test]
+    pub fn test_16px_tileset_sets_default_tile_size() {
+        let mut grid = Grid {
+            tile_size: 64.,
+            default_tile_size: 32.,
+            offset: Vec2::new(64., 32.),
+            min_zoom: 6.,
+            max_zoom: 128.,
+            instantiated_grid: None,
+        };
+
+        // The sprites of the tileset are 16px wide and drawn with a pixelscale of 2
+        let tileset = LegacyTilesetLoader::new(PathBuf::from("./testing_data/pixelscale_tileset")).load().unwrap();
+        grid.fit_to_tileset(&tileset.info);
+
+        assert_eq!(grid.default_tile_size, 32.);
+        assert_eq!(grid.tile_size, 32.);
+        assert_eq!(grid.get_sprite_scale(), 1.);
+        assert_eq!(grid.offset, Vec2::new(32., 16.));
+    }
+}
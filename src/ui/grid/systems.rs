@@ -1,10 +1,12 @@
 use bevy::input::ButtonInput;
 use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::input::touchpad::TouchpadMagnify;
 use bevy::math::Vec2;
-use bevy::prelude::{Commands, CursorMoved, EventReader, MouseButton, Query, Res, ResMut, Transform, Vec2Swizzles, Window, With, Without};
+use bevy::prelude::{EventReader, KeyCode, MouseButton, Query, Res, ResMut, Transform, Vec2Swizzles, Window, With, Without};
 use bevy::window::{PrimaryWindow, WindowResized};
 
-use crate::tiles::data::Tile;
+use crate::program::data::Program;
+use crate::tiles::data::{Brush, Tile};
 use crate::ui::grid::{DragInfo, Grid, GridMarker};
 
 pub fn window_grid_resize_system(
@@ -22,66 +24,102 @@ pub fn window_grid_resize_system(
     }
 }
 
+/// Scrolling one line with a mouse wheel changes the tile size by this amount
+const ZOOM_PER_LINE: f32 = 2.;
+
+/// Trackpads scroll by pixels instead of lines, this many pixels are treated as one line
+const PIXELS_PER_LINE: f32 = 16.;
+
 pub fn grid_resize_system(
     mut scroll_event: EventReader<MouseWheel>,
+    mut e_magnify: EventReader<TouchpadMagnify>,
     mut r_grid: ResMut<Grid>,
+    mut r_brush: ResMut<Brush>,
     mut tiles: Query<(&mut Tile, &mut Transform), Without<GridMarker>>,
+    r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
 ) {
+    let keybindings = &r_program.config.keybindings;
+    let is_size_modifier_pressed = r_keys.any_pressed(keybindings.brush_size_modifier.clone());
+    let is_layer_modifier_pressed = r_keys.any_pressed(keybindings.brush_layer_modifier.clone());
+    let cursor_position = q_windows.single().cursor_position().unwrap_or(Vec2::new(0., 0.));
+    let old_size = r_grid.tile_size;
+
     for event in scroll_event.read() {
         match event.unit {
             MouseScrollUnit::Line => {
-                let window = q_windows.single();
-
-                let old_size = r_grid.tile_size.clone();
-                r_grid.tile_size = (old_size + event.y * 2.).clamp(r_grid.min_zoom, r_grid.max_zoom);
-                let new_size = r_grid.tile_size.clone();
-
-                let old_position = (window.cursor_position().unwrap_or(Vec2::new(0., 0.)) + r_grid.offset) / old_size;
-                let new_position = (window.cursor_position().unwrap_or(Vec2::new(0., 0.)) + r_grid.offset) / new_size;
-                r_grid.offset -= (new_position - old_position) * new_size;
+                if is_size_modifier_pressed {
+                    r_brush.change_size(event.y.signum() as i32);
+                    continue;
+                }
 
-                for (_, mut transform) in tiles.iter_mut() {
-                    transform.scale.x = r_grid.tile_size / r_grid.default_tile_size;
-                    transform.scale.y = r_grid.tile_size / r_grid.default_tile_size;
+                if is_layer_modifier_pressed {
+                    r_brush.layer = match event.y > 0. {
+                        true => r_brush.layer.next(),
+                        false => r_brush.layer.previous()
+                    };
+                    continue;
                 }
+
+                r_grid.zoom(event.y * ZOOM_PER_LINE, cursor_position);
             }
-            MouseScrollUnit::Pixel => todo!()
+            // Trackpads send a lot of small events, so they only zoom to not flip through the brush sizes and layers
+            MouseScrollUnit::Pixel => r_grid.zoom(event.y / PIXELS_PER_LINE * ZOOM_PER_LINE, cursor_position)
         }
     }
+
+    // Only emitted on platforms which support pinch gestures
+    for event in e_magnify.read() {
+        let amount = r_grid.tile_size * event.0;
+        r_grid.zoom(amount, cursor_position);
+    }
+
+    if r_grid.tile_size == old_size { return; }
+
+    for (_, mut transform) in tiles.iter_mut() {
+        transform.scale.x = r_grid.get_sprite_scale();
+        transform.scale.y = r_grid.get_sprite_scale();
+    }
 }
 
+/// Pans the view while the middle mouse button, or the left mouse button together with the pan modifier, is held
 pub fn drag_system(
-    mut commands: Commands,
     buttons: Res<ButtonInput<MouseButton>>,
-    mut cursor_motion: EventReader<CursorMoved>,
+    keys: Res<ButtonInput<KeyCode>>,
+    r_program: Res<Program>,
     mut res_grid: ResMut<Grid>,
     mut res_drag: ResMut<DragInfo>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
 ) {
-    let window = q_windows.single();
-
-    if buttons.just_pressed(MouseButton::Middle) {
-        let xy = window.cursor_position().unwrap_or(Vec2::default()).xy();
-        commands.insert_resource(DragInfo {
-            drag_started: Some(xy),
-            last_position: Some(xy),
-        })
-    }
+    let is_pan_modifier_pressed = keys.any_pressed(r_program.config.keybindings.pan_modifier.clone());
+    let is_pan_pressed = buttons.pressed(MouseButton::Middle) || (is_pan_modifier_pressed && buttons.pressed(MouseButton::Left));
 
-    if buttons.just_released(MouseButton::Middle) {
+    if !is_pan_pressed {
+        res_drag.drag_started = None;
         res_drag.last_position = None;
-        res_drag.drag_started = None
+        return;
     }
 
-    if buttons.pressed(MouseButton::Middle) {
-        match cursor_motion.read().last() {
-            None => return,
-            Some(m) => {
-                let offset = res_grid.offset.clone();
-                res_grid.offset = offset + res_drag.last_position.unwrap_or(m.position) - m.position;
-                res_drag.last_position = Some(m.position);
-            }
-        }
+    let xy = match q_windows.single().cursor_position() {
+        None => return,
+        Some(p) => p.xy()
+    };
+
+    if res_drag.drag_started.is_none() {
+        res_drag.drag_started = Some(xy);
+        return;
     }
+
+    // Small movements while clicking should not nudge the view
+    let last_position = match res_drag.last_position {
+        Some(p) => p,
+        None => {
+            if !res_drag.is_past_threshold(xy) { return; }
+            res_drag.drag_started.unwrap_or(xy)
+        }
+    };
+
+    res_grid.offset += last_position - xy;
+    res_drag.last_position = Some(xy);
 }
\ No newline at end of file
@@ -6,6 +6,9 @@ pub struct SaveIconMarker;
 #[derive(Component)]
 pub struct ImportIconMarker;
 
+#[derive(Component)]
+pub struct ExportIconMarker;
+
 #[derive(Component)]
 pub struct TopHotbarMarker;
 
@@ -24,6 +27,27 @@ pub struct TileSettingsMarker;
 #[derive(Component)]
 pub struct ProjectSettingsMarker;
 
+#[derive(Component)]
+pub struct LayerFocusMarker;
+
+#[derive(Component)]
+pub struct LayerFocusTextMarker;
+
+#[derive(Component)]
+pub struct ItemIndicatorMarker;
+
+#[derive(Component)]
+pub struct ItemIndicatorTextMarker;
+
+#[derive(Component)]
+pub struct TileStatisticsMarker;
+
+#[derive(Component)]
+pub struct BookmarksMarker;
+
+#[derive(Component)]
+pub struct PaletteEditorMarker;
+
 
 #[derive(Component)]
 pub struct CustomTitleBarMarker;
\ No newline at end of file
@@ -1,13 +1,13 @@
 use bevy::asset::{AssetServer, Handle};
 use bevy::hierarchy::{BuildChildren, ChildBuilder};
-use bevy::prelude::{AlignItems, BackgroundColor, Bundle, ButtonBundle, Color, Commands, default, Display, FlexDirection, Image, ImageBundle, JustifyContent, NodeBundle, Res, Style, Text, TextBundle, TextStyle, UiImage, UiRect, Val};
+use bevy::prelude::{AlignItems, BackgroundColor, Bundle, ButtonBundle, Color, Commands, default, Display, FlexDirection, Font, Image, ImageBundle, JustifyContent, NodeBundle, Res, Style, Text, TextBundle, TextStyle, UiImage, UiRect, Val};
 use bevy::ui::PositionType;
 
 use crate::program::data::Program;
 use crate::ui::{HoverEffect, OriginalColor};
-use crate::ui::hotbar::components::{CloseIconMarker, CustomTitleBarMarker, ImportIconMarker, OpenIconMarker, ProjectSettingsMarker, SaveIconMarker, SettingsIconMarker, TileSettingsMarker, TopHotbarMarker};
+use crate::ui::hotbar::components::{BookmarksMarker, CloseIconMarker, CustomTitleBarMarker, ExportIconMarker, ImportIconMarker, ItemIndicatorMarker, ItemIndicatorTextMarker, LayerFocusMarker, LayerFocusTextMarker, OpenIconMarker, PaletteEditorMarker, ProjectSettingsMarker, SaveIconMarker, SettingsIconMarker, TileSettingsMarker, TileStatisticsMarker, TopHotbarMarker};
 
-pub(crate) mod components;
+pub mod components;
 
 fn spawn_button_icon<T: Bundle>(
     container: &mut ChildBuilder,
@@ -47,6 +47,50 @@ fn spawn_button_icon<T: Bundle>(
     });
 }
 
+/// Spawns a button with a label, the text marker is added to the label so it can be changed later
+fn spawn_text_button<T: Bundle, U: Bundle>(
+    container: &mut ChildBuilder,
+    program: &Res<Program>,
+    font: Handle<Font>,
+    label: &str,
+    marker: T,
+    text_marker: U,
+) {
+    container.spawn((
+        ButtonBundle {
+            style: Style {
+                height: Val::Px(32.),
+                padding: UiRect::horizontal(Val::Px(8.)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: BackgroundColor::from(program.config.style.gray_darker),
+            ..default()
+        },
+        OriginalColor(program.config.style.gray_darker),
+        HoverEffect {
+            original_color: program.config.style.gray_darker,
+            hover_color: program.config.style.selected,
+        },
+        marker
+    )).with_children(|button| {
+        button.spawn((
+            TextBundle {
+                text: Text::from_section(
+                    label,
+                    TextStyle {
+                        font,
+                        font_size: 12.,
+                        color: program.config.style.white,
+                    }),
+                ..default()
+            },
+            text_marker
+        ));
+    });
+}
+
 pub fn spawn_hotbar(
     mut commands: Commands,
     r_asset_server: Res<AssetServer>,
@@ -174,7 +218,13 @@ pub fn build_hotbar(
                 editor_data.config.style.gray_darker,
                 SaveIconMarker {},
             );
-            //spawn_button_icon(icons_container, asset_server.load("icons/upload-file.png"), PRIMARY_COLOR_FADED);
+            spawn_button_icon(
+                icons_container,
+                editor_data,
+                asset_server.load("icons/upload-file.png"),
+                editor_data.config.style.gray_darker,
+                ExportIconMarker {},
+            );
             spawn_button_icon(
                 icons_container,
                 editor_data,
@@ -201,6 +251,51 @@ pub fn build_hotbar(
             },
             ..default()
         }).with_children(|icons_container| {
+            spawn_text_button(
+                icons_container,
+                editor_data,
+                font.clone(),
+                "Focus: Off",
+                LayerFocusMarker,
+                LayerFocusTextMarker,
+            );
+
+            spawn_text_button(
+                icons_container,
+                editor_data,
+                font.clone(),
+                "Items: Off",
+                ItemIndicatorMarker,
+                ItemIndicatorTextMarker,
+            );
+
+            spawn_text_button(
+                icons_container,
+                editor_data,
+                font.clone(),
+                "Statistics",
+                TileStatisticsMarker,
+                (),
+            );
+
+            spawn_text_button(
+                icons_container,
+                editor_data,
+                font.clone(),
+                "Bookmarks",
+                BookmarksMarker,
+                (),
+            );
+
+            spawn_text_button(
+                icons_container,
+                editor_data,
+                font.clone(),
+                "Palettes",
+                PaletteEditorMarker,
+                (),
+            );
+
             spawn_button_icon(
                 icons_container,
                 editor_data,
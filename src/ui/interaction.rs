@@ -1,23 +1,41 @@
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use bevy::app::AppExit;
-use bevy::prelude::{Assets, Changed, Commands, Entity, Event, EventReader, EventWriter, Image, Interaction, Query, Res, ResMut, State, With};
+use bevy::prelude::{Assets, Changed, Commands, Component, DetectChanges, Entity, Event, EventReader, EventWriter, Image, Interaction, Query, Res, ResMut, State, Text, Transform, With, Without};
+use bevy::tasks::{AsyncComputeTaskPool, block_on, Task};
+use bevy::tasks::futures_lite::future;
 use bevy_egui::egui;
 use bevy_egui::egui::{Align, Color32, Margin, Ui, WidgetText};
 use bevy_file_dialog::{DialogDirectoryPicked, DialogFileLoaded, FileDialogExt};
 use bevy_inspector_egui::bevy_egui::EguiContexts;
 
-use crate::graphics::{GraphicsResource, LegacyTextures};
+use crate::common::io::{Load, open_in_file_explorer};
+use crate::common::LogMessage;
+use crate::graphics::{CachedTileset, GraphicsResource, KnownTileIds, LegacyTextures};
 use crate::graphics::tileset::legacy::LegacyTilesetLoader;
-use crate::map::data::MapEntity;
+use crate::graphics::tileset::TilesetLoader;
+use crate::map::data::{ClearTiles, MapEntity, SpawnMapEntity};
+use crate::palettes::data::Palette;
 use crate::program::data::{IntoColor32, OpenedProject, Program, ProgramState};
-use crate::project::data::{Project, ProjectSaveState};
+use crate::project::data::{Project, ProjectFileWatcher, ProjectSaveState};
+use crate::project::io::save_project_to_path;
 use crate::region_settings::io::RegionSettingsLoader;
-use crate::settings::data::Settings;
+use crate::settings::data::{FallbackColor, MapEdge, OriginCorner, RowSizeMismatch, Settings};
+use crate::tiles::data::{Brush, EditLayer, LayerFocus, Tile};
 use crate::ui::CDDADirContents;
-use crate::ui::egui_utils::add_settings_frame;
-use crate::ui::hotbar::components::{CloseIconMarker, ImportIconMarker, OpenIconMarker, SaveIconMarker, SettingsIconMarker, TileSettingsMarker};
+use crate::ui::egui_utils::{add_settings_frame, requirement_banner, tile_id_input};
+use crate::ui::error_dialog::ShowError;
+use crate::ui::export::ExportMenuData;
+use crate::ui::grid::GridMarker;
+use crate::ui::grid::resources::Grid;
+use crate::ui::hotbar::components::{BookmarksMarker, CloseIconMarker, ExportIconMarker, ImportIconMarker, ItemIndicatorMarker, ItemIndicatorTextMarker, LayerFocusMarker, LayerFocusTextMarker, OpenIconMarker, PaletteEditorMarker, ProjectSettingsMarker, SaveIconMarker, SettingsIconMarker, TileSettingsMarker, TileStatisticsMarker};
+use crate::ui::item_indicators::ItemIndicators;
+use crate::ui::overwrite_prompt::OverwritePromptData;
+use crate::ui::palette_editor::PaletteEditorData;
+use crate::ui::project_settings::ProjectSettingsMenuData;
+use crate::ui::style::{Theme, ThemeChanged};
 use crate::ui::tabs::events::SpawnTab;
 use crate::ui::terrain::TerrainMenuData;
 use crate::program::data::Menus;
@@ -51,20 +69,57 @@ pub fn save_button_interaction(
         Some(p) => p
     };
 
+    for interaction in interaction_query.iter() {
+        match interaction {
+            Interaction::Pressed => {
+                match &project.save_state {
+                    // Ask before the existing file is replaced
+                    ProjectSaveState::Saved(path) if path.exists() => {
+                        commands.insert_resource(OverwritePromptData { index, path: path.clone() });
+                    }
+                    _ => open_save_dialog(&mut commands, index, project)
+                }
+            }
+            _ => {}
+        };
+    }
+}
+
+/// The save dialog of the Project at the index, which is still open
+#[derive(Component)]
+pub struct SaveDialog {
+    pub index: usize,
+    pub task: Task<Option<PathBuf>>,
+}
+
+/// Opens the dialog to pick where the Project at the index should be saved.
+/// Only the path is picked, the Project is written by [file_saved_reader] so it is saved atomically like every other save
+pub fn open_save_dialog(commands: &mut Commands, index: usize, project: &Project) {
     let filename = match &project.map_entity {
         MapEntity::Single(s) => s.om_terrain.clone(),
-        MapEntity::Multi(_) => todo!(),
-        MapEntity::Nested(_) => "Nested_TODO".to_string()
+        // Renaming a single om_terrain of a Multi map keeps the name of the Project
+        MapEntity::Multi(_) => project.name.clone(),
+        MapEntity::Nested(n) => n.get_name()
     };
 
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        return rfd::AsyncFileDialog::new()
+            .set_file_name(filename)
+            .save_file()
+            .await
+            .map(|file| file.path().to_path_buf());
+    });
+
+    commands.spawn(SaveDialog { index, task });
+}
+
+pub fn export_button_interaction(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ExportIconMarker>)>,
+    mut commands: Commands,
+) {
     for interaction in interaction_query.iter() {
         match interaction {
-            Interaction::Pressed => {
-                let project_json = serde_json::to_string(&project).unwrap();
-                commands.dialog()
-                    .set_file_name(filename.clone())
-                    .save_file::<Project>(project_json.into_bytes());
-            }
+            Interaction::Pressed => commands.insert_resource(ExportMenuData::default()),
             _ => {}
         };
     }
@@ -105,6 +160,7 @@ pub fn open_button_interaction(
 pub fn file_loaded_reader(
     mut e_file_loaded: EventReader<DialogFileLoaded<Project>>,
     mut e_spawn_tab: EventWriter<SpawnTab>,
+    mut e_show_error: EventWriter<ShowError>,
     mut r_program: ResMut<Program>,
 ) {
     for event in e_file_loaded.read() {
@@ -119,20 +175,72 @@ pub fn file_loaded_reader(
             return;
         };
 
-        let project = serde_json::from_slice::<Project>(event.contents.as_slice()).unwrap();
+        let mut project = match serde_json::from_slice::<Project>(event.contents.as_slice()) {
+            Ok(p) => p,
+            Err(e) => {
+                e_show_error.send(ShowError::with_path("Could not open the Project", e.to_string(), event.path.clone()));
+                continue;
+            }
+        };
+
+        if let Err(e) = project.map_entity.verify_tiles() {
+            e_show_error.send(ShowError::with_path("Could not open the Project", format!("Its tiles are corrupted: {}", e), event.path.clone()));
+            continue;
+        }
+
+        project.save_state = ProjectSaveState::Saved(event.path.clone());
 
         let name = match &project.map_entity {
             MapEntity::Single(s) => s.om_terrain.clone(),
-            MapEntity::Multi(_) => todo!(),
-            MapEntity::Nested(_) => todo!()
+            MapEntity::Multi(_) => project.name.clone(),
+            MapEntity::Nested(n) => n.get_name()
         };
 
+        let index = r_program.add_project(project);
+
         e_spawn_tab.send(SpawnTab {
             name,
-            index: r_program.projects.len() as u32,
+            index: index as u32,
         });
+    }
+}
 
-        r_program.projects.push(project);
+pub fn file_saved_reader(
+    mut commands: Commands,
+    mut e_show_error: EventWriter<ShowError>,
+    mut r_program: ResMut<Program>,
+    mut r_watcher: ResMut<ProjectFileWatcher>,
+    mut q_save_dialog: Query<(Entity, &mut SaveDialog)>,
+) {
+    for (entity, mut save_dialog) in q_save_dialog.iter_mut() {
+        let picked_path = match block_on(future::poll_once(&mut save_dialog.task)) {
+            None => continue,
+            Some(p) => p
+        };
+
+        commands.entity(entity).despawn();
+
+        // The dialog was cancelled
+        let path = match picked_path {
+            None => continue,
+            Some(p) => p
+        };
+
+        let project = match r_program.projects.get_mut(save_dialog.index) {
+            None => continue,
+            Some(p) => p
+        };
+
+        if let Err(e) = save_project_to_path(project, &path) {
+            e_show_error.send(ShowError::save("Could not save the Project", &e));
+            continue;
+        }
+
+        project.save_state = ProjectSaveState::Saved(path.clone());
+        project.is_dirty = false;
+
+        // Do not reload the Project because of our own save
+        r_watcher.last_modified.remove(&path);
     }
 }
 
@@ -154,17 +262,40 @@ pub fn file_dialog_cdda_dir_picked(
 
 pub fn cdda_folder_picked(
     mut e_cdda_dir_picked: EventReader<CDDADirPicked>,
+    mut e_show_error: EventWriter<ShowError>,
     mut r_settings: ResMut<Settings>,
     mut r_program: ResMut<Program>,
+    mut r_graphics: ResMut<GraphicsResource>,
 ) {
     for e in e_cdda_dir_picked.read() {
         r_settings.selected_cdda_dir = Some(e.path.clone());
-        r_program.config.load_cdda_dir(e.path.clone());
 
-        fs::read_dir(&e.path.join("gfx")).unwrap().into_iter().for_each(|e| {
+        // The cached tilesets were loaded from the previous directory
+        r_graphics.unload_cached();
+
+        if let Err(err) = r_program.config.load_cdda_data(&r_settings) {
+            e_show_error.send(ShowError::load("Could not load the CDDA palettes", &err));
+        }
+
+        let gfx_dir = match r_settings.gfx_dir() {
+            None => continue,
+            Some(dir) => dir
+        };
+
+        let entries = match fs::read_dir(&gfx_dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                e_show_error.send(ShowError::with_path("Could not load the tilesets", err.to_string(), gfx_dir));
+                continue;
+            }
+        };
+
+        entries.into_iter().for_each(|e| {
             match e {
                 Ok(e) => {
-                    if !e.path().is_dir() { return; }
+                    // Tilepacks can also be selected without extracting them first
+                    let is_archive = e.path().extension().map_or(false, |e| e.eq_ignore_ascii_case("zip"));
+                    if !e.path().is_dir() && !is_archive { return; }
 
                     if r_settings.selectable_tilesets.contains(&e.file_name().to_str().unwrap().to_string()) {
                         return;
@@ -185,29 +316,92 @@ pub struct TilesetSelected {
 
 pub fn tileset_selected(
     mut e_tileset_selected: EventReader<TilesetSelected>,
+    mut e_clear_tiles: EventWriter<ClearTiles>,
+    mut e_spawn_map_entity: EventWriter<SpawnMapEntity>,
+    mut e_log: EventWriter<LogMessage>,
     r_settings: Res<Settings>,
+    r_program: Res<Program>,
     mut r_graphics_resource: ResMut<GraphicsResource>,
     mut r_images: ResMut<Assets<Image>>,
+    mut r_grid: ResMut<Grid>,
+    mut q_tiles: Query<&mut Transform, (With<Tile>, Without<GridMarker>)>,
+    q_opened_project: Query<&OpenedProject>,
 ) {
-    match &r_settings.selected_cdda_dir {
-        None => return,
-        Some(_) => {}
+    let (gfx_dir, data_json_dir) = match (r_settings.gfx_dir(), r_settings.data_json_dir()) {
+        (Some(gfx_dir), Some(data_json_dir)) => (gfx_dir, data_json_dir),
+        _ => {
+            for e in e_tileset_selected.read() {
+                log::warn!("Cannot load the tileset {}, no CDDA directory is configured", e.name);
+                e_log.send(LogMessage::warning(format!("Cannot load the tileset {}, select the CDDA directory first", e.name)));
+            }
+            return;
+        }
     };
 
     for e in e_tileset_selected.read() {
-        let tileset_loader = LegacyTilesetLoader::new(r_settings.gfx_dir().unwrap().join(e.name.clone()));
-        let region_settings_loader = RegionSettingsLoader::new(
-            r_settings.data_json_dir().unwrap().join(r"regional_map_settings.json"),
-            "default".into(),
-        );
-
-        let textures = LegacyTextures::new(
-            tileset_loader,
-            region_settings_loader,
-            &mut r_images,
-        );
-
-        r_graphics_resource.textures = Some(Box::new(textures));
+        if r_graphics_resource.selected.as_ref().is_some_and(|(name, _)| *name == e.name) { continue; }
+
+        let had_textures = r_graphics_resource.textures.is_some();
+
+        let tileset = match r_graphics_resource.take_cached(&e.name) {
+            Some(cached) => cached,
+            None => {
+                let mut tileset_loader = LegacyTilesetLoader::new(gfx_dir.join(e.name.clone()));
+                tileset_loader.log_details = r_settings.log_tileset_details;
+                let region_settings_loader = RegionSettingsLoader::new(
+                    data_json_dir.join("regional_map_settings.json"),
+                    "default".into(),
+                );
+
+                let info = match tileset_loader.load() {
+                    Ok(tileset) => tileset.info,
+                    Err(err) => {
+                        log::error!("Could not load the tileset {} {:?}", e.name, err);
+                        continue;
+                    }
+                };
+
+                if info.tile_width != info.tile_height {
+                    log::warn!("The tileset {} has non square tiles, only their width is used for the grid", e.name);
+                }
+
+                let textures = LegacyTextures::new(
+                    &tileset_loader,
+                    region_settings_loader,
+                    &mut r_images,
+                );
+
+                e_log.send(LogMessage::info(tileset_loader.get_statistics().summary(&e.name)));
+
+                CachedTileset { name: e.name.clone(), textures: Box::new(textures), info }
+            }
+        };
+
+        // Sprites are scaled relative to the tile width of the tileset, not to their own size
+        r_grid.fit_to_tileset(&tileset.info);
+
+        for mut transform in q_tiles.iter_mut() {
+            transform.scale.x = r_grid.get_sprite_scale();
+            transform.scale.y = r_grid.get_sprite_scale();
+        }
+
+        r_graphics_resource.select(tileset);
+
+        // Cached tilesets might still use the fallback color and map edge from before they were changed
+        if let Some(textures) = r_graphics_resource.textures.as_mut() {
+            textures.set_fallback_color(r_settings.fallback_color);
+            textures.set_map_edge(r_settings.map_edge);
+        }
+
+        // The sprites of the opened Project still belong to the previous tileset
+        if !had_textures { continue; }
+
+        if let Some(project) = q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+            e_clear_tiles.send(ClearTiles {});
+            e_spawn_map_entity.send(SpawnMapEntity {
+                map_entity: Arc::new(project.map_entity.clone())
+            });
+        }
     }
 }
 
@@ -237,6 +431,120 @@ pub fn define_terrain_button_interaction(
     }
 }
 
+/// Cycles the focus between off, the terrain and the furniture layer.
+/// The focused layer is also the layer which is edited by the brush
+pub fn layer_focus_button_interaction(
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<LayerFocusMarker>)>,
+    mut q_text: Query<&mut Text, With<LayerFocusTextMarker>>,
+    mut r_focus: ResMut<LayerFocus>,
+    mut r_brush: ResMut<Brush>,
+) {
+    for interaction in q_interaction.iter() {
+        if *interaction != Interaction::Pressed { continue; }
+
+        match (r_focus.is_enabled, r_brush.layer) {
+            (false, _) => {
+                r_focus.is_enabled = true;
+                r_brush.layer = EditLayer::Terrain;
+            }
+            (true, EditLayer::Terrain) => r_brush.layer = EditLayer::Furniture,
+            (true, EditLayer::Furniture) => r_focus.is_enabled = false
+        }
+    }
+
+    if !r_focus.is_changed() && !r_brush.is_changed() { return; }
+
+    let label = match (r_focus.is_enabled, r_brush.layer) {
+        (false, _) => "Focus: Off",
+        (true, EditLayer::Terrain) => "Focus: Terrain",
+        (true, EditLayer::Furniture) => "Focus: Furniture"
+    };
+
+    for mut text in q_text.iter_mut() {
+        text.sections[0].value = label.into();
+    }
+}
+
+pub fn item_indicator_button_interaction(
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<ItemIndicatorMarker>)>,
+    mut q_text: Query<&mut Text, With<ItemIndicatorTextMarker>>,
+    mut r_item_indicators: ResMut<ItemIndicators>,
+) {
+    for interaction in q_interaction.iter() {
+        if *interaction != Interaction::Pressed { continue; }
+
+        r_item_indicators.is_enabled = !r_item_indicators.is_enabled;
+    }
+
+    if !r_item_indicators.is_changed() { return; }
+
+    let label = match r_item_indicators.is_enabled {
+        true => "Items: On",
+        false => "Items: Off"
+    };
+
+    for mut text in q_text.iter_mut() {
+        text.sections[0].value = label.into();
+    }
+}
+
+pub fn tile_statistics_button_interaction(
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<TileStatisticsMarker>)>,
+    mut r_menus: ResMut<Menus>,
+) {
+    for interaction in q_interaction.iter() {
+        if *interaction != Interaction::Pressed { continue; }
+
+        r_menus.is_tile_statistics_open = !r_menus.is_tile_statistics_open;
+    }
+}
+
+pub fn bookmarks_button_interaction(
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<BookmarksMarker>)>,
+    mut r_menus: ResMut<Menus>,
+) {
+    for interaction in q_interaction.iter() {
+        if *interaction != Interaction::Pressed { continue; }
+
+        r_menus.is_bookmarks_open = !r_menus.is_bookmarks_open;
+    }
+}
+
+/// Opens the palette editor with a new palette or closes it if it is already open
+pub fn palette_editor_button_interaction(
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<PaletteEditorMarker>)>,
+    r_palette_editor_data: Option<Res<PaletteEditorData>>,
+    mut commands: Commands,
+) {
+    for interaction in q_interaction.iter() {
+        if *interaction != Interaction::Pressed { continue; }
+
+        match r_palette_editor_data {
+            None => commands.insert_resource(PaletteEditorData::new(Palette::default())),
+            Some(_) => commands.remove_resource::<PaletteEditorData>()
+        };
+    }
+}
+
+pub fn project_settings_button_interaction(
+    q_interaction: Query<&Interaction, (Changed<Interaction>, With<ProjectSettingsMarker>)>,
+    mut r_menus: ResMut<Menus>,
+    mut commands: Commands,
+) {
+    for interaction in q_interaction.iter() {
+        match interaction {
+            Interaction::Pressed => {
+                match r_menus.is_project_settings_menu_open {
+                    false => commands.insert_resource(ProjectSettingsMenuData::default()),
+                    true => commands.remove_resource::<ProjectSettingsMenuData>()
+                };
+                r_menus.is_project_settings_menu_open = !r_menus.is_project_settings_menu_open;
+            }
+            _ => {}
+        }
+    }
+}
+
 pub fn settings_button_interaction(
     q_interaction: Query<&Interaction, (Changed<Interaction>, With<SettingsIconMarker>)>,
     mut contexts: EguiContexts,
@@ -245,8 +553,18 @@ pub fn settings_button_interaction(
     mut commands: Commands,
     mut r_settings: ResMut<Settings>,
     mut e_tileset_selected: EventWriter<TilesetSelected>,
+    mut e_clear_tiles: EventWriter<ClearTiles>,
+    mut e_spawn_map_entity: EventWriter<SpawnMapEntity>,
+    mut e_theme_changed: EventWriter<ThemeChanged>,
+    mut r_graphics: ResMut<GraphicsResource>,
+    r_known_tile_ids: Res<KnownTileIds>,
+    q_opened_project: Query<&OpenedProject>,
 ) {
     let gray_dark_color32 = r_program.config.style.gray_dark.into_color32();
+    let error_color32 = r_program.config.style.error.into_color32();
+    let selected_color32 = r_program.config.style.selected.into_color32();
+    let is_onboarding = r_menus.is_onboarding;
+    let mut is_rendering_changed = false;
 
     egui::Window::new("General Settings")
         .open(&mut r_menus.is_settings_menu_open)
@@ -256,6 +574,14 @@ pub fn settings_button_interaction(
                 ui.label("Here you can change the general settings of the editor which applies to all Projects");
                 ui.set_max_width(500.);
 
+                if is_onboarding {
+                    match (r_settings.needs_onboarding(), &r_settings.selected_tileset) {
+                        (true, _) => requirement_banner(ui, "Welcome! Click the CDDA Directory field below and pick the folder of your CDDA installation.", selected_color32),
+                        (false, None) => requirement_banner(ui, "Now select a tileset in the Tile Settings below.", selected_color32),
+                        (false, Some(_)) => {}
+                    }
+                }
+
                 add_settings_frame(
                     "General",
                     gray_dark_color32,
@@ -282,6 +608,159 @@ pub fn settings_button_interaction(
 
                             ui.label("CDDA Directory");
                         });
+
+                        ui.checkbox(
+                            &mut r_settings.watch_project_files,
+                            "Reload the opened Project when its file changes on disk",
+                        );
+
+                        let response = ui.checkbox(
+                            &mut r_settings.fill_missing_backgrounds,
+                            "Fill terrain without a background sprite with a solid color",
+                        );
+
+                        is_rendering_changed = response.changed();
+
+                        let response = ui.checkbox(
+                            &mut r_settings.render_floor_under_furniture,
+                            "Render a floor beneath furniture which is placed on undefined terrain",
+                        );
+
+                        is_rendering_changed |= response.changed();
+
+                        // Only changes the visibility of the sprites, so they do not have to be respawned
+                        ui.checkbox(
+                            &mut r_settings.render_furniture_backgrounds,
+                            "Render the background sprites of furniture",
+                        );
+
+                        let response = ui.checkbox(
+                            &mut r_settings.show_missing_textures,
+                            "Draw ids which are missing in the tileset with a checkerboard",
+                        );
+
+                        is_rendering_changed |= response.changed();
+
+                        ui.checkbox(
+                            &mut r_settings.log_tileset_details,
+                            "Log every missing sprite when a tileset is loaded instead of only a summary",
+                        );
+
+                        ui.horizontal(|ui| {
+                            let mut default_floor = r_settings.default_floor.clone();
+
+                            if tile_id_input(ui, &mut default_floor, &r_known_tile_ids, error_color32) {
+                                r_settings.default_floor = default_floor;
+                                is_rendering_changed |= r_settings.render_floor_under_furniture;
+                            }
+
+                            ui.label("Floor used if the map has no fill terrain");
+                        });
+
+                        if let Some(data_json_dir) = r_settings.data_json_dir() {
+                            if ui.button("Open CDDA JSON Folder").clicked() {
+                                match open_in_file_explorer(&data_json_dir) {
+                                    Ok(_) => {}
+                                    Err(e) => log::error!("Could not open {:?} in the file explorer: {}", data_json_dir, e)
+                                }
+                            }
+                        }
+                    },
+                );
+
+                add_settings_frame(
+                    "Appearance",
+                    gray_dark_color32,
+                    ui,
+                    |ui| {
+                        let mut theme = r_program.config.theme;
+
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut theme, Theme::Dark, "Dark");
+                            ui.selectable_value(&mut theme, Theme::Light, "Light");
+                            ui.label("Theme");
+                        });
+
+                        if theme != r_program.config.theme {
+                            r_program.config.set_theme(theme);
+                            e_theme_changed.send(ThemeChanged);
+                        }
+                    },
+                );
+
+                add_settings_frame(
+                    "New Projects",
+                    gray_dark_color32,
+                    ui,
+                    |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut r_settings.default_map_size.x).clamp_range(1..=240));
+                            ui.label("x");
+                            ui.add(egui::DragValue::new(&mut r_settings.default_map_size.y).clamp_range(1..=240));
+                            ui.label("Default Map Size");
+                        });
+
+                        ui.horizontal(|ui| {
+                            let mut default_fill_ter = r_settings.default_fill_ter.clone().unwrap_or_default();
+
+                            if tile_id_input(ui, &mut default_fill_ter, &r_known_tile_ids, error_color32) {
+                                r_settings.default_fill_ter = match default_fill_ter.is_empty() {
+                                    true => None,
+                                    false => Some(default_fill_ter)
+                                };
+                            }
+
+                            ui.label("Default Fill Terrain");
+                        });
+
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_id_source("row_size_mismatch")
+                                .selected_text(r_settings.row_size_mismatch.name())
+                                .show_ui(ui, |ui| {
+                                    for handling in RowSizeMismatch::ALL {
+                                        ui.selectable_value(&mut r_settings.row_size_mismatch, handling, handling.name());
+                                    }
+                                });
+                            ui.label("If imported rows do not fit the declared size");
+                        });
+                    },
+                );
+
+                add_settings_frame(
+                    "Grid",
+                    gray_dark_color32,
+                    ui,
+                    |ui| {
+                        ui.checkbox(
+                            &mut r_settings.show_overmap_guides,
+                            "Show guide lines at the boundaries of overmap tiles",
+                        );
+
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut r_settings.overmap_guide_spacing).clamp_range(1..=240));
+                            ui.label("Tiles between the guide lines");
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut r_settings.grid_fade_start).clamp_range(0. ..=64.));
+                            ui.label("Tile size below which the grid lines fade out");
+                        });
+
+                        ui.checkbox(
+                            &mut r_settings.show_origin_marker,
+                            "Show a marker at the origin of the map",
+                        );
+
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_id_source("origin_corner")
+                                .selected_text(r_settings.origin_corner.name())
+                                .show_ui(ui, |ui| {
+                                    for corner in OriginCorner::ALL {
+                                        ui.selectable_value(&mut r_settings.origin_corner, corner, corner.name());
+                                    }
+                                });
+                            ui.label("Corner from which the displayed coordinates are counted");
+                        });
                     },
                 );
 
@@ -310,11 +789,78 @@ pub fn settings_button_interaction(
                                     }
                                 });
                         });
+
+                        ui.checkbox(
+                            &mut r_settings.strict_painting,
+                            "Only allow painting characters which are defined in the map or its palettes",
+                        );
+
+                        ui.horizontal(|ui| {
+                            let previous_fallback_color = r_settings.fallback_color;
+
+                            egui::ComboBox::from_id_source("fallback_color")
+                                .selected_text(r_settings.fallback_color.name())
+                                .show_ui(ui, |ui| {
+                                    for color in FallbackColor::ALL {
+                                        ui.selectable_value(&mut r_settings.fallback_color, color, color.name());
+                                    }
+                                });
+                            ui.label("Color of the characters which are drawn for tiles without a sprite");
+
+                            if r_settings.fallback_color != previous_fallback_color {
+                                if let Some(textures) = r_graphics.textures.as_mut() {
+                                    textures.set_fallback_color(r_settings.fallback_color);
+                                }
+                                is_rendering_changed = true;
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            let previous_map_edge = r_settings.map_edge;
+
+                            egui::ComboBox::from_id_source("map_edge")
+                                .selected_text(r_settings.map_edge.name())
+                                .show_ui(ui, |ui| {
+                                    for map_edge in MapEdge::ALL {
+                                        ui.selectable_value(&mut r_settings.map_edge, map_edge, map_edge.name());
+                                    }
+                                });
+                            ui.label("Edge of the map").on_hover_text("Connected draws walls at the edge as if they continue in the next map");
+
+                            if r_settings.map_edge != previous_map_edge {
+                                if let Some(textures) = r_graphics.textures.as_mut() {
+                                    textures.set_map_edge(r_settings.map_edge);
+                                }
+                                is_rendering_changed = true;
+                            }
+                        });
+
+                        // Switching back to a cached tileset is instant, unloading them frees their sprites
+                        let cached_count = r_graphics.cached.len();
+                        let unload_button = ui.add_enabled(cached_count > 0, egui::Button::new(format!("Unload {} cached tilesets", cached_count)));
+                        if unload_button.clicked() {
+                            r_graphics.unload_cached();
+                        }
                     },
                 );
             });
         });
 
+    // The onboarding is finished once everything is configured or the user closed the settings
+    if r_menus.is_onboarding && (!r_menus.is_settings_menu_open || (!r_settings.needs_onboarding() && r_settings.selected_tileset.is_some())) {
+        r_menus.is_onboarding = false;
+    }
+
+    if is_rendering_changed {
+        // The fill colors and floors are spawned together with the sprites, so respawn the opened Project
+        if let Some(project) = q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+            e_clear_tiles.send(ClearTiles {});
+            e_spawn_map_entity.send(SpawnMapEntity {
+                map_entity: Arc::new(project.map_entity.clone())
+            });
+        }
+    }
+
     for interaction in q_interaction.iter() {
         match interaction {
             Interaction::Pressed => {
@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::{Commands, Query, Res, Resource, Window, With};
+use bevy::window::PrimaryWindow;
+use bevy_egui::egui::{Area, Button, Id, Pos2, RichText};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::common::Coordinates;
+use crate::program::data::{IntoColor32, OpenedProject, Program};
+use crate::ui::grid::resources::Grid;
+
+/// If enabled, every tile whose character has loot defined is marked with a badge
+#[derive(Resource, Default, Debug)]
+pub struct ItemIndicators {
+    pub is_enabled: bool,
+}
+
+/// Inserted when the badge of a tile was clicked, shows the loot of its character
+#[derive(Resource, Debug)]
+pub struct ItemMenuData {
+    pub character: char,
+}
+
+pub fn item_indicators(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    r_item_indicators: Res<ItemIndicators>,
+    r_program: Res<Program>,
+    r_grid: Res<Grid>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !r_item_indicators.is_enabled { return; }
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let cdda_data = match &r_program.config.cdda_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let window = q_windows.single();
+    let mut has_items: HashMap<char, bool> = HashMap::new();
+
+    for (coordinates, tile) in project.map_entity.tiles().iter() {
+        let x = coordinates.x as f32 * r_grid.tile_size - r_grid.offset.x;
+        let y = coordinates.y as f32 * r_grid.tile_size - r_grid.offset.y;
+
+        // Tiles outside of the window do not need a badge
+        if x + r_grid.tile_size < 0. || y + r_grid.tile_size < 0. || x > window.width() || y > window.height() { continue; }
+
+        let has_items = *has_items.entry(tile.character)
+            .or_insert_with(|| !project.map_entity.get_ids(cdda_data, &tile.character).items.is_empty());
+
+        if !has_items { continue; }
+
+        Area::new(Id::new(("item_indicator", coordinates.x, coordinates.y)))
+            .fixed_pos(Pos2::new(x, y))
+            .show(contexts.ctx_mut(), |ui| {
+                if ui.add(Button::new(RichText::new("i").size(10.)).small()).clicked() {
+                    commands.insert_resource(ItemMenuData { character: tile.character });
+                }
+            });
+    }
+}
+
+/// Marks every tile at which place_loot of the opened Project could place items, only shown together with the item badges
+pub fn place_loot_indicators(
+    mut contexts: EguiContexts,
+    r_item_indicators: Res<ItemIndicators>,
+    r_program: Res<Program>,
+    r_grid: Res<Grid>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !r_item_indicators.is_enabled { return; }
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let window = q_windows.single();
+    let color = r_program.config.style.selected.into_color32();
+
+    // Entries with overlapping ranges only get a single badge per tile
+    let coordinates: HashSet<Coordinates> = project.map_entity.object().place_loot.iter()
+        .flat_map(|p| p.get_coordinates())
+        .collect();
+
+    for coordinates in coordinates.iter() {
+        let x = coordinates.x as f32 * r_grid.tile_size - r_grid.offset.x;
+        let y = coordinates.y as f32 * r_grid.tile_size - r_grid.offset.y;
+
+        if x + r_grid.tile_size < 0. || y + r_grid.tile_size < 0. || x > window.width() || y > window.height() { continue; }
+
+        // Placed at the bottom right corner so it does not cover the badge of the character
+        Area::new(Id::new(("place_loot_indicator", coordinates.x, coordinates.y)))
+            .fixed_pos(Pos2::new(x + r_grid.tile_size - 10., y + r_grid.tile_size - 14.))
+            .interactable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.label(RichText::new("L").size(10.).strong().color(color));
+            });
+    }
+}
+
+pub fn item_menu(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    r_program: Res<Program>,
+    r_item_menu_data: Option<Res<ItemMenuData>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let menu_data = match r_item_menu_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let (project, cdda_data) = match (q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)), &r_program.config.cdda_data) {
+        (Some(p), Some(d)) => (p, d),
+        _ => {
+            commands.remove_resource::<ItemMenuData>();
+            return;
+        }
+    };
+
+    let items = project.map_entity.get_ids(cdda_data, &menu_data.character).items;
+    let mut is_open = true;
+
+    bevy_egui::egui::Window::new(format!("Items of '{}'", menu_data.character))
+        .collapsible(false)
+        .open(&mut is_open)
+        .show(contexts.ctx_mut(), |ui| {
+            if items.is_empty() {
+                ui.label("The character has no items");
+            }
+
+            for item in items.iter() {
+                ui.label(item.get_label());
+            }
+        });
+
+    if !is_open {
+        commands.remove_resource::<ItemMenuData>();
+    }
+}
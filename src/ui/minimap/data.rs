@@ -0,0 +1,53 @@
+use bevy::math::Vec2;
+
+/// Converts between the pixels of the minimap and the tiles of the map.
+/// The whole map is scaled to fit into the minimap and centered inside of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapProjection {
+    /// The position of the top left corner of the map in the minimap
+    pub origin: Vec2,
+    /// The size of a single tile in the minimap
+    pub tile_size: f32,
+}
+
+impl MinimapProjection {
+    pub fn new(minimap_position: Vec2, minimap_size: Vec2, map_size: Vec2) -> Self {
+        let tile_size = (minimap_size.x / map_size.x.max(1.)).min(minimap_size.y / map_size.y.max(1.));
+        let margin = (minimap_size - map_size * tile_size) / 2.;
+
+        return Self {
+            origin: minimap_position + margin,
+            tile_size,
+        };
+    }
+
+    /// Returns the position in the minimap of the position in tiles
+    pub fn to_minimap(&self, tiles: Vec2) -> Vec2 {
+        return self.origin + tiles * self.tile_size;
+    }
+
+    /// Returns the position in tiles of the position in the minimap
+    pub fn to_tiles(&self, minimap: Vec2) -> Vec2 {
+        return (minimap - self.origin) / self.tile_size;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use bevy::math::Vec2;
+
+    use crate::ui::minimap::data::MinimapProjection;
+
+    #[test]
+    pub fn test_wide_map_is_centered_and_converts_back() {
+        let projection = MinimapProjection::new(Vec2::new(10., 10.), Vec2::new(200., 200.), Vec2::new(40., 20.));
+
+        // The width limits the scale, so the map is centered vertically
+        assert_eq!(projection.tile_size, 5.);
+        assert_eq!(projection.to_minimap(Vec2::ZERO), Vec2::new(10., 60.));
+        assert_eq!(projection.to_minimap(Vec2::new(40., 20.)), Vec2::new(210., 160.));
+
+        let tiles = Vec2::new(12., 6.);
+        assert!(projection.to_tiles(projection.to_minimap(tiles)).abs_diff_eq(tiles, 0.0001));
+    }
+}
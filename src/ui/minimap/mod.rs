@@ -1,2 +1,3 @@
-pub(crate) mod plugin;
-pub(crate) mod systems;
\ No newline at end of file
+pub mod data;
+pub mod plugin;
+pub mod systems;
\ No newline at end of file
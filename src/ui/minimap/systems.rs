@@ -1,22 +1,37 @@
-use bevy::prelude::{Component, Entity, Query, Res, With};
+use bevy::math::Vec2 as BevyVec2;
+use bevy::prelude::{Component, Entity, Query, Res, ResMut, With};
 use bevy::window::PrimaryWindow;
-use bevy_egui::egui::{Align2, Direction, Frame, Layout, Margin, RichText, Vec2, Window};
+use bevy_egui::egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Vec2, Window};
+use bevy_egui::egui::ecolor::Hsva;
 use bevy_inspector_egui::bevy_egui::EguiContexts;
 use log::warn;
 
-use crate::common::Coordinates;
-use crate::program::data::{Config, IntoColor32, OpenedProject, Program};
-use crate::project::data::Project;
-use crate::tiles::data::Tile;
+use crate::program::data::{IntoColor32, OpenedProject, Program};
 use crate::ui::grid::resources::Grid;
+use crate::ui::minimap::data::MinimapProjection;
 
 #[derive(Component)]
 pub struct MinimapMarker;
 
+/// Characters are drawn on top of their tiles once the tiles are at least this big
+const MIN_CHARACTER_TILE_SIZE: f32 = 8.;
+
+fn to_pos2(position: BevyVec2) -> Pos2 {
+    return Pos2::new(position.x, position.y);
+}
+
+/// Every character gets its own dark color so the characters on top of it stay readable
+fn get_character_color(character: char) -> Color32 {
+    let hue = (character as u32 * 47 % 360) as f32 / 360.;
+    return Hsva::new(hue, 0.5, 0.35, 1.).into();
+}
+
+/// Shows the whole map with a rectangle around the area which is visible in the main view.
+/// Dragging on the minimap pans the main view, clicking centers it on the clicked tile
 pub fn show_minimap(
     mut contexts: EguiContexts,
+    mut r_grid: ResMut<Grid>,
     r_program: Res<Program>,
-    r_grid: Res<Grid>,
     q_windows: Query<&bevy::prelude::Window, With<PrimaryWindow>>,
     q_opened_project: Query<(Entity, &OpenedProject)>,
 ) {
@@ -26,8 +41,9 @@ pub fn show_minimap(
     };
 
     let window = q_windows.single();
+    let window_size = BevyVec2::new(window.resolution.width(), window.resolution.height());
 
-    let selected_color = r_program.config.style.black.into_color32();
+    let selected_color = r_program.config.style.selected.into_color32();
     let white = r_program.config.style.white.into_color32();
 
     Window::new("")
@@ -38,9 +54,6 @@ pub fn show_minimap(
         .min_size(Vec2::new(200., 200.))
         .max_size(Vec2::new(200., 200.))
         .show(contexts.ctx_mut(), |ui| {
-            ui.set_width(200.);
-            ui.set_height(200.);
-
             let project = match r_program.projects.get(opened_project.index) {
                 None => {
                     warn!("Failed to get Project at index {}. Project does not exist", opened_project.index);
@@ -49,44 +62,57 @@ pub fn show_minimap(
                 Some(p) => p
             };
 
-            let tile_camera_offset = r_grid.offset / r_grid.tile_size;
-
-            let window_tile_center_w = (window.resolution.width() / r_grid.tile_size) / 2.;
-            let window_tile_center_h = (window.resolution.height() / r_grid.tile_size) / 2.;
-            let cursor_position = ((window.cursor_position().unwrap_or(bevy::prelude::Vec2::new(0., 0.)) + r_grid.offset) / r_grid.tile_size).as_ivec2();
-
-            ui.vertical(|ui| {
-                for y in (tile_camera_offset.y + window_tile_center_h) as i32..(16. + tile_camera_offset.y + window_tile_center_h) as i32 {
-                    let mut row: Vec<RichText> = vec![];
-
-                    for x in (tile_camera_offset.x + window_tile_center_w) as i32..(16. + tile_camera_offset.x + window_tile_center_w) as i32 {
-                        let tile = match project.map_entity.tiles().get(&Coordinates::new(x, y)) {
-                            None => {
-                                row.push(" ".into());
-                                continue;
-                            }
-                            Some(t) => t
-                        };
-
-
-                        let character = RichText::new(tile.character)
-                            .size(12.)
-                            .extra_letter_spacing(6.);
-
-                        let character = match x == cursor_position.x && y == cursor_position.y {
-                            true => character.color(selected_color)
-                                .background_color(white),
-                            false => character.color(white)
-                        };
-
-                        row.push(character);
-                    }
-
-
-                    ui.horizontal(|ui| {
-                        row.into_iter().for_each(|rt| { ui.label(rt); })
-                    });
+            let (response, painter) = ui.allocate_painter(Vec2::new(200., 200.), Sense::click_and_drag());
+            let projection = MinimapProjection::new(
+                BevyVec2::new(response.rect.min.x, response.rect.min.y),
+                BevyVec2::new(response.rect.width(), response.rect.height()),
+                project.map_entity.size(),
+            );
+
+            let cursor_position = ((window.cursor_position().unwrap_or(BevyVec2::ZERO) + r_grid.offset) / r_grid.tile_size).floor();
+
+            for (coordinates, tile) in project.map_entity.tiles().iter() {
+                let position = BevyVec2::new(coordinates.x as f32, coordinates.y as f32);
+                let tile_rect = Rect::from_min_size(
+                    to_pos2(projection.to_minimap(position)),
+                    Vec2::splat(projection.tile_size),
+                );
+
+                let color = match position == cursor_position {
+                    true => white,
+                    false => get_character_color(tile.character)
+                };
+
+                painter.rect_filled(tile_rect, 0., color);
+
+                if projection.tile_size >= MIN_CHARACTER_TILE_SIZE {
+                    painter.text(
+                        tile_rect.center(),
+                        Align2::CENTER_CENTER,
+                        tile.character,
+                        FontId::monospace(projection.tile_size * 0.8),
+                        white,
+                    );
+                }
+            }
+
+            // The area of the map which is visible in the main view
+            let view_start = r_grid.offset / r_grid.tile_size;
+            let view_end = view_start + window_size / r_grid.tile_size;
+
+            painter.rect_stroke(
+                Rect::from_min_max(to_pos2(projection.to_minimap(view_start)), to_pos2(projection.to_minimap(view_end))),
+                0.,
+                Stroke::new(1.5, selected_color),
+            );
+
+            if response.dragged() {
+                let delta = response.drag_delta();
+                r_grid.offset += BevyVec2::new(delta.x, delta.y) / projection.tile_size * r_grid.tile_size;
+            } else if response.clicked() {
+                if let Some(pointer) = response.interact_pointer_pos() {
+                    r_grid.center_on(projection.to_tiles(BevyVec2::new(pointer.x, pointer.y)), window_size);
                 }
-            });
+            }
         });
-}
\ No newline at end of file
+}
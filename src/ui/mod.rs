@@ -1,25 +1,56 @@
 use bevy::app::{App, Plugin, PostStartup, Update};
 use bevy::prelude::{apply_deferred, Color, Component, in_state, IntoSystemConfigs, Resource};
 
+use crate::graphics::KnownTileIds;
 use crate::program::data::ProgramState;
 use crate::ui::hotbar::spawn_hotbar;
-use crate::ui::interaction::{cdda_folder_picked, CDDADirPicked, define_terrain_button_interaction, file_dialog_cdda_dir_picked, file_loaded_reader, import_button_interaction, open_button_interaction, save_button_interaction, settings_button_interaction, tileset_selected, TilesetSelected};
+use crate::ui::interaction::{bookmarks_button_interaction, palette_editor_button_interaction, cdda_folder_picked, CDDADirPicked, define_terrain_button_interaction, export_button_interaction, file_dialog_cdda_dir_picked, file_loaded_reader, file_saved_reader, import_button_interaction, item_indicator_button_interaction, layer_focus_button_interaction, open_button_interaction, project_settings_button_interaction, save_button_interaction, settings_button_interaction, tile_statistics_button_interaction, tileset_selected, TilesetSelected};
+use crate::ui::close_prompt::close_prompt;
+use crate::ui::item_indicators::{item_indicators, item_menu, ItemIndicators, place_loot_indicators};
+use crate::ui::overwrite_prompt::overwrite_prompt;
+use crate::ui::palette_editor::{palette_editor, palette_saved_reader};
 use crate::ui::minimap::plugin::MinimapPlugin;
 use crate::ui::minimap::systems::show_minimap;
-use crate::ui::systems::{button_hover_system, button_toggle_system, check_ui_interaction, reset_toggle_reader, ResetToggle, spawn_initial_tabs};
-use crate::ui::tabs::{create_project_menu, on_add_tab_button_click, setup, spawn_tab_reader, tab_clicked};
+use crate::ui::project_settings::project_settings_menu;
+use crate::ui::reload_prompt::reload_prompt;
+use crate::ui::status_bar::status_bar;
+use crate::ui::style::ThemeChanged;
+use crate::ui::systems::{button_hover_system, button_toggle_system, check_ui_interaction, close_menu_on_escape, empty_map_placeholder, reset_toggle_reader, ResetToggle, spawn_initial_tabs, toggle_fullscreen, update_known_tile_ids};
+use crate::ui::tabs::{create_project_menu, on_add_tab_button_click, selected_tabs_menu, setup, spawn_tab_reader, tab_clicked, tab_context_menu, tab_drag_system, update_tab_names};
 use crate::ui::tabs::events::SpawnTab;
 use crate::ui::terrain::terrain_menu;
+use crate::ui::tile_statistics::tile_statistics;
+use crate::ui::export::{export_menu, mapgen_saved_reader};
+use crate::ui::bookmarks::{bookmarks_menu, next_bookmark_system};
+use crate::ui::error_dialog::{error_dialog, ShowError};
+use crate::ui::origin_marker::origin_marker;
+use crate::ui::om_terrain_labels::om_terrain_labels;
+use crate::tiles::data::in_editor_mode;
+use crate::ui::tile_context_menu::{open_tile_context_menu, tile_context_menu};
 
 mod systems;
 mod terrain;
-pub(crate) mod interaction;
-pub(crate) mod hotbar;
-pub(crate) mod tabs;
-pub(crate) mod grid;
-pub(crate) mod style;
+pub mod interaction;
+pub mod hotbar;
+pub mod tabs;
+pub mod grid;
+pub mod style;
 mod egui_utils;
-pub(crate) mod minimap;
+pub mod minimap;
+pub mod project_settings;
+pub mod reload_prompt;
+pub mod close_prompt;
+pub mod overwrite_prompt;
+pub mod palette_editor;
+pub mod item_indicators;
+pub mod export;
+pub mod bookmarks;
+pub mod tile_statistics;
+pub mod status_bar;
+pub mod error_dialog;
+pub mod origin_marker;
+pub mod om_terrain_labels;
+pub mod tile_context_menu;
 
 pub struct UiPlugin;
 
@@ -27,21 +58,53 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(PostStartup, (spawn_hotbar, spawn_initial_tabs, apply_deferred, setup).chain());
         app.insert_resource(IsCursorCaptured(false));
+        app.insert_resource(KnownTileIds::default());
+        app.insert_resource(ItemIndicators::default());
         
         app.add_event::<CDDADirPicked>();
         app.add_event::<TilesetSelected>();
         app.add_event::<ResetToggle>();
         app.add_event::<SpawnTab>();
+        app.add_event::<ThemeChanged>();
+        app.add_event::<ShowError>();
 
         app.add_systems(
             Update,
             (
                 save_button_interaction,
+                export_button_interaction,
                 file_dialog_cdda_dir_picked,
                 tileset_selected,
                 define_terrain_button_interaction,
                 terrain_menu,
-                show_minimap
+                project_settings_button_interaction,
+                layer_focus_button_interaction,
+                item_indicator_button_interaction,
+                project_settings_menu,
+                reload_prompt,
+                close_prompt,
+                overwrite_prompt,
+                show_minimap,
+                status_bar,
+                item_indicators,
+                item_menu,
+                tile_statistics_button_interaction,
+                tile_statistics
+            ).run_if(in_state(ProgramState::ProjectOpen)),
+        );
+
+        app.add_systems(
+            Update,
+            (
+                bookmarks_button_interaction,
+                palette_editor_button_interaction,
+                bookmarks_menu,
+                next_bookmark_system,
+                origin_marker,
+                place_loot_indicators,
+                om_terrain_labels,
+                open_tile_context_menu.run_if(in_editor_mode),
+                tile_context_menu
             ).run_if(in_state(ProgramState::ProjectOpen)),
         );
 
@@ -57,14 +120,20 @@ impl Plugin for UiPlugin {
                 import_button_interaction,
                 open_button_interaction,
                 file_loaded_reader,
+                file_saved_reader,
                 spawn_tab_reader,
                 on_add_tab_button_click,
                 tab_clicked,
+                tab_drag_system,
+                tab_context_menu,
                 file_dialog_cdda_dir_picked,
                 tileset_selected,
-                create_project_menu
+                create_project_menu,
+                selected_tabs_menu
             ).chain(),
         );
+
+        app.add_systems(Update, (update_known_tile_ids, update_tab_names, close_menu_on_escape, palette_editor, palette_saved_reader, export_menu, mapgen_saved_reader, empty_map_placeholder, toggle_fullscreen, error_dialog));
     }
 }
 
@@ -89,5 +158,11 @@ pub struct ToggleEffect {
 #[derive(Debug)]
 pub struct CDDADirContents;
 
+#[derive(Debug)]
+pub struct CDDAMapgenContents;
+
+#[derive(Debug)]
+pub struct CDDAPaletteContents;
+
 #[derive(Resource)]
 pub struct IsCursorCaptured(pub bool);
\ No newline at end of file
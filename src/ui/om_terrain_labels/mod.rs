@@ -0,0 +1,58 @@
+use bevy::prelude::{IVec2, Query, Res};
+use bevy_egui::egui::{Area, Id, Order, Pos2, RichText};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::map::data::{MapEntity, Multi};
+use crate::program::data::{IntoColor32, OpenedProject, Program};
+use crate::tiles::data::InteractionMode;
+use crate::ui::grid::resources::Grid;
+
+/// Shows the name of every overmap terrain of a Multi map at its top left corner
+pub fn om_terrain_labels(
+    mut contexts: EguiContexts,
+    r_program: Res<Program>,
+    r_grid: Res<Grid>,
+    r_mode: Res<InteractionMode>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let multi = match &project.map_entity {
+        MapEntity::Multi(m) => m,
+        _ => return
+    };
+
+    // The labels would not match the turned tiles of the Preview Mode
+    if *r_mode == InteractionMode::Preview && project.map_entity.rotation() != 0 { return; }
+
+    // The ids of a flat om_terrain list share one overmap terrain, so they are joined into one label
+    let mut labels: Vec<(IVec2, String)> = Vec::new();
+
+    for (i, (position, om_terrain)) in multi.om_terrain.get_positioned().into_iter().enumerate() {
+        match labels.last_mut() {
+            Some((last, text)) if *last == position => text.push_str(format!(", {}: {}", i, om_terrain).as_str()),
+            _ => labels.push((position, format!("{}: {}", i, om_terrain)))
+        }
+    }
+
+    for (position, text) in labels.iter() {
+        let x = (position.x * Multi::OVERMAP_TERRAIN_SIZE) as f32 * r_grid.tile_size - r_grid.offset.x;
+        let y = (position.y * Multi::OVERMAP_TERRAIN_SIZE) as f32 * r_grid.tile_size - r_grid.offset.y;
+
+        Area::new(Id::new("om_terrain_label").with(position))
+            .fixed_pos(Pos2::new(x, y))
+            .order(Order::Background)
+            .interactable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.label(
+                    RichText::new(text)
+                        .size(10.)
+                        .background_color(r_program.config.style.gray_dark.into_color32())
+                        .color(r_program.config.style.white.into_color32())
+                );
+            });
+    }
+}
@@ -0,0 +1,47 @@
+use bevy::prelude::{Query, Res};
+use bevy_egui::egui::{Area, Id, Order, Pos2, RichText};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::common::Coordinates;
+use crate::program::data::{IntoColor32, OpenedProject, Program};
+use crate::settings::data::Settings;
+use crate::ui::grid::resources::Grid;
+
+/// Marks the tile which is shown as (0, 0) and the directions in which the displayed coordinates increase
+pub fn origin_marker(
+    mut contexts: EguiContexts,
+    r_settings: Res<Settings>,
+    r_program: Res<Program>,
+    r_grid: Res<Grid>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !r_settings.show_origin_marker { return; }
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let map_size = project.map_entity.size().as_ivec2();
+    if map_size.x < 1 || map_size.y < 1 { return; }
+
+    // Converting the displayed origin returns the stored coordinates of the origin tile
+    let origin = r_settings.origin_corner.to_display(&Coordinates::new(0, 0), map_size);
+    let (x_arrow, y_arrow) = r_settings.origin_corner.axis_arrows();
+
+    let x = origin.x as f32 * r_grid.tile_size - r_grid.offset.x;
+    let y = origin.y as f32 * r_grid.tile_size - r_grid.offset.y;
+
+    Area::new(Id::new("origin_marker"))
+        .fixed_pos(Pos2::new(x, y))
+        .order(Order::Background)
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(
+                RichText::new(format!("0,0 x{} y{}", x_arrow, y_arrow))
+                    .size(10.)
+                    .background_color(r_program.config.style.gray_dark.into_color32())
+                    .color(r_program.config.style.selected.into_color32())
+            );
+        });
+}
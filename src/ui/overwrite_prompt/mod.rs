@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use bevy::prelude::{Commands, EventWriter, Res, ResMut, Resource};
+use bevy_egui::egui::{Align2, Vec2, Window};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::program::data::Program;
+use crate::project::data::{ProjectFileWatcher, ProjectSaveState};
+use crate::project::io::save_project_to_path;
+use crate::ui::error_dialog::ShowError;
+use crate::ui::interaction::open_save_dialog;
+
+/// Inserted when a Project is about to be saved over its existing file
+#[derive(Resource)]
+pub struct OverwritePromptData {
+    pub index: usize,
+    pub path: PathBuf,
+}
+
+pub fn overwrite_prompt(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut e_show_error: EventWriter<ShowError>,
+    mut r_program: ResMut<Program>,
+    mut r_watcher: ResMut<ProjectFileWatcher>,
+    r_overwrite_prompt_data: Option<Res<OverwritePromptData>>,
+) {
+    let prompt_data = match r_overwrite_prompt_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let project = match r_program.projects.get_mut(prompt_data.index) {
+        None => {
+            commands.remove_resource::<OverwritePromptData>();
+            return;
+        }
+        Some(p) => p
+    };
+
+    let mut should_overwrite = false;
+    let mut should_save_as = false;
+    let mut should_close = false;
+
+    Window::new("Overwrite File")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("The file {:?} already exists.", prompt_data.path));
+            ui.label("Do you want to overwrite it? The current file will be kept as a .bak copy.");
+
+            ui.horizontal(|ui| {
+                if ui.button("Overwrite").clicked() {
+                    should_overwrite = true;
+                }
+
+                if ui.button("Save as").clicked() {
+                    should_save_as = true;
+                }
+
+                if ui.button("Cancel").clicked() {
+                    should_close = true;
+                }
+            });
+        });
+
+    if should_overwrite {
+        match save_project_to_path(project, &prompt_data.path) {
+            Ok(_) => {
+                project.save_state = ProjectSaveState::Saved(prompt_data.path.clone());
+                project.is_dirty = false;
+
+                // Do not reload the Project because of our own save
+                r_watcher.last_modified.remove(&prompt_data.path);
+            }
+            Err(e) => {
+                e_show_error.send(ShowError::save("Could not save the Project", &e));
+            }
+        }
+
+        should_close = true;
+    }
+
+    if should_save_as {
+        open_save_dialog(&mut commands, prompt_data.index, project);
+
+        should_close = true;
+    }
+
+    if should_close {
+        commands.remove_resource::<OverwritePromptData>();
+    }
+}
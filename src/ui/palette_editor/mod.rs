@@ -0,0 +1,280 @@
+use bevy::prelude::{Commands, EventReader, EventWriter, Res, ResMut, Resource};
+use bevy_egui::egui::{Button, ComboBox, DragValue, RichText, ScrollArea, Ui, Vec2, Window};
+use bevy_file_dialog::{DialogFileSaved, FileDialogExt};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::common::{LogMessage, MeabyWeighted};
+use crate::graphics::KnownTileIds;
+use crate::palettes::data::{MapGenValue, MapObjectId, MeabyParam, Palette, Parameter, ParameterType};
+use crate::palettes::io::palette_to_cdda_json_string;
+use crate::program::data::{IntoColor32, Program};
+use crate::project::data::DefinitionKind;
+use crate::ui::CDDAPaletteContents;
+use crate::ui::egui_utils::tile_id_input;
+use crate::ui::error_dialog::ShowError;
+
+/// Inserted while a palette is edited, the editor is shown as long as this exists
+#[derive(Resource)]
+pub struct PaletteEditorData {
+    pub palette: Palette,
+    new_character: String,
+    new_id: String,
+    new_kind: DefinitionKind,
+    new_parameter_name: String,
+    new_parameter_default: String,
+    new_parameter_kind: DefinitionKind,
+}
+
+impl PaletteEditorData {
+    pub fn new(palette: Palette) -> Self {
+        return Self {
+            palette,
+            new_character: String::new(),
+            new_id: String::new(),
+            new_kind: DefinitionKind::Terrain,
+            new_parameter_name: String::new(),
+            new_parameter_default: String::new(),
+            new_parameter_kind: DefinitionKind::Terrain,
+        };
+    }
+}
+
+enum PaletteEditorAction {
+    RemoveCharacter(DefinitionKind, char),
+    RemoveEntry(DefinitionKind, char, usize),
+    SetWeight(DefinitionKind, char, usize, u32),
+    RemoveParameter(String),
+}
+
+fn get_kind_name(kind: &DefinitionKind) -> &'static str {
+    return match kind {
+        DefinitionKind::Terrain => "Terrain",
+        DefinitionKind::Furniture => "Furniture",
+    };
+}
+
+/// Picks if the terrain or the furniture definitions are edited
+pub fn kind_combo_box(ui: &mut Ui, id: &str, kind: &mut DefinitionKind) {
+    ComboBox::from_id_source(id)
+        .selected_text(get_kind_name(kind))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(kind, DefinitionKind::Terrain, get_kind_name(&DefinitionKind::Terrain));
+            ui.selectable_value(kind, DefinitionKind::Furniture, get_kind_name(&DefinitionKind::Furniture));
+        });
+}
+
+fn add_definitions(
+    ui: &mut Ui,
+    palette: &Palette,
+    kind: DefinitionKind,
+    r_program: &Program,
+) -> Option<PaletteEditorAction> {
+    let mut action = None;
+
+    let definitions = match kind {
+        DefinitionKind::Terrain => &palette.terrain,
+        DefinitionKind::Furniture => &palette.furniture,
+    };
+
+    let mut characters: Vec<&char> = definitions.keys().collect();
+    characters.sort();
+
+    ui.label(RichText::new(get_kind_name(&kind)).size(16.));
+
+    for character in characters {
+        let entries: Vec<&MeabyWeighted<MeabyParam>> = match definitions.get(character).unwrap() {
+            MapObjectId::Single(entry) => vec![entry],
+            MapObjectId::Grouped(entries) | MapObjectId::Distribution { distribution: entries } => entries.iter().collect(),
+            // Parameters and switches can only be removed
+            _ => vec![]
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(character.to_string()).size(16.));
+
+            for (i, entry) in entries.iter().enumerate() {
+                let id = match entry.value() {
+                    MeabyParam::TileId(id) => id.clone(),
+                    MeabyParam::Parameter(p) => p.param.clone(),
+                };
+
+                let mut weight = match entry {
+                    MeabyWeighted::NotWeighted(_) => 1,
+                    MeabyWeighted::Weighted(w) => w.weight
+                };
+
+                ui.label(id);
+
+                if ui.add(DragValue::new(&mut weight).clamp_range(1..=1000)).changed() {
+                    action = Some(PaletteEditorAction::SetWeight(kind.clone(), *character, i, weight));
+                }
+
+                if entries.len() > 1 && ui.small_button("x").clicked() {
+                    action = Some(PaletteEditorAction::RemoveEntry(kind.clone(), *character, i));
+                }
+            }
+
+            if entries.is_empty() {
+                ui.label("Parameter or Switch");
+            }
+
+            let response = ui.add_sized(
+                Vec2::new(24., 24.),
+                Button::new("X").fill(r_program.config.style.error.into_color32()),
+            );
+
+            if response.clicked() {
+                action = Some(PaletteEditorAction::RemoveCharacter(kind.clone(), *character));
+            }
+        });
+    }
+
+    return action;
+}
+
+pub fn palette_editor(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    r_program: Res<Program>,
+    r_known_tile_ids: Res<KnownTileIds>,
+    r_palette_editor_data: Option<ResMut<PaletteEditorData>>,
+) {
+    let mut data = match r_palette_editor_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let data = data.as_mut();
+    let error_color32 = r_program.config.style.error.into_color32();
+
+    let mut is_open = true;
+    let mut action = None;
+    let mut should_add_entry = false;
+    let mut should_add_parameter = false;
+    let mut should_save = false;
+
+    Window::new("Palette Editor")
+        .open(&mut is_open)
+        .resizable(true)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut data.palette.id);
+                ui.label("Id");
+            });
+
+            ScrollArea::vertical().max_height(400.).show(ui, |ui| {
+                for kind in [DefinitionKind::Terrain, DefinitionKind::Furniture] {
+                    if let Some(a) = add_definitions(ui, &data.palette, kind, r_program.as_ref()) {
+                        action = Some(a);
+                    }
+                }
+
+                ui.label(RichText::new("Parameters").size(16.));
+
+                let mut parameters: Vec<&String> = data.palette.parameters.keys().collect();
+                parameters.sort();
+
+                for name in parameters {
+                    let parameter = data.palette.parameters.get(name).unwrap();
+
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{} ({})", name, parameter.parameter_type.name()));
+                        ui.label(parameter.default.get_possible_values().join(", "));
+
+                        if ui.small_button("X").clicked() {
+                            action = Some(PaletteEditorAction::RemoveParameter(name.clone()));
+                        }
+                    });
+                }
+            });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.add(bevy_egui::egui::TextEdit::singleline(&mut data.new_character).char_limit(1).desired_width(24.));
+                tile_id_input(ui, &mut data.new_id, &r_known_tile_ids, error_color32);
+                kind_combo_box(ui, "palette_editor_new_kind", &mut data.new_kind);
+
+                if ui.button("Add").clicked() {
+                    should_add_entry = true;
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.add(bevy_egui::egui::TextEdit::singleline(&mut data.new_parameter_name).hint_text("Parameter").desired_width(120.));
+                tile_id_input(ui, &mut data.new_parameter_default, &r_known_tile_ids, error_color32);
+                kind_combo_box(ui, "palette_editor_new_parameter_kind", &mut data.new_parameter_kind);
+
+                if ui.button("Add Parameter").clicked() {
+                    should_add_parameter = true;
+                }
+            });
+
+            if ui.button("Save").clicked() {
+                should_save = true;
+            }
+        });
+
+    if should_add_entry && !data.new_id.is_empty() {
+        if let Some(character) = data.new_character.chars().next() {
+            data.palette.add_entry(&data.new_kind, character, data.new_id.clone());
+        }
+    }
+
+    if should_add_parameter && !data.new_parameter_name.is_empty() && !data.new_parameter_default.is_empty() {
+        let parameter_type = match data.new_parameter_kind {
+            DefinitionKind::Terrain => ParameterType::TerStrId,
+            DefinitionKind::Furniture => ParameterType::FurnStrId,
+        };
+
+        data.palette.parameters.insert(
+            data.new_parameter_name.clone(),
+            Parameter {
+                parameter_type,
+                default: MapGenValue::Simple(data.new_parameter_default.clone()),
+                calculated_value: None,
+            },
+        );
+    }
+
+    match action {
+        None => {}
+        Some(PaletteEditorAction::RemoveCharacter(kind, character)) => {
+            data.palette.get_definitions_mut(&kind).remove(&character);
+        }
+        Some(PaletteEditorAction::RemoveEntry(kind, character, index)) => {
+            data.palette.remove_entry(&kind, character, index);
+        }
+        Some(PaletteEditorAction::SetWeight(kind, character, index, weight)) => {
+            data.palette.set_entry_weight(&kind, character, index, weight);
+        }
+        Some(PaletteEditorAction::RemoveParameter(name)) => {
+            data.palette.parameters.remove(&name);
+        }
+    }
+
+    if should_save {
+        commands.dialog()
+            .add_filter("", vec!["json"].as_slice())
+            .set_file_name(format!("{}.json", data.palette.id))
+            .save_file::<CDDAPaletteContents>(palette_to_cdda_json_string(&data.palette).into_bytes());
+    }
+
+    if !is_open {
+        commands.remove_resource::<PaletteEditorData>();
+    }
+}
+
+/// Tells the user where the palette was saved or why it could not be saved
+pub fn palette_saved_reader(
+    mut e_file_saved: EventReader<DialogFileSaved<CDDAPaletteContents>>,
+    mut e_log: EventWriter<LogMessage>,
+    mut e_show_error: EventWriter<ShowError>,
+) {
+    for event in e_file_saved.read() {
+        match &event.result {
+            Ok(_) => { e_log.send(LogMessage::info(format!("Saved the palette to {:?}", event.path))); }
+            Err(e) => { e_show_error.send(ShowError::with_path("Could not save the palette", e.to_string(), event.path.clone())); }
+        };
+    }
+}
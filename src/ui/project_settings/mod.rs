@@ -0,0 +1,607 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bevy::prelude::{EventWriter, IVec2, Query, Res, ResMut, Resource};
+use bevy_egui::egui::{Align, Align2, Button, ComboBox, Layout, RichText, TextEdit, Vec2, Window};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::common::MeabyWeighted;
+use crate::graphics::KnownTileIds;
+use crate::map::data::{CharacterUsageReport, ClearTiles, ComputedParameters, MapEntity, Multi, MultiOmTerrain, SpawnMapEntity, TileDeleteEvent, TilePlaceEvent, UpdateSpriteEvent};
+use crate::map::io::ParameterId;
+use crate::palettes::data::{MapGenValue, MapObjectId, MeabyParam, ParameterType};
+use crate::program::data::{CDDAData, IntoColor32, Menus, OpenedProject, Program};
+use crate::tiles::data::Tile;
+use crate::ui::egui_utils::{add_settings_frame, CDDA_DIR_NOT_CONFIGURED, requirement_banner, tile_id_input};
+
+#[derive(Resource, Default)]
+pub struct ProjectSettingsMenuData {
+    selected_palette: Option<String>,
+    /// The edited om_terrain and the index of the Project it belongs to
+    om_terrain_input: Option<(usize, String)>,
+    /// The edited om_terrains of a map with multiple om_terrains, filled together with [Self::om_terrain_input]
+    multi_om_terrain_inputs: Vec<String>,
+    om_terrain_error: Option<String>,
+    /// The typed ids of ter_str_id and furn_str_id parameters, an override is only applied once the id is known
+    parameter_inputs: HashMap<ParameterId, String>,
+    is_clear_map_confirmation_open: bool,
+    is_flatten_confirmation_open: bool,
+    character_usage_report: Option<CharacterUsageReport>,
+    report_sort_order: ReportSortOrder,
+}
+
+#[derive(Default, PartialEq, Clone, Copy)]
+enum ReportSortOrder {
+    #[default]
+    Character,
+    Usage,
+}
+
+struct ParameterInfo {
+    id: ParameterId,
+    parameter_type: ParameterType,
+    default: MapGenValue,
+    /// The id of the palette which defines this parameter or None if it is defined by the map itself
+    source: Option<String>,
+}
+
+fn collect_palette_parameters(
+    computed_parameters: &ComputedParameters,
+    cdda_data: &CDDAData,
+    parameters: &mut Vec<ParameterInfo>,
+) {
+    for (palette_id, palette_parameters) in computed_parameters.palettes.iter() {
+        if let Some(palette) = cdda_data.palettes.get(palette_id) {
+            for (id, parameter) in palette.parameters.iter() {
+                parameters.push(ParameterInfo {
+                    id: id.clone(),
+                    parameter_type: parameter.parameter_type.clone(),
+                    default: parameter.default.clone(),
+                    source: Some(palette_id.clone()),
+                });
+            }
+        }
+
+        collect_palette_parameters(palette_parameters, cdda_data, parameters);
+    }
+}
+
+pub fn project_settings_menu(
+    mut contexts: EguiContexts,
+    mut r_program: ResMut<Program>,
+    mut r_menus: ResMut<Menus>,
+    mut e_update_sprite: EventWriter<UpdateSpriteEvent>,
+    mut e_delete_tile: EventWriter<TileDeleteEvent>,
+    mut e_place_tile: EventWriter<TilePlaceEvent>,
+    mut e_clear_tiles: EventWriter<ClearTiles>,
+    mut e_spawn_map_entity: EventWriter<SpawnMapEntity>,
+    r_project_settings_menu_data: Option<ResMut<ProjectSettingsMenuData>>,
+    r_known_tile_ids: Res<KnownTileIds>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let mut menu_data = match r_project_settings_menu_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let opened_project = match q_opened_project.iter().next() {
+        None => return,
+        Some(p) => p
+    };
+
+    let cdda_data = match r_program.config.cdda_data.clone() {
+        None => {
+            let error_color32 = r_program.config.style.error.into_color32();
+
+            Window::new("Project Settings")
+                .open(&mut r_menus.is_project_settings_menu_open)
+                .show(contexts.ctx_mut(), |ui| requirement_banner(ui, CDDA_DIR_NOT_CONFIGURED, error_color32));
+
+            return;
+        }
+        Some(d) => d
+    };
+
+    let gray_dark_color32 = r_program.config.style.gray_dark.into_color32();
+    let error_color32 = r_program.config.style.error.into_color32();
+
+    let mut available_palettes: Vec<String> = cdda_data.palettes.keys().cloned().collect();
+    available_palettes.sort();
+
+    let project = match r_program.projects.get_mut(opened_project.index) {
+        None => return,
+        Some(p) => p
+    };
+
+    // Fill the input with the id of the Project every time another Project is opened
+    if menu_data.om_terrain_input.as_ref().map(|(i, _)| *i) != Some(opened_project.index) {
+        let om_terrain = match &project.map_entity {
+            MapEntity::Single(s) => s.om_terrain.clone(),
+            _ => String::new()
+        };
+
+        menu_data.multi_om_terrain_inputs = match &project.map_entity {
+            MapEntity::Multi(m) => m.om_terrain.get_positioned().iter().map(|(_, id)| id.to_string()).collect(),
+            _ => Vec::new()
+        };
+
+        menu_data.om_terrain_input = Some((opened_project.index, om_terrain));
+        menu_data.om_terrain_error = None;
+        menu_data.parameter_inputs.clear();
+    }
+
+    let mut should_rename = false;
+    let mut multi_om_terrain_to_rename: Option<usize> = None;
+    let mut palette_to_remove: Option<usize> = None;
+    let mut palette_to_add: Option<String> = None;
+    let mut is_space_empty_changed = false;
+    let mut is_fill_ter_changed = false;
+    let mut should_clear_map = false;
+    let mut should_flatten_palettes = false;
+    let mut parameter_to_override: Option<(ParameterId, String)> = None;
+    let mut parameter_to_reset: Option<ParameterId> = None;
+
+    let mut parameters: Vec<ParameterInfo> = project.map_entity.object().parameters.iter()
+        .map(|(id, parameter)| ParameterInfo {
+            id: id.clone(),
+            parameter_type: parameter.parameter_type.clone(),
+            default: parameter.default.clone(),
+            source: None,
+        })
+        .collect();
+
+    collect_palette_parameters(&project.map_entity.object().computed_parameters, &cdda_data, &mut parameters);
+    parameters.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Window::new("Project Settings")
+        .open(&mut r_menus.is_project_settings_menu_open)
+        .resizable(true)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.vertical(|ui| {
+                ui.label("Here you can change the settings which only apply to the currently opened Project");
+                ui.set_max_width(500.);
+
+                add_settings_frame(
+                    "Palettes",
+                    gray_dark_color32,
+                    ui,
+                    |ui| {
+                        for (i, palette) in project.map_entity.object().palettes.iter().enumerate() {
+                            let palette_id = project.map_entity.object()
+                                .get_palette_id(palette)
+                                .unwrap_or("Unknown Palette".into());
+
+                            ui.horizontal(|ui| {
+                                ui.label(RichText::new(palette_id).size(16.));
+
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    let response = ui.add_sized(
+                                        Vec2::new(32., 32.),
+                                        Button::new("X").fill(error_color32),
+                                    );
+
+                                    if response.clicked() {
+                                        palette_to_remove = Some(i);
+                                    }
+                                });
+                            });
+                        }
+
+                        ui.horizontal(|ui| {
+                            ComboBox::from_id_source("project_settings_palettes")
+                                .selected_text(menu_data.selected_palette.clone().unwrap_or("".into()))
+                                .show_ui(ui, |ui| {
+                                    for palette_id in available_palettes.iter() {
+                                        ui.selectable_value(
+                                            &mut menu_data.selected_palette,
+                                            Some(palette_id.clone()),
+                                            palette_id,
+                                        );
+                                    }
+                                });
+
+                            if ui.button("Add").clicked() {
+                                palette_to_add = menu_data.selected_palette.clone();
+                            }
+                        });
+
+                        let response = ui.add_enabled(
+                            !project.map_entity.object().palettes.is_empty(),
+                            Button::new("Flatten Palettes"),
+                        ).on_hover_text("Copies the definitions of the palettes into the map, so it can be shared without them");
+
+                        if response.clicked() {
+                            menu_data.is_flatten_confirmation_open = true;
+                        }
+                    },
+                );
+
+                add_settings_frame(
+                    "Tiles",
+                    gray_dark_color32,
+                    ui,
+                    |ui| {
+                        let response = ui.checkbox(
+                            &mut project.treat_space_as_empty,
+                            "Treat the space character as empty instead of filling it with the fill terrain",
+                        );
+
+                        is_space_empty_changed = response.changed();
+
+                        // Parameters and distributions can not be edited here, typing an id replaces them
+                        let (mut fill_ter, is_plain_id) = match &project.map_entity.object().fill_ter {
+                            None => (String::new(), true),
+                            Some(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId(id)))) => (id.clone(), true),
+                            Some(_) => (String::new(), false)
+                        };
+
+                        ui.horizontal(|ui| {
+                            if tile_id_input(ui, &mut fill_ter, &r_known_tile_ids, error_color32) {
+                                project.map_entity.object_mut().fill_ter = match fill_ter.is_empty() {
+                                    true => None,
+                                    false => Some(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId(fill_ter))))
+                                };
+                                project.map_entity.object_mut().roll_fill_ter();
+                                is_fill_ter_changed = true;
+                            }
+
+                            ui.label("Fill Terrain");
+                        });
+
+                        if !is_plain_id {
+                            let resolved = project.map_entity.object().get_fill_ter().unwrap_or("nothing".into());
+                            ui.label(RichText::new(format!("The fill terrain is a parameter or distribution which currently resolves to {}", resolved)).weak());
+                        }
+                    },
+                );
+
+                add_settings_frame(
+                    "Parameters",
+                    gray_dark_color32,
+                    ui,
+                    |ui| {
+                        if parameters.is_empty() {
+                            ui.label("Neither the map nor its palettes define any parameters");
+                        }
+
+                        for parameter in parameters.iter() {
+                            let current_value = project.map_entity.object().computed_parameters
+                                .get_value(&parameter.id)
+                                .cloned()
+                                .unwrap_or_default();
+
+                            let is_tile_id = matches!(parameter.parameter_type, ParameterType::TerStrId | ParameterType::FurnStrId);
+
+                            let mut possible_values = parameter.default.get_possible_values();
+                            possible_values.sort();
+                            possible_values.dedup();
+
+                            ui.horizontal(|ui| {
+                                ui.vertical(|ui| {
+                                    ui.label(RichText::new(&parameter.id).size(16.));
+                                    ui.label(RichText::new(format!(
+                                        "{} from {}",
+                                        parameter.parameter_type.name(),
+                                        parameter.source.clone().unwrap_or("this map".into())
+                                    )).weak());
+                                });
+
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    if project.parameter_overrides.contains_key(&parameter.id) {
+                                        if ui.button("Reset").clicked() {
+                                            parameter_to_reset = Some(parameter.id.clone());
+                                        }
+                                    }
+
+                                    if is_tile_id {
+                                        let input = menu_data.parameter_inputs
+                                            .entry(parameter.id.clone())
+                                            .or_insert_with(|| current_value.clone());
+
+                                        if tile_id_input(ui, input, &r_known_tile_ids, error_color32) && r_known_tile_ids.contains(input) && *input != current_value {
+                                            parameter_to_override = Some((parameter.id.clone(), input.clone()));
+                                        }
+
+                                        return;
+                                    }
+
+                                    ComboBox::from_id_source(format!("project_settings_parameter_{}", parameter.id))
+                                        .selected_text(current_value.clone())
+                                        .show_ui(ui, |ui| {
+                                            for value in possible_values.iter() {
+                                                if ui.selectable_label(*value == current_value, value).clicked() {
+                                                    parameter_to_override = Some((parameter.id.clone(), value.clone()));
+                                                }
+                                            }
+                                        });
+                                });
+                            });
+                        }
+                    },
+                );
+
+                add_settings_frame(
+                    "Report",
+                    gray_dark_color32,
+                    ui,
+                    |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.button("Analyze").clicked() {
+                                menu_data.character_usage_report = Some(project.map_entity.get_character_usage_report(&cdda_data));
+                            }
+
+                            ui.label("Sort by");
+                            ui.selectable_value(&mut menu_data.report_sort_order, ReportSortOrder::Character, "Character");
+                            ui.selectable_value(&mut menu_data.report_sort_order, ReportSortOrder::Usage, "Usage");
+                        });
+
+                        let sort_order = menu_data.report_sort_order;
+
+                        let report = match menu_data.character_usage_report.as_mut() {
+                            None => return,
+                            Some(r) => r
+                        };
+
+                        match sort_order {
+                            ReportSortOrder::Character => {
+                                report.undefined_characters.sort_by_key(|u| u.character);
+                                report.unused_definitions.sort_by_key(|u| u.character);
+                            }
+                            ReportSortOrder::Usage => {
+                                report.undefined_characters.sort_by(|a, b| b.count.cmp(&a.count).then(a.character.cmp(&b.character)));
+                                report.unused_definitions.sort_by(|a, b| a.palette_id.cmp(&b.palette_id).then(a.character.cmp(&b.character)));
+                            }
+                        }
+
+                        ui.label(RichText::new("Undefined Characters").size(16.));
+
+                        if report.undefined_characters.is_empty() {
+                            ui.label(RichText::new("Every placed character is defined").weak());
+                        }
+
+                        for undefined in report.undefined_characters.iter() {
+                            ui.label(RichText::new(format!("'{}' is placed {} times", undefined.character, undefined.count)).color(error_color32));
+                        }
+
+                        ui.label(RichText::new("Unused Definitions").size(16.));
+
+                        if report.unused_definitions.is_empty() {
+                            ui.label(RichText::new("Every definition is used").weak());
+                        }
+
+                        for unused in report.unused_definitions.iter() {
+                            ui.label(format!(
+                                "'{}' {} from {}",
+                                unused.character,
+                                unused.kind,
+                                unused.palette_id.clone().unwrap_or("this map".into())
+                            ));
+                        }
+                    },
+                );
+
+                add_settings_frame(
+                    "Map",
+                    gray_dark_color32,
+                    ui,
+                    |ui| {
+                        if let (MapEntity::Single(_), Some((_, om_terrain))) = (&project.map_entity, &mut menu_data.om_terrain_input) {
+                            ui.horizontal(|ui| {
+                                ui.add(TextEdit::singleline(om_terrain).desired_width(200.));
+
+                                if ui.button("Rename").clicked() {
+                                    should_rename = true;
+                                }
+
+                                ui.label("Overmap Terrain");
+                            });
+                        }
+
+                        if let MapEntity::Multi(m) = &project.map_entity {
+                            let positions: Vec<IVec2> = m.om_terrain.get_positioned().iter().map(|(p, _)| *p).collect();
+                            let is_shared = matches!(m.om_terrain, MultiOmTerrain::Shared(_));
+
+                            for (i, om_terrain) in menu_data.multi_om_terrain_inputs.iter_mut().enumerate() {
+                                ui.horizontal(|ui| {
+                                    ui.add(TextEdit::singleline(om_terrain).desired_width(200.));
+
+                                    if ui.button("Rename").clicked() {
+                                        multi_om_terrain_to_rename = Some(i);
+                                    }
+
+                                    // The ids of a flat om_terrain list all use the whole map
+                                    match (is_shared, positions.get(i)) {
+                                        (false, Some(position)) => {
+                                            let start = *position * Multi::OVERMAP_TERRAIN_SIZE;
+                                            let end = start + Multi::OVERMAP_TERRAIN_SIZE - 1;
+                                            ui.label(format!("Overmap Terrain {} (x {} - {}, y {} - {})", i, start.x, end.x, start.y, end.y));
+                                        }
+                                        _ => { ui.label(format!("Overmap Terrain {} (whole map)", i)); }
+                                    }
+                                });
+                            }
+                        }
+
+                        if let Some(error) = &menu_data.om_terrain_error {
+                            ui.label(RichText::new(error).color(error_color32));
+                        }
+
+                        let response = ui.add_sized(
+                            Vec2::new(ui.available_width(), 32.),
+                            Button::new("Clear Map").fill(error_color32),
+                        );
+
+                        if response.clicked() {
+                            menu_data.is_clear_map_confirmation_open = true;
+                        }
+                    },
+                );
+            });
+        });
+
+    if menu_data.is_clear_map_confirmation_open {
+        Window::new("Clear Map?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.label("This removes every painted tile. The palettes and definitions of the map are kept.");
+
+                ui.horizontal(|ui| {
+                    if ui.button("Clear").clicked() {
+                        should_clear_map = true;
+                        menu_data.is_clear_map_confirmation_open = false;
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        menu_data.is_clear_map_confirmation_open = false;
+                    }
+                });
+            });
+    }
+
+    if menu_data.is_flatten_confirmation_open {
+        Window::new("Flatten Palettes?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.label("This copies the terrain, furniture, items and parameters of every palette into the map and removes the palettes.");
+                ui.label(RichText::new("Changes to the palettes will no longer affect this map.").color(error_color32));
+
+                ui.horizontal(|ui| {
+                    if ui.button("Flatten").clicked() {
+                        should_flatten_palettes = true;
+                        menu_data.is_flatten_confirmation_open = false;
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        menu_data.is_flatten_confirmation_open = false;
+                    }
+                });
+            });
+    }
+
+    if should_flatten_palettes {
+        project.flatten_palettes(&cdda_data);
+
+        e_clear_tiles.send(ClearTiles {});
+        e_spawn_map_entity.send(SpawnMapEntity {
+            map_entity: Arc::new(project.map_entity.clone())
+        });
+    }
+
+    if should_rename {
+        let om_terrain = menu_data.om_terrain_input.as_ref().map(|(_, o)| o.clone()).unwrap_or_default();
+
+        menu_data.om_terrain_error = match project.set_om_terrain(&om_terrain) {
+            Ok(_) => None,
+            Err(e) => Some(e)
+        };
+    }
+
+    if let Some(i) = multi_om_terrain_to_rename {
+        let om_terrain = menu_data.multi_om_terrain_inputs.get(i).cloned().unwrap_or_default();
+
+        menu_data.om_terrain_error = match project.set_multi_om_terrain(i, &om_terrain) {
+            Ok(_) => None,
+            Err(e) => Some(e)
+        };
+    }
+
+    if should_clear_map {
+        project.clear_tiles();
+
+        e_clear_tiles.send(ClearTiles {});
+        e_spawn_map_entity.send(SpawnMapEntity {
+            map_entity: Arc::new(project.map_entity.clone())
+        });
+    }
+
+    if parameter_to_override.is_some() || parameter_to_reset.is_some() {
+        if let Some((id, value)) = parameter_to_override {
+            project.parameter_overrides.insert(id, value);
+        }
+
+        if let Some(id) = parameter_to_reset {
+            project.parameter_overrides.remove(&id);
+            menu_data.parameter_inputs.remove(&id);
+
+            // Roll the parameter again if it belongs to the map, palette parameters are rolled by the recompute below
+            if let Some(parameter) = project.map_entity.object().parameters.get(&id).cloned() {
+                project.map_entity.object_mut().computed_parameters.this.insert(id, parameter.default.get_value());
+            }
+
+            project.map_entity.object_mut().recompute_palette_parameters(&cdda_data);
+        }
+
+        // Palette id parameters change which palettes are attached, so the palette parameters have to be computed again
+        let has_palette_override = parameters.iter()
+            .any(|p| matches!(p.parameter_type, ParameterType::PaletteId) && project.parameter_overrides.contains_key(&p.id));
+
+        if has_palette_override {
+            project.apply_parameter_overrides();
+            project.map_entity.object_mut().recompute_palette_parameters(&cdda_data);
+        }
+
+        project.apply_parameter_overrides();
+        project.is_dirty = true;
+
+        e_clear_tiles.send(ClearTiles {});
+        e_spawn_map_entity.send(SpawnMapEntity {
+            map_entity: Arc::new(project.map_entity.clone())
+        });
+    }
+
+    if is_fill_ter_changed {
+        project.is_dirty = true;
+
+        e_clear_tiles.send(ClearTiles {});
+        e_spawn_map_entity.send(SpawnMapEntity {
+            map_entity: Arc::new(project.map_entity.clone())
+        });
+    }
+
+    if is_space_empty_changed {
+        // The sprites of the space character have to be removed or added, so respawn every tile
+        for (coordinates, tile) in project.map_entity.tiles().iter() {
+            e_delete_tile.send(TileDeleteEvent {
+                tile: *tile,
+                coordinates: coordinates.clone(),
+            });
+
+            e_place_tile.send(TilePlaceEvent {
+                tile: Tile::with_rotation(tile.character, tile.rotation),
+                coordinates: coordinates.clone(),
+                should_update_sprites: false,
+            });
+        }
+    }
+
+    if palette_to_remove.is_none() && palette_to_add.is_none() {
+        return;
+    }
+
+    if let Some(index) = palette_to_remove {
+        project.map_entity.object_mut().palettes.remove(index);
+    }
+
+    if let Some(palette_id) = palette_to_add {
+        let is_already_attached = project.map_entity.object().palettes.iter()
+            .any(|p| project.map_entity.object().get_palette_id(p).as_ref() == Some(&palette_id));
+
+        if !is_already_attached {
+            project.map_entity.object_mut().palettes.push(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId(palette_id))));
+        }
+    }
+
+    project.map_entity.object_mut().recompute_palette_parameters(&cdda_data);
+    project.apply_parameter_overrides();
+
+    for (coordinates, tile) in project.map_entity.tiles().iter() {
+        e_update_sprite.send(UpdateSpriteEvent {
+            tile: *tile,
+            coordinates: coordinates.clone(),
+        });
+    }
+}
@@ -0,0 +1,71 @@
+use bevy::prelude::{Commands, EventWriter, Res, Resource};
+use bevy_egui::egui::{Align2, Vec2, Window};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::project::data::ReloadProject;
+
+/// Inserted when the Project at the index is about to be reloaded from its file and would lose its changes
+#[derive(Resource)]
+pub struct ReloadPromptData {
+    pub index: usize,
+    pub reason: ReloadReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReloadReason {
+    /// The file of a Project with unsaved changes was changed on disk
+    FileChanged,
+    /// The user wants to discard all changes since the Project was last saved
+    Revert,
+}
+
+pub fn reload_prompt(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut e_reload_project: EventWriter<ReloadProject>,
+    r_reload_prompt_data: Option<Res<ReloadPromptData>>,
+) {
+    let prompt_data = match r_reload_prompt_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let mut should_close = false;
+
+    let (title, description, confirm) = match prompt_data.reason {
+        ReloadReason::FileChanged => (
+            "File changed on disk",
+            "The file of this Project was changed outside of the editor, but the Project has unsaved changes.",
+            "Reload"
+        ),
+        ReloadReason::Revert => (
+            "Revert to saved",
+            "All changes since this Project was last saved will be lost.",
+            "Revert"
+        )
+    };
+
+    Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(description);
+            ui.label("Do you want to reload the file and discard your changes?");
+
+            ui.horizontal(|ui| {
+                if ui.button(confirm).clicked() {
+                    e_reload_project.send(ReloadProject { index: prompt_data.index });
+                    should_close = true;
+                }
+
+                if ui.button("Keep my changes").clicked() {
+                    should_close = true;
+                }
+            });
+        });
+
+    if should_close {
+        commands.remove_resource::<ReloadPromptData>();
+    }
+}
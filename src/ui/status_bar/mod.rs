@@ -0,0 +1,120 @@
+use bevy::prelude::{Local, Query, Res, ResMut, Window as BevyWindow, With};
+use bevy::window::PrimaryWindow;
+use bevy_egui::egui::{Align2, RichText, TextEdit, Vec2, Window};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::common::Coordinates;
+use crate::map::data::MapEntity;
+use crate::program::data::{IntoColor32, OpenedProject, Program};
+use crate::settings::data::Settings;
+use crate::tiles::data::{Brush, InteractionMode};
+use crate::ui::grid::resources::Grid;
+
+pub fn status_bar(
+    mut contexts: EguiContexts,
+    mut r_brush: ResMut<Brush>,
+    mut character_input: Local<String>,
+    r_program: Res<Program>,
+    r_mode: Res<InteractionMode>,
+    r_settings: Res<Settings>,
+    r_grid: Res<Grid>,
+    q_windows: Query<&BevyWindow, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let mode_color = match *r_mode {
+        InteractionMode::Editor => r_program.config.style.white.into_color32(),
+        InteractionMode::Preview => r_program.config.style.selected.into_color32()
+    };
+
+    let toggle_keys = r_program.config.keybindings.toggle_preview_mode.iter()
+        .map(|k| format!("{:?}", k))
+        .collect::<Vec<String>>()
+        .join(" + ");
+
+    let swap_keys = r_program.config.keybindings.swap_brush_characters.iter()
+        .map(|k| format!("{:?}", k))
+        .collect::<Vec<String>>()
+        .join(" + ");
+
+    let project = q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index));
+
+    let is_brush_blocked = match project {
+        None => false,
+        Some(p) => r_settings.is_brush_blocked(p, r_program.config.cdda_data.as_deref(), &r_brush.character)
+    };
+
+    // The hovered tile is shown from the configured origin corner, together with the om_terrain it belongs to on maps with multiple om_terrains
+    let hovered = match (q_windows.single().cursor_position(), project) {
+        (Some(xy), Some(project)) => {
+            let map_size = project.map_entity.size().as_ivec2();
+            let coordinates = Coordinates::new(
+                ((xy.x + r_grid.offset.x) / r_grid.tile_size).floor() as i32,
+                ((xy.y + r_grid.offset.y) / r_grid.tile_size).floor() as i32,
+            );
+
+            let om_terrain = match &project.map_entity {
+                MapEntity::Multi(m) => m.get_om_terrain_at(&coordinates)
+                    .map(|(ids, _)| ids.iter().map(|id| id.as_str()).collect::<Vec<&str>>().join(", ")),
+                _ => None
+            };
+
+            match coordinates.x >= 0 && coordinates.y >= 0 && coordinates.x < map_size.x && coordinates.y < map_size.y {
+                true => Some((r_settings.origin_corner.to_display(&coordinates, map_size), om_terrain)),
+                false => None
+            }
+        }
+        _ => None
+    };
+
+    Window::new("status_bar")
+        .resizable(false)
+        .collapsible(false)
+        .title_bar(false)
+        .anchor(Align2::LEFT_BOTTOM, Vec2::new(8., -8.))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(format!("{} Mode", r_mode.name())).color(mode_color));
+                ui.label(RichText::new(format!("Press {} to toggle", toggle_keys)).weak());
+
+                ui.separator();
+
+                ui.label("Brush");
+                ui.label(RichText::new(format!("'{}'", r_brush.character)).size(18.).strong().color(r_program.config.style.selected.into_color32()));
+
+                if let Some(previous) = r_brush.previous_character {
+                    ui.label(RichText::new(format!("'{}'", previous)).weak())
+                        .on_hover_text(format!("Press {} to swap to the previous character", swap_keys));
+                }
+
+                if is_brush_blocked {
+                    ui.label(RichText::new("Undefined, painting is blocked").color(r_program.config.style.error.into_color32()))
+                        .on_hover_text("Strict painting only allows characters which are defined in the map or its palettes");
+                }
+
+                // Accepts characters which need shift or conflict with a shortcut
+                let response = ui.add(
+                    TextEdit::singleline(&mut *character_input)
+                        .char_limit(1)
+                        .desired_width(16.)
+                        .hint_text("…")
+                ).on_hover_text("Type a character to use it as the brush");
+
+                if response.changed() {
+                    if let Some(character) = character_input.chars().next() {
+                        r_brush.character = character;
+                    }
+
+                    character_input.clear();
+                }
+
+                if let Some((coordinates, om_terrain)) = &hovered {
+                    ui.separator();
+                    ui.label(format!("x: {} y: {}", coordinates.x, coordinates.y));
+
+                    if let Some(om_terrain) = om_terrain {
+                        ui.label(RichText::new(om_terrain).weak());
+                    }
+                }
+            });
+        });
+}
@@ -1,4 +1,17 @@
-use bevy::prelude::{Color, Resource};
+use bevy::prelude::{Color, Event, Resource};
+use serde::{Deserialize, Serialize};
+
+/// The colors which the editor is drawn with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// Sent after the theme was changed, so everything which is not redrawn every frame can take the new colors
+#[derive(Event, Debug)]
+pub struct ThemeChanged;
 
 #[derive(Debug, Resource)]
 pub struct Style {
@@ -15,6 +28,13 @@ pub struct Style {
 }
 
 impl Style {
+    pub fn from_theme(theme: Theme) -> Self {
+        return match theme {
+            Theme::Dark => Self::dark(),
+            Theme::Light => Self::light()
+        };
+    }
+
     pub fn dark() -> Self {
         return Self {
             gray_dark: Color::rgb(0.11, 0.11, 0.11),
@@ -30,7 +50,19 @@ impl Style {
         };
     }
 
+    /// The names of the colors are kept from the dark style, so the backgrounds are light and the text is dark
     pub fn light() -> Self {
-        todo!()
+        return Self {
+            gray_dark: Color::rgb(0.88, 0.88, 0.88),
+            gray_darker: Color::rgb(0.80, 0.80, 0.80),
+            gray_light: Color::rgb(0.95, 0.95, 0.95),
+            selected: Color::rgb(0.23, 0.54, 0.95),
+            white: Color::rgb(0.08, 0.08, 0.08),
+            black: Color::rgb(1., 1., 1.),
+            blue_light: Color::rgb(0.137, 0.254, 0.431),
+            blue_dark: Color::rgb(0.698, 0.815, 0.956),
+            blue_darkest: Color::rgb(0.580, 0.705, 0.870),
+            error: Color::rgb(0.80, 0.16, 0.16),
+        };
     }
-}
\ No newline at end of file
+}
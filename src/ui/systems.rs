@@ -1,13 +1,25 @@
+use std::sync::Arc;
+
+use bevy::input::ButtonInput;
 use bevy::math::Vec2;
-use bevy::prelude::{BackgroundColor, Button, Changed, Entity, Event, EventReader, EventWriter, GlobalTransform, Query, Res, ResMut, Vec3Swizzles, Visibility, Window, With};
+use bevy::prelude::{BackgroundColor, Button, Changed, Commands, Entity, Event, EventReader, EventWriter, GlobalTransform, KeyCode, Local, Query, Res, ResMut, Vec3Swizzles, Visibility, Window, With};
 use bevy::ui::{Interaction, Node};
 use bevy::window::PrimaryWindow;
+use bevy_egui::egui::{Align2, Area, Id, RichText, Vec2 as EguiVec2};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
 
+use crate::common::TileId;
+use crate::graphics::{GraphicsResource, KnownTileIds};
 use crate::map::data::MapEntity;
-use crate::program::data::Program;
+use crate::program::data::{CDDAData, Keybinds, Menus, OpenedProject, Program};
+use crate::settings::data::Settings;
+use crate::tiles::data::CharacterHighlight;
 use crate::ui::{HoverEffect, ToggleEffect};
 use crate::ui::IsCursorCaptured;
+use crate::ui::project_settings::ProjectSettingsMenuData;
 use crate::ui::tabs::events::SpawnTab;
+use crate::ui::palette_editor::PaletteEditorData;
+use crate::ui::terrain::TerrainMenuData;
 
 pub fn button_hover_system(
     mut q_interaction: Query<(
@@ -88,10 +100,17 @@ pub fn button_toggle_system(
 }
 
 pub fn check_ui_interaction(
+    mut contexts: EguiContexts,
     windows: Query<&Window, With<PrimaryWindow>>,
     mut res_cursor: ResMut<IsCursorCaptured>,
     node_query: Query<(&Node, &GlobalTransform, &Visibility)>,
 ) {
+    // Dragging inside of an egui window, like the minimap, should not paint the tiles below it
+    if contexts.ctx_mut().is_pointer_over_area() {
+        res_cursor.0 = true;
+        return;
+    }
+
     let cursor_position = windows.single().cursor_position().unwrap_or(Vec2::default());
     res_cursor.0 = node_query.iter()
         .any(|(&node, &transform, &visibility)| {
@@ -111,10 +130,143 @@ pub fn spawn_initial_tabs(
     for (i, project) in r_program.projects.iter().enumerate() {
         let name = match &project.map_entity {
             MapEntity::Single(s) => s.om_terrain.clone(),
-            MapEntity::Nested(_) => "Nested_TODO".to_string(),
-            _ => todo!()
+            MapEntity::Nested(n) => n.get_name(),
+            // Renaming a single om_terrain of a Multi map keeps the name of the Project
+            MapEntity::Multi(_) => project.name.clone()
         };
 
         e_spawn_tab.send(SpawnTab { name, index: i as u32 });
     }
 }
+
+/// Collects the known terrain and furniture ids every time another tileset or other CDDA data is loaded
+pub fn update_known_tile_ids(
+    mut last_cdda_data: Local<Option<Arc<CDDAData>>>,
+    r_graphics: Res<GraphicsResource>,
+    r_program: Res<Program>,
+    mut r_known_tile_ids: ResMut<KnownTileIds>,
+) {
+    // The Program is changed every frame, so the loaded CDDA data is compared instead
+    let is_cdda_data_changed = match (last_cdda_data.as_ref(), r_program.config.cdda_data.as_ref()) {
+        (None, None) => false,
+        (Some(last), Some(current)) => !Arc::ptr_eq(last, current),
+        _ => true
+    };
+
+    if !r_graphics.is_changed() && !is_cdda_data_changed { return; }
+
+    *last_cdda_data = r_program.config.cdda_data.clone();
+
+    let mut ids: Vec<TileId> = match &r_graphics.textures {
+        None => vec![],
+        Some(t) => t.get_tile_ids().into_iter()
+            .filter(|id| id.starts_with("t_") || id.starts_with("f_"))
+            .collect()
+    };
+
+    if let Some(cdda_data) = &r_program.config.cdda_data {
+        for palette in cdda_data.palettes.values() {
+            for definition in palette.terrain.values().chain(palette.furniture.values()) {
+                ids.extend(definition.get_tile_ids());
+            }
+        }
+    }
+
+    ids.sort();
+    ids.dedup();
+
+    r_known_tile_ids.ids = ids;
+}
+
+/// Closes the frontmost open menu when the close keybind is pressed.
+/// Menus are checked in the order create project, define terrain, project settings, settings, statistics, bookmarks and the palette editor
+pub fn close_menu_on_escape(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut r_menus: ResMut<Menus>,
+    r_palette_editor_data: Option<Res<PaletteEditorData>>,
+    r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !Keybinds::is_just_pressed(&r_program.config.keybindings.close_menu, &r_keys) { return; }
+
+    // The key only stops editing the text field in this frame
+    if contexts.ctx_mut().wants_keyboard_input() { return; }
+
+    if r_menus.is_create_project_menu_open {
+        r_menus.is_create_project_menu_open = false;
+        return;
+    }
+
+    if r_menus.is_define_terrain_menu_open {
+        r_menus.is_define_terrain_menu_open = false;
+        commands.remove_resource::<TerrainMenuData>();
+        return;
+    }
+
+    if r_menus.is_project_settings_menu_open {
+        r_menus.is_project_settings_menu_open = false;
+        commands.remove_resource::<ProjectSettingsMenuData>();
+        return;
+    }
+
+    if r_menus.is_settings_menu_open {
+        r_menus.is_settings_menu_open = false;
+        return;
+    }
+
+    if r_menus.is_tile_statistics_open {
+        r_menus.is_tile_statistics_open = false;
+        commands.remove_resource::<CharacterHighlight>();
+        return;
+    }
+
+    if r_menus.is_bookmarks_open {
+        r_menus.is_bookmarks_open = false;
+        return;
+    }
+
+    if r_palette_editor_data.is_some() {
+        commands.remove_resource::<PaletteEditorData>();
+    }
+}
+
+/// Tells the user that the opened Project has no tiles instead of only showing the empty grid
+pub fn empty_map_placeholder(
+    mut contexts: EguiContexts,
+    r_program: Res<Program>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let size = project.map_entity.size();
+    if !project.map_entity.tiles().is_empty() && size.x >= 1. && size.y >= 1. { return; }
+
+    Area::new(Id::new("empty_map_placeholder"))
+        .anchor(Align2::CENTER_CENTER, EguiVec2::ZERO)
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(RichText::new("This map is empty").size(20.).strong());
+            ui.label(RichText::new("It has no tiles which could be edited, create a new Project with a size of at least 1x1").weak());
+        });
+}
+
+/// Switches between a windowed and a borderless fullscreen window, the choice is saved with the Settings
+pub fn toggle_fullscreen(
+    mut r_settings: ResMut<Settings>,
+    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+    r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !Keybinds::is_just_pressed(&r_program.config.keybindings.toggle_fullscreen, &r_keys) { return; }
+
+    r_settings.fullscreen = !r_settings.fullscreen;
+
+    // The grid and egui react to the WindowResized event which is sent once the resolution changed
+    for mut window in q_windows.iter_mut() {
+        window.mode = r_settings.get_window_mode();
+    }
+}
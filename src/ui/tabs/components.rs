@@ -5,6 +5,16 @@ pub struct Tab {
     pub index: u32,
 }
 
+/// The text of a tab, shows the name of the Project and if it has unsaved changes
+#[derive(Component, Debug)]
+pub struct TabNameMarker {
+    pub index: u32,
+}
+
+/// Marks a tab which was selected to be closed together with other tabs
+#[derive(Component)]
+pub struct SelectedTabMarker;
+
 #[derive(Component)]
 pub struct TabContainerMarker;
 
@@ -2,31 +2,101 @@ use std::collections::HashMap;
 
 use bevy::asset::AssetServer;
 use bevy::hierarchy::BuildChildren;
-use bevy::prelude::{AlignContent, BackgroundColor, ButtonBundle, Changed, Color, Commands, default, Display, Entity, EventReader, EventWriter, ImageBundle, Interaction, IVec2, NodeBundle, Query, Res, ResMut, Resource, State, Style, Text, TextBundle, TextStyle, UiImage, UiRect, Val, With};
+use bevy::input::ButtonInput;
+use bevy::prelude::{AlignContent, BackgroundColor, ButtonBundle, Changed, Color, Commands, default, Display, Entity, EventReader, EventWriter, GlobalTransform, ImageBundle, Interaction, IVec2, KeyCode, MouseButton, Node, NodeBundle, Outline, Query, Res, ResMut, Resource, State, Style, Text, TextBundle, TextStyle, UiImage, UiRect, Val, With};
+use bevy::window::PrimaryWindow;
 use bevy::utils::petgraph::visit::Walker;
-use bevy_egui::egui::{Align2, Button, Vec2, Window};
+use bevy_egui::egui::{Align2, Area, Button, Frame, Id, Pos2, RichText, Vec2, Window};
 use bevy_inspector_egui::bevy_egui::EguiContexts;
 
-use crate::common::Coordinates;
-use crate::map::data::{MapEntity, Single};
+use crate::common::{Coordinates, MeabyWeighted};
+use crate::map::data::{MapEntity, Single, TileSelection};
+use crate::palettes::data::{MapObjectId, MeabyParam};
 use crate::program::data::{IntoColor32, OpenedProject, Program, ProgramState};
 use crate::program::data::Menus;
-use crate::project::data::{CloseProject, CreateProject, Project};
+use crate::settings::data::Settings;
+use crate::project::data::{CloseProject, CreateProject, MoveProject, Project, ProjectSaveState, RemoveProject};
 use crate::project::data::OpenProjectAtIndex;
-use crate::tiles::data::Tile;
+use crate::tiles::data::{ProjectDiffData, Tile};
 use crate::ui::{HoverEffect, ToggleEffect};
 use crate::ui::egui_utils::{add_settings_frame, input_group};
 use crate::ui::hotbar::components::TopHotbarMarker;
-use crate::ui::tabs::components::{AddTabButtonMarker, Tab, TabContainerMarker};
+use crate::ui::close_prompt::ClosePromptData;
+use crate::ui::reload_prompt::{ReloadPromptData, ReloadReason};
+use crate::ui::tabs::components::{AddTabButtonMarker, SelectedTabMarker, Tab, TabContainerMarker, TabNameMarker};
 use crate::ui::tabs::events::SpawnTab;
 
-pub(crate) mod events;
-pub(crate) mod components;
+pub mod events;
+pub mod components;
+
+/// Inserted while a tab is dragged to another position
+#[derive(Resource)]
+pub struct TabDragData {
+    pub index: u32,
+}
+
+/// Inserted when a tab was right clicked, the context menu is shown at the position of the cursor
+#[derive(Resource)]
+pub struct TabContextMenuData {
+    pub index: u32,
+    pub position: Pos2,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TabCloseAction {
+    All,
+    /// Closes every tab except the one with the index
+    Others(usize),
+}
+
+impl TabCloseAction {
+    /// Returns the indices of the Projects which are closed by this action
+    pub fn get_indices(&self, project_count: usize) -> Vec<usize> {
+        return match self {
+            TabCloseAction::All => (0..project_count).collect(),
+            TabCloseAction::Others(kept) => (0..project_count).filter(|i| i != kept).collect()
+        };
+    }
+}
 
 #[derive(Resource, Debug, Default)]
 pub struct CreateData {
     name: String,
     size: String,
+    fill_ter: String,
+    /// Shown below the inputs if the Project could not be created
+    error: Option<String>,
+}
+
+/// The largest width and height a new Project can have
+const MAX_MAP_SIZE: i32 = 240;
+
+/// Parses a size in the form of "24x24", both dimensions have to be between 1 and [MAX_MAP_SIZE]
+pub fn parse_map_size(size: &str) -> Result<IVec2, String> {
+    let nums = size.splitn(2, "x").map(|n| n.trim()).collect::<Vec<&str>>();
+
+    let map_size = match (nums.get(0).map(|n| n.parse::<i32>()), nums.get(1).map(|n| n.parse::<i32>())) {
+        (Some(Ok(width)), Some(Ok(height))) => IVec2::new(width, height),
+        _ => return Err(format!("The map size '{}' is not in the form of WIDTHxHEIGHT", size))
+    };
+
+    if map_size.x < 1 || map_size.y < 1 || map_size.x > MAX_MAP_SIZE || map_size.y > MAX_MAP_SIZE {
+        return Err(format!("The width and height of the map have to be between 1 and {}", MAX_MAP_SIZE));
+    }
+
+    return Ok(map_size);
+}
+
+impl CreateData {
+    /// Prefills the size and the fill terrain with the defaults from the settings
+    pub fn from_settings(settings: &Settings) -> Self {
+        return Self {
+            name: String::new(),
+            size: format!("{}x{}", settings.default_map_size.x, settings.default_map_size.y),
+            fill_ter: settings.default_fill_ter.clone().unwrap_or_default(),
+            error: None,
+        };
+    }
 }
 
 pub fn setup(
@@ -100,7 +170,6 @@ pub fn create_project_menu(
     mut r_program: ResMut<Program>,
     mut r_menus: ResMut<Menus>,
     mut r_create_data: Option<ResMut<CreateData>>,
-    mut e_spawn_tab: EventWriter<SpawnTab>,
     mut e_create_project: EventWriter<CreateProject>,
     mut commands: Commands,
 ) {
@@ -110,7 +179,7 @@ pub fn create_project_menu(
     };
 
     let gray_dark_color32 = r_program.config.style.gray_dark.into_color32();
-    let amount_of_projects = r_program.projects.len();
+    let error_color32 = r_program.config.style.error.into_color32();
 
     Window::new("Create new Project")
         .open(&mut r_menus.is_create_project_menu_open)
@@ -137,26 +206,35 @@ pub fn create_project_menu(
                         &mut r_create_data.size,
                         "Map Size".into(),
                     );
+
+                    input_group(
+                        ui,
+                        &mut r_create_data.fill_ter,
+                        "Fill Terrain".into(),
+                    );
                 },
             );
 
+            if let Some(error) = &r_create_data.error {
+                ui.label(RichText::new(error).color(error_color32));
+            }
+
             let button = Button::new("Create");
             let response = ui.add_sized([64., 32.], button);
 
             if response.clicked() {
-                if r_create_data.name.is_empty() { return; }
-                if r_create_data.size.is_empty() { return; }
-
-                let nums = r_create_data.size.splitn(2, "x").collect::<Vec<&str>>();
+                if r_create_data.name.is_empty() {
+                    r_create_data.error = Some("The Project needs a name".into());
+                    return;
+                }
 
-                let map_size: IVec2 = match (nums.get(0), nums.get(1)) {
-                    (Some(width), Some(height)) => {
-                        match (width.parse(), height.parse()) {
-                            (Ok(v1), Ok(v2)) => IVec2::new(v1, v2),
-                            _ => return
-                        }
+                // A map without any tiles can not be edited
+                let map_size = match parse_map_size(&r_create_data.size) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        r_create_data.error = Some(e);
+                        return;
                     }
-                    _ => return
                 };
 
                 let mut default_tiles = HashMap::new();
@@ -174,22 +252,28 @@ pub fn create_project_menu(
                     name: r_create_data.name.clone(),
                     map_entity: MapEntity::Single(Single {
                         om_terrain: r_create_data.name.clone(),
-                        tile_selection: Default::default(),
+                        tile_selection: TileSelection {
+                            fill_ter: match r_create_data.fill_ter.is_empty() {
+                                true => None,
+                                false => Some(MapObjectId::Single(MeabyWeighted::NotWeighted(MeabyParam::TileId(r_create_data.fill_ter.clone()))))
+                            },
+                            ..Default::default()
+                        },
                         tiles: default_tiles,
                         size: map_size,
+                        comment: None,
+                        weight: None,
+                        variant: 0,
+                        rotation: None,
                     }),
-                    save_state: Default::default(),
+                    ..Default::default()
                 };
 
+                // The tab is spawned after the Project was added, so it gets the correct index
                 e_create_project.send(CreateProject {
                     project
                 });
 
-                e_spawn_tab.send(SpawnTab {
-                    name: r_create_data.name.clone(),
-                    index: amount_of_projects as u32,
-                });
-
                 commands.remove_resource::<CreateData>();
             }
         });
@@ -199,6 +283,7 @@ pub fn on_add_tab_button_click(
     q_interaction: Query<&Interaction, (Changed<Interaction>, With<AddTabButtonMarker>)>,
     mut r_menus: ResMut<Menus>,
     mut commands: Commands,
+    r_settings: Res<Settings>,
 ) {
     let interaction = match q_interaction.iter().next() {
         None => { return; }
@@ -212,7 +297,7 @@ pub fn on_add_tab_button_click(
             match project.map_entity {
                 MapEntity::Single(ref mut s) => {
                     r_menus.is_create_project_menu_open = !r_menus.is_create_project_menu_open;
-                    commands.insert_resource(CreateData::default())
+                    commands.insert_resource(CreateData::from_settings(&r_settings))
                 }
                 _ => todo!()
             }
@@ -257,17 +342,20 @@ pub fn spawn_tab_reader(
                 },
                 Tab { index: event.index }
             )).with_children(|parent| {
-                parent.spawn(TextBundle {
-                    text: Text::from_section(
-                        event.name.clone(),
-                        TextStyle {
-                            font: asset_server.load("fonts/unifont.ttf"),
-                            font_size: 12.,
-                            color: Color::hex("#FFFFFF").unwrap(),
-                        },
-                    ),
-                    ..default()
-                });
+                parent.spawn((
+                    TextBundle {
+                        text: Text::from_section(
+                            event.name.clone(),
+                            TextStyle {
+                                font: asset_server.load("fonts/unifont.ttf"),
+                                font_size: 12.,
+                                color: Color::hex("#FFFFFF").unwrap(),
+                            },
+                        ),
+                        ..default()
+                    },
+                    TabNameMarker { index: event.index }
+                ));
 
                 parent.spawn((
                     ButtonBundle {
@@ -299,16 +387,33 @@ pub fn spawn_tab_reader(
 }
 
 pub fn tab_clicked(
+    mut commands: Commands,
     mut e_open_project: EventWriter<OpenProjectAtIndex>,
     mut e_close_project: EventWriter<CloseProject>,
-    mut q_interaction: Query<(&Interaction, &Tab), (Changed<Interaction>, With<Tab>)>,
+    mut q_interaction: Query<(Entity, &Interaction, &Tab), (Changed<Interaction>, With<Tab>)>,
     r_program: Res<Program>,
+    r_keys: Res<ButtonInput<KeyCode>>,
     s_state: Res<State<ProgramState>>,
     q_opened_project: Query<(Entity, &OpenedProject)>,
+    q_selected_tabs: Query<Entity, With<SelectedTabMarker>>,
 ) {
-    for (interaction, tab) in q_interaction.iter_mut() {
+    for (entity, interaction, tab) in q_interaction.iter_mut() {
         match *interaction {
             Interaction::Pressed => {
+                if r_keys.any_pressed(r_program.config.keybindings.tab_selection_modifier.clone()) {
+                    match q_selected_tabs.contains(entity) {
+                        true => { commands.entity(entity).remove::<(SelectedTabMarker, Outline)>(); }
+                        false => {
+                            commands.entity(entity).insert((
+                                SelectedTabMarker {},
+                                Outline::new(Val::Px(1.), Val::ZERO, r_program.config.style.white),
+                            ));
+                        }
+                    }
+
+                    continue;
+                }
+
                 match s_state.get() {
                     ProgramState::ProjectOpen => {
                         let index = match q_opened_project.iter().next() {
@@ -317,7 +422,14 @@ pub fn tab_clicked(
                         };
 
                         if tab.index == index as u32 {
-                            e_close_project.send(CloseProject {});
+                            let is_dirty = r_program.projects.get(index).map_or(false, |p| p.is_dirty);
+
+                            // Ask before throwing away unsaved changes
+                            match is_dirty {
+                                true => commands.insert_resource(ClosePromptData { index, remove: false }),
+                                false => { e_close_project.send(CloseProject {}); }
+                            };
+
                             return;
                         }
                     }
@@ -336,4 +448,257 @@ pub fn tab_clicked(
             _ => {}
         }
     };
-}
\ No newline at end of file
+}
+
+/// Moves the Project of a dragged tab to the position of the tab it was dropped on
+pub fn tab_drag_system(
+    mut commands: Commands,
+    mut e_move_project: EventWriter<MoveProject>,
+    r_mouse: Res<ButtonInput<MouseButton>>,
+    r_drag_data: Option<Res<TabDragData>>,
+    q_windows: Query<&bevy::window::Window, With<PrimaryWindow>>,
+    q_tabs: Query<(&Interaction, &Tab, &Node, &GlobalTransform)>,
+) {
+    if r_mouse.just_pressed(MouseButton::Left) {
+        if let Some((_, tab, _, _)) = q_tabs.iter().find(|(interaction, ..)| **interaction == Interaction::Pressed) {
+            commands.insert_resource(TabDragData { index: tab.index });
+        }
+
+        return;
+    }
+
+    if !r_mouse.just_released(MouseButton::Left) { return; }
+
+    let drag_data = match r_drag_data {
+        None => return,
+        Some(d) => d
+    };
+
+    commands.remove_resource::<TabDragData>();
+
+    let cursor_position = match q_windows.single().cursor_position() {
+        None => return,
+        Some(p) => p
+    };
+
+    let target = q_tabs.iter().find(|(_, _, node, transform)| {
+        let min = transform.translation().truncate() - 0.5 * node.size();
+        let max = transform.translation().truncate() + 0.5 * node.size();
+
+        (min.x..max.x).contains(&cursor_position.x) && (min.y..max.y).contains(&cursor_position.y)
+    });
+
+    match target {
+        Some((_, tab, _, _)) if tab.index != drag_data.index => {
+            e_move_project.send(MoveProject { from: drag_data.index as usize, to: tab.index as usize });
+        }
+        _ => {}
+    }
+}
+
+/// Opens the context menu of a tab on right click.
+/// Projects without unsaved changes are closed right away, the tabs of the others are selected,
+/// so the selected tabs menu asks once before all of their changes are thrown away
+pub fn tab_context_menu(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut e_remove_project: EventWriter<RemoveProject>,
+    r_program: Res<Program>,
+    r_mouse: Res<ButtonInput<MouseButton>>,
+    r_context_menu_data: Option<Res<TabContextMenuData>>,
+    r_diff_data: Option<Res<ProjectDiffData>>,
+    q_windows: Query<&bevy::window::Window, With<PrimaryWindow>>,
+    q_tabs: Query<(Entity, &Interaction, &Tab)>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if r_mouse.just_pressed(MouseButton::Right) {
+        let hovered_tab = q_tabs.iter().find(|(_, interaction, _)| **interaction == Interaction::Hovered);
+
+        if let (Some((_, _, tab)), Some(position)) = (hovered_tab, q_windows.single().cursor_position()) {
+            commands.insert_resource(TabContextMenuData { index: tab.index, position: Pos2::new(position.x, position.y) });
+            return;
+        }
+    }
+
+    let context_menu_data = match r_context_menu_data {
+        None => return,
+        Some(d) => d
+    };
+
+    let mut close_action = None;
+    let mut should_close = false;
+
+    let response = Area::new(Id::new("tab_context_menu"))
+        .fixed_pos(context_menu_data.position)
+        .show(contexts.ctx_mut(), |ui| {
+            Frame::menu(ui.style()).show(ui, |ui| {
+                if ui.button("Close others").clicked() {
+                    close_action = Some(TabCloseAction::Others(context_menu_data.index as usize));
+                }
+
+                if ui.button("Close all").clicked() {
+                    close_action = Some(TabCloseAction::All);
+                }
+
+                let is_opened = q_opened_project.iter().any(|o| o.index == context_menu_data.index as usize);
+
+                if !is_opened && ui.button("Compare with opened Project").clicked() {
+                    commands.insert_resource(ProjectDiffData { index: context_menu_data.index as usize, differences: Vec::new() });
+                    should_close = true;
+                }
+
+                if r_diff_data.is_some() && ui.button("Stop comparing").clicked() {
+                    commands.remove_resource::<ProjectDiffData>();
+                    should_close = true;
+                }
+
+                if is_opened {
+                    // Only Projects which were saved have a file to revert to
+                    let is_saved = r_program.projects.get(context_menu_data.index as usize)
+                        .map_or(false, |p| matches!(p.save_state, ProjectSaveState::Saved(_)));
+
+                    if ui.add_enabled(is_saved, Button::new("Revert to saved")).clicked() {
+                        commands.insert_resource(ReloadPromptData { index: context_menu_data.index as usize, reason: ReloadReason::Revert });
+                        should_close = true;
+                    }
+                }
+            });
+        });
+
+    // Close the menu when something else is clicked
+    let is_pressed_outside = contexts.ctx_mut().input(|i| {
+        i.pointer.any_pressed() && i.pointer.interact_pos().map_or(false, |p| !response.response.rect.contains(p))
+    });
+
+    if is_pressed_outside {
+        should_close = true;
+    }
+
+    if let Some(close_action) = close_action {
+        let indices = close_action.get_indices(r_program.projects.len());
+
+        for (entity, _, tab) in q_tabs.iter() {
+            if !indices.contains(&(tab.index as usize)) { continue; }
+
+            let is_dirty = r_program.projects.get(tab.index as usize).map_or(false, |p| p.is_dirty);
+
+            match is_dirty {
+                true => {
+                    commands.entity(entity).insert((
+                        SelectedTabMarker {},
+                        Outline::new(Val::Px(1.), Val::ZERO, r_program.config.style.white),
+                    ));
+                }
+                false => { e_remove_project.send(RemoveProject { index: tab.index as usize }); }
+            }
+        }
+
+        should_close = true;
+    }
+
+    if should_close {
+        commands.remove_resource::<TabContextMenuData>();
+    }
+}
+
+/// Shown while tabs are selected, closes all of their Projects at once
+pub fn selected_tabs_menu(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut e_remove_project: EventWriter<RemoveProject>,
+    r_program: Res<Program>,
+    r_close_prompt_data: Option<Res<ClosePromptData>>,
+    q_selected_tabs: Query<(Entity, &Tab), With<SelectedTabMarker>>,
+) {
+    // The selected tabs are closed one after another while the prompt is shown
+    if q_selected_tabs.is_empty() || r_close_prompt_data.is_some() { return; }
+
+    let dirty_count = q_selected_tabs.iter()
+        .filter(|(_, tab)| r_program.projects.get(tab.index as usize).map_or(false, |p| p.is_dirty))
+        .count();
+
+    Window::new("Selected Tabs")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_TOP, Vec2::new(0., 48.))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("{} tabs are selected", q_selected_tabs.iter().count()));
+
+            if dirty_count > 0 {
+                ui.label(format!("{} of them have unsaved changes, you are asked to save them first", dirty_count));
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Close selected").clicked() {
+                    let dirty_tab = q_selected_tabs.iter()
+                        .find(|(_, tab)| r_program.projects.get(tab.index as usize).map_or(false, |p| p.is_dirty));
+
+                    // Ask before throwing away unsaved changes, the other tabs stay selected until it was answered
+                    match dirty_tab {
+                        Some((entity, tab)) => {
+                            commands.entity(entity).remove::<(SelectedTabMarker, Outline)>();
+                            commands.insert_resource(ClosePromptData { index: tab.index as usize, remove: true });
+                        }
+                        None => {
+                            for (_, tab) in q_selected_tabs.iter() {
+                                e_remove_project.send(RemoveProject { index: tab.index as usize });
+                            }
+                        }
+                    }
+                }
+
+                if ui.button("Clear selection").clicked() {
+                    for (entity, _) in q_selected_tabs.iter() {
+                        commands.entity(entity).remove::<(SelectedTabMarker, Outline)>();
+                    }
+                }
+            });
+        });
+}
+
+/// Keeps the text of the tabs in sync with the name and the unsaved changes of their Project
+pub fn update_tab_names(
+    r_program: Res<Program>,
+    mut q_tab_names: Query<(&mut Text, &TabNameMarker)>,
+) {
+    if !r_program.is_changed() { return; }
+
+    for (mut text, tab_name) in q_tab_names.iter_mut() {
+        let project = match r_program.projects.get(tab_name.index as usize) {
+            None => continue,
+            Some(p) => p
+        };
+
+        let name = match project.is_dirty {
+            true => format!("{} *", project.name),
+            false => project.name.clone()
+        };
+
+        // Only write when the name changes to not trigger a relayout every frame
+        if text.sections[0].value != name {
+            text.sections[0].value = name;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use bevy::prelude::IVec2;
+
+    use crate::ui::tabs::{parse_map_size, TabCloseAction};
+
+    #[test]
+    pub fn test_close_others_keeps_one_project() {
+        assert_eq!(TabCloseAction::Others(1).get_indices(4), vec![0, 2, 3]);
+        assert_eq!(TabCloseAction::All.get_indices(3), vec![0, 1, 2]);
+    }
+
+    #[test]
+    pub fn test_zero_size_create_is_rejected() {
+        assert_eq!(parse_map_size("24x48"), Ok(IVec2::new(24, 48)));
+        assert!(parse_map_size("0x24").is_err());
+        assert!(parse_map_size("24x0").is_err());
+        assert!(parse_map_size("24").is_err());
+        assert!(parse_map_size("").is_err());
+    }
+}
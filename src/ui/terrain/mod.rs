@@ -1,23 +1,42 @@
-use bevy::prelude::{AssetServer, Query, Res, ResMut, Resource};
-use bevy_egui::egui::{Align, Button, Color32, Frame, Layout, Margin, RichText, ScrollArea, TextureId, Ui, Vec2, Window};
+use std::sync::Arc;
+
+use bevy::prelude::{AssetServer, EventWriter, Query, Res, ResMut, Resource};
+use bevy_egui::egui::{Align, Button, Color32, ComboBox, Context, Frame, Layout, Margin, RichText, ScrollArea, TextureId, Ui, Vec2, Window};
 use bevy_egui::egui::load::SizedTexture;
 use bevy_inspector_egui::bevy_egui::EguiContexts;
 
 use crate::common::{MeabyWeighted, TileId};
-use crate::graphics::GraphicsResource;
+use crate::graphics::{GraphicsResource, KnownTileIds};
+use crate::map::data::{ClearTiles, SpawnMapEntity};
 use crate::palettes::data::{MapGenValue, MapObjectId, MeabyParam};
 use crate::program::data::{IntoColor32, Menus, OpenedProject, Program};
+use crate::project::data::DefinitionKind;
+use crate::ui::egui_utils::{get_missing_cdda_requirement, requirement_banner, tile_id_input};
+use crate::ui::palette_editor::kind_combo_box;
 
 #[derive(Resource, Default)]
 pub struct TerrainMenuData {
     search_text: String,
+    /// If the terrain or the furniture definitions are shown
+    kind: DefinitionKind,
+}
+
+enum DefinitionAction {
+    /// Removes the whole definition of the character
+    Remove(char),
+    /// Removes a single entry of a grouped definition
+    RemoveEntry(char, usize),
+    /// Previews a case of the switch of the character, None uses the case which is selected by the parameter again
+    PreviewSwitchCase(char, Option<String>),
 }
 
 fn add_single_tile(
     repr: SingleTileRepr,
     ui: &mut Ui,
     r_program: &Program,
-) {
+) -> Option<DefinitionAction> {
+    let mut action = None;
+
     Frame::none()
         .fill(r_program.config.style.gray_dark.into_color32())
         .inner_margin(Margin::same(4.))
@@ -45,13 +64,80 @@ fn add_single_tile(
                 });
 
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                    ui.add_sized(
+                    let response = ui.add_sized(
                         Vec2::new(32., 32.),
                         Button::new("X").fill(r_program.config.style.error.into_color32()),
                     );
+
+                    if response.clicked() {
+                        action = Some(DefinitionAction::Remove(repr.char));
+                    }
                 });
             });
         });
+
+    return action;
+}
+
+fn add_param_tile(
+    repr: ParamTileRepr,
+    ui: &mut Ui,
+    r_program: &Program,
+) -> Option<DefinitionAction> {
+    let mut action = None;
+
+    Frame::none()
+        .fill(r_program.config.style.gray_dark.into_color32())
+        .inner_margin(Margin::same(4.))
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+
+            ui.horizontal(|ui| {
+                ui.horizontal(|ui| {
+                    Frame::none()
+                        .fill(r_program.config.style.selected.into_color32())
+                        .show(ui, |ui| {
+                            ui.set_min_size(Vec2::new(32., 32.));
+                            ui.set_max_size(Vec2::new(32., 32.));
+
+                            ui.centered_and_justified(|ui| {
+                                ui.label(RichText::new(format!("{}", repr.char)).size(16.))
+                            });
+                        });
+
+                    ui.image(SizedTexture::new(repr.param_icon, Vec2::new(32., 32.)));
+                    ui.label(RichText::new(repr.param).size(16.));
+                    ui.label(RichText::new("=").size(16.));
+
+                    // Show what the parameter currently resolves to
+                    match repr.value {
+                        None => {
+                            ui.label(RichText::new("No Value").size(16.).color(r_program.config.style.error.into_color32()));
+                        }
+                        Some(value) => {
+                            if let Some(fg) = repr.fg {
+                                ui.image(SizedTexture::new(fg, Vec2::new(32., 32.)));
+                            }
+
+                            ui.label(RichText::new(value).size(16.));
+                        }
+                    }
+                });
+
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    let response = ui.add_sized(
+                        Vec2::new(32., 32.),
+                        Button::new("X").fill(r_program.config.style.error.into_color32()),
+                    );
+
+                    if response.clicked() {
+                        action = Some(DefinitionAction::Remove(repr.char));
+                    }
+                });
+            });
+        });
+
+    return action;
 }
 
 fn add_parameter(
@@ -160,7 +246,9 @@ fn add_grouped_tile(
     repr: GroupedTileRepr,
     ui: &mut Ui,
     r_program: &Program,
-) {
+) -> Option<DefinitionAction> {
+    let mut action = None;
+
     Frame::none()
         .fill(r_program.config.style.gray_dark.into_color32())
         .inner_margin(Margin::same(2.))
@@ -184,11 +272,15 @@ fn add_grouped_tile(
                         });
 
                         ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                            ui.add_sized(
+                            let response = ui.add_sized(
                                 Vec2::new(32., 32.),
                                 Button::new("X").fill(r_program.config.style.error.into_color32()),
                             );
 
+                            if response.clicked() {
+                                action = Some(DefinitionAction::Remove(repr.char));
+                            }
+
                             ui.add_sized(
                                 Vec2::new(32., 32.),
                                 Button::new("+").fill(r_program.config.style.blue_dark.into_color32()),
@@ -207,7 +299,7 @@ fn add_grouped_tile(
                 })
                 .sum();
 
-            for (tile, fg_id, bg_id) in repr.distribution {
+            for (i, (tile, fg_id, bg_id)) in repr.distribution.into_iter().enumerate() {
                 let weight = match &tile {
                     MeabyWeighted::NotWeighted(_) => 1,
                     MeabyWeighted::Weighted(w) => w.weight
@@ -250,16 +342,134 @@ fn add_grouped_tile(
                             }
 
                             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                                ui.add_sized(
+                                let response = ui.add_sized(
                                     Vec2::new(32., 32.),
                                     Button::new("X").fill(r_program.config.style.error.into_color32()),
                                 );
+
+                                if response.clicked() {
+                                    action = Some(DefinitionAction::RemoveEntry(repr.char, i));
+                                }
+
                                 ui.label(RichText::new(format!("{:.2}%", (weight as f32 / total_weight as f32) * 100.)).size(16.));
                             });
                         });
                     });
             }
         });
+
+    return action;
+}
+
+fn add_switch_tile(
+    repr: SwitchTileRepr,
+    ui: &mut Ui,
+    r_program: &Program,
+) -> Option<DefinitionAction> {
+    let mut action = None;
+
+    Frame::none()
+        .fill(r_program.config.style.gray_dark.into_color32())
+        .inner_margin(Margin::same(2.))
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+
+            Frame::none()
+                .fill(r_program.config.style.selected.into_color32())
+                .inner_margin(Margin {
+                    left: 14.0,
+                    right: 4.0,
+                    top: 4.0,
+                    bottom: 4.0,
+                })
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.set_width(ui.available_width());
+                        ui.set_height(32.);
+                        ui.horizontal_centered(|ui| {
+                            ui.label(RichText::new(format!("{} switches on {}", repr.char, repr.param)).size(16.));
+                        });
+
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            let response = ui.add_sized(
+                                Vec2::new(32., 32.),
+                                Button::new("X").fill(r_program.config.style.error.into_color32()),
+                            );
+
+                            if response.clicked() {
+                                action = Some(DefinitionAction::Remove(repr.char));
+                            }
+
+                            let selected_text = match (&repr.active_case, repr.is_previewed) {
+                                (None, _) => "No case".to_string(),
+                                (Some(case), true) => format!("{} (Preview)", case),
+                                (Some(case), false) => case.clone()
+                            };
+
+                            ComboBox::from_id_source(format!("switch_preview_{}", repr.char))
+                                .selected_text(selected_text)
+                                .show_ui(ui, |ui| {
+                                    if ui.selectable_label(!repr.is_previewed, "From Parameter").clicked() {
+                                        action = Some(DefinitionAction::PreviewSwitchCase(repr.char, None));
+                                    }
+
+                                    for (case, _, _) in repr.cases.iter() {
+                                        let is_selected = repr.is_previewed && repr.active_case.as_ref() == Some(case);
+
+                                        if ui.selectable_label(is_selected, case).clicked() {
+                                            action = Some(DefinitionAction::PreviewSwitchCase(repr.char, Some(case.clone())));
+                                        }
+                                    }
+                                });
+                        });
+                    });
+                });
+
+            for (case, id, fg_id) in repr.cases.iter() {
+                let is_active = repr.active_case.as_ref() == Some(case);
+
+                Frame::none()
+                    .fill(r_program.config.style.gray_dark.into_color32())
+                    .inner_margin(Margin {
+                        left: 0.0,
+                        right: 4.0,
+                        top: 0.0,
+                        bottom: 0.0,
+                    })
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.set_width(ui.available_width());
+                            ui.set_height(32.);
+
+                            let marker_color = match is_active {
+                                true => Color32::from_hex("#79FD8E").unwrap(),
+                                false => r_program.config.style.gray_light.into_color32()
+                            };
+
+                            Frame::none()
+                                .fill(marker_color)
+                                .show(ui, |ui| {
+                                    ui.set_width(2.);
+                                    ui.set_height(ui.available_height());
+                                });
+
+                            if let Some(fg_id) = fg_id {
+                                ui.image(SizedTexture::new(*fg_id, Vec2::new(32., 32.)));
+                            }
+
+                            ui.label(RichText::new(format!("{} => {}", case, id)).size(16.));
+
+                            if is_active {
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    ui.label(RichText::new("Active").size(16.));
+                                });
+                            }
+                        });
+                    });
+            }
+        });
+
+    return action;
 }
 
 struct ParameterRepr {
@@ -280,12 +490,49 @@ struct GroupedTileRepr {
     distribution: Vec<(MeabyWeighted<MeabyParam>, Option<TextureId>, Option<TextureId>)>,
 }
 
+struct ParamTileRepr {
+    char: char,
+    param: String,
+    param_icon: TextureId,
+    /// The current value of the parameter and the sprite it resolves to
+    value: Option<TileId>,
+    fg: Option<TextureId>,
+}
+
+struct SwitchTileRepr {
+    char: char,
+    param: String,
+    /// The cases sorted by their name with the tile id and the sprite they resolve to
+    cases: Vec<(String, TileId, Option<TextureId>)>,
+    active_case: Option<String>,
+    is_previewed: bool,
+}
+
+/// Shows a banner instead of the menu if the CDDA data or the tileset are missing, returns if the banner was shown
+pub fn show_terrain_menu_requirement(ctx: &Context, program: &Program, graphics: &GraphicsResource, is_open: &mut bool) -> bool {
+    let message = match get_missing_cdda_requirement(program, graphics) {
+        None => return false,
+        Some(m) => m
+    };
+
+    let error_color32 = program.config.style.error.into_color32();
+
+    Window::new("Define new Terrain")
+        .open(is_open)
+        .show(ctx, |ui| requirement_banner(ui, message, error_color32));
+
+    return true;
+}
+
 pub fn terrain_menu(
     mut contexts: EguiContexts,
     mut r_program: ResMut<Program>,
     mut r_menus: ResMut<Menus>,
     r_graphics: Res<GraphicsResource>,
+    r_known_tile_ids: Res<KnownTileIds>,
     r_asset_server: Res<AssetServer>,
+    mut e_clear_tiles: EventWriter<ClearTiles>,
+    mut e_spawn_map_entity: EventWriter<SpawnMapEntity>,
     q_open_project: Query<&OpenedProject>,
     r_terrain_menu_data: Option<ResMut<TerrainMenuData>>,
 ) {
@@ -299,23 +546,40 @@ pub fn terrain_menu(
         Some(p) => p
     };
 
+    if show_terrain_menu_requirement(contexts.ctx_mut(), &r_program, &r_graphics, &mut r_menus.is_define_terrain_menu_open) {
+        return;
+    }
+
     let textures = match &r_graphics.textures {
         None => return,
         Some(t) => t
     };
 
-    let project = r_program.projects.get(opened_project.index).unwrap();
+    let project = match r_program.projects.get(opened_project.index) {
+        None => return,
+        Some(p) => p
+    };
 
-    let terrain = &project
-        .map_entity
-        .object()
-        .terrain;
+    let definitions = match terrain_menu_data.kind {
+        DefinitionKind::Terrain => &project.map_entity.object().terrain,
+        DefinitionKind::Furniture => &project.map_entity.object().furniture,
+    };
+
+    // The maps iterate in an arbitrary order, sorting keeps the entries from reshuffling between runs
+    let mut terrain: Vec<(&char, &MapObjectId<MeabyWeighted<MeabyParam>>)> = definitions.iter().collect();
+    terrain.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut sorted_parameters: Vec<_> = project.map_entity.object().parameters.iter().collect();
+    sorted_parameters.sort_by(|(a, _), (b, _)| a.cmp(b));
 
     let mut single_tiles: Vec<SingleTileRepr> = vec![];
     let mut grouped_tiles: Vec<GroupedTileRepr> = vec![];
+    let mut switch_tiles: Vec<SwitchTileRepr> = vec![];
+    let mut param_tiles: Vec<ParamTileRepr> = vec![];
     let mut parameters: Vec<ParameterRepr> = vec![];
+    let mut action: Option<DefinitionAction> = None;
 
-    project.map_entity.object().parameters.iter()
+    sorted_parameters.into_iter()
         .for_each(|(name, parameter)| {
             let distribution = match &parameter.default {
                 MapGenValue::Distribution { distribution } => {
@@ -340,7 +604,7 @@ pub fn terrain_menu(
             })
         });
 
-    terrain.iter()
+    terrain.into_iter()
         .for_each(|(char, object_id)| {
             match object_id {
                 MapObjectId::Single(s) => {
@@ -363,7 +627,7 @@ pub fn terrain_menu(
                         MeabyParam::Parameter(_) => todo!()
                     }
                 }
-                MapObjectId::Grouped(v) => {
+                MapObjectId::Grouped(v) | MapObjectId::Distribution { distribution: v } => {
                     let distribution: Vec<(MeabyWeighted<MeabyParam>, Option<TextureId>, Option<TextureId>)> = v.iter().map(|mw| {
                         match mw.value() {
                             MeabyParam::TileId(id) => {
@@ -386,20 +650,52 @@ pub fn terrain_menu(
                         distribution,
                     })
                 }
-                MapObjectId::Param { param, .. } => {
-                    let fg_sprite = contexts.add_image(r_asset_server.load("parameter.png"));
+                MapObjectId::Param { param, fallback } => {
+                    let param_icon = contexts.add_image(r_asset_server.load("parameter.png"));
+                    let value = project.map_entity.object().get_param_value(param, fallback);
+
+                    let fg_sprite = value.as_ref()
+                        .and_then(|id| textures.get_terrain_representation(id).fg.as_ref())
+                        .map(|v| contexts.add_image(v.get_representative_sprite().clone_weak()));
+
+                    param_tiles.push(ParamTileRepr {
+                        char: char.clone(),
+                        param: param.clone(),
+                        param_icon,
+                        value,
+                        fg: fg_sprite,
+                    })
+                }
+                MapObjectId::Switch { switch, cases } => {
+                    let mut cases: Vec<(String, TileId, Option<TextureId>)> = cases.iter()
+                        .filter_map(|(case, mw)| match mw.value() {
+                            MeabyParam::TileId(id) => {
+                                let repr = textures.get_terrain_representation(id);
+                                let fg_sprite = repr.fg.as_ref()
+                                    .map(|v| contexts.add_image(v.get_representative_sprite().clone_weak()));
+
+                                Some((case.clone(), id.clone(), fg_sprite))
+                            }
+                            MeabyParam::Parameter(_) => None
+                        })
+                        .collect();
+
+                    cases.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
 
-                    single_tiles.push(SingleTileRepr {
+                    switch_tiles.push(SwitchTileRepr {
                         char: char.clone(),
-                        id: param.clone(),
-                        fg: Some(fg_sprite),
-                        bg: None,
+                        param: switch.param.clone(),
+                        cases,
+                        active_case: project.map_entity.object().get_active_switch_case(char, switch),
+                        is_previewed: project.map_entity.object().switch_previews.contains_key(char),
                     })
                 }
                 _ => return,
             };
         });
 
+    let error_color32 = r_program.config.style.error.into_color32();
+
     Window::new("Define new Terrain")
         .open(&mut r_menus.is_define_terrain_menu_open)
         .resizable(true)
@@ -413,11 +709,13 @@ pub fn terrain_menu(
                 ui.horizontal(|ui| {
                     ui.set_height(32.);
 
+                    kind_combo_box(ui, "terrain_menu_kind", &mut terrain_menu_data.kind);
+
                     ui.with_layout(
                         Layout::right_to_left(Align::Center),
                         |ui| {
                             ui.button("+");
-                            ui.text_edit_singleline(&mut terrain_menu_data.search_text);
+                            tile_id_input(ui, &mut terrain_menu_data.search_text, &r_known_tile_ids, error_color32);
                         },
                     );
                 });
@@ -438,24 +736,130 @@ pub fn terrain_menu(
                     ScrollArea::vertical()
                         .show(ui, |ui| {
                             for tile in single_tiles {
-                                add_single_tile(
-                                    tile,
-                                    ui,
-                                    r_program.as_ref(),
-                                );
+                                if let Some(a) = add_single_tile(tile, ui, r_program.as_ref()) {
+                                    action = Some(a);
+                                }
                             }
                         });
 
                     ui.label(RichText::new("Nested Tiles").size(16.));
 
                     for tiles in grouped_tiles {
-                        add_grouped_tile(
-                            tiles,
-                            ui,
-                            r_program.as_ref(),
-                        )
+                        if let Some(a) = add_grouped_tile(tiles, ui, r_program.as_ref()) {
+                            action = Some(a);
+                        }
+                    }
+
+                    ui.label(RichText::new("Parameter Tiles").size(16.));
+
+                    for tile in param_tiles {
+                        if let Some(a) = add_param_tile(tile, ui, r_program.as_ref()) {
+                            action = Some(a);
+                        }
+                    }
+
+                    ui.label(RichText::new("Switches").size(16.));
+
+                    for tile in switch_tiles {
+                        if let Some(a) = add_switch_tile(tile, ui, r_program.as_ref()) {
+                            action = Some(a);
+                        }
                     }
                 });
             });
         });
-}
\ No newline at end of file
+
+    let action = match action {
+        None => return,
+        Some(a) => a
+    };
+
+    let project = match r_program.projects.get_mut(opened_project.index) {
+        None => return,
+        Some(p) => p
+    };
+
+    let kind = terrain_menu_data.kind.clone();
+
+    match action {
+        DefinitionAction::Remove(character) => {
+            project.set_definition(kind, character, None);
+        }
+        DefinitionAction::RemoveEntry(character, index) => {
+            let definitions = match kind {
+                DefinitionKind::Terrain => &project.map_entity.object().terrain,
+                DefinitionKind::Furniture => &project.map_entity.object().furniture,
+            };
+
+            let definition = match definitions.get(&character).cloned() {
+                Some(MapObjectId::Grouped(mut entries)) => {
+                    entries.remove(index);
+
+                    match entries.is_empty() {
+                        true => None,
+                        false => Some(MapObjectId::Grouped(entries))
+                    }
+                }
+                _ => return
+            };
+
+            project.set_definition(kind, character, definition);
+        }
+        DefinitionAction::PreviewSwitchCase(character, case) => {
+            let switch_previews = &mut project.map_entity.object_mut().switch_previews;
+
+            match case {
+                None => { switch_previews.remove(&character); }
+                Some(c) => { switch_previews.insert(character, c); }
+            }
+        }
+    }
+
+    // The definition can change the sprites of any amount of tiles
+    e_clear_tiles.send(ClearTiles {});
+    e_spawn_map_entity.send(SpawnMapEntity {
+        map_entity: Arc::new(project.map_entity.clone())
+    });
+}
+
+#[cfg(test)]
+pub mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{Interaction, State, World};
+    use bevy_egui::egui::{Context, RawInput};
+
+    use crate::graphics::GraphicsResource;
+    use crate::program::data::{Menus, Program, ProgramState};
+    use crate::project::data::Project;
+    use crate::ui::hotbar::components::TileSettingsMarker;
+    use crate::ui::interaction::define_terrain_button_interaction;
+    use crate::ui::terrain::{show_terrain_menu_requirement, TerrainMenuData};
+
+    #[test]
+    pub fn test_open_terrain_menu_without_cdda_dir() {
+        let mut world = World::new();
+
+        world.insert_resource(Program::new(vec![Project::default()], vec![]));
+        world.insert_resource(Menus::default());
+        world.insert_resource(GraphicsResource::default());
+        world.insert_resource(State::new(ProgramState::ProjectOpen));
+        world.spawn((Interaction::Pressed, TileSettingsMarker));
+
+        world.run_system_once(define_terrain_button_interaction);
+
+        assert!(world.resource::<Menus>().is_define_terrain_menu_open);
+        assert!(world.get_resource::<TerrainMenuData>().is_some());
+
+        let ctx = Context::default();
+        ctx.begin_frame(RawInput::default());
+
+        let mut is_open = world.resource::<Menus>().is_define_terrain_menu_open;
+        let shown = show_terrain_menu_requirement(&ctx, world.resource::<Program>(), world.resource::<GraphicsResource>(), &mut is_open);
+
+        ctx.end_frame();
+
+        // terrain_menu returns after the banner instead of reaching the CDDA data
+        assert!(shown);
+        assert!(is_open);
+    }
+}
@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use bevy::input::ButtonInput;
+use bevy::math::Vec2;
+use bevy::prelude::{Commands, EventWriter, KeyCode, MouseButton, Query, Res, ResMut, Resource, Window, With};
+use bevy::window::PrimaryWindow;
+use bevy_egui::egui::{Area, Button, Frame, Id, Order, Pos2, RichText};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::common::{Coordinates, LogMessage};
+use crate::map::data::{ClearTiles, SpawnMapEntity, TileDeleteEvent, TilePlaceEvent, UpdateSpriteEvent};
+use crate::program::data::{Menus, OpenedProject, Program};
+use crate::project::data::{HistoryEntry, MapLine, TileChange};
+use crate::settings::data::Settings;
+use crate::tiles::data::{Brush, CharacterHighlight, HighlightTarget, Tile};
+use crate::ui::grid::resources::Grid;
+use crate::ui::IsCursorCaptured;
+
+/// Inserted when a tile was right clicked with the context menu modifier held
+#[derive(Resource)]
+pub struct TileContextMenu {
+    pub coordinates: Coordinates,
+    /// The position of the cursor when the menu was opened
+    pub position: Vec2,
+    pub show_info: bool,
+}
+
+pub fn open_tile_context_menu(
+    mut commands: Commands,
+    r_program: Res<Program>,
+    r_grid: Res<Grid>,
+    r_captured: Res<IsCursorCaptured>,
+    r_buttons: Res<ButtonInput<MouseButton>>,
+    r_keys: Res<ButtonInput<KeyCode>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !r_buttons.just_pressed(MouseButton::Right) { return; }
+    if !r_keys.any_pressed(r_program.config.keybindings.context_menu_modifier.clone()) { return; }
+    if r_captured.0 { return; }
+
+    let xy = match q_windows.single().cursor_position() {
+        None => return,
+        Some(p) => p
+    };
+
+    let project = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index)) {
+        None => return,
+        Some(p) => p
+    };
+
+    let coordinates = Coordinates::new(
+        ((xy.x + r_grid.offset.x) / r_grid.tile_size).floor() as i32,
+        ((xy.y + r_grid.offset.y) / r_grid.tile_size).floor() as i32,
+    );
+
+    if !project.map_entity.tiles().contains_key(&coordinates) { return; }
+
+    commands.insert_resource(TileContextMenu { coordinates, position: xy, show_info: false });
+}
+
+/// Shows the actions for the right clicked tile, clicking anywhere else closes the menu
+pub fn tile_context_menu(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut r_program: ResMut<Program>,
+    mut r_brush: ResMut<Brush>,
+    mut r_menus: ResMut<Menus>,
+    mut e_delete_tile: EventWriter<TileDeleteEvent>,
+    mut e_place_tile: EventWriter<TilePlaceEvent>,
+    mut e_update_sprite: EventWriter<UpdateSpriteEvent>,
+    mut e_clear_tiles: EventWriter<ClearTiles>,
+    mut e_spawn_map_entity: EventWriter<SpawnMapEntity>,
+    mut e_log: EventWriter<LogMessage>,
+    r_settings: Res<Settings>,
+    r_menu: Option<ResMut<TileContextMenu>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    let mut menu = match r_menu {
+        None => return,
+        Some(m) => m
+    };
+
+    let index = match q_opened_project.iter().next() {
+        None => {
+            commands.remove_resource::<TileContextMenu>();
+            return;
+        }
+        Some(o) => o.index
+    };
+
+    let project = match r_program.projects.get(index) {
+        None => {
+            commands.remove_resource::<TileContextMenu>();
+            return;
+        }
+        Some(p) => p
+    };
+
+    // The tile can be gone after the map was resized or another Project was opened
+    let tile = match project.map_entity.tiles().get(&menu.coordinates) {
+        None => {
+            commands.remove_resource::<TileContextMenu>();
+            return;
+        }
+        Some(t) => *t
+    };
+
+    let displayed = r_settings.origin_corner.to_display(&menu.coordinates, project.map_entity.size().as_ivec2());
+    let ids = r_program.config.cdda_data.as_ref().map(|d| project.map_entity.get_ids(d, &tile.character));
+
+    let mut should_set_brush = false;
+    let mut should_select_all = false;
+    let mut should_reroll = false;
+    let mut should_erase = false;
+    // The line and if it is inserted in front of the tile or deleted
+    let mut line_change: Option<(MapLine, bool)> = None;
+    let mut show_info = menu.show_info;
+
+    let response = Area::new(Id::new("tile_context_menu"))
+        .fixed_pos(Pos2::new(menu.position.x, menu.position.y))
+        .order(Order::Foreground)
+        .show(contexts.ctx_mut(), |ui| {
+            Frame::menu(ui.style()).show(ui, |ui| {
+                ui.label(RichText::new(format!("'{}' at {}, {}", tile.character, displayed.x, displayed.y)).weak());
+
+                should_set_brush = ui.button("Set as brush").clicked();
+                should_select_all = ui.button(format!("Select all '{}'", tile.character)).clicked();
+
+                if ui.button("Show tile info").clicked() {
+                    show_info = !show_info;
+                }
+
+                if show_info {
+                    match &ids {
+                        None => { ui.label(RichText::new("The CDDA directory is not configured").weak()); }
+                        Some(ids) => {
+                            ui.label(format!("Terrain: {}", ids.terrain.clone().unwrap_or("None".into())));
+                            ui.label(format!("Furniture: {}", ids.furniture.clone().unwrap_or("None".into())));
+                            ui.label(format!("Rotation: {}°", tile.rotation as u32 * 90));
+                        }
+                    }
+                }
+
+                should_reroll = ui.button("Re-roll")
+                    .on_hover_text("Picks another entry of the distributions of this tile")
+                    .clicked();
+
+                should_erase = ui.add_enabled(tile.character != ' ', Button::new("Erase")).clicked();
+
+                ui.separator();
+
+                if ui.button("Insert row above").clicked() {
+                    line_change = Some((MapLine::Row, true));
+                }
+
+                if ui.button("Insert column to the left").clicked() {
+                    line_change = Some((MapLine::Column, true));
+                }
+
+                if ui.button("Delete row").clicked() {
+                    line_change = Some((MapLine::Row, false));
+                }
+
+                if ui.button("Delete column").clicked() {
+                    line_change = Some((MapLine::Column, false));
+                }
+            });
+        })
+        .response;
+
+    menu.show_info = show_info;
+
+    if should_set_brush {
+        r_brush.character = tile.character;
+    }
+
+    if should_select_all {
+        commands.insert_resource(CharacterHighlight { target: HighlightTarget::Character(tile.character), highlighted: Vec::new() });
+        // The highlight is cleared from the statistics window
+        r_menus.is_tile_statistics_open = true;
+    }
+
+    if should_reroll {
+        e_update_sprite.send(UpdateSpriteEvent { tile, coordinates: menu.coordinates.clone() });
+    }
+
+    if should_erase {
+        if let Some(project) = r_program.projects.get_mut(index) {
+            project.history.push(HistoryEntry::Tiles(vec![TileChange {
+                coordinates: menu.coordinates.clone(),
+                before: Some(tile.character),
+                after: Some(' '),
+                before_rotation: tile.rotation,
+                after_rotation: 0,
+            }]));
+        }
+
+        e_delete_tile.send(TileDeleteEvent { tile, coordinates: menu.coordinates.clone() });
+        e_place_tile.send(TilePlaceEvent { tile: Tile::from(' '), coordinates: menu.coordinates.clone(), should_update_sprites: true });
+    }
+
+    if let Some((line, is_insert)) = line_change {
+        if let Some(project) = r_program.projects.get_mut(index) {
+            let line_index = line.get_index(&menu.coordinates);
+
+            let result = match is_insert {
+                true => project.insert_line(line, line_index),
+                false => project.delete_line(line, line_index)
+            };
+
+            match result {
+                Err(e) => { e_log.send(LogMessage::error(e)); }
+                Ok(_) => {
+                    // Every tile behind the line moved, so the whole map is spawned again
+                    e_clear_tiles.send(ClearTiles {});
+                    e_spawn_map_entity.send(SpawnMapEntity {
+                        map_entity: Arc::new(project.map_entity.clone())
+                    });
+                }
+            }
+        }
+    }
+
+    if should_set_brush || should_select_all || should_reroll || should_erase || line_change.is_some() || response.clicked_elsewhere() {
+        commands.remove_resource::<TileContextMenu>();
+    }
+}
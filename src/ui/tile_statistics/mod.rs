@@ -0,0 +1,165 @@
+use bevy::math::Vec2 as BevyVec2;
+use bevy::prelude::{Commands, Local, Query, Res, ResMut, Window as BevyWindow, With};
+use bevy::window::PrimaryWindow;
+use bevy_egui::egui::{Grid, RichText, ScrollArea, Vec2, Window};
+use bevy_egui::egui::load::SizedTexture;
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::graphics::{GraphicsResource, KnownTileIds};
+use crate::program::data::{IntoColor32, Menus, OpenedProject, Program};
+use crate::tiles::data::{Brush, CharacterHighlight, HighlightTarget};
+use crate::ui::egui_utils::tile_id_input;
+use crate::ui::grid::resources::Grid as ViewGrid;
+
+/// Shows how many tiles use each character of the opened Project
+pub fn tile_statistics(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut r_menus: ResMut<Menus>,
+    mut r_brush: ResMut<Brush>,
+    mut r_grid: ResMut<ViewGrid>,
+    mut terrain_input: Local<String>,
+    // The Project and terrain of the previous jump and how many jumps were made to it
+    mut last_jump: Local<Option<(usize, String, usize)>>,
+    r_program: Res<Program>,
+    r_graphics: Res<GraphicsResource>,
+    r_known_tile_ids: Res<KnownTileIds>,
+    r_highlight: Option<Res<CharacterHighlight>>,
+    q_windows: Query<&BevyWindow, With<PrimaryWindow>>,
+    q_opened_project: Query<&OpenedProject>,
+) {
+    if !r_menus.is_tile_statistics_open { return; }
+
+    let (project_index, project) = match q_opened_project.iter().next().and_then(|o| r_program.projects.get(o.index).map(|p| (o.index, p))) {
+        None => return,
+        Some(p) => p
+    };
+
+    let counts = project.map_entity.get_character_counts();
+
+    // Resolve the sprites before the window is shown, because adding images needs the egui contexts
+    let swatches: Vec<Option<SizedTexture>> = counts.iter()
+        .map(|count| {
+            let (textures, cdda_data) = match (&r_graphics.textures, &r_program.config.cdda_data) {
+                (Some(t), Some(d)) => (t, d),
+                _ => return None
+            };
+
+            let ids = project.map_entity.get_ids(cdda_data, &count.character);
+
+            let sprite = match ids.furniture.or(ids.terrain) {
+                None => textures.get_fallback_texture(&count.character),
+                Some(id) => textures.get_terrain_representation(&id)
+            };
+
+            let handle = match (&sprite.fg, &sprite.bg) {
+                (Some(fg), _) => fg.get_representative_sprite(),
+                (None, Some(bg)) => bg.get_representative_sprite(),
+                (None, None) => return None
+            };
+
+            return Some(SizedTexture::new(contexts.add_image(handle.clone_weak()), Vec2::new(16., 16.)));
+        })
+        .collect();
+
+    let highlighted_target = r_highlight.as_ref().map(|h| h.target.clone());
+    let error_color = r_program.config.style.error.into_color32();
+    let mut jump_to_terrain = false;
+
+    Window::new("Tile Statistics")
+        .open(&mut r_menus.is_tile_statistics_open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(RichText::new("Click a character to paint with it").weak());
+
+            ScrollArea::vertical().max_height(400.).show(ui, |ui| {
+                Grid::new("tile_statistics_grid").striped(true).show(ui, |ui| {
+                    for (count, swatch) in counts.iter().zip(swatches.into_iter()) {
+                        match swatch {
+                            None => { ui.label(""); }
+                            Some(s) => { ui.image(s); }
+                        }
+
+                        let is_brush = r_brush.character == count.character;
+
+                        if ui.selectable_label(is_brush, format!("'{}'", count.character)).clicked() {
+                            r_brush.character = count.character;
+                        }
+
+                        ui.label(format!("{}", count.count));
+                        ui.label(format!("{:.1}%", count.percentage));
+
+                        match highlighted_target == Some(HighlightTarget::Character(count.character)) {
+                            true => {
+                                if ui.button("Clear").clicked() {
+                                    commands.remove_resource::<CharacterHighlight>();
+                                }
+                            }
+                            false => {
+                                if ui.button("Select all").clicked() {
+                                    commands.insert_resource(CharacterHighlight { target: HighlightTarget::Character(count.character), highlighted: Vec::new() });
+                                }
+                            }
+                        }
+
+                        ui.end_row();
+                    }
+                });
+            });
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                tile_id_input(ui, &mut terrain_input, &r_known_tile_ids, error_color);
+                ui.label("Terrain");
+            });
+
+            ui.add_enabled_ui(!terrain_input.is_empty() && r_program.config.cdda_data.is_some(), |ui| {
+                ui.horizontal(|ui| {
+                    let terrain_target = HighlightTarget::Terrain(terrain_input.clone());
+
+                    match highlighted_target == Some(terrain_target.clone()) {
+                        true => {
+                            if ui.button("Clear").clicked() {
+                                commands.remove_resource::<CharacterHighlight>();
+                            }
+                        }
+                        false => {
+                            if ui.button("Select all").clicked() {
+                                commands.insert_resource(CharacterHighlight { target: terrain_target, highlighted: Vec::new() });
+                            }
+                        }
+                    }
+
+                    if ui.button("Jump to next").clicked() {
+                        jump_to_terrain = true;
+                    }
+                });
+            });
+        });
+
+    if jump_to_terrain {
+        let coordinates = match &r_program.config.cdda_data {
+            None => Vec::new(),
+            Some(cdda_data) => project.map_entity.coordinates_with_terrain(cdda_data, &terrain_input)
+        };
+
+        // Jumping to another terrain or in another Project starts again at its first tile
+        let jumps = match &*last_jump {
+            Some((index, terrain, jumps)) if *index == project_index && *terrain == *terrain_input => *jumps,
+            _ => 0
+        };
+
+        if let Some(c) = coordinates.get(jumps % coordinates.len().max(1)) {
+            let window = q_windows.single();
+            let window_size = BevyVec2::new(window.resolution.width(), window.resolution.height());
+
+            r_grid.center_on(BevyVec2::new(c.x as f32 + 0.5, c.y as f32 + 0.5), window_size);
+            *last_jump = Some((project_index, terrain_input.clone(), jumps + 1));
+        }
+    }
+
+    if !r_menus.is_tile_statistics_open {
+        commands.remove_resource::<CharacterHighlight>();
+    }
+}